@@ -0,0 +1,14 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use nova::bench;
+
+const ITERATIONS: i64 = 10_000;
+
+fn opcodes(c: &mut Criterion) {
+    for program in bench::suite(ITERATIONS) {
+        c.bench_function(program.name, |b| b.iter(|| bench::run(&program.program)));
+    }
+}
+
+criterion_group!(benches, opcodes);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+//! embeds the nova sources of the standard library in `stdlib/`, see `src/stdlib.rs`
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    println!("cargo:rerun-if-changed=stdlib");
+
+    let mut modules: Vec<_> = fs::read_dir("stdlib")
+        .expect("the stdlib directory is missing")
+        .map(|entry| entry.expect("cannot read the stdlib directory").path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "nova")
+        })
+        .collect();
+    modules.sort();
+
+    let mut generated =
+        String::from("/// the name and source of every module, in the order they are loaded\n");
+    generated.push_str("pub const MODULES: &[(&str, &str)] = &[\n");
+    for path in modules {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let path = fs::canonicalize(&path).expect("cannot find a stdlib module");
+        generated.push_str(&format!("    ({:?}, include_str!({:?})),\n", name, path));
+    }
+    generated.push_str("];\n");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("stdlib_modules.rs"), generated)
+        .expect("cannot write the stdlib modules");
+}
@@ -1,6 +1,4 @@
-use nova::{
-    instruction::InstructionBuilder, machine::VirtualMachine, object::NovaObject, program::Program,
-};
+use nova::{machine::VirtualMachine, nova_asm, program::Program};
 
 fn main() {
     let mut vm = VirtualMachine::new();
@@ -10,37 +8,25 @@ fn main() {
 }
 
 fn get_program() -> Program {
-    let immutables = vec![
-        NovaObject::String(Box::new("number1".to_string())),
-        NovaObject::String(Box::new("number2".to_string())),
-    ];
-
-    let instructions = vec![
+    nova_asm! {
         // define global variables
-        InstructionBuilder::new_define_global_indirect(0u32),
-        InstructionBuilder::new_define_global_indirect(1u32),
+        define_global "number1";
+        define_global "number2";
         // load constant numbers into register
-        InstructionBuilder::new_load_float32_instruction(0),
-        1000f32.to_bits(),
-        InstructionBuilder::new_load_float32_instruction(1),
-        88f32.to_bits(),
+        load_float 0, 1000.0;
+        load_float 1, 88.0;
         // print register values
-        InstructionBuilder::new_print_instruction(0, true),
-        InstructionBuilder::new_print_instruction(1, true),
+        println 0;
+        println 1;
         // store the numbers into the named global variables
-        InstructionBuilder::new_store_global_indirect(0, 0),
-        InstructionBuilder::new_store_global_indirect(1, 1),
+        store_global 0, "number1";
+        store_global 1, "number2";
         // load the global variables into registers, switching the order
-        InstructionBuilder::new_load_global_indirect(1, 0),
-        InstructionBuilder::new_load_global_indirect(0, 1),
+        load_global 1, "number1";
+        load_global 0, "number2";
         // print the global variables
-        InstructionBuilder::new_print_instruction(0, true),
-        InstructionBuilder::new_print_instruction(1, true),
-        InstructionBuilder::new_halt_instruction(),
-    ];
-    Program {
-        instructions,
-        immutables,
-        ..Default::default()
+        println 0;
+        println 1;
+        halt;
     }
 }
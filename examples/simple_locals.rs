@@ -1,4 +1,4 @@
-use nova::{instruction::InstructionBuilder, machine::VirtualMachine, program::Program};
+use nova::{machine::VirtualMachine, nova_asm, program::Program};
 
 fn main() {
     let mut vm = VirtualMachine::new();
@@ -8,28 +8,19 @@ fn main() {
 }
 
 fn get_program() -> Program {
-    let immutables = Vec::new();
-    let instructions = vec![
-        InstructionBuilder::new_allocate_local(2),
-        InstructionBuilder::new_load_float32_instruction(0),
-        100f32.to_bits(),
-        InstructionBuilder::new_load_float32_instruction(1),
-        (-60f32).to_bits(),
-        InstructionBuilder::new_print_instruction(0, true),
-        InstructionBuilder::new_print_instruction(1, true),
-        InstructionBuilder::new_store_local(0, 0),
-        InstructionBuilder::new_store_local(1, 1),
-        InstructionBuilder::new_load_local(1, 0),
-        InstructionBuilder::new_load_local(0, 1),
-        InstructionBuilder::new_print_instruction(0, true),
-        InstructionBuilder::new_print_instruction(1, true),
-        InstructionBuilder::new_deallocate_local(2),
-        InstructionBuilder::new_halt_instruction(),
-    ];
-
-    Program {
-        instructions,
-        immutables,
-        ..Default::default()
+    nova_asm! {
+        allocate_local 2;
+        load_float 0, 100.0;
+        load_float 1, -60.0;
+        println 0;
+        println 1;
+        store_local 0, 0;
+        store_local 1, 1;
+        load_local 1, 0;
+        load_local 0, 1;
+        println 0;
+        println 1;
+        deallocate_local 2;
+        halt;
     }
 }
@@ -1,8 +1,5 @@
 // Testing execution of bytecodes for simple mathematics
-use nova::{
-    bytecode::OpCode, instruction::InstructionBuilder, machine::VirtualMachine, object::NovaObject,
-    program::Program,
-};
+use nova::{machine::VirtualMachine, nova_asm, program::Program};
 
 fn main() {
     let mut vm = VirtualMachine::new();
@@ -12,26 +9,17 @@ fn main() {
 }
 
 fn get_program() -> Program {
-    let immutables = vec![NovaObject::String(Box::new("I am Timothy".to_string()))];
-
-    let instructions = vec![
-        InstructionBuilder::new_load_float32_instruction(0),
-        10.0f32.to_bits(),
-        InstructionBuilder::new_load_float32_instruction(1),
-        15.0f32.to_bits(),
-        InstructionBuilder::new_binary_op_instruction(OpCode::Add, 0, 0, 1),
-        InstructionBuilder::new_print_instruction(0, true),
-        InstructionBuilder::new_binary_op_instruction(OpCode::Mod, 0, 0, 1),
-        InstructionBuilder::new_print_instruction(0, true),
-        InstructionBuilder::new_load_constant_instruction(2, 0),
-        InstructionBuilder::new_print_instruction(2, true),
-        InstructionBuilder::new_binary_op_instruction(OpCode::Add, 0, 0, 2),
-        InstructionBuilder::new_print_instruction(0, true),
-        InstructionBuilder::new_halt_instruction(),
-    ];
-    Program {
-        instructions,
-        immutables,
-        ..Default::default()
+    nova_asm! {
+        load_float 0, 10.0;
+        load_float 1, 15.0;
+        add 0, 0, 1;
+        println 0;
+        rem 0, 0, 1;
+        println 0;
+        load_string 2, "I am Timothy";
+        println 2;
+        add 0, 0, 2;
+        println 0;
+        halt;
     }
 }
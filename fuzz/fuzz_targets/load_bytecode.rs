@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nova::fuzz::load_untrusted;
+
+// arbitrary bytes through the .nvc loader, then the verifier and the virtual machine
+fuzz_target!(|data: &[u8]| {
+    load_untrusted(data);
+});
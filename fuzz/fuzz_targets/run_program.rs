@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nova::fuzz::{program_from_bytes, run_untrusted};
+
+// arbitrary instructions and constants through the verifier and the virtual machine
+fuzz_target!(|data: &[u8]| {
+    run_untrusted(program_from_bytes(data));
+});
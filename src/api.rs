@@ -0,0 +1,22 @@
+//! the supported way to embed nova: compile source, run it in a virtual machine, talk to it
+//! through native functions and read the errors back. everything here checks its input, the
+//! execution internals the machine is built from are not public
+
+#[cfg(feature = "nova_tw")]
+pub use crate::frontend::NovaFrontend;
+pub use crate::{
+    capabilities::{Capabilities, Capability},
+    error::{
+        CompileError, CompileErrorKind, NovaError, ReplaceFunctionError, SourceLocation, VmError,
+        VmErrorKind,
+    },
+    frontend::Frontend,
+    hooks::{FunctionEvent, VmHooks},
+    limits::ResourceLimits,
+    machine::{ContextId, VirtualMachine},
+    natives::{common_native_functions, NativeArguments, NativeContext},
+    number_format::FloatFormat,
+    object::{NativeFunction, NativeResult, NovaMap, NovaObject as NovaValue},
+    prelude::{NativeModule, Prelude},
+    program::Program,
+};
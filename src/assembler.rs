@@ -0,0 +1,515 @@
+use alloc::{boxed::Box, string::ToString, sync::Arc, vec::Vec};
+
+use crate::{
+    bytecode::OpCode,
+    extensions,
+    instruction::{instruction_decoder, Instruction, InstructionBuilder},
+    object::{NovaFunction, NovaObject},
+    program::Program,
+};
+
+/// build a `Program` from readable mnemonics. every mnemonic is a method of `Assembler`
+/// taking its operands, and `name:` places a label that jumps can refer to by name.
+/// jump offsets are worked out from the labels, and a misspelled label or mnemonic is a
+/// compile error in the crate using the macro
+///
+/// ```
+/// // count down from 3
+/// let program = nova::nova_asm! {
+///     load_int 0, 3;
+///     load_int 1, 0;
+///     load_int 2, 1;
+///     top:
+///     less 3, 1, 0;
+///     jump_false 3, done;
+///     println 0;
+///     sub 0, 0, 2;
+///     jump top;
+///     done:
+///     halt;
+/// };
+/// assert_eq!(program.instructions.len(), 13);
+/// ```
+#[macro_export]
+macro_rules! nova_asm {
+    // declare every label up front so jumps can refer to labels placed after them
+    (@labels $assembler:ident;) => {};
+    (@labels $assembler:ident; $label:ident : $($rest:tt)*) => {
+        let $label = $assembler.new_label();
+        $crate::nova_asm!(@labels $assembler; $($rest)*);
+    };
+    (@labels $assembler:ident; $mnemonic:ident $($operand:expr),* ; $($rest:tt)*) => {
+        $crate::nova_asm!(@labels $assembler; $($rest)*);
+    };
+
+    (@emit $assembler:ident;) => {};
+    (@emit $assembler:ident; $label:ident : $($rest:tt)*) => {
+        $assembler.place($label);
+        $crate::nova_asm!(@emit $assembler; $($rest)*);
+    };
+    (@emit $assembler:ident; $mnemonic:ident $($operand:expr),* ; $($rest:tt)*) => {
+        $assembler.$mnemonic($($operand),*);
+        $crate::nova_asm!(@emit $assembler; $($rest)*);
+    };
+
+    ($($body:tt)*) => {{
+        let mut assembler = $crate::assembler::Assembler::new();
+        $crate::nova_asm!(@labels assembler; $($body)*);
+        $crate::nova_asm!(@emit assembler; $($body)*);
+        assembler.finish()
+    }};
+}
+
+/// a position in the program that jumps can target, placed once with `Assembler::place`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// writes instructions one mnemonic at a time, resolving jumps once every label is placed.
+/// strings passed to the mnemonics are added to the immutables as needed
+#[derive(Default)]
+pub struct Assembler {
+    instructions: Vec<Instruction>,
+    immutables: Vec<NovaObject>,
+    labels: Vec<Option<usize>>,
+    /// index of every jump along with the label it goes to
+    jumps: Vec<(usize, Label)>,
+    /// immutable index of every function along with the label its body starts at
+    functions: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// make the label point at the next instruction
+    pub fn place(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.instructions.len());
+    }
+
+    /// resolve the jumps and return the program.
+    /// panics if a jump goes to a label that was never placed
+    pub fn finish(mut self) -> Program {
+        for &(index, label) in &self.jumps {
+            let target = self.labels[label.0]
+                .unwrap_or_else(|| panic!("jump to label {} which is never placed", label.0));
+
+            self.instructions[index] = if target > index {
+                InstructionBuilder::new_jump_instruction((target - index) as Instruction, true)
+            } else {
+                InstructionBuilder::new_jump_instruction((index - target) as Instruction, false)
+            };
+        }
+
+        for &(index, label) in &self.functions {
+            let address = self.labels[label.0]
+                .unwrap_or_else(|| panic!("function at label {} which is never placed", label.0));
+
+            if let NovaObject::NovaFunction(function) = &mut self.immutables[index] {
+                function.address = address as Instruction;
+            }
+        }
+
+        Program {
+            instructions: self.instructions,
+            immutables: Arc::new(self.immutables.into()),
+            ..Default::default()
+        }
+    }
+
+    /// add an instruction word as is
+    pub fn raw(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+
+    /// index of the immutable, added if it is not there yet
+    pub fn immutable(&mut self, immutable: NovaObject) -> Instruction {
+        let index = match self.immutables.iter().position(|value| *value == immutable) {
+            Some(index) => index,
+            None => {
+                self.immutables.push(immutable);
+                self.immutables.len() - 1
+            }
+        };
+
+        index as Instruction
+    }
+
+    fn string(&mut self, string: &str) -> Instruction {
+        self.immutable(NovaObject::String(Box::new(string.to_string())))
+    }
+
+    pub fn halt(&mut self) {
+        self.raw(InstructionBuilder::new_halt_instruction());
+    }
+
+    pub fn load_int(&mut self, destination: Instruction, number: i64) {
+        let Ok(number) = i32::try_from(number) else {
+            self.raw(
+                InstructionBuilder::new()
+                    .add_opcode(OpCode::LoadInt64)
+                    .add_destination_register(destination)
+                    .build(),
+            );
+            let (first, second) = instruction_decoder::split_u64(number as u64);
+            self.raw(first);
+            self.raw(second);
+            return;
+        };
+
+        self.raw(
+            InstructionBuilder::new()
+                .add_opcode(OpCode::LoadInt32)
+                .add_destination_register(destination)
+                .build(),
+        );
+        self.raw(number as Instruction);
+    }
+
+    /// loads a float32 when the number fits in one without losing precision
+    pub fn load_float(&mut self, destination: Instruction, number: f64) {
+        if number as f32 as f64 == number {
+            self.raw(InstructionBuilder::new_load_float32_instruction(
+                destination,
+            ));
+            self.raw((number as f32).to_bits());
+            return;
+        }
+
+        self.raw(
+            InstructionBuilder::new()
+                .add_opcode(OpCode::LoadFloat64)
+                .add_destination_register(destination)
+                .build(),
+        );
+        let (first, second) = instruction_decoder::split_u64(number.to_bits());
+        self.raw(first);
+        self.raw(second);
+    }
+
+    pub fn load_bool(&mut self, destination: Instruction, value: bool) {
+        self.raw(InstructionBuilder::new_load_bool(
+            destination,
+            value as Instruction,
+        ));
+    }
+
+    pub fn load_nil(&mut self, destination: Instruction) {
+        self.raw(
+            InstructionBuilder::new()
+                .add_opcode(OpCode::LoadNil)
+                .add_destination_register(destination)
+                .build(),
+        );
+    }
+
+    pub fn load_string(&mut self, destination: Instruction, string: &str) {
+        let address = self.string(string);
+        self.load_constant(destination, address);
+    }
+
+    /// load a function whose body starts at the label. the arguments arrive in the registers
+    /// from 0 and the body ends with a return
+    pub fn load_function(
+        &mut self,
+        destination: Instruction,
+        name: &str,
+        arity: Instruction,
+        label: Label,
+    ) {
+        let function = NovaObject::NovaFunction(NovaFunction {
+            name: Box::new(name.to_string()),
+            address: 0,
+            arity,
+            default_parameters: 0,
+            is_variadic: false,
+            is_method: false,
+            number_of_locals: 0,
+        });
+
+        let address = match self.functions.iter().find(|&&(_, placed)| placed == label) {
+            Some(&(index, _)) => index as Instruction,
+            None => {
+                self.immutables.push(function);
+                self.functions.push((self.immutables.len() - 1, label));
+                (self.immutables.len() - 1) as Instruction
+            }
+        };
+        self.load_constant(destination, address);
+    }
+
+    pub fn load_constant(&mut self, destination: Instruction, address: Instruction) {
+        self.raw(InstructionBuilder::new_load_constant_instruction(
+            destination,
+            address,
+        ));
+    }
+
+    pub fn mov(&mut self, destination: Instruction, source: Instruction) {
+        self.raw(InstructionBuilder::new_move_instruction(
+            destination,
+            source,
+        ));
+    }
+
+    fn binary(
+        &mut self,
+        opcode: OpCode,
+        destination: Instruction,
+        left: Instruction,
+        right: Instruction,
+    ) {
+        self.raw(InstructionBuilder::new_binary_op_instruction(
+            opcode,
+            destination,
+            left,
+            right,
+        ));
+    }
+
+    pub fn add(&mut self, destination: Instruction, left: Instruction, right: Instruction) {
+        self.binary(OpCode::Add, destination, left, right);
+    }
+
+    pub fn sub(&mut self, destination: Instruction, left: Instruction, right: Instruction) {
+        self.binary(OpCode::Sub, destination, left, right);
+    }
+
+    pub fn mul(&mut self, destination: Instruction, left: Instruction, right: Instruction) {
+        self.binary(OpCode::Mul, destination, left, right);
+    }
+
+    pub fn div(&mut self, destination: Instruction, left: Instruction, right: Instruction) {
+        self.binary(OpCode::Div, destination, left, right);
+    }
+
+    pub fn rem(&mut self, destination: Instruction, left: Instruction, right: Instruction) {
+        self.binary(OpCode::Mod, destination, left, right);
+    }
+
+    pub fn pow(&mut self, destination: Instruction, left: Instruction, right: Instruction) {
+        self.binary(OpCode::Pow, destination, left, right);
+    }
+
+    pub fn less(&mut self, destination: Instruction, left: Instruction, right: Instruction) {
+        self.binary(OpCode::Less, destination, left, right);
+    }
+
+    pub fn less_equal(&mut self, destination: Instruction, left: Instruction, right: Instruction) {
+        self.binary(OpCode::LessEqual, destination, left, right);
+    }
+
+    pub fn equal(&mut self, destination: Instruction, left: Instruction, right: Instruction) {
+        self.binary(OpCode::Equal, destination, left, right);
+    }
+
+    pub fn not(&mut self, register: Instruction) {
+        self.raw(InstructionBuilder::new_not_instruction(register));
+    }
+
+    pub fn neg(&mut self, register: Instruction) {
+        self.raw(
+            InstructionBuilder::new()
+                .add_opcode(OpCode::Neg)
+                .add_source_register_1(register)
+                .build(),
+        );
+    }
+
+    pub fn pos(&mut self, register: Instruction) {
+        self.raw(
+            InstructionBuilder::new()
+                .add_opcode(OpCode::Pos)
+                .add_source_register_1(register)
+                .build(),
+        );
+    }
+
+    /// add the amount, below 256, to the local slot in place
+    pub fn inc(&mut self, slot: Instruction, amount: Instruction) {
+        self.raw(InstructionBuilder::new_step_local(
+            OpCode::Inc,
+            slot,
+            amount,
+        ));
+    }
+
+    /// subtract the amount, below 256, from the local slot in place
+    pub fn dec(&mut self, slot: Instruction, amount: Instruction) {
+        self.raw(InstructionBuilder::new_step_local(
+            OpCode::Dec,
+            slot,
+            amount,
+        ));
+    }
+
+    pub fn sqrt(&mut self, register: Instruction) {
+        self.raw(InstructionBuilder::new_math_instruction(
+            OpCode::Sqrt,
+            register,
+        ));
+    }
+
+    pub fn abs(&mut self, register: Instruction) {
+        self.raw(InstructionBuilder::new_math_instruction(
+            OpCode::Abs,
+            register,
+        ));
+    }
+
+    pub fn floor(&mut self, register: Instruction) {
+        self.raw(InstructionBuilder::new_math_instruction(
+            OpCode::Floor,
+            register,
+        ));
+    }
+
+    pub fn ceil(&mut self, register: Instruction) {
+        self.raw(InstructionBuilder::new_math_instruction(
+            OpCode::Ceil,
+            register,
+        ));
+    }
+
+    pub fn jump(&mut self, label: Label) {
+        self.jumps.push((self.instructions.len(), label));
+        // replaced by the real jump once the label is placed
+        self.raw(InstructionBuilder::new_jump_instruction(0, true));
+    }
+
+    /// jump to the label when the register is falsy
+    pub fn jump_false(&mut self, condition: Instruction, label: Label) {
+        self.raw(InstructionBuilder::new_jump_false_instruction(condition));
+        self.jump(label);
+    }
+
+    /// jump to the label when the register is None
+    pub fn jump_none(&mut self, value: Instruction, label: Label) {
+        self.raw(InstructionBuilder::new_jump_none_instruction(value));
+        self.jump(label);
+    }
+
+    pub fn define_global(&mut self, name: &str) {
+        let address = self.string(name);
+        self.raw(InstructionBuilder::new_define_global_indirect(address));
+    }
+
+    pub fn store_global(&mut self, source: Instruction, name: &str) {
+        let address = self.string(name);
+        self.raw(InstructionBuilder::new_store_global_indirect(
+            source, address,
+        ));
+    }
+
+    pub fn load_global(&mut self, destination: Instruction, name: &str) {
+        let address = self.string(name);
+        self.raw(InstructionBuilder::new_load_global_indirect(
+            destination,
+            address,
+        ));
+    }
+
+    pub fn allocate_local(&mut self, slots: Instruction) {
+        self.raw(InstructionBuilder::new_allocate_local(slots));
+    }
+
+    pub fn deallocate_local(&mut self, slots: Instruction) {
+        self.raw(InstructionBuilder::new_deallocate_local(slots));
+    }
+
+    pub fn store_local(&mut self, source: Instruction, slot: Instruction) {
+        self.raw(InstructionBuilder::new_store_local(source, slot));
+    }
+
+    pub fn load_local(&mut self, destination: Instruction, slot: Instruction) {
+        self.raw(InstructionBuilder::new_load_local(destination, slot));
+    }
+
+    /// call a function with the arguments in the registers from `start`,
+    /// the function itself is in the register right after the last argument
+    pub fn invoke(&mut self, start: Instruction, arguments: Instruction) {
+        self.raw(InstructionBuilder::new_invoke_instruction(
+            start,
+            arguments,
+            start + arguments,
+        ));
+    }
+
+    pub fn load_return(&mut self, destination: Instruction) {
+        self.raw(
+            InstructionBuilder::new()
+                .add_opcode(OpCode::LoadReturn)
+                .add_destination_register(destination)
+                .build(),
+        );
+    }
+
+    pub fn return_none(&mut self) {
+        self.raw(InstructionBuilder::new_return_none_instruction());
+    }
+
+    pub fn return_value(&mut self, source: Instruction) {
+        self.raw(InstructionBuilder::new_return_value(source));
+    }
+
+    pub fn print(&mut self, source: Instruction) {
+        self.raw(InstructionBuilder::new_print_instruction(source, false));
+    }
+
+    pub fn println(&mut self, source: Instruction) {
+        self.raw(InstructionBuilder::new_print_instruction(source, true));
+    }
+
+    /// an opcode the host registers, see `extensions::Extensions`
+    pub fn host(
+        &mut self,
+        number: u32,
+        destination: Instruction,
+        left: Instruction,
+        right: Instruction,
+    ) {
+        self.raw(extensions::new_host_instruction(
+            number,
+            destination,
+            left,
+            right,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod assembler_tests {
+    use crate::{instruction::InstructionBuilder, object::NovaObject};
+
+    #[test]
+    fn test_labels() {
+        let program = crate::nova_asm! {
+            define_global "count";
+            jump start;
+            back:
+            halt;
+            start:
+            load_bool 0, false;
+            jump_false 0, back;
+        };
+
+        let expected = vec![
+            InstructionBuilder::new_define_global_indirect(0),
+            InstructionBuilder::new_jump_instruction(2, true),
+            InstructionBuilder::new_halt_instruction(),
+            InstructionBuilder::new_load_bool(0, 0),
+            InstructionBuilder::new_jump_false_instruction(0),
+            InstructionBuilder::new_jump_instruction(3, false),
+        ];
+        assert_eq!(program.instructions, expected);
+        assert_eq!(
+            *program.immutables,
+            vec![NovaObject::String(Box::new("count".to_string()))]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! representative programs for timing the virtual machine, built with the assembler so they
+//! do not depend on the compiler. the criterion benches in `benches/` and `nova-bench` run them
+//! to see what a change to dispatch or code generation costs
+
+use std::io;
+
+use crate::{machine::VirtualMachine, nova_asm, prelude::Prelude, program::Program};
+
+/// a program of the suite, looping `iterations` times over the work it measures
+pub struct BenchProgram {
+    pub name: &'static str,
+    pub program: Program,
+}
+
+/// the benchmark programs, each doing its work the given number of times
+pub fn suite(iterations: i64) -> Vec<BenchProgram> {
+    vec![
+        BenchProgram {
+            name: "arith_loop",
+            program: arith_loop(iterations),
+        },
+        BenchProgram {
+            name: "string_building",
+            // every append copies the string, so it takes a tenth of the steps
+            program: string_building(iterations / 10),
+        },
+        BenchProgram {
+            name: "calls",
+            program: calls(iterations),
+        },
+        BenchProgram {
+            name: "global_access",
+            program: global_access(iterations),
+        },
+    ]
+}
+
+/// run the program in a fresh virtual machine with the common natives, discarding what it
+/// prints. returns the exit code
+pub fn run(program: &Program) -> u32 {
+    let mut vm = VirtualMachine::with_prelude(Prelude::natives());
+    vm.set_output(Box::new(io::sink()));
+    vm.load_program(program.clone());
+    vm.start_vm(0)
+}
+
+/// integer and float arithmetic on registers
+fn arith_loop(iterations: i64) -> Program {
+    nova_asm! {
+        load_int 0, 0;
+        load_int 1, iterations;
+        load_int 2, 1;
+        load_float 4, 0.0;
+        load_float 5, 1.5;
+        top:
+        less 3, 0, 1;
+        jump_false 3, done;
+        mul 6, 0, 2;
+        add 4, 4, 5;
+        sub 6, 6, 2;
+        add 0, 0, 2;
+        jump top;
+        done:
+        halt;
+    }
+}
+
+/// appending to a string, which allocates on every step
+fn string_building(iterations: i64) -> Program {
+    nova_asm! {
+        load_int 0, 0;
+        load_int 1, iterations;
+        load_int 2, 1;
+        load_string 4, "";
+        load_string 5, "ab";
+        top:
+        less 3, 0, 1;
+        jump_false 3, done;
+        add 4, 4, 5;
+        add 0, 0, 2;
+        jump top;
+        done:
+        halt;
+    }
+}
+
+/// calling a function of one parameter and reading its result
+fn calls(iterations: i64) -> Program {
+    nova_asm! {
+        jump start;
+        square:
+        mul 0, 0, 0;
+        return_value 0;
+        start:
+        load_int 0, 0;
+        load_int 1, iterations;
+        load_int 2, 1;
+        top:
+        less 3, 0, 1;
+        jump_false 3, done;
+        mov 5, 0;
+        load_function 6, "square", 1, square;
+        invoke 5, 1;
+        load_return 7;
+        add 0, 0, 2;
+        jump top;
+        done:
+        halt;
+    }
+}
+
+/// reading and writing a global by name
+fn global_access(iterations: i64) -> Program {
+    nova_asm! {
+        define_global "total";
+        load_int 4, 0;
+        store_global 4, "total";
+        load_int 0, 0;
+        load_int 1, iterations;
+        load_int 2, 1;
+        top:
+        less 3, 0, 1;
+        jump_false 3, done;
+        load_global 4, "total";
+        add 4, 4, 2;
+        store_global 4, "total";
+        add 0, 0, 2;
+        jump top;
+        done:
+        halt;
+    }
+}
+
+#[cfg(test)]
+mod bench_tests {
+    use super::{run, suite};
+
+    #[test]
+    fn test_suite_runs() {
+        for bench in suite(100) {
+            assert_eq!(run(&bench.program), 0, "{} failed", bench.name);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    env, fs,
+    process::exit,
+    time::{Duration, Instant},
+};
+
+use nova::{bench, compiler, program::Program};
+
+const DEFAULT_RUNS: usize = 10;
+const DEFAULT_ITERATIONS: i64 = 100_000;
+
+/// time the `.nova` files given, or the built in suite when there are none. every program is
+/// compiled once and run `--runs=N` times in a fresh virtual machine, printing is discarded
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let runs = number_flag(&args, "--runs=")
+        .unwrap_or(DEFAULT_RUNS as i64)
+        .max(1) as usize;
+    let iterations = number_flag(&args, "--iterations=").unwrap_or(DEFAULT_ITERATIONS);
+    let paths: Vec<&String> = args
+        .iter()
+        .filter(|argument| !argument.starts_with('-'))
+        .collect();
+
+    let mut programs = Vec::new();
+    if paths.is_empty() {
+        for program in bench::suite(iterations) {
+            programs.push((program.name.to_string(), program.program));
+        }
+    }
+
+    for path in paths {
+        match compile_file(path) {
+            Ok(program) => programs.push((path.clone(), program)),
+            Err(message) => {
+                eprintln!("{}: {}", path, message);
+                exit(1);
+            }
+        }
+    }
+
+    let name_width = programs
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("program".len());
+
+    println!(
+        "{:<name_width$}  {:>10}  {:>10}  {:>10}",
+        "program", "min", "mean", "max"
+    );
+
+    let mut failed = false;
+    for (name, program) in &programs {
+        let mut times = Vec::with_capacity(runs);
+        for _ in 0..runs {
+            let start = Instant::now();
+            let exit_code = bench::run(program);
+            times.push(start.elapsed());
+
+            if exit_code != 0 {
+                failed = true;
+                break;
+            }
+        }
+
+        let min = times.iter().min().copied().unwrap_or_default();
+        let max = times.iter().max().copied().unwrap_or_default();
+        let mean = times.iter().sum::<Duration>() / times.len().max(1) as u32;
+        println!(
+            "{:<name_width$}  {:>10}  {:>10}  {:>10}",
+            name,
+            format_duration(min),
+            format_duration(mean),
+            format_duration(max)
+        );
+    }
+
+    if failed {
+        exit(1);
+    }
+}
+
+/// the value of a flag such as `--runs=5`
+fn number_flag(arguments: &[String], prefix: &str) -> Option<i64> {
+    arguments
+        .iter()
+        .rev()
+        .find_map(|argument| argument.strip_prefix(prefix))
+        .and_then(|value| value.parse().ok())
+}
+
+fn compile_file(path: &str) -> Result<Program, String> {
+    let code = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    compiler::compile(&code, path).map_err(|err| err.to_string())
+}
+
+fn format_duration(duration: Duration) -> String {
+    let micros = duration.as_secs_f64() * 1e6;
+    if micros < 1000.0 {
+        format!("{:.1}us", micros)
+    } else {
+        format!("{:.2}ms", micros / 1000.0)
+    }
+}
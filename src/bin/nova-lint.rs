@@ -0,0 +1,77 @@
+use std::{env, fs, io, process::exit};
+
+use nova::{
+    compiler::{self, optimizer::OptLevel},
+    diagnostics::{ColorChoice, Diagnostic, Palette},
+    file,
+    lint::{self, Severity},
+    program::Program,
+};
+
+const SOURCE_EXTENSION: &str = ".nova";
+
+fn main() {
+    let (flags, paths): (Vec<String>, Vec<String>) = env::args()
+        .skip(1)
+        .partition(|argument| argument.starts_with("--"));
+    if paths.is_empty() {
+        println!("Usage: nova-lint [--color=auto|always|never] <file.nova | bytecode file>...");
+        exit(1);
+    }
+
+    let palette = ColorChoice::from_args(&flags).palette_for(io::stdout());
+    let mut failed = false;
+    for path in &paths {
+        failed |= !lint_file(path, palette);
+    }
+
+    if failed {
+        exit(1);
+    }
+}
+
+/// print the lints of one file, false if it has errors
+fn lint_file(path: &str, palette: Palette) -> bool {
+    let Some(program) = load_program(path, palette) else {
+        return false;
+    };
+
+    let lints = lint::lint(&program);
+    for lint in &lints {
+        println!("{}: {}", path, lint.render_with(palette));
+    }
+
+    !lints.iter().any(|lint| lint.severity == Severity::Error)
+}
+
+/// source files are compiled without optimizations so the lints see every instruction the
+/// generator wrote, anything else is read as bytecode
+fn load_program(path: &str, palette: Palette) -> Option<Program> {
+    if !path.ends_with(SOURCE_EXTENSION) {
+        return match file::read_program_file(path) {
+            Ok(program) => Some(program),
+            Err(err) => {
+                println!("{}: error: {}", path, err);
+                None
+            }
+        };
+    }
+
+    let code = match fs::read_to_string(path) {
+        Ok(code) => code,
+        Err(err) => {
+            println!("{}: error: {}", path, err);
+            return None;
+        }
+    };
+
+    match compiler::compile_with_opt_level(&code, path, OptLevel::O0) {
+        Ok((program, _, _)) => Some(program),
+        Err(errors) => {
+            for error in &errors {
+                println!("{}", error.render_with(palette));
+            }
+            None
+        }
+    }
+}
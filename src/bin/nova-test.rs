@@ -0,0 +1,257 @@
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::exit,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use nova::{
+    capabilities::Capabilities, compiler, machine::VirtualMachine, object::NovaObject,
+    prelude::Prelude, program::Program,
+};
+
+const TEST_FILE_SUFFIX: &str = "_test.nova";
+const TEST_FUNCTION_PREFIX: &str = "test_";
+
+/// output sink shared between the virtual machine and the test runner
+#[derive(Clone, Default)]
+struct CapturedOutput {
+    buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CapturedOutput {
+    fn take(&self) -> String {
+        let bytes = std::mem::take(&mut *self.buffer.borrow_mut());
+        String::from_utf8_lossy(&bytes).to_string()
+    }
+}
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct TestResult {
+    name: String,
+    passed: bool,
+    duration: Duration,
+    output: String,
+}
+
+#[derive(Default)]
+struct Summary {
+    passed: usize,
+    failed: usize,
+    failures: Vec<String>,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let roots = if args.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        args.iter().map(PathBuf::from).collect()
+    };
+
+    let mut test_files = Vec::new();
+    for root in &roots {
+        discover_test_files(root, &mut test_files);
+    }
+    test_files.sort();
+
+    if test_files.is_empty() {
+        println!("no '*{}' files found", TEST_FILE_SUFFIX);
+        return;
+    }
+
+    let start_time = Instant::now();
+    let mut summary = Summary::default();
+
+    for path in &test_files {
+        run_test_file(path, &mut summary);
+    }
+
+    let elapsed = start_time.elapsed();
+
+    if !summary.failures.is_empty() {
+        println!();
+        println!("failures:");
+        for failure in &summary.failures {
+            println!("    {}", failure);
+        }
+    }
+
+    println!();
+    println!(
+        "test result: {}. {} passed; {} failed; finished in {:.2}s",
+        if summary.failed == 0 { "ok" } else { "FAILED" },
+        summary.passed,
+        summary.failed,
+        elapsed.as_secs_f64()
+    );
+
+    if summary.failed > 0 {
+        exit(1);
+    }
+}
+
+fn discover_test_files(path: &Path, test_files: &mut Vec<PathBuf>) {
+    if path.is_file() {
+        if is_test_file(path) {
+            test_files.push(path.to_path_buf());
+        }
+        return;
+    }
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("cannot read '{}': {}", path.display(), err);
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            discover_test_files(&path, test_files);
+        } else if is_test_file(&path) {
+            test_files.push(path);
+        }
+    }
+}
+
+fn is_test_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(TEST_FILE_SUFFIX))
+}
+
+fn run_test_file(path: &Path, summary: &mut Summary) {
+    let filename = path.display().to_string();
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            println!("{} ... FAILED ({})", filename, err);
+            summary.failed += 1;
+            summary.failures.push(filename);
+            return;
+        }
+    };
+
+    let test_names = match compiler::compile(&source, &filename) {
+        Ok(program) => collect_test_names(&program),
+        Err(err) => {
+            println!("{} ... FAILED (compile error)", filename);
+            println!("{}", err);
+            summary.failed += 1;
+            summary.failures.push(filename);
+            return;
+        }
+    };
+
+    println!("running {} tests in {}", test_names.len(), filename);
+
+    for test_name in test_names {
+        let result = run_test(&source, &filename, &test_name);
+
+        if result.passed {
+            summary.passed += 1;
+            println!(
+                "test {}::{} ... ok ({:.2?})",
+                filename, result.name, result.duration
+            );
+        } else {
+            summary.failed += 1;
+            summary
+                .failures
+                .push(format!("{}::{}", filename, result.name));
+            println!(
+                "test {}::{} ... FAILED ({:.2?})",
+                filename, result.name, result.duration
+            );
+
+            if !result.output.is_empty() {
+                println!("---- {} output ----", result.name);
+                print!("{}", result.output);
+                if !result.output.ends_with('\n') {
+                    println!();
+                }
+            }
+        }
+    }
+}
+
+/// every test gets a fresh virtual machine, the top level code of the file is run
+/// before the test function is called
+fn run_test(source: &str, filename: &str, test_name: &str) -> TestResult {
+    let output = CapturedOutput::default();
+    let mut result = TestResult {
+        name: test_name.to_string(),
+        passed: false,
+        duration: Duration::ZERO,
+        output: String::new(),
+    };
+
+    // the file compiled once already, so this is not expected to fail
+    let program = match compiler::compile(source, filename) {
+        Ok(program) => program,
+        Err(err) => {
+            result.output = err.to_string();
+            return result;
+        }
+    };
+
+    let call = match compiler::compile(&format!("{}()\n", test_name), filename) {
+        Ok(program) => program,
+        Err(err) => {
+            result.output = err.to_string();
+            return result;
+        }
+    };
+
+    let mut vm = VirtualMachine::with_prelude(Prelude::natives());
+    vm.set_capabilities(Capabilities::all());
+    vm.set_output(Box::new(output.clone()));
+
+    vm.load_program(program);
+    if vm.start_vm(0) != 0 {
+        result.output = output.take();
+        return result;
+    }
+
+    let offset = vm.get_instruction_count();
+    vm.load_program(call);
+
+    let start_time = Instant::now();
+    let code = vm.start_vm(offset);
+    result.duration = start_time.elapsed();
+
+    result.passed = code == 0;
+    result.output = output.take();
+    result
+}
+
+fn collect_test_names(program: &Program) -> Vec<String> {
+    program
+        .immutables
+        .iter()
+        .filter_map(|immutable| match immutable {
+            NovaObject::NovaFunction(function)
+                if function.name.starts_with(TEST_FUNCTION_PREFIX) =>
+            {
+                Some(function.name.to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
@@ -1,63 +1,205 @@
-use std::{env, fs};
+use std::{env, fs, io, path::Path};
 
 use nova::{
-    bytecode::OpCode, compiler, debug::debug_instruction, instruction::instruction_decoder,
+    compiler::{self, optimizer::OptLevel},
+    diagnostics::{ColorChoice, Diagnostic, Palette, Style},
+    file,
     program::Program,
+    tools,
+    watch::FileWatcher,
 };
 
+const SOURCE_EXTENSION: &str = ".nova";
+const BYTECODE_EXTENSION: &str = "nvc";
+
 fn main() {
     let args: Vec<String> = env::args().collect::<Vec<String>>();
-    if args.len() > 1 {
-        run_file(&args[1], &args);
-    } else {
+
+    if args.iter().any(|argument| argument == "--diff") {
+        if !diff_files(&args) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let path = args
+        .iter()
+        .skip(1)
+        .find(|argument| !argument.starts_with('-'));
+
+    let Some(path) = path else {
         println!("Error: an argument is required");
         std::process::exit(1);
+    };
+
+    if !args.iter().any(|argument| argument == "--watch") {
+        if !run_file(path, &args) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // compile again every time the file is saved, failures do not stop the watch
+    let mut watcher = FileWatcher::new(&[path]);
+    loop {
+        run_file(path, &args);
+        eprintln!("[watching {} for changes]", path);
+        watcher.wait_for_change();
     }
 }
 
-fn run_file(path: &str, _arguments: &[String]) {
+/// compile the file and print its listing, false if compilation failed
+fn run_file(path: &str, arguments: &[String]) -> bool {
+    let deny_warnings = arguments
+        .iter()
+        .any(|argument| argument == "--deny-warnings");
+    let opt_stats = arguments.iter().any(|argument| argument == "--opt-stats");
+    let report = arguments.iter().any(|argument| argument == "--report");
+    let symbols = arguments.iter().any(|argument| argument == "--symbols");
+    let strip = arguments.iter().any(|argument| argument == "--strip");
+    let palette = ColorChoice::from_args(arguments).palette();
+
+    // the last level given wins
+    let opt_level = arguments
+        .iter()
+        .rev()
+        .find_map(|argument| OptLevel::from_flag(argument))
+        .unwrap_or_default();
+
     let result = fs::read_to_string(path);
     if let Err(err) = result {
         println!("{}", err);
-        return;
+        return true;
     }
 
     let code = result.unwrap();
 
-    let program = compiler::compile(&code, path).unwrap();
+    let (program, warnings, stats) = match compiler::compile_with_opt_level(&code, path, opt_level)
+    {
+        Ok(result) => result,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}", error.render_with(palette));
+            }
+            eprintln!(
+                "{} compilation failed with {} error(s)",
+                palette.paint(Style::Error, "Error:"),
+                errors.len()
+            );
+            return false;
+        }
+    };
+
+    for warning in &warnings {
+        eprintln!("{}", warning.render_with(palette));
+    }
 
-    debug_code(&program);
-    debug_immutables(&program);
+    if deny_warnings && !warnings.is_empty() {
+        eprintln!(
+            "{} compilation failed with {} warning(s) (--deny-warnings)",
+            palette.paint(Style::Error, "Error:"),
+            warnings.len()
+        );
+        return false;
+    }
+
+    if opt_stats {
+        eprintln!("optimization level: {:?}", opt_level);
+        eprintln!("{}", stats);
+    }
+
+    // the report and symbol table replace the listing, they are meant to be read on their own
+    if strip {
+        return write_stripped(path, &program);
+    } else if report {
+        print!("{}", tools::report(&program));
+    } else if symbols {
+        print!("{}", program.symbols());
+    } else if let Err(err) = program.dump(&mut io::stdout()) {
+        println!("{}", err);
+    }
+
+    true
 }
 
-fn debug_code(program: &Program) {
-    println!("Instructions");
+/// `novac --strip main.nova` writes `main.nvc` without debug info and the line table and local
+/// symbols to the `main.nvd` sidecar, which `file::read_program_file` picks up when present
+fn write_stripped(path: &str, program: &Program) -> bool {
+    let bytecode_path = Path::new(path)
+        .with_extension(BYTECODE_EXTENSION)
+        .to_string_lossy()
+        .into_owned();
+    let sidecar_path = file::sidecar_path(&bytecode_path);
+
+    let written = file::write_program_file(&bytecode_path, program)
+        .and_then(|_| file::write_debug_info_file(&sidecar_path, program));
+    if let Err(err) = written {
+        eprintln!("{}: {}", bytecode_path, err);
+        return false;
+    }
+
+    eprintln!("wrote {} and {}", bytecode_path, sidecar_path);
+    true
+}
 
-    let mut index = 0;
+/// `novac --diff old.nvc new.nvc` prints how the instructions of two bytecode files differ,
+/// false if they could not be read. source files are compiled first
+fn diff_files(arguments: &[String]) -> bool {
+    let paths: Vec<&String> = arguments
+        .iter()
+        .skip(1)
+        .filter(|argument| !argument.starts_with('-'))
+        .collect();
 
-    while index < program.instructions.len() {
-        let instruction_dbg = debug_instruction(&program.instructions, index as u64);
-        println!("[{}]: {}", index, instruction_dbg);
+    let [old_path, new_path] = paths[..] else {
+        eprintln!("Usage: novac --diff <old.nvc> <new.nvc>");
+        return false;
+    };
 
-        let code = instruction_decoder::decode_opcode(program.instructions[index]);
-        if code == OpCode::LoadFloat32 as u32 {
-            index += 1;
-        } else if code == OpCode::LoadFloat64 as u32 {
-            index += 2;
-        } else if code == OpCode::LoadInt32 as u32 {
-            index += 1;
-        } else if code == OpCode::LoadInt64 as u32 {
-            index += 2;
-        }
+    let palette = ColorChoice::from_args(arguments).palette();
+    let (Some(old), Some(new)) = (
+        load_program(old_path, palette),
+        load_program(new_path, palette),
+    ) else {
+        return false;
+    };
 
-        index += 1;
+    let diff = tools::diff_programs(&old, &new);
+    if diff.is_empty() {
+        println!("no differences");
+    } else {
+        print!("{}", diff);
     }
+
+    true
 }
 
-fn debug_immutables(program: &Program) {
-    println!("Immutables");
+fn load_program(path: &str, palette: Palette) -> Option<Program> {
+    if !path.ends_with(SOURCE_EXTENSION) {
+        return match file::read_program_file(path) {
+            Ok(program) => Some(program),
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                None
+            }
+        };
+    }
+
+    let code = match fs::read_to_string(path) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return None;
+        }
+    };
 
-    for (index, novaobject) in program.immutables.iter().enumerate() {
-        println!("[{}]: {}", index, novaobject);
+    match compiler::compile_with_warnings(&code, path) {
+        Ok((program, _)) => Some(program),
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}", error.render_with(palette));
+            }
+            None
+        }
     }
 }
@@ -4,23 +4,77 @@ use std::{
     process::exit,
 };
 
-use nova::{compiler, instruction::Instruction, machine::VirtualMachine, natives};
+use nova::{
+    capabilities::Capabilities,
+    compiler,
+    diagnostics::{ColorChoice, Diagnostic, Palette},
+    file,
+    instruction::Instruction,
+    machine::{Coercion, VirtualMachine},
+    prelude::Prelude,
+    profiler::{FunctionProfiler, OpcodeProfiler},
+    program::Program,
+    watch::FileWatcher,
+};
 
 const PROMPT: &str = ">>";
+const BYTECODE_EXTENSION: &str = ".nvc";
 
 fn main() {
     let args: Vec<String> = env::args().collect::<Vec<String>>();
-    if args.len() > 1 {
-        run_file(&args[1])
+    let path = args
+        .iter()
+        .skip(1)
+        .find(|argument| !argument.starts_with('-'));
+    let watch = args.iter().any(|argument| argument == "--watch");
+    let profile = args.iter().any(|argument| argument == "--profile");
+    let opcode_stats = args.iter().any(|argument| argument == "--opcode-stats");
+    let folded_path = args
+        .iter()
+        .find_map(|argument| argument.strip_prefix("--profile-folded="));
+    // scripts run by hand are trusted unless asked otherwise
+    let capabilities = if args.iter().any(|argument| argument == "--sandbox") {
+        Capabilities::none()
+    } else {
+        Capabilities::all()
+    };
+    let palette = ColorChoice::from_args(&args).palette();
+    let coercion = if args.iter().any(|argument| argument == "--strict") {
+        Coercion::Strict
     } else {
-        repl()
+        Coercion::Coercive
+    };
+
+    match path {
+        Some(path) if watch => watch_file(path, capabilities, palette, coercion),
+        Some(path) if profile || folded_path.is_some() || opcode_stats => profile_file(
+            path,
+            profile || folded_path.is_some(),
+            folded_path,
+            opcode_stats,
+            capabilities,
+            palette,
+            coercion,
+        ),
+        Some(path) => run_file(path, capabilities, palette, coercion),
+        None => repl(capabilities, palette, coercion),
     }
 }
 
-fn repl() {
-    let native_functions = natives::common_native_functions();
-    let mut interpreter = VirtualMachine::new();
-    interpreter.load_natives(native_functions);
+fn new_interpreter(
+    capabilities: Capabilities,
+    palette: Palette,
+    coercion: Coercion,
+) -> VirtualMachine {
+    let mut interpreter = VirtualMachine::with_prelude(Prelude::natives());
+    interpreter.set_capabilities(capabilities);
+    interpreter.set_coercion(coercion);
+    interpreter.set_palette(palette);
+    interpreter
+}
+
+fn repl(capabilities: Capabilities, palette: Palette, coercion: Coercion) {
+    let mut interpreter = new_interpreter(capabilities, palette, coercion);
     let mut offset = 0 as Instruction;
 
     loop {
@@ -38,7 +92,10 @@ fn repl() {
             break;
         }
 
-        let program = compiler::compile(&input, "").unwrap();
+        // a line that does not compile is reported and the session goes on
+        let Some(program) = compile_source(&input, "", palette) else {
+            continue;
+        };
         let new_offset = program.instructions.len() as Instruction;
 
         interpreter.load_program(program);
@@ -48,7 +105,20 @@ fn repl() {
     }
 }
 
-fn run_file(path: &str) {
+fn run_file(path: &str, capabilities: Capabilities, palette: Palette, coercion: Coercion) {
+    // bytecode brings the debug info of its sidecar, if there is one, for error locations
+    if path.ends_with(BYTECODE_EXTENSION) {
+        match file::read_program_file(path) {
+            Ok(program) => {
+                let mut interpreter = new_interpreter(capabilities, palette, coercion);
+                interpreter.load_program(program);
+                interpreter.start_vm(0);
+            }
+            Err(err) => println!("{}: {}", path, err),
+        }
+        return;
+    }
+
     let result = fs::read_to_string(path);
     if let Err(err) = result {
         println!("{}", err);
@@ -57,13 +127,114 @@ fn run_file(path: &str) {
 
     let code = result.unwrap();
 
-    let natives = natives::common_native_functions();
-    let mut interpreter = VirtualMachine::new();
-    interpreter.load_natives(natives);
+    let mut interpreter = new_interpreter(capabilities, palette, coercion);
     let offset = 0 as Instruction;
 
-    let program = compiler::compile(&code, path).unwrap();
+    let Some(program) = compile_source(&code, path, palette) else {
+        exit(1)
+    };
 
     interpreter.load_program(program);
     interpreter.start_vm(offset);
 }
+
+/// run the file printing the time spent in each function and how often each opcode ran.
+/// the call stacks are written in the folded format used by flamegraph tools when a path is given
+fn profile_file(
+    path: &str,
+    time_functions: bool,
+    folded_path: Option<&str>,
+    count_opcodes: bool,
+    capabilities: Capabilities,
+    palette: Palette,
+    coercion: Coercion,
+) {
+    let code = match fs::read_to_string(path) {
+        Ok(code) => code,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
+    let Some(program) = compile_source(&code, path, palette) else {
+        exit(1)
+    };
+
+    let mut interpreter = new_interpreter(capabilities, palette, coercion);
+    interpreter.load_program(program);
+
+    let mut profilers = (
+        time_functions.then(FunctionProfiler::new),
+        count_opcodes.then(OpcodeProfiler::new),
+    );
+    interpreter.start_vm_with_profiler(0, &mut profilers);
+
+    let (function_profiler, opcode_profiler) = profilers;
+    if let Some(opcode_profiler) = opcode_profiler {
+        eprintln!("{}", opcode_profiler);
+    }
+
+    let Some(profiler) = function_profiler else {
+        return;
+    };
+    eprintln!("{}", profiler.report(&interpreter));
+
+    if let Some(folded_path) = folded_path {
+        let written = fs::File::create(folded_path)
+            .and_then(|mut file| profiler.write_folded(&interpreter, &mut file));
+        if let Err(err) = written {
+            eprintln!("could not write {}: {}", folded_path, err);
+        }
+    }
+}
+
+/// run the file again every time it is saved, errors are reported without exiting
+fn watch_file(path: &str, capabilities: Capabilities, palette: Palette, coercion: Coercion) {
+    let mut interpreter = new_interpreter(capabilities, palette, coercion);
+    let mut watcher = FileWatcher::new(&[path]);
+
+    loop {
+        interpreter.reset();
+        run_watched_file(&mut interpreter, path, palette);
+
+        eprintln!("[watching {} for changes]", path);
+        watcher.wait_for_change();
+    }
+}
+
+fn run_watched_file(interpreter: &mut VirtualMachine, path: &str, palette: Palette) {
+    let code = match fs::read_to_string(path) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        }
+    };
+
+    let Some(program) = compile_source(&code, path, palette) else {
+        return;
+    };
+
+    // the runtime error is already printed by the virtual machine
+    interpreter.load_program(program);
+    interpreter.start_vm(0);
+}
+
+/// compile the source printing its warnings, or print every error and return None
+fn compile_source(code: &str, path: &str, palette: Palette) -> Option<Program> {
+    match compiler::compile_with_warnings(code, path) {
+        Ok((program, warnings)) => {
+            for warning in &warnings {
+                eprintln!("{}", warning.render_with(palette));
+            }
+            Some(program)
+        }
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}", error.render_with(palette));
+            }
+            None
+        }
+    }
+}
@@ -1,98 +1,164 @@
+use core::{error::Error, fmt::Display, ops::RangeInclusive};
+
+/// the opcodes, numbered explicitly since the numbers are stored in bytecode files. a number
+/// is never reused or moved, a new opcode takes one of the `RESERVED_OPCODES` of its category
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpCode {
-    NoInstruction,
+    NoInstruction = 0,
     /// Copy a value between registers(MOVE A <- B)
-    Move,
+    Move = 1,
     /// Load a constant into a register (LOAD A <- K)
-    LoadK,
+    LoadK = 2,
     /// Load nil values into a range of registers
-    LoadNil,
+    LoadNil = 3,
     /// Load Boolean values into a register
-    LoadBool,
+    LoadBool = 4,
     /// Load int32 values into a register
-    LoadInt32,
+    LoadInt32 = 5,
     /// Load int64 values into a register
-    LoadInt64,
+    LoadInt64 = 6,
     /// Load float32 values into a register
-    LoadFloat32,
+    LoadFloat32 = 7,
     /// Load float64 values into the register
-    LoadFloat64,
+    LoadFloat64 = 8,
     /// Load a return value (LOADRETURN DR SR1)
-    LoadReturn,
+    LoadReturn = 9,
     /// Clear return value in register
-    ClearReturn,
+    ClearReturn = 10,
     /// Prepare an object method for calling
-    This,
+    This = 11,
     /// Addition operator
-    Add,
+    Add = 12,
     /// Subtraction operator
-    Sub,
+    Sub = 13,
     /// Multiplication operator
-    Mul,
+    Mul = 14,
     /// Division operator
-    Div,
+    Div = 15,
     /// Modulus (remainder) operator
-    Mod,
+    Mod = 16,
     /// Exponentation operator
-    Pow,
+    Pow = 17,
     /// Unary Minus
-    Neg,
+    Neg = 18,
     /// Logical Not
-    Not,
+    Not = 19,
     /// Logical And
-    And,
+    And = 20,
     /// Logical Or
-    Or,
+    Or = 21,
     /// Less than Test (Jump if false) (A < B) ? Skip Jump : Jump
-    Less,
+    Less = 22,
     /// Less than or equal Test (Jump if false) (A < B) ? Skip Jump : Jump
-    LessEqual,
+    LessEqual = 23,
     /// Equality test
-    Equal,
+    Equal = 24,
     /// Jump if a condition returned false
-    JumpFalse,
+    JumpFalse = 25,
     /// Unconditional Jump with Offset
-    Jump,
+    Jump = 26,
     /// Define Global Variable by looking up variable name
-    DefineGlobalIndirect,
+    DefineGlobalIndirect = 27,
     /// Store value in global variable by looking up variable name
-    StoreGlobalIndirect,
+    StoreGlobalIndirect = 28,
     /// Load value from Global Variable looking up variable name
-    LoadGlobalIndirect,
+    LoadGlobalIndirect = 29,
     /// Load Global Variable
-    LoadGlobal,
+    LoadGlobal = 30,
     /// Allocate space for local variables
-    AllocateLocal,
+    AllocateLocal = 31,
     /// Deallocate space for local variables
-    DeallocateLocal,
+    DeallocateLocal = 32,
     /// Store local variable
-    StoreLocal,
+    StoreLocal = 33,
     /// Load local variable
-    LoadLocal,
+    LoadLocal = 34,
     /// Print value in register
-    Print,
+    Print = 35,
     /// Invoke call
-    Invoke,
+    Invoke = 36,
     /// While loop
-    While,
+    While = 37,
     /// Unconditional Loop
-    Loop,
+    Loop = 38,
     /// Exit loop
-    Break,
+    Break = 39,
     /// Push new stack frame
-    NewFrame,
+    NewFrame = 40,
     /// Return null
-    ReturnNone,
+    ReturnNone = 41,
     /// Return value
-    ReturnVal,
+    ReturnVal = 42,
+    /// Jump if a value is None
+    JumpNone = 43,
     /// Stop the interpreter
-    Halt,
+    Halt = 44,
+    /// Square root in place
+    Sqrt = 45,
+    /// Absolute value in place
+    Abs = 46,
+    /// Round down in place
+    Floor = 47,
+    /// Round up in place
+    Ceil = 48,
+    /// Unary plus, an error unless the register holds a number
+    Pos = 49,
+    /// Add a small immediate to a local slot in place
+    Inc = 50,
+    /// Subtract a small immediate from a local slot in place
+    Dec = 51,
+    /// Test a counting loop before its first run, taking the jump after it when it does not run
+    ForPrep = 52,
+    /// Step the index of a counting loop, taking the jump after it while it runs
+    ForLoop = 53,
 }
 
-pub const BYTECODE_COUNT: u32 = 44;
+pub const BYTECODE_COUNT: u32 = 54;
+
+/// opcodes fit in the top 6 bits of an instruction
+pub const OPCODE_LIMIT: u32 = 64;
+
+/// the numbers hosts give their own opcodes, see `extensions::Extensions`
+pub const HOST_OPCODES: RangeInclusive<u32> = 60..=63;
+
+/// a category of opcodes kept free for, and the numbers it may take
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReservedOpCodes {
+    pub category: &'static str,
+    pub numbers: RangeInclusive<u32>,
+}
+
+/// the numbers after `BYTECODE_COUNT`, kept for the categories of opcodes still to come.
+/// a program using one does not verify
+pub const RESERVED_OPCODES: [ReservedOpCodes; 4] = [
+    ReservedOpCodes {
+        category: "collections",
+        numbers: 54..=56,
+    },
+    ReservedOpCodes {
+        category: "exceptions",
+        numbers: 57..=58,
+    },
+    ReservedOpCodes {
+        category: "coroutines",
+        numbers: 59..=59,
+    },
+    ReservedOpCodes {
+        category: "host extensions",
+        numbers: HOST_OPCODES,
+    },
+];
+
+/// the category an opcode number is reserved for
+pub fn reserved_category(opcode: u32) -> Option<&'static str> {
+    RESERVED_OPCODES
+        .iter()
+        .find(|reserved| reserved.numbers.contains(&opcode))
+        .map(|reserved| reserved.category)
+}
 
-pub const BYTECODE_LOOKUP_TABLE: [OpCode; 44] = [
+pub const BYTECODE_LOOKUP_TABLE: [OpCode; 54] = [
     OpCode::NoInstruction,
     OpCode::Move,
     OpCode::LoadK,
@@ -136,7 +202,17 @@ pub const BYTECODE_LOOKUP_TABLE: [OpCode; 44] = [
     OpCode::NewFrame,
     OpCode::ReturnNone,
     OpCode::ReturnVal,
+    OpCode::JumpNone,
     OpCode::Halt,
+    OpCode::Sqrt,
+    OpCode::Abs,
+    OpCode::Floor,
+    OpCode::Ceil,
+    OpCode::Pos,
+    OpCode::Inc,
+    OpCode::Dec,
+    OpCode::ForPrep,
+    OpCode::ForLoop,
 ];
 
 impl OpCode {
@@ -144,4 +220,105 @@ impl OpCode {
     pub fn to_u32(&self) -> u32 {
         *self as u32
     }
+
+    /// number of words after the instruction holding its operand, the immediate number of the
+    /// wide loads
+    pub fn operand_words(&self) -> usize {
+        match self {
+            OpCode::LoadFloat32 | OpCode::LoadInt32 | OpCode::ForPrep | OpCode::ForLoop => 1,
+            OpCode::LoadFloat64 | OpCode::LoadInt64 => 2,
+            _ => 0,
+        }
+    }
+
+    /// conditional opcodes, which take or skip the jump right after their operand words
+    pub fn takes_next_jump(&self) -> bool {
+        matches!(
+            self,
+            OpCode::JumpFalse | OpCode::JumpNone | OpCode::ForPrep | OpCode::ForLoop
+        )
+    }
+
+    /// opcodes that are numbered but not run by the virtual machine yet, running one is an
+    /// illegal instruction
+    pub fn is_reserved(&self) -> bool {
+        matches!(
+            self,
+            OpCode::ClearReturn
+                | OpCode::This
+                | OpCode::And
+                | OpCode::Or
+                | OpCode::While
+                | OpCode::Loop
+                | OpCode::Break
+                | OpCode::NewFrame
+        )
+    }
+}
+
+/// an opcode value with no matching `OpCode`, usually from a corrupted instruction word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidOpCode(pub u32);
+
+impl Display for InvalidOpCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid opcode ({:#x})", self.0)
+    }
+}
+
+impl Error for InvalidOpCode {}
+
+impl TryFrom<u32> for OpCode {
+    type Error = InvalidOpCode;
+
+    /// checked lookup of a decoded opcode, the virtual machine skips this for verified programs
+    fn try_from(opcode: u32) -> Result<Self, Self::Error> {
+        BYTECODE_LOOKUP_TABLE
+            .get(opcode as usize)
+            .copied()
+            .ok_or(InvalidOpCode(opcode))
+    }
+}
+
+#[cfg(test)]
+mod bytecode_tests {
+    use super::{
+        reserved_category, InvalidOpCode, OpCode, BYTECODE_COUNT, BYTECODE_LOOKUP_TABLE,
+        OPCODE_LIMIT, RESERVED_OPCODES,
+    };
+
+    #[test]
+    fn test_opcode_conversion() {
+        for opcode in BYTECODE_LOOKUP_TABLE {
+            assert_eq!(OpCode::try_from(opcode.to_u32()), Ok(opcode));
+        }
+
+        assert_eq!(
+            OpCode::try_from(BYTECODE_COUNT),
+            Err(InvalidOpCode(BYTECODE_COUNT))
+        );
+        assert_eq!(OpCode::try_from(63), Err(InvalidOpCode(63)));
+    }
+
+    #[test]
+    fn test_opcode_numbers_are_stable() {
+        // numbers stored in bytecode files, changing one breaks every compiled program
+        assert_eq!(OpCode::Move as u32, 1);
+        assert_eq!(OpCode::Jump as u32, 26);
+        assert_eq!(OpCode::Invoke as u32, 36);
+        assert_eq!(OpCode::Halt as u32, 44);
+        assert_eq!(OpCode::ForLoop as u32, BYTECODE_COUNT - 1);
+
+        // every number up to the limit is an opcode or reserved, and only one of them
+        for number in 0..OPCODE_LIMIT {
+            let known = OpCode::try_from(number).is_ok();
+            let reserved = reserved_category(number).is_some();
+            assert!(known != reserved, "opcode number {}", number);
+        }
+
+        assert_eq!(
+            RESERVED_OPCODES.last().unwrap().numbers.end() + 1,
+            OPCODE_LIMIT
+        );
+    }
 }
@@ -1,25 +1,34 @@
-use std::collections::VecDeque;
+use alloc::collections::VecDeque;
 
-use rustc_hash::FxHashMap;
+use crate::collections::Map;
 
 /// for caching memory locations with an id
 pub struct MemoryCache {
     /// The key is the id for the specific cache, the value is the Memory address for the id
-    map: FxHashMap<usize, usize>,
+    /// along with the version it was cached in
+    map: Map<usize, (usize, u32)>,
     id_queue: VecDeque<usize>,
+    /// entries cached in an older version are stale
+    version: u32,
 }
 
 impl MemoryCache {
     #[inline(always)]
     pub fn new(capacity: usize) -> Self {
         Self {
-            map: FxHashMap::default(),
+            map: Map::default(),
             id_queue: VecDeque::with_capacity(capacity),
+            version: 0,
         }
     }
 
     #[inline(always)]
     pub fn add_cache(&mut self, id: usize, address: usize) {
+        // a stale entry is replaced in place rather than queued twice
+        if self.map.insert(id, (address, self.version)).is_some() {
+            return;
+        }
+
         // If queue has reached capacity remove the earliest added cache
         if self.id_queue.len() == self.id_queue.capacity() {
             let discarded_id = self.id_queue.pop_front();
@@ -29,12 +38,27 @@ impl MemoryCache {
         }
 
         self.id_queue.push_back(id);
-        self.map.insert(id, address);
     }
 
     #[inline(always)]
-    pub fn get_cache(&self, id: &usize) -> Option<&usize> {
-        self.map.get(id)
+    pub fn get_cache(&self, id: &usize) -> Option<usize> {
+        match self.map.get(id) {
+            Some(&(address, version)) if version == self.version => Some(address),
+            _ => None,
+        }
+    }
+
+    /// forget every id cached to the address, for when the address stops holding what the ids refer to
+    pub fn invalidate_address(&mut self, address: usize) {
+        self.map
+            .retain(|_, (cached_address, _)| *cached_address != address);
+        let map = &self.map;
+        self.id_queue.retain(|id| map.contains_key(id));
+    }
+
+    /// make every cached address stale at once, for when many addresses move together
+    pub fn invalidate_all(&mut self) {
+        self.version = self.version.wrapping_add(1);
     }
 }
 
@@ -44,3 +68,27 @@ impl Default for MemoryCache {
         Self::new(16)
     }
 }
+
+#[cfg(test)]
+mod cache_tests {
+    use super::MemoryCache;
+
+    #[test]
+    fn test_invalidation() {
+        let mut cache = MemoryCache::new(4);
+        cache.add_cache(1, 10);
+        cache.add_cache(2, 10);
+        cache.add_cache(3, 30);
+
+        cache.invalidate_address(10);
+        assert_eq!(cache.get_cache(&1), None);
+        assert_eq!(cache.get_cache(&2), None);
+        assert_eq!(cache.get_cache(&3), Some(30));
+
+        cache.invalidate_all();
+        assert_eq!(cache.get_cache(&3), None);
+
+        cache.add_cache(3, 31);
+        assert_eq!(cache.get_cache(&3), Some(31));
+    }
+}
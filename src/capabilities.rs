@@ -0,0 +1,78 @@
+use alloc::{format, string::String};
+use core::fmt::Display;
+
+/// something a script can do outside the virtual machine, natives that need one fail unless
+/// the embedder allowed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// read environment variables and the working directory
+    ReadEnvironment,
+    /// set environment variables and change the working directory of the whole process
+    WriteEnvironment,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 2] = [Capability::ReadEnvironment, Capability::WriteEnvironment];
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            Capability::ReadEnvironment => "read_environment",
+            Capability::WriteEnvironment => "write_environment",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// the capabilities allowed to a virtual machine, set with `VirtualMachine::set_capabilities`.
+/// nothing is allowed by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    allowed: u32,
+}
+
+impl Capabilities {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn all() -> Self {
+        Capability::ALL
+            .into_iter()
+            .fold(Self::none(), |capabilities, capability| {
+                capabilities.allow(capability)
+            })
+    }
+
+    pub fn allow(mut self, capability: Capability) -> Self {
+        self.allowed |= capability.bit();
+        self
+    }
+
+    pub fn deny(mut self, capability: Capability) -> Self {
+        self.allowed &= !capability.bit();
+        self
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.allowed & capability.bit() != 0
+    }
+
+    /// an error naming the native function when the capability is not allowed
+    pub fn require(&self, capability: Capability, function: &str) -> Result<(), String> {
+        if self.allows(capability) {
+            return Ok(());
+        }
+
+        Err(format!(
+            "'{}()' needs the {} capability, which is not allowed",
+            function, capability
+        ))
+    }
+}
@@ -0,0 +1,7 @@
+//! the maps of the virtual machine. `FxHashMap` needs the `std` feature, without it they are
+//! the ordered maps of `alloc`
+
+#[cfg(not(feature = "std"))]
+pub(crate) use alloc::collections::{BTreeMap as Map, BTreeSet as Set};
+#[cfg(feature = "std")]
+pub(crate) use rustc_hash::{FxHashMap as Map, FxHashSet as Set};
@@ -1,21 +1,134 @@
+//! bytecode generation for the nova_tw syntax. the error and warning types and the
+//! optimizer do not depend on nova_tw so other frontends can use them the same way
+
+pub mod error;
+#[cfg(feature = "nova_tw")]
 mod generator;
-use nova_tw::language::{errors, AstParser, Scanner};
+pub mod optimizer;
+#[cfg(feature = "nova_tw")]
+mod register_allocator;
+pub mod warning;
+
+#[cfg(feature = "nova_tw")]
+use nova_tw::language::{errors, AstParser, Scanner, Statement};
+
+#[cfg(feature = "nova_tw")]
+use crate::{
+    error::NovaError,
+    program::{ColumnSpan, Program},
+};
 
-use crate::program::Program;
+#[cfg(feature = "nova_tw")]
+use self::{
+    error::{CompileError, CompileErrorKind},
+    optimizer::{OptLevel, OptStats},
+    warning::CompileWarning,
+};
 
+#[cfg(feature = "nova_tw")]
 #[allow(dead_code)]
 pub fn compile(source: &str, filename: &str) -> Result<Program, errors::Error> {
+    let ast = parse(source, filename)?;
+
+    let generator = generator::BytecodeGenerator::new();
+    match generator.generate_bytecode(&ast) {
+        Ok((mut program, _, _)) => {
+            attach_statement_columns(&mut program, source, filename);
+            Ok(program)
+        }
+        Err(mut errors) => {
+            let messages: Vec<String> = errors
+                .iter_mut()
+                .map(|error| {
+                    error.attach_snippet(source);
+                    error.render()
+                })
+                .collect();
+            Err(errors::Error::Interpret(messages.join("\n")))
+        }
+    }
+}
+
+/// compile the source and return the non fatal warnings alongside the program.
+/// on failure every error found in the source is returned
+#[cfg(feature = "nova_tw")]
+pub fn compile_with_warnings(
+    source: &str,
+    filename: &str,
+) -> Result<(Program, Vec<CompileWarning>), Vec<CompileError>> {
+    compile_with_opt_level(source, filename, OptLevel::default())
+        .map(|(program, warnings, _)| (program, warnings))
+}
+
+/// compile the source running only the passes enabled by `opt_level`, returning the
+/// warnings and what the optimizer changed alongside the program
+#[cfg(feature = "nova_tw")]
+pub fn compile_with_opt_level(
+    source: &str,
+    filename: &str,
+    opt_level: OptLevel,
+) -> Result<(Program, Vec<CompileWarning>, OptStats), Vec<CompileError>> {
+    let ast = parse(source, filename).map_err(|error| {
+        vec![CompileError::new(
+            CompileErrorKind::Syntax,
+            error.to_string(),
+            filename.to_string(),
+            0,
+        )]
+    })?;
+
+    let generator = generator::BytecodeGenerator::new().with_opt_level(opt_level);
+    let (mut program, warnings, stats) =
+        generator.generate_bytecode(&ast).map_err(|mut errors| {
+            for error in errors.iter_mut() {
+                error.attach_snippet(source);
+            }
+            errors
+        })?;
+
+    attach_statement_columns(&mut program, source, filename);
+    Ok((program, warnings, stats))
+}
+
+/// generate bytecode for statements that were already parsed, for tools that build
+/// or transform the syntax tree themselves. the errors carry no source snippet and
+/// only the first one is returned
+#[cfg(feature = "nova_tw")]
+pub fn compile_ast(statements: &[Statement]) -> Result<Program, NovaError> {
+    let generator = generator::BytecodeGenerator::new();
+    match generator.generate_bytecode(statements) {
+        Ok((program, _, _)) => Ok(program),
+        Err(errors) => Err(errors
+            .into_iter()
+            .next()
+            .map(NovaError::Compile)
+            .expect("compilation failed without an error")),
+    }
+}
+
+#[cfg(feature = "nova_tw")]
+fn parse(source: &str, filename: &str) -> Result<Vec<Statement>, errors::Error> {
     let scanner = Scanner::new();
     let tokens = scanner.scan_tokens_with_filename(source, filename)?;
 
-    let ast = AstParser::new(tokens).parse_ast()?;
+    AstParser::new(tokens).parse_ast()
+}
 
-    let generator = generator::BytecodeGenerator::new();
-    let program = generator.generate_bytecode(&ast);
+/// nova_tw only reports the line of a statement, so the instructions of a line in the
+/// compiled file are mapped to the columns its statement takes up
+#[cfg(feature = "nova_tw")]
+fn attach_statement_columns(program: &mut Program, source: &str, filename: &str) {
+    let lines: Vec<&str> = source.lines().collect();
 
-    if let Err(error) = program {
-        return Err(errors::Error::Interpret(error));
-    }
+    for line_definition in program.line_definitions.iter_mut() {
+        if line_definition.columns.is_some() || line_definition.source_file != filename {
+            continue;
+        }
 
-    Ok(program.unwrap())
+        line_definition.columns = line_definition
+            .source_line
+            .checked_sub(1)
+            .and_then(|index| lines.get(index))
+            .and_then(|line| ColumnSpan::of_trimmed(line));
+    }
 }
@@ -0,0 +1,133 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::fmt::Display;
+
+use crate::{
+    diagnostics::{write_excerpt, Diagnostic, Palette, Style},
+    program::ColumnSpan,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompileErrorKind {
+    /// the source could not be scanned or parsed
+    Syntax,
+    /// an operator the bytecode generator has no instruction for
+    UnsupportedOperator,
+    /// a call expression that cannot be compiled
+    InvalidCall,
+    /// an expression the bytecode generator cannot compile yet
+    UnsupportedExpression,
+    /// the generator reached an inconsistent state
+    Internal,
+}
+
+impl Display for CompileErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            CompileErrorKind::Syntax => "syntax error",
+            CompileErrorKind::UnsupportedOperator => "unsupported operator",
+            CompileErrorKind::InvalidCall => "invalid call",
+            CompileErrorKind::UnsupportedExpression => "unsupported expression",
+            CompileErrorKind::Internal => "internal compiler error",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// an error found while compiling, located at the statement that caused it
+#[derive(Debug, Clone)]
+pub struct CompileError {
+    pub kind: CompileErrorKind,
+    pub message: String,
+    pub file: String,
+    /// 1 based source line, 0 when the location is unknown
+    pub line: usize,
+    /// 1 based column when the frontend reports one
+    pub column: Option<usize>,
+    /// the text of the offending source line
+    pub snippet: Option<String>,
+}
+
+impl CompileError {
+    pub fn new(kind: CompileErrorKind, message: String, file: String, line: usize) -> Self {
+        Self {
+            kind,
+            message,
+            file,
+            line,
+            column: None,
+            snippet: None,
+        }
+    }
+
+    /// fill in the snippet from the source the error was found in
+    pub fn attach_snippet(&mut self, source: &str) {
+        if self.line == 0 {
+            return;
+        }
+
+        self.snippet = source
+            .lines()
+            .nth(self.line - 1)
+            .map(|line| line.to_string());
+    }
+
+    /// render the error with the offending line underlined, e.g.
+    ///
+    /// ```text
+    /// error: unsupported operator: unhandled unary operator: Not
+    ///  --> main.nova:2
+    ///   |
+    /// 2 | b := not a
+    ///   | ^^^^^^^^^^
+    /// ```
+    pub fn render(&self) -> String {
+        self.render_with(Palette::plain())
+    }
+}
+
+impl Diagnostic for CompileError {
+    fn render_with(&self, palette: Palette) -> String {
+        let mut rendered = format!(
+            "{}: {}: {}",
+            palette.paint(Style::Error, "error"),
+            self.kind,
+            self.message
+        );
+        let arrow = palette.paint(Style::Note, "-->");
+
+        if self.line == 0 {
+            if !self.file.is_empty() {
+                rendered.push_str(&format!("\n {} {}", arrow, self.file));
+            }
+            return rendered;
+        }
+
+        rendered.push_str(&format!("\n {} {}:{}", arrow, self.file, self.line));
+        if let Some(column) = self.column {
+            rendered.push_str(&format!(":{}", column));
+        }
+
+        if let Some(snippet) = &self.snippet {
+            write_excerpt(
+                &mut rendered,
+                palette,
+                Style::Error,
+                self.line,
+                snippet,
+                self.column.map(ColumnSpan::at),
+            );
+        }
+
+        rendered
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
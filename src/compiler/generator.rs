@@ -1,25 +1,101 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use nova_tw::language::{
-    Expression, ExpressionVisitor, Object, Statement, StatementVisitor, TokenType,
+    AstParser, Block, Expression, ExpressionVisitor, Object, Scanner, Statement, StatementVisitor,
+    TokenType, WhileLoop,
 };
 
 use crate::{
     bytecode::OpCode,
-    instruction::{instruction_decoder, Instruction, InstructionBuilder},
+    instruction::{
+        instruction_decoder, Instruction, InstructionBuilder, FOR_DESCENDING, FOR_INCLUSIVE,
+        FOR_LIMIT_IMMEDIATE,
+    },
     object::{NovaFunction, NovaObject},
-    program::{LineDefinition, Program},
+    program::{LineDefinition, LocalSymbol, Program},
+};
+
+use super::{
+    error::{CompileError, CompileErrorKind},
+    optimizer::{self, OptLevel, OptStats},
+    register_allocator::{Location, RegisterAllocator, VirtualRegister, GENERAL_REGISTERS},
+    warning::{CompileWarning, WarningKind},
 };
 
+/// parameter list of a function declared in the program, used to resolve named arguments
+#[derive(Clone)]
+struct FunctionSignature {
+    parameters: Vec<String>,
+}
+
+impl FunctionSignature {
+    fn new(function_statement: &nova_tw::language::function::FunctionStatement) -> Self {
+        Self {
+            parameters: function_statement
+                .parameters
+                .iter()
+                .map(|parameter| parameter.object.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// locals of the enclosing code, put aside while a function body is generated
+struct EnclosingLocals {
+    local_variable_indices: Vec<HashMap<String, u32>>,
+    declared_locals: Vec<Vec<DeclaredLocal>>,
+    local_variable_count: u32,
+    max_local_count: u32,
+    registers: RegisterAllocator,
+    scope: u32,
+    after_return: bool,
+}
+
+/// a local variable tracked for the unused local diagnostic
+struct DeclaredLocal {
+    name: String,
+    index: u32,
+    line: usize,
+    file: String,
+    used: bool,
+    /// index of the local in the local symbols of the program
+    symbol: usize,
+}
+
 pub struct BytecodeGenerator {
     program: Program,
-    error: Option<String>,
-    temp_stack: Vec<()>,
+    /// first error of the statement currently being compiled
+    error: Option<CompileError>,
+    errors: Vec<CompileError>,
+    registers: RegisterAllocator,
     _frame_stack: Vec<()>,
     global_variables: HashMap<String, u32>,
+    /// every top level variable of the program, including the ones declared further down
+    declared_globals: HashSet<String>,
+    function_signatures: HashMap<String, FunctionSignature>,
+    /// name and immutable index of each function whose body is being generated
+    enclosing_functions: Vec<(String, Instruction)>,
+    /// local slots in use by the function being generated, slots are reused once their block ends
     local_variable_count: u32,
+    /// most slots the function being generated needs at once, reserved when its frame is created
+    max_local_count: u32,
     local_variable_indices: Vec<HashMap<String, u32>>,
+    declared_locals: Vec<Vec<DeclaredLocal>>,
     scope: u32,
+    warnings: Vec<CompileWarning>,
+    current_line: usize,
+    current_file: String,
+    /// set after a return statement until the enclosing block ends
+    after_return: bool,
+    /// top level code spilled registers, so the main frame needs local slots reserved
+    main_spills_locals: bool,
+    /// numbers loaded so far, a number seen again is loaded from the immutable pool
+    numeric_literals: HashSet<(bool, u64)>,
+    opt_level: OptLevel,
+    opt_stats: OptStats,
 }
 
 impl BytecodeGenerator {
@@ -27,32 +103,114 @@ impl BytecodeGenerator {
         Self {
             program: Program::default(),
             error: None,
-            temp_stack: Vec::new(),
+            errors: Vec::new(),
+            registers: RegisterAllocator::default(),
             _frame_stack: Vec::new(),
             global_variables: HashMap::new(),
+            declared_globals: HashSet::new(),
+            function_signatures: HashMap::new(),
+            enclosing_functions: Vec::new(),
             local_variable_count: 0,
+            max_local_count: 0,
             local_variable_indices: Vec::new(),
+            declared_locals: Vec::new(),
             scope: 0,
+            warnings: Vec::new(),
+            current_line: 0,
+            current_file: String::new(),
+            after_return: false,
+            main_spills_locals: false,
+            numeric_literals: HashSet::new(),
+            opt_level: OptLevel::default(),
+            opt_stats: OptStats::default(),
         }
     }
 
-    pub fn generate_bytecode(mut self, statements: &Vec<Statement>) -> Result<Program, String> {
+    pub fn with_opt_level(mut self, opt_level: OptLevel) -> Self {
+        self.opt_level = opt_level;
+        self
+    }
+
+    /// generate the program along with any non fatal warnings found on the way and what the
+    /// optimizer changed. compilation continues past a failing statement so every error is
+    /// reported at once
+    pub fn generate_bytecode(
+        mut self,
+        statements: &[Statement],
+    ) -> Result<(Program, Vec<CompileWarning>, OptStats), Vec<CompileError>> {
+        self.declare_functions(statements);
+
         for statement in statements {
             self.execute(statement);
-            if let Some(error) = self.error {
-                return Err(error);
-            }
+        }
+
+        if !self.errors.is_empty() {
+            return Err(self.errors);
         }
 
         self.program
             .instructions
             .push(InstructionBuilder::new_halt_instruction());
 
-        Ok(self.program)
+        optimizer::optimize(&mut self.program, self.opt_level, &mut self.opt_stats);
+
+        if self.main_spills_locals {
+            self.reserve_main_locals();
+        }
+
+        Ok((self.program, self.warnings, self.opt_stats))
+    }
+
+    /// allocate the local slots used by top level code before the first instruction runs
+    fn reserve_main_locals(&mut self) {
+        let allocate = InstructionBuilder::new_allocate_local(self.max_local_count);
+        self.program.instructions.insert(0, allocate);
+
+        // jumps are relative, only function addresses and the line table move
+        for immutable in Arc::make_mut(&mut self.program.immutables).iter_mut() {
+            if let NovaObject::NovaFunction(function) = immutable {
+                function.address += 1;
+            }
+        }
+
+        for line_definition in self.program.line_definitions.iter_mut() {
+            line_definition.last_instruction += 1;
+        }
+    }
+
+    /// register the signature of every top level function before generating any code,
+    /// so calls that come before a definition can still use named arguments.
+    /// invalid declarations are skipped here and reported when they are compiled
+    fn declare_functions(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            if let Statement::VarDeclaration(var_declaration) = statement {
+                let name = var_declaration.name.object.to_string();
+                self.declared_globals.insert(name);
+            }
+
+            let Statement::Function(function_statement) = statement else {
+                continue;
+            };
+
+            let function_name = function_statement.name.object.to_string();
+            let string_immutable = NovaObject::String(Box::new(function_name.clone()));
+            let _ = self.get_immutable_index(&string_immutable);
+
+            self.function_signatures
+                .insert(function_name, FunctionSignature::new(function_statement));
+        }
     }
 
     fn execute(&mut self, statement: &Statement) {
         statement.accept(self);
+
+        if let Some(error) = self.error.take() {
+            self.errors.push(error);
+            self.after_return = false;
+        }
+
+        // temporaries never outlive the statement that made them
+        self.registers.reset();
     }
 
     fn reference_source_lines_and_filename(
@@ -74,21 +232,292 @@ impl BytecodeGenerator {
             last_instruction: instruction_number,
             source_line,
             source_file: filename,
+            columns: None,
         };
 
         self.program.line_definitions.push(line_definition)
     }
 
-    fn evaluate(&mut self, expression: &Expression) {
+    /// record the location of the statement being compiled, warning if it can never run
+    fn set_location(&mut self, line: usize, file: &str) {
+        self.current_line = line;
+        self.current_file = file.to_string();
+
+        if self.after_return {
+            // only report the first unreachable statement of a block
+            self.after_return = false;
+            self.generate_warning(WarningKind::UnreachableCode, "unreachable code".to_string());
+        }
+    }
+
+    fn generate_warning(&mut self, kind: WarningKind, message: String) {
+        self.warnings.push(CompileWarning {
+            kind,
+            message,
+            line: self.current_line,
+            file: self.current_file.clone(),
+        })
+    }
+
+    fn evaluate(&mut self, expression: &Expression) -> VirtualRegister {
         expression.accept(self)
     }
 
-    fn generate_error(&mut self, error: String) {
+    /// give a new value a register, spilling an older value if all of them are taken
+    fn allocate_register(&mut self) -> (VirtualRegister, Instruction) {
+        // a call result is left in the return register only until other code is generated
+        self.load_pending_return();
+
+        let register = self.take_free_register();
+        let value = self.registers.new_value(Location::Register(register));
+        (value, register)
+    }
+
+    fn take_free_register(&mut self) -> Instruction {
+        if let Some(register) = self.registers.free_register() {
+            return register;
+        }
+
+        match self.registers.spill_candidate() {
+            Some(value) => self.spill(value),
+            None => {
+                self.generate_error(
+                    CompileErrorKind::Internal,
+                    format!("expression needs more than {} registers", GENERAL_REGISTERS),
+                );
+                0
+            }
+        }
+    }
+
+    /// store a value in a spill slot and return the register it used
+    fn spill(&mut self, value: VirtualRegister) -> Instruction {
+        let Some(Location::Register(register)) = self.registers.location(value) else {
+            return 0;
+        };
+
+        let slot = self.registers.allocate_spill_slot();
+        let local_index = self.spill_slot_index(slot);
+        self.add_instruction(InstructionBuilder::new_store_local(register, local_index));
+        self.registers.move_to(value, Location::Spilled(slot));
+
+        register
+    }
+
+    /// local slot of a spill slot, spill slots come after the locals currently in scope
+    fn spill_slot_index(&mut self, slot: Instruction) -> Instruction {
+        let local_index = self.local_variable_count + slot;
+        self.max_local_count = self.max_local_count.max(local_index + 1);
+
+        if self.enclosing_functions.is_empty() {
+            self.main_spills_locals = true;
+        }
+
+        local_index
+    }
+
+    /// the register holding a value, loading it back first if it was spilled
+    /// or is still in the return register
+    fn use_register(&mut self, value: VirtualRegister) -> Instruction {
+        let location = self.registers.location(value);
+
+        if let Some(Location::Register(register)) = location {
+            return register;
+        }
+
+        self.registers.pin(value);
+        let register = self.take_free_register();
+        self.registers.unpin(value);
+
+        match location {
+            Some(Location::Spilled(slot)) => {
+                let local_index = self.local_variable_count + slot;
+                self.add_instruction(InstructionBuilder::new_load_local(register, local_index));
+            }
+            Some(Location::Return) => {
+                self.add_instruction(
+                    InstructionBuilder::new()
+                        .add_opcode(OpCode::LoadReturn)
+                        .add_destination_register(register)
+                        .build(),
+                );
+            }
+            _ => {
+                self.generate_error(
+                    CompileErrorKind::Internal,
+                    "use of a released register".to_string(),
+                );
+            }
+        }
+
+        self.registers.move_to(value, Location::Register(register));
+        register
+    }
+
+    /// registers for the operands of one instruction, none of them is spilled to make room
+    /// for another
+    fn use_registers(&mut self, values: &[VirtualRegister]) -> Vec<Instruction> {
+        for &value in values {
+            self.registers.pin(value);
+        }
+
+        let registers = values
+            .iter()
+            .map(|&value| self.use_register(value))
+            .collect();
+
+        for &value in values {
+            self.registers.unpin(value);
+        }
+
+        registers
+    }
+
+    /// copy a call result out of the return register before anything can overwrite it
+    fn load_pending_return(&mut self) {
+        if let Some(value) = self.registers.pending_return() {
+            self.use_register(value);
+        }
+    }
+
+    /// load a function by name for a call, locals and the enclosing function come
+    /// before the globals
+    fn load_function(&mut self, name: String) -> VirtualRegister {
+        let (value, destination) = self.allocate_register();
+
+        if let Some(index) = self.get_local_index(name.as_str()) {
+            self.mark_local_used(index);
+            self.add_instruction(InstructionBuilder::new_load_local(destination, index));
+        } else if let Some(function_index) = self.get_enclosing_function(&name) {
+            self.add_instruction(InstructionBuilder::new_load_constant_instruction(
+                destination,
+                function_index,
+            ));
+        } else {
+            let name = NovaObject::String(Box::new(name));
+            let name_index = self.get_immutable_index(&name);
+            self.add_instruction(InstructionBuilder::new_load_global_indirect(
+                destination,
+                name_index,
+            ));
+        }
+
+        value
+    }
+
+    /// place the arguments of a call in consecutive registers followed by the function,
+    /// moving them where needed. returns the register of the first argument
+    fn arrange_call(&mut self, values: &[VirtualRegister]) -> Instruction {
+        if values.len() > GENERAL_REGISTERS {
+            self.generate_error(
+                CompileErrorKind::InvalidCall,
+                format!(
+                    "a call can take at most {} arguments",
+                    GENERAL_REGISTERS - 1
+                ),
+            );
+            return 0;
+        }
+
+        self.load_pending_return();
+        let mut registers = self.use_registers(values);
+
+        let in_place = registers.windows(2).all(|pair| pair[1] == pair[0] + 1);
+        if in_place {
+            return registers[0];
+        }
+
+        for &value in values {
+            self.registers.pin(value);
+        }
+
+        // lowest block where every register is free or already holds one of the values
+        let start = loop {
+            let start = (0..=GENERAL_REGISTERS - values.len()).find(|&start| {
+                (start..start + values.len()).all(|register| {
+                    self.registers
+                        .value_in(register as Instruction)
+                        .is_none_or(|held| values.contains(&held))
+                })
+            });
+
+            if let Some(start) = start {
+                break start as Instruction;
+            }
+
+            // make room by spilling a value that is not part of the call
+            match self.registers.spill_candidate() {
+                Some(value) => {
+                    self.spill(value);
+                }
+                None => {
+                    self.generate_error(
+                        CompileErrorKind::Internal,
+                        "no registers left to arrange call arguments".to_string(),
+                    );
+                    break 0;
+                }
+            }
+        };
+
+        // move every value to its place, going through a free register to break cycles
+        let mut pending: Vec<usize> = (0..values.len())
+            .filter(|&index| registers[index] != start + index as Instruction)
+            .collect();
+
+        while !pending.is_empty() {
+            let ready = pending.iter().position(|&index| {
+                let target = start + index as Instruction;
+                !pending.iter().any(|&other| registers[other] == target)
+            });
+
+            let (index, target) = match ready {
+                Some(position) => {
+                    let index = pending.remove(position);
+                    (index, start + index as Instruction)
+                }
+                None => {
+                    let index = pending[0];
+                    match self.registers.free_register() {
+                        Some(free) => (index, free),
+                        None => {
+                            self.generate_error(
+                                CompileErrorKind::Internal,
+                                "no registers left to arrange call arguments".to_string(),
+                            );
+                            break;
+                        }
+                    }
+                }
+            };
+
+            self.add_instruction(InstructionBuilder::new_move_instruction(
+                target,
+                registers[index],
+            ));
+            self.registers
+                .move_to(values[index], Location::Register(target));
+            registers[index] = target;
+        }
+
+        for &value in values {
+            self.registers.unpin(value);
+        }
+
+        start
+    }
+
+    fn generate_error(&mut self, kind: CompileErrorKind, message: String) {
         if self.error.is_some() {
             return;
         }
 
-        self.error = Some(format!("[Bytecode Gen Error]: {}", error))
+        self.error = Some(CompileError::new(
+            kind,
+            message,
+            self.current_file.clone(),
+            self.current_line,
+        ))
     }
 
     fn get_immutable_index(&mut self, immutable: &NovaObject) -> Instruction {
@@ -99,7 +528,7 @@ impl BytecodeGenerator {
                 .position(|value| value == immutable)
                 .unwrap() as Instruction
         } else {
-            self.program.immutables.push(immutable.clone());
+            Arc::make_mut(&mut self.program.immutables).push(immutable.clone());
             self.program.immutables.len() as Instruction - 1
         }
     }
@@ -107,20 +536,92 @@ impl BytecodeGenerator {
     fn allocate_local(&mut self, name: &str) -> Instruction {
         let index = self.local_variable_count;
         self.local_variable_count += 1;
+        self.max_local_count = self.max_local_count.max(self.local_variable_count);
 
         let map = self.local_variable_indices.last_mut();
         if map.is_none() {
-            self.generate_error("Error allocating local variable".to_string());
+            self.generate_error(
+                CompileErrorKind::Internal,
+                "error allocating local variable".to_string(),
+            );
             return 0;
         }
 
         let map = map.unwrap();
         map.insert(name.to_string(), index);
 
+        // the end of the range is filled in when the scope ends
+        let start = self.program.instructions.len();
+        self.program.local_symbols.push(LocalSymbol {
+            name: name.to_string(),
+            function: self
+                .enclosing_functions
+                .last()
+                .map(|&(_, function)| function as usize),
+            slot: index,
+            start,
+            end: start,
+        });
+        let symbol = self.program.local_symbols.len() - 1;
+
+        if let Some(declared) = self.declared_locals.last_mut() {
+            declared.push(DeclaredLocal {
+                name: name.to_string(),
+                index,
+                line: self.current_line,
+                file: self.current_file.clone(),
+                used: false,
+                symbol,
+            });
+        }
+
         index
     }
 
-    fn get_local_index(&mut self, name: &str) -> Option<Instruction> {
+    fn mark_local_used(&mut self, index: Instruction) {
+        let declared = self
+            .declared_locals
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|local| local.index == index);
+
+        if let Some(local) = declared {
+            local.used = true;
+        }
+    }
+
+    fn begin_local_scope(&mut self) {
+        self.scope += 1;
+        self.local_variable_indices.push(HashMap::new());
+        self.declared_locals.push(Vec::new());
+    }
+
+    /// close the innermost scope, warning about locals that were never read
+    fn end_local_scope(&mut self) -> HashMap<String, u32> {
+        self.scope -= 1;
+        self.after_return = false;
+
+        let end = self.program.instructions.len();
+        for local in self.declared_locals.pop().unwrap_or_default() {
+            self.program.local_symbols[local.symbol].end = end;
+
+            if local.used || local.name.starts_with('_') {
+                continue;
+            }
+
+            self.warnings.push(CompileWarning {
+                kind: WarningKind::UnusedLocal,
+                message: format!("unused local variable '{}'", local.name),
+                line: local.line,
+                file: local.file,
+            });
+        }
+
+        self.local_variable_indices.pop().unwrap()
+    }
+
+    fn get_local_index(&self, name: &str) -> Option<Instruction> {
         let mut scope = self.local_variable_indices.len() as isize - 1;
 
         while scope >= 0 {
@@ -142,7 +643,46 @@ impl BytecodeGenerator {
         index as Instruction
     }
 
+    /// load a number with `LoadK` when it would need two extra words inline or has been
+    /// loaded before, returns false if it should be inlined instead
+    fn load_numeric_constant(
+        &mut self,
+        number: NovaObject,
+        wide: bool,
+        register_index: Instruction,
+    ) -> bool {
+        let key = match number {
+            NovaObject::Int64(number) => (false, number as u64),
+            NovaObject::Float64(number) => (true, number.to_bits()),
+            _ => return false,
+        };
+
+        let repeated = !self.numeric_literals.insert(key);
+        if !wide && !repeated {
+            return false;
+        }
+
+        // LoadK can only address the first 2^16 immutables
+        if !self.program.immutables.contains(&number)
+            && self.program.immutables.len() > u16::MAX as usize
+        {
+            return false;
+        }
+
+        let immutable_index = self.get_immutable_index(&number);
+        self.add_instruction(InstructionBuilder::new_load_constant_instruction(
+            register_index,
+            immutable_index,
+        ));
+        true
+    }
+
     fn add_integer(&mut self, number: i64, register_index: Instruction) {
+        let wide = i32::try_from(number).is_err();
+        if self.load_numeric_constant(NovaObject::Int64(number), wide, register_index) {
+            return;
+        }
+
         if number > i32::MAX as i64 {
             self.add_instruction(
                 InstructionBuilder::new()
@@ -164,61 +704,815 @@ impl BytecodeGenerator {
                 .add_destination_register(register_index)
                 .build(),
         );
-        self.program.instructions.push(number as u32);
+        self.program.instructions.push(number as u32);
+    }
+
+    fn add_number(&mut self, number: f64, register_index: Instruction) {
+        if let Some(number) = integer_literal(number) {
+            self.add_integer(number, register_index);
+            return;
+        }
+        // a float32 is only used when it holds the number exactly, 0.1 would lose digits
+        let wide = number as f32 as f64 != number;
+        if self.load_numeric_constant(NovaObject::Float64(number), wide, register_index) {
+            return;
+        }
+
+        // check if number can be stored as a float32
+        if wide {
+            self.add_instruction(
+                InstructionBuilder::new()
+                    .add_opcode(OpCode::LoadFloat64)
+                    .add_destination_register(register_index)
+                    .build(),
+            );
+            let number_bits = number.to_bits();
+            let (first, second) = instruction_decoder::split_u64(number_bits);
+            self.add_instruction(first);
+            self.add_instruction(second);
+            return;
+        }
+
+        let number = number as f32;
+        self.program
+            .instructions
+            .push(InstructionBuilder::new_load_float32_instruction(
+                register_index,
+            ));
+        self.program.instructions.push(number.to_bits());
+    }
+
+    /// evaluate an expression made only of literals
+    fn constant_value(&self, expression: &Expression) -> Result<NovaObject, String> {
+        match expression {
+            Expression::Literal(literal) => match &literal.object {
+                Object::Number(number) if integer_literal(*number).is_some() => {
+                    Ok(NovaObject::Int64(*number as i64))
+                }
+                Object::Number(number) => Ok(NovaObject::Float64(*number)),
+                Object::String(string) => interpolation_parts(string)
+                    .ok()
+                    .and_then(|parts| plain_string(&parts))
+                    .map(|text| NovaObject::String(Box::new(text)))
+                    .ok_or_else(|| "an interpolated string is not a constant".to_string()),
+                Object::Bool(bool) => Ok(NovaObject::Bool(*bool)),
+                Object::None => Ok(NovaObject::None),
+                _ => Err("literal cannot be used in a constant".to_string()),
+            },
+
+            Expression::Grouping(grouping) => self.constant_value(&grouping.expression),
+
+            Expression::Unary(unary) => {
+                let right = self.constant_value(&unary.right)?;
+                Self::fold_unary(&unary.operator.token_type, right)
+            }
+
+            Expression::Binary(binary) => {
+                let left = self.constant_value(&binary.left)?;
+                let right = self.constant_value(&binary.right)?;
+                Self::fold_arithmetic(&binary.operator.token_type, left, right)
+            }
+
+            _ => Err("expression is not a compile time constant".to_string()),
+        }
+    }
+
+    fn fold_unary(operator: &TokenType, right: NovaObject) -> Result<NovaObject, String> {
+        match (operator, right) {
+            (TokenType::Minus, NovaObject::Int64(number)) => Ok(NovaObject::Int64(-number)),
+            (TokenType::Minus, NovaObject::Float64(number)) => Ok(NovaObject::Float64(-number)),
+            (TokenType::Plus, number @ (NovaObject::Int64(_) | NovaObject::Float64(_))) => {
+                Ok(number)
+            }
+            (operator, _) => Err(format!("cannot apply {:?} in a constant", operator)),
+        }
+    }
+
+    /// apply an arithmetic operator the same way the virtual machine does
+    fn fold_arithmetic(
+        operator: &TokenType,
+        left: NovaObject,
+        right: NovaObject,
+    ) -> Result<NovaObject, String> {
+        if let (NovaObject::Int64(left), NovaObject::Int64(right)) = (&left, &right) {
+            let (left, right) = (*left, *right);
+            let result = match operator {
+                TokenType::Plus => left.checked_add(right),
+                TokenType::Minus => left.checked_sub(right),
+                TokenType::Star => left.checked_mul(right),
+                TokenType::Slash => left.checked_div(right),
+                TokenType::Percent => left.checked_rem(right),
+                TokenType::Caret => Some((left as f64).powf(right as f64) as i64),
+                _ => return Err(format!("cannot apply {:?} in a constant", operator)),
+            };
+
+            return result
+                .map(NovaObject::Int64)
+                .ok_or("integer overflow or division by zero in constant".to_string());
+        }
+
+        let as_float = |object: &NovaObject| match object {
+            NovaObject::Int64(number) => Some(*number as f64),
+            NovaObject::Float64(number) => Some(*number),
+            _ => None,
+        };
+
+        let (left, right) = match (as_float(&left), as_float(&right)) {
+            (Some(left), Some(right)) => (left, right),
+            _ => {
+                return Err(format!(
+                    "cannot apply {:?} to {} and {}",
+                    operator, left, right
+                ))
+            }
+        };
+
+        let result = match operator {
+            TokenType::Plus => left + right,
+            TokenType::Minus => left - right,
+            TokenType::Star => left * right,
+            TokenType::Slash => left / right,
+            TokenType::Percent => left % right,
+            TokenType::Caret => left.powf(right),
+            _ => return Err(format!("cannot apply {:?} in a constant", operator)),
+        };
+
+        Ok(NovaObject::Float64(result))
+    }
+
+    /// load the value of an expression computed at compile time when the optimization level
+    /// allows it, None if it has to be evaluated at runtime
+    fn fold_constant_expression(
+        &mut self,
+        fold: impl FnOnce(&Self) -> Result<NovaObject, String>,
+    ) -> Option<VirtualRegister> {
+        if !self.opt_level.folds_constants() {
+            return None;
+        }
+
+        let value = fold(self).ok()?;
+        self.opt_stats.constants_folded += 1;
+        Some(self.load_constant_value(&value))
+    }
+
+    /// whether a condition known at compile time is truthy, when the optimization level
+    /// removes dead code. None if it has to be tested at runtime
+    fn constant_condition(&mut self, condition: &Expression) -> Option<bool> {
+        if !self.opt_level.eliminates_dead_code() {
+            return None;
+        }
+
+        let value = self.constant_value(condition).ok()?;
+        self.opt_stats.conditions_folded += 1;
+        Some(value.is_truthy())
+    }
+
+    /// load the value of a constant into a new temporary register.
+    /// numbers, booleans and None are loaded as immediates, strings through the immutable pool
+    fn load_constant_value(&mut self, value: &NovaObject) -> VirtualRegister {
+        let (register, register_index) = self.allocate_register();
+        match value {
+            NovaObject::Int64(number) => self.add_integer(*number, register_index),
+            NovaObject::Float64(number) => self.add_number(*number, register_index),
+            NovaObject::Bool(bool) => {
+                self.add_instruction(InstructionBuilder::new_load_bool(
+                    register_index,
+                    *bool as Instruction,
+                ));
+            }
+            NovaObject::String(_) => {
+                let immutable_index = self.get_immutable_index(value);
+                self.add_instruction(InstructionBuilder::new_load_constant_instruction(
+                    register_index,
+                    immutable_index,
+                ));
+            }
+            _ => {
+                self.add_instruction(
+                    InstructionBuilder::new()
+                        .add_opcode(OpCode::LoadNil)
+                        .add_destination_register(register_index)
+                        .build(),
+                );
+            }
+        }
+
+        register
+    }
+
+    /// compile a function body behind a jump so it only runs when called,
+    /// and return the index of the function in the immutables
+    fn generate_function(
+        &mut self,
+        function_statement: &nova_tw::language::function::FunctionStatement,
+        name: String,
+    ) -> Instruction {
+        let jump_index = self.add_instruction(0 as Instruction); // placeholder instruction
+        let enclosing_locals = self.enter_function();
+        self.begin_local_scope();
+
+        let current_instruction_index = self.program.instructions.len() as Instruction;
+        let function_immutable = NovaObject::NovaFunction(NovaFunction {
+            name: Box::new(name.clone()),
+            address: current_instruction_index,
+            arity: function_statement.parameters.len() as Instruction,
+            default_parameters: 0,
+            is_variadic: false,
+            is_method: false,
+            number_of_locals: 0,
+        });
+
+        let function_index = self.get_immutable_index(&function_immutable);
+        self.enclosing_functions.push((name, function_index));
+
+        //self.add_instruction(InstructionBuilder::new_call_indirect_instruction(number_of_parameters, function_name_index));
+        let mut parameter_locals = Vec::new();
+
+        // loop through the parameter list and allocate local variables
+        for parameter in &function_statement.parameters {
+            let index = self.allocate_local(parameter.object.to_string().as_str());
+            parameter_locals.push(index);
+        }
+
+        /* let place_holder =
+        self.add_instruction(InstructionBuilder::new_allocate_local(1 as Instruction)); */
+
+        for (register_index, &local_index) in parameter_locals.iter().enumerate() {
+            self.add_instruction(InstructionBuilder::new_store_local(
+                register_index as Instruction,
+                local_index,
+            ));
+        }
+
+        for statement in &function_statement.body.statements {
+            self.execute(statement);
+        }
+
+        self.end_local_scope();
+        let num_locals = self.max_local_count;
+
+        if let NovaObject::NovaFunction(fuction) =
+            &mut Arc::make_mut(&mut self.program.immutables)[function_index as usize]
+        {
+            fuction.number_of_locals = num_locals;
+        }
+
+        self.add_instruction(InstructionBuilder::new_return_none_instruction());
+        self.exit_function(enclosing_locals);
+
+        let current = self.program.instructions.len() as Instruction;
+        self.program.instructions[jump_index as usize] =
+            InstructionBuilder::new_jump_instruction(current - jump_index, true);
+
+        self.enclosing_functions.pop();
+        function_index
+    }
+
+    /// start generating a function body in a fresh frame, the enclosing locals are
+    /// not visible inside it and its slots are numbered from zero
+    fn enter_function(&mut self) -> EnclosingLocals {
+        EnclosingLocals {
+            local_variable_indices: std::mem::take(&mut self.local_variable_indices),
+            declared_locals: std::mem::take(&mut self.declared_locals),
+            local_variable_count: std::mem::replace(&mut self.local_variable_count, 0),
+            max_local_count: std::mem::replace(&mut self.max_local_count, 0),
+            registers: std::mem::take(&mut self.registers),
+            scope: std::mem::replace(&mut self.scope, 0),
+            after_return: std::mem::replace(&mut self.after_return, false),
+        }
+    }
+
+    fn exit_function(&mut self, enclosing_locals: EnclosingLocals) {
+        self.local_variable_indices = enclosing_locals.local_variable_indices;
+        self.declared_locals = enclosing_locals.declared_locals;
+        self.local_variable_count = enclosing_locals.local_variable_count;
+        self.max_local_count = enclosing_locals.max_local_count;
+        self.registers = enclosing_locals.registers;
+        self.scope = enclosing_locals.scope;
+        self.after_return = enclosing_locals.after_return;
+    }
+
+    /// immutable index of the enclosing function with this name, so a function
+    /// can call itself without looking its name up in the globals
+    fn get_enclosing_function(&self, name: &str) -> Option<Instruction> {
+        self.enclosing_functions
+            .iter()
+            .rev()
+            .find(|(function_name, _)| function_name == name)
+            .map(|&(_, function_index)| function_index)
+    }
+
+    /// place call arguments in parameter order, resolving `name = value` arguments against
+    /// the signature of the called function
+    fn order_arguments<'a>(
+        &self,
+        function_name: &str,
+        arguments: &'a [Expression],
+    ) -> Result<Vec<&'a Expression>, String> {
+        let is_named = |argument: &Expression| matches!(argument, Expression::Assign(_));
+
+        if !arguments.iter().any(is_named) {
+            return Ok(arguments.iter().collect());
+        }
+
+        let signature = match self.function_signatures.get(function_name) {
+            Some(signature) if self.get_local_index(function_name).is_none() => signature,
+            _ => {
+                return Err(format!(
+                    "named arguments need '{}' to be a function declared in this program",
+                    function_name
+                ))
+            }
+        };
+
+        let parameters = &signature.parameters;
+        let mut ordered: Vec<Option<&Expression>> = vec![None; parameters.len()];
+        let mut positional = 0;
+
+        for argument in arguments {
+            if let Expression::Assign(assign) = argument {
+                let name = assign.name.object.to_string();
+                let index = parameters
+                    .iter()
+                    .position(|parameter| *parameter == name)
+                    .ok_or(format!(
+                        "'{}' has no parameter named '{}'",
+                        function_name, name
+                    ))?;
+
+                if ordered[index].is_some() {
+                    return Err(format!("parameter '{}' is given more than once", name));
+                }
+
+                ordered[index] = Some(&assign.value);
+                continue;
+            }
+
+            if positional != ordered.iter().filter(|slot| slot.is_some()).count() {
+                return Err("positional arguments must come before named arguments".to_string());
+            }
+
+            if positional >= parameters.len() {
+                return Err(format!(
+                    "'{}' takes at most {} arguments",
+                    function_name,
+                    parameters.len()
+                ));
+            }
+
+            ordered[positional] = Some(argument);
+            positional += 1;
+        }
+
+        if let Some(missing) = ordered.iter().position(Option::is_none) {
+            return Err(format!(
+                "missing argument for parameter '{}' of '{}'",
+                parameters[missing], function_name
+            ));
+        }
+
+        Ok(ordered.into_iter().flatten().collect())
+    }
+
+    fn load_string(&mut self, text: String) -> VirtualRegister {
+        let (value, register_index) = self.allocate_register();
+        let immutable_index = self.get_immutable_index(&NovaObject::String(Box::new(text)));
+        self.add_instruction(InstructionBuilder::new_load_constant_instruction(
+            register_index,
+            immutable_index,
+        ));
+
+        value
+    }
+
+    /// whether the name is a local, function or global of the program. those hide the native
+    /// of the same name, so calls to it cannot be lowered
+    fn is_declared(&self, name: &str) -> bool {
+        self.get_local_index(name).is_some()
+            || self.get_enclosing_function(name).is_some()
+            || self.function_signatures.contains_key(name)
+            || self.declared_globals.contains(name)
+    }
+
+    /// lower a string literal with `${expression}` parts into the concatenation of its text
+    /// and `format(expression)` of every part, so any value is written the way print writes
+    /// it. None for a string without parts
+    fn generate_interpolation(&mut self, string: &str) -> Option<VirtualRegister> {
+        let parts = match interpolation_parts(string) {
+            Ok(parts) if plain_string(&parts).is_some() => return None,
+            Ok(parts) => parts,
+            Err(message) => {
+                self.generate_error(CompileErrorKind::Syntax, message);
+                return Some(self.allocate_register().0);
+            }
+        };
+
+        if self.is_declared(INTERPOLATION_NATIVE) {
+            self.generate_error(
+                CompileErrorKind::InvalidCall,
+                format!(
+                    "string interpolation calls the native '{}()', which is hidden by a declaration",
+                    INTERPOLATION_NATIVE
+                ),
+            );
+            return Some(self.allocate_register().0);
+        }
+
+        let mut result: Option<VirtualRegister> = None;
+        for part in parts {
+            let value = match part {
+                InterpolationPart::Text(text) => self.load_string(text),
+                InterpolationPart::Expression(source) => match parse_interpolated(&source) {
+                    Ok(expression) => self.generate_format_call(&expression),
+                    Err(message) => {
+                        self.generate_error(CompileErrorKind::Syntax, message);
+                        self.allocate_register().0
+                    }
+                },
+            };
+
+            let Some(left) = result else {
+                result = Some(value);
+                continue;
+            };
+
+            let operands = self.use_registers(&[left, value]);
+            self.registers.release(value);
+            self.add_instruction(InstructionBuilder::new_binary_op_instruction(
+                OpCode::Add,
+                operands[0],
+                operands[0],
+                operands[1],
+            ));
+        }
+
+        result
+    }
+
+    /// `format(expression)`, the value written as a string
+    fn generate_format_call(&mut self, expression: &Expression) -> VirtualRegister {
+        let value = self.evaluate(expression);
+        let callee = self.load_function(INTERPOLATION_NATIVE.to_string());
+        let values = [value, callee];
+        let parameter_start = self.arrange_call(&values);
+
+        self.add_instruction(InstructionBuilder::new_invoke_instruction(
+            parameter_start,
+            1,
+            parameter_start + 1,
+        ));
+
+        for value in values {
+            self.registers.release(value);
+        }
+
+        self.registers.new_value(Location::Return)
+    }
+
+    /// a call to one of the math natives as its opcode, when the optimization level allows it
+    /// and nothing in the program can have replaced the native. None if it has to be called
+    fn generate_math_call(
+        &mut self,
+        call: &nova_tw::language::call::Call,
+    ) -> Option<VirtualRegister> {
+        if !self.opt_level.lowers_math_calls() {
+            return None;
+        }
+
+        let Expression::Variable(variable) = &call.callee else {
+            return None;
+        };
+
+        let name = variable.name.object.to_string();
+        let opcode = match name.as_str() {
+            "sqrt" => OpCode::Sqrt,
+            "abs" => OpCode::Abs,
+            "floor" => OpCode::Floor,
+            "ceil" => OpCode::Ceil,
+            _ => return None,
+        };
+
+        if self.is_declared(&name) {
+            return None;
+        }
+
+        let argument = match call.arguments.as_slice() {
+            [argument] if !matches!(argument, Expression::Assign(_)) => argument,
+            _ => return None,
+        };
+
+        let value = self.evaluate(argument);
+        let index = self.use_register(value);
+        self.add_instruction(InstructionBuilder::new_math_instruction(opcode, index));
+        self.opt_stats.math_calls_lowered += 1;
+
+        Some(value)
+    }
+
+    /// compile the statement `x = x + n` or `x = x - n` on a local to `Inc` or `Dec` when `n`
+    /// is an integer constant from 1 to 255. false when it does not have that shape.
+    /// this is the only form lowered: the frontend has no `++` or `+=` to compile
+    fn generate_local_step(&mut self, expression: &Expression) -> bool {
+        if !self.opt_level.lowers_local_steps() {
+            return false;
+        }
+
+        let Expression::Assign(assign) = expression else {
+            return false;
+        };
+        let Expression::Binary(binary) = &assign.value else {
+            return false;
+        };
+
+        let name = assign.name.object.to_string();
+        let Some(slot) = self.get_local_index(&name) else {
+            return false;
+        };
+
+        // only the local on the left, `n + x` writes a string after the number
+        let opcode = match (&binary.operator.token_type, &binary.left) {
+            (TokenType::Plus, Expression::Variable(variable))
+                if variable.name.object.to_string() == name =>
+            {
+                OpCode::Inc
+            }
+            (TokenType::Minus, Expression::Variable(variable))
+                if variable.name.object.to_string() == name =>
+            {
+                OpCode::Dec
+            }
+            _ => return false,
+        };
+
+        let amount = match self.constant_value(&binary.right) {
+            Ok(NovaObject::Int64(amount @ 1..=255)) => amount as Instruction,
+            _ => return false,
+        };
+
+        self.mark_local_used(slot);
+        self.add_instruction(InstructionBuilder::new_step_local(opcode, slot, amount));
+        self.opt_stats.local_steps_lowered += 1;
+        true
+    }
+
+    /// compile `while i < limit ... i = i + step end` on a local index to `ForPrep` and
+    /// `ForLoop`, which test and step the index in its slot. `>` and `>=` count down with
+    /// `i = i - step`. the step is an integer constant from 1 to 15, the limit an integer
+    /// constant or another local, and only the last statement of the body may assign either.
+    /// false when the loop does not have that shape
+    fn generate_counting_loop(&mut self, while_loop: &WhileLoop) -> bool {
+        if !self.opt_level.fuses_counting_loops() {
+            return false;
+        }
+
+        let Expression::Binary(condition) = &while_loop.condition else {
+            return false;
+        };
+        let Expression::Variable(index) = &condition.left else {
+            return false;
+        };
+
+        let name = index.name.object.to_string();
+        let Some(slot) = self.get_local_index(&name) else {
+            return false;
+        };
+
+        let mut flags = match condition.operator.token_type {
+            TokenType::Less => 0,
+            TokenType::LessEqual => FOR_INCLUSIVE,
+            TokenType::Greater => FOR_DESCENDING,
+            TokenType::GreaterEqual => FOR_DESCENDING | FOR_INCLUSIVE,
+            _ => return false,
+        };
+        let descending = flags & FOR_DESCENDING != 0;
+
+        let Statement::Block(body) = &while_loop.body else {
+            return false;
+        };
+        let Some((Statement::Expression((Expression::Assign(update), line, file)), statements)) =
+            body.statements.split_last()
+        else {
+            return false;
+        };
+        let Expression::Binary(step) = &update.value else {
+            return false;
+        };
+
+        let steps_index = update.name.object.to_string() == name
+            && matches!(&step.left, Expression::Variable(variable)
+                if variable.name.object.to_string() == name)
+            && matches!(
+                (&step.operator.token_type, descending),
+                (TokenType::Plus, false) | (TokenType::Minus, true)
+            );
+        let step = match self.constant_value(&step.right) {
+            Ok(NovaObject::Int64(step @ 1..=15)) if steps_index => step as Instruction,
+            _ => return false,
+        };
+
+        let (operand, limit) = match self.constant_value(&condition.right) {
+            Ok(NovaObject::Int64(limit)) => match i32::try_from(limit) {
+                Ok(limit) => {
+                    flags |= FOR_LIMIT_IMMEDIATE;
+                    (limit as Instruction, None)
+                }
+                Err(_) => return false,
+            },
+            _ => match &condition.right {
+                Expression::Variable(variable) => {
+                    let limit = variable.name.object.to_string();
+                    match self.get_local_index(&limit) {
+                        Some(limit_slot) if limit != name => (limit_slot, Some(limit)),
+                        _ => return false,
+                    }
+                }
+                _ => return false,
+            },
+        };
+
+        let assigned = statements.iter().any(|statement| {
+            statement_assigns(statement, &name)
+                || limit
+                    .as_ref()
+                    .is_some_and(|limit| statement_assigns(statement, limit))
+        });
+        if assigned {
+            return false;
+        }
+
+        self.mark_local_used(slot);
+        if limit.is_some() {
+            self.mark_local_used(operand);
+        }
+
+        self.add_instruction(InstructionBuilder::new_for_instruction(
+            OpCode::ForPrep,
+            slot,
+            step,
+            flags,
+        ));
+        self.add_instruction(operand);
+        let jump_to_exit = self.add_instruction(InstructionBuilder::new_jump_instruction(1, true));
+        let body_start = self.program.instructions.len() as Instruction;
+
+        // the body without the update, which `ForLoop` does
+        self.execute(&Statement::Block(Block {
+            statements: statements.to_vec(),
+            line: body.line,
+            filename: body.filename.clone(),
+        }));
+        self.after_return = false;
+
+        self.set_location(*line, file);
+        self.add_instruction(InstructionBuilder::new_for_instruction(
+            OpCode::ForLoop,
+            slot,
+            step,
+            flags,
+        ));
+        self.add_instruction(operand);
+        let jump_back = self.program.instructions.len() as Instruction;
+        self.add_instruction(InstructionBuilder::new_jump_instruction(
+            jump_back - body_start,
+            false,
+        ));
+
+        let end = self.program.instructions.len() as Instruction;
+        self.program.instructions[jump_to_exit as usize] =
+            InstructionBuilder::new_jump_instruction(end - jump_to_exit, true);
+
+        self.reference_source_lines_and_filename(
+            self.program.instructions.len() - 1,
+            *line,
+            file.clone(),
+        );
+        self.opt_stats.counting_loops_fused += 1;
+        true
     }
 
-    fn add_number(&mut self, number: f64, register_index: Instruction) {
-        // if number can be an integer
-        if number.fract() == 0.0 {
-            let number = number as i64;
-            self.add_integer(number, register_index);
-            return;
+    /// compile `and` / `or` so the right operand only runs when it decides the result.
+    /// the result is the last operand evaluated, left in the left operand's register
+    fn generate_short_circuit(
+        &mut self,
+        binary: &nova_tw::language::binary::Binary,
+        is_and: bool,
+    ) -> VirtualRegister {
+        let left = self.evaluate(&binary.left);
+        let left_index = self.use_register(left);
+
+        // jump false falls through to the next instruction when the value is falsy
+        // and skips it otherwise
+        self.add_instruction(InstructionBuilder::new_jump_false_instruction(left_index));
+
+        let jump_to_end = if is_and {
+            // falsy: the result is already decided
+            self.add_instruction(InstructionBuilder::new_jump_instruction(1, true))
+        } else {
+            // falsy: go evaluate the right operand, truthy: the result is decided
+            self.add_instruction(InstructionBuilder::new_jump_instruction(2, true));
+            self.add_instruction(InstructionBuilder::new_jump_instruction(1, true))
+        };
+
+        self.generate_conditional_operand(left, &binary.right, jump_to_end);
+        left
+    }
+
+    /// compile `a < b < c` like `a < b and b < c` with `b` evaluated once. the comparisons run
+    /// left to right and stop at the first false one, which is the result
+    fn generate_chained_comparison(
+        &mut self,
+        binary: &nova_tw::language::binary::Binary,
+    ) -> VirtualRegister {
+        // the parser nests the chain to the left, `(a < b) < c`
+        let mut comparisons = vec![(&binary.operator.token_type, &binary.right)];
+        let mut leftmost = &binary.left;
+        while let Expression::Binary(inner) = leftmost {
+            if comparison_opcode(&inner.operator.token_type).is_none() {
+                break;
+            }
+            comparisons.push((&inner.operator.token_type, &inner.right));
+            leftmost = &inner.left;
         }
-        // check if number can be stored as a float32
-        if number >= f32::MAX as f64 {
-            self.add_instruction(
-                InstructionBuilder::new()
-                    .add_opcode(OpCode::LoadFloat64)
-                    .add_destination_register(register_index)
-                    .build(),
-            );
-            let number_bits = number.to_bits();
-            let (first, second) = instruction_decoder::split_u64(number_bits);
-            self.add_instruction(first);
-            self.add_instruction(second);
-            return;
+        comparisons.reverse();
+
+        let (result, _) = self.allocate_register();
+        let mut previous = self.evaluate(leftmost);
+        let mut jumps_to_end = Vec::new();
+        let mut region = None;
+
+        for (index, (operator, operand)) in comparisons.iter().enumerate() {
+            let right = self.evaluate(operand);
+            let operands = self.use_registers(&[result, previous, right]);
+            let (opcode, invert) = comparison_opcode(operator).unwrap();
+            self.add_instruction(InstructionBuilder::new_binary_op_instruction(
+                opcode,
+                operands[0],
+                operands[1],
+                operands[2],
+            ));
+            if invert {
+                self.add_instruction(InstructionBuilder::new_not_instruction(operands[0]));
+            }
+
+            self.registers.release(previous);
+            previous = right;
+
+            if index + 1 < comparisons.len() {
+                // false: the result is decided, the operands after it are never evaluated
+                self.load_pending_return();
+                self.add_instruction(InstructionBuilder::new_jump_false_instruction(operands[0]));
+                jumps_to_end
+                    .push(self.add_instruction(InstructionBuilder::new_jump_instruction(1, true)));
+
+                if region.is_none() {
+                    self.registers.pin(result);
+                }
+                let start = self.registers.begin_region();
+                region.get_or_insert(start);
+            }
         }
 
-        let number = number as f32;
-        self.program
-            .instructions
-            .push(InstructionBuilder::new_load_float32_instruction(
-                register_index,
-            ));
-        self.program.instructions.push(number.to_bits());
+        self.registers.release(previous);
+        if let Some(region) = region {
+            self.registers.end_region(region);
+            self.registers.unpin(result);
+        }
+
+        let current = self.program.instructions.len() as Instruction;
+        for jump_to_end in jumps_to_end {
+            self.program.instructions[jump_to_end as usize] =
+                InstructionBuilder::new_jump_instruction(current - jump_to_end, true);
+        }
+
+        result
     }
 
-    /// check if previous instruction was a call and if true
-    /// load the return value
-    fn check_call_and_load_return(&mut self) {
-        let last_instruction = *self.program.instructions.last().unwrap_or(&0);
+    /// evaluate an operand that may be jumped over and move it into the result register.
+    /// values from before the jump stay where they are so both paths agree on their location
+    fn generate_conditional_operand(
+        &mut self,
+        result: VirtualRegister,
+        operand: &Expression,
+        jump_to_end: Instruction,
+    ) {
+        self.load_pending_return();
+        self.registers.pin(result);
+        let region = self.registers.begin_region();
 
-        let opcode = instruction_decoder::decode_opcode(last_instruction);
+        let right = self.evaluate(operand);
+        let operands = self.use_registers(&[result, right]);
+        self.add_instruction(InstructionBuilder::new_move_instruction(
+            operands[0],
+            operands[1],
+        ));
+        self.registers.release(right);
 
-        match opcode {
-            x if x == OpCode::Print.to_u32() => {}
-            x if x == OpCode::Invoke.to_u32() => {}
-            _ => return,
-        }
+        self.registers.end_region(region);
+        self.registers.unpin(result);
 
-        let destination = self.temp_stack.len() as Instruction;
-        self.temp_stack.push(());
-        self.add_instruction(
-            InstructionBuilder::new()
-                .add_opcode(OpCode::LoadReturn)
-                .add_destination_register(destination)
-                .build(),
-        );
+        let current = self.program.instructions.len() as Instruction;
+        self.program.instructions[jump_to_end as usize] =
+            InstructionBuilder::new_jump_instruction(current - jump_to_end, true);
     }
 
     fn generate_local_memory_instruction(allocate: bool, slots: Instruction) -> Instruction {
@@ -237,13 +1531,29 @@ impl BytecodeGenerator {
 }
 
 impl ExpressionVisitor for BytecodeGenerator {
-    type Output = ();
+    type Output = VirtualRegister;
 
     fn visit_binary(&mut self, binary: &nova_tw::language::binary::Binary) -> Self::Output {
-        self.evaluate(&binary.left);
-        self.check_call_and_load_return();
-        self.evaluate(&binary.right);
-        self.check_call_and_load_return();
+        match binary.operator.token_type {
+            TokenType::And => return self.generate_short_circuit(binary, true),
+            TokenType::Or => return self.generate_short_circuit(binary, false),
+            _ => {}
+        }
+
+        if is_chained_comparison(binary) {
+            return self.generate_chained_comparison(binary);
+        }
+
+        if let Some(value) = self.fold_constant_expression(|generator| {
+            let left = generator.constant_value(&binary.left)?;
+            let right = generator.constant_value(&binary.right)?;
+            Self::fold_arithmetic(&binary.operator.token_type, left, right)
+        }) {
+            return value;
+        }
+
+        let left = self.evaluate(&binary.left);
+        let right = self.evaluate(&binary.right);
 
         let mut invert_condition = false;
 
@@ -254,35 +1564,29 @@ impl ExpressionVisitor for BytecodeGenerator {
             TokenType::Star => OpCode::Mul,
             TokenType::Caret => OpCode::Pow,
             TokenType::Percent => OpCode::Mod,
-            TokenType::Less => OpCode::Less,
-            TokenType::LessEqual => OpCode::LessEqual,
-            TokenType::Greater => {
-                invert_condition = true;
-                OpCode::LessEqual
-            }
-            TokenType::GreaterEqual => {
-                invert_condition = true;
-                OpCode::Less
-            }
-            TokenType::EqualEqual => OpCode::Equal,
-            TokenType::NotEqual => {
-                invert_condition = true;
-                OpCode::Equal
-            }
-
-            _ => {
-                self.generate_error(format!(
-                    "[Unhandled binary operator: {:?}]",
-                    binary.operator.token_type
-                ));
-                return;
-            }
+            ref operator => match comparison_opcode(operator) {
+                Some((opcode, invert)) => {
+                    invert_condition = invert;
+                    opcode
+                }
+
+                None => {
+                    self.generate_error(
+                        CompileErrorKind::UnsupportedOperator,
+                        format!(
+                            "unhandled binary operator: {:?}",
+                            binary.operator.token_type
+                        ),
+                    );
+                    self.registers.release(right);
+                    return left;
+                }
+            },
         };
 
-        let right_index = self.temp_stack.len() as Instruction - 1;
-        let left_index = self.temp_stack.len() as Instruction - 2;
-
-        self.temp_stack.pop();
+        let operands = self.use_registers(&[left, right]);
+        let (left_index, right_index) = (operands[0], operands[1]);
+        self.registers.release(right);
 
         self.program
             .instructions
@@ -296,13 +1600,21 @@ impl ExpressionVisitor for BytecodeGenerator {
         if invert_condition {
             self.add_instruction(InstructionBuilder::new_not_instruction(left_index));
         }
+
+        left
     }
 
     fn visit_unary(&mut self, unary: &nova_tw::language::unary::Unary) -> Self::Output {
-        self.evaluate(&unary.right);
-        self.check_call_and_load_return();
+        if let Some(value) = self.fold_constant_expression(|generator| {
+            let right = generator.constant_value(&unary.right)?;
+            Self::fold_unary(&unary.operator.token_type, right)
+        }) {
+            return value;
+        }
+
+        let value = self.evaluate(&unary.right);
+        let index = self.use_register(value);
 
-        let index = self.temp_stack.len() as Instruction - 1;
         match unary.operator.token_type {
             TokenType::Minus => self.program.instructions.push(
                 InstructionBuilder::new()
@@ -311,13 +1623,23 @@ impl ExpressionVisitor for BytecodeGenerator {
                     .build(),
             ),
 
+            // checks the value is a number and leaves it as it is
+            TokenType::Plus => self.program.instructions.push(
+                InstructionBuilder::new()
+                    .add_opcode(OpCode::Pos)
+                    .add_source_register_1(index)
+                    .build(),
+            ),
+
             _ => {
-                self.generate_error(format!(
-                    "[Unhandled unary operator: {:?}]",
-                    unary.operator.token_type
-                ));
+                self.generate_error(
+                    CompileErrorKind::UnsupportedOperator,
+                    format!("unhandled unary operator: {:?}", unary.operator.token_type),
+                );
             }
         }
+
+        value
     }
 
     fn visit_grouping(&mut self, grouping: &nova_tw::language::grouping::Grouping) -> Self::Output {
@@ -325,8 +1647,14 @@ impl ExpressionVisitor for BytecodeGenerator {
     }
 
     fn visit_literal(&mut self, literal: &nova_tw::language::literal::Literal) -> Self::Output {
+        if let Object::String(string) = &literal.object {
+            if let Some(value) = self.generate_interpolation(string) {
+                return value;
+            }
+        }
+
         let object = literal.object.clone();
-        let register_index = self.temp_stack.len() as Instruction;
+        let (value, register_index) = self.allocate_register();
         match object {
             Object::Number(number) => {
                 self.add_number(number, register_index);
@@ -348,7 +1676,11 @@ impl ExpressionVisitor for BytecodeGenerator {
                     .build(),
             ),
             Object::String(string) => {
-                let object = NovaObject::String(Box::new(string));
+                let text = interpolation_parts(&string)
+                    .ok()
+                    .and_then(|parts| plain_string(&parts))
+                    .unwrap_or(string);
+                let object = NovaObject::String(Box::new(text));
                 let immutable_index = self.get_immutable_index(&object);
                 self.program
                     .instructions
@@ -358,113 +1690,144 @@ impl ExpressionVisitor for BytecodeGenerator {
                     ))
             }
 
-            Object::Callable(_) => todo!(),
-            Object::Instance(_) => todo!(),
+            Object::Callable(_) | Object::Instance(_) => {
+                self.generate_error(
+                    CompileErrorKind::Internal,
+                    format!("cannot compile literal {}", literal.object),
+                );
+            }
         }
 
-        self.temp_stack.push(())
+        value
     }
 
     fn visit_call(&mut self, function: &nova_tw::language::call::Call) -> Self::Output {
-        if let Expression::Variable(variable) = &function.callee {
-            let name = variable.name.object.to_string();
-            let parameter_start = self.temp_stack.len() as Instruction;
-            for argument in &function.arguments {
-                self.evaluate(argument);
-            }
+        if let Some(value) = self.generate_math_call(function) {
+            return value;
+        }
 
-            let parameters = function.arguments.len() as Instruction;
+        // calls through an expression (a list element, a map value) only take positional arguments
+        let name = match &function.callee {
+            Expression::Variable(variable) => variable.name.object.to_string(),
+            _ => "<expression>".to_string(),
+        };
 
-            if let Some(index) = self.get_local_index(name.as_str()) {
-                let destination = self.temp_stack.len() as Instruction;
-                self.program
-                    .instructions
-                    .push(InstructionBuilder::new_load_local(destination, index));
-                self.temp_stack.push(());
-            } else {
-                let name = NovaObject::String(Box::new(name));
-                let name_index = self.get_immutable_index(&name);
-                let destination = self.temp_stack.len() as Instruction;
-                self.program
-                    .instructions
-                    .push(InstructionBuilder::new_load_global_indirect(
-                        destination,
-                        name_index,
-                    ));
-                self.temp_stack.push(());
+        let arguments = match self.order_arguments(&name, &function.arguments) {
+            Ok(arguments) => arguments,
+            Err(message) => {
+                self.generate_error(CompileErrorKind::InvalidCall, message);
+                return self.allocate_register().0;
             }
+        };
 
-            self.temp_stack.pop();
-            let invoke_register = self.temp_stack.len() as Instruction;
+        let mut values = Vec::new();
+        for argument in &arguments {
+            let value = self.evaluate(argument);
+            values.push(value);
+        }
 
-            self.add_instruction(InstructionBuilder::new_invoke_instruction(
-                parameter_start,
-                parameters,
-                invoke_register,
-            ));
+        let callee = match &function.callee {
+            Expression::Variable(_) => self.load_function(name),
+            callee => self.evaluate(callee),
+        };
+        values.push(callee);
 
-            for _ in &function.arguments {
-                self.temp_stack.pop();
-            }
+        let parameters = arguments.len() as Instruction;
+        let parameter_start = self.arrange_call(&values);
 
-            return;
+        self.add_instruction(InstructionBuilder::new_invoke_instruction(
+            parameter_start,
+            parameters,
+            parameter_start + parameters,
+        ));
+
+        for value in values {
+            self.registers.release(value);
         }
 
-        self.generate_error("Error compiling function call".to_string());
+        // the result is only copied out of the return register once something uses it
+        self.registers.new_value(Location::Return)
     }
 
     fn visit_variable(&mut self, variable: &nova_tw::language::variable::Variable) -> Self::Output {
         let name = variable.name.object.to_string();
         if let Some(index) = self.get_local_index(name.as_str()) {
-            let destination = self.temp_stack.len() as Instruction;
+            self.mark_local_used(index);
+            let (value, destination) = self.allocate_register();
             self.program
                 .instructions
                 .push(InstructionBuilder::new_load_local(destination, index));
-            self.temp_stack.push(());
-            return;
+            return value;
+        }
+
+        if let Some(function_index) = self.get_enclosing_function(&name) {
+            let (value, destination) = self.allocate_register();
+            self.add_instruction(InstructionBuilder::new_load_constant_instruction(
+                destination,
+                function_index,
+            ));
+            return value;
         }
 
         let name = NovaObject::String(Box::new(name));
         let name_index = self.get_immutable_index(&name);
-        let destination = self.temp_stack.len() as Instruction;
+        let (value, destination) = self.allocate_register();
         self.program
             .instructions
             .push(InstructionBuilder::new_load_global_indirect(
                 destination,
                 name_index,
             ));
-        self.temp_stack.push(());
+        value
     }
 
     fn visit_assign(&mut self, assign: &nova_tw::language::assignment::Assign) -> Self::Output {
-        self.evaluate(&assign.value);
+        let value = self.evaluate(&assign.value);
         let name = assign.name.object.to_string();
 
-        self.check_call_and_load_return();
-
         if let Some(index) = self.get_local_index(name.as_str()) {
             // check if variable is a local
-            let source = self.temp_stack.len() as Instruction - 1;
-            self.temp_stack.pop();
+            let source = self.use_register(value);
             self.program
                 .instructions
                 .push(InstructionBuilder::new_store_local(source, index));
-            return;
+            return value;
         }
 
         let name = NovaObject::String(Box::new(name));
         let name_index = self.get_immutable_index(&name);
-        let source = self.temp_stack.len() as Instruction - 1;
-        self.temp_stack.pop();
+        let source = self.use_register(value);
         self.program
             .instructions
             .push(InstructionBuilder::new_store_global_indirect(
                 source, name_index,
             ));
+        value
     }
 
-    fn visit_get(&mut self, _get: &nova_tw::language::assignment::Get) -> Self::Output {
-        todo!()
+    /// objects have no properties yet, a dotted name like `csv.parse_line` is the name of a
+    /// global. that is how namespaced natives are reached
+    fn visit_get(&mut self, get: &nova_tw::language::assignment::Get) -> Self::Output {
+        let Expression::Variable(variable) = &get.object else {
+            self.generate_error(
+                CompileErrorKind::UnsupportedExpression,
+                format!(
+                    "cannot get '{}', only dotted names are supported",
+                    get.name.object
+                ),
+            );
+            return self.allocate_register().0;
+        };
+
+        let name = format!("{}.{}", variable.name.object, get.name.object);
+        let name = NovaObject::String(Box::new(name));
+        let name_index = self.get_immutable_index(&name);
+        let (value, destination) = self.allocate_register();
+        self.add_instruction(InstructionBuilder::new_load_global_indirect(
+            destination,
+            name_index,
+        ));
+        value
     }
 
     fn visit_set(&mut self, _set: &nova_tw::language::assignment::Set) -> Self::Output {
@@ -480,67 +1843,109 @@ impl StatementVisitor for BytecodeGenerator {
     }
 
     fn visit_if(&mut self, if_statement: &nova_tw::language::IfStatement) -> Self::Output {
-        self.evaluate(&if_statement.condition);
-        self.check_call_and_load_return();
-
-        let source = self.temp_stack.len() as Instruction - 1;
-
-        self.temp_stack.pop();
+        // an elif is an if in the else branch, the whole chain is compiled as a list of arms
+        // sharing one exit
+        let mut arms = vec![if_statement];
+        let mut else_branch = if_statement.else_branch.as_ref();
+        while let Some(Statement::If(elif)) = else_branch {
+            arms.push(elif);
+            else_branch = elif.else_branch.as_ref();
+        }
 
-        self.add_instruction(InstructionBuilder::new_jump_false_instruction(source));
-        let jump_then_branch =
-            self.add_instruction(InstructionBuilder::new_jump_instruction(1, true));
-        self.execute(&if_statement.then_branch);
-        let current = self.program.instructions.len() as Instruction;
-        let offset = current - jump_then_branch;
+        let mut jumps_to_exit = Vec::new();
+        for (index, arm) in arms.iter().enumerate() {
+            self.set_location(arm.line, &arm.filename);
+
+            // a constant condition always runs the arm or always jumps over it
+            let jump_to_next_arm = match self.constant_condition(&arm.condition) {
+                Some(true) => None,
+                Some(false) => {
+                    Some(self.add_instruction(InstructionBuilder::new_jump_instruction(1, true)))
+                }
+                None => {
+                    let condition = self.evaluate(&arm.condition);
+                    let source = self.use_register(condition);
+                    self.registers.release(condition);
+
+                    self.add_instruction(InstructionBuilder::new_jump_false_instruction(source));
+                    Some(self.add_instruction(InstructionBuilder::new_jump_instruction(1, true)))
+                }
+            };
+
+            self.execute(&arm.then_branch);
+            self.after_return = false;
+
+            if index + 1 < arms.len() || else_branch.is_some() {
+                jumps_to_exit
+                    .push(self.add_instruction(InstructionBuilder::new_jump_instruction(1, true)));
+            }
 
-        let mut jump_correction = 0;
+            if let Some(jump_to_next_arm) = jump_to_next_arm {
+                let current = self.program.instructions.len() as Instruction;
+                self.program.instructions[jump_to_next_arm as usize] =
+                    InstructionBuilder::new_jump_instruction(current - jump_to_next_arm, true);
+            }
+        }
 
-        if let Some(else_branch) = &if_statement.else_branch {
-            jump_correction = 2;
-            let jump_else_branch =
-                self.add_instruction(InstructionBuilder::new_jump_instruction(1, true));
+        if let Some(else_branch) = else_branch {
             self.execute(else_branch);
-            let current = self.program.instructions.len() as Instruction - 1;
-            let offset = current - jump_else_branch;
-            self.program.instructions[jump_else_branch as usize] =
-                InstructionBuilder::new_jump_instruction(offset + 1, true);
+            self.after_return = false;
         }
 
-        self.program.instructions[jump_then_branch as usize] =
-            InstructionBuilder::new_jump_instruction(offset + jump_correction, true);
+        let current = self.program.instructions.len() as Instruction;
+        for jump_to_exit in jumps_to_exit {
+            self.program.instructions[jump_to_exit as usize] =
+                InstructionBuilder::new_jump_instruction(current - jump_to_exit, true);
+        }
 
-        let source_line = if_statement.line;
-        let source_file = if_statement.filename.clone();
-        self.reference_source_lines_and_filename(
-            self.program.instructions.len() - 1,
-            source_line,
-            source_file,
-        );
+        for arm in arms.iter().rev() {
+            self.reference_source_lines_and_filename(
+                self.program.instructions.len() - 1,
+                arm.line,
+                arm.filename.clone(),
+            );
+        }
     }
 
     fn visit_while(&mut self, while_loop: &nova_tw::language::WhileLoop) -> Self::Output {
+        self.set_location(while_loop.line, &while_loop.filename);
+        if self.generate_counting_loop(while_loop) {
+            return;
+        }
+
         let loop_start = self.program.instructions.len() as Instruction;
-        self.evaluate(&while_loop.condition);
-        self.check_call_and_load_return();
 
-        let source = self.temp_stack.len() as Instruction - 1;
-        self.temp_stack.pop();
+        // `while true` loops without testing anything, `while false` jumps over the body once
+        let constant = self.constant_condition(&while_loop.condition);
+        let jump_loop_index = match constant {
+            Some(true) => None,
+            Some(false) => {
+                Some(self.add_instruction(InstructionBuilder::new_jump_instruction(1, true)))
+            }
+            None => {
+                let condition = self.evaluate(&while_loop.condition);
+                let source = self.use_register(condition);
+                self.registers.release(condition);
 
-        self.add_instruction(InstructionBuilder::new_jump_false_instruction(source));
-        let jump_loop_index =
-            self.add_instruction(InstructionBuilder::new_jump_instruction(1, true));
+                self.add_instruction(InstructionBuilder::new_jump_false_instruction(source));
+                Some(self.add_instruction(InstructionBuilder::new_jump_instruction(1, true)))
+            }
+        };
 
         self.execute(&while_loop.body);
+        self.after_return = false;
 
-        let current_index = self.program.instructions.len() as Instruction;
-        let back_offset = current_index - loop_start;
+        if constant != Some(false) {
+            let current_index = self.program.instructions.len() as Instruction;
+            let back_offset = current_index - loop_start;
+            self.add_instruction(InstructionBuilder::new_jump_instruction(back_offset, false));
+        }
 
-        self.add_instruction(InstructionBuilder::new_jump_instruction(back_offset, false));
-        let current_index = self.program.instructions.len() as Instruction - 1;
-        let jump_forward_offset = current_index - jump_loop_index;
-        self.program.instructions[jump_loop_index as usize] =
-            InstructionBuilder::new_jump_instruction(jump_forward_offset + 1, true);
+        if let Some(jump_loop_index) = jump_loop_index {
+            let current_index = self.program.instructions.len() as Instruction;
+            self.program.instructions[jump_loop_index as usize] =
+                InstructionBuilder::new_jump_instruction(current_index - jump_loop_index, true);
+        }
 
         let source_line = while_loop.line;
         let source_file = while_loop.filename.clone();
@@ -552,29 +1957,37 @@ impl StatementVisitor for BytecodeGenerator {
     }
 
     fn visit_block(&mut self, block: &nova_tw::language::Block) -> Self::Output {
-        self.scope += 1;
-        self.local_variable_indices.push(HashMap::new());
-        self.program
-            .instructions
-            .push(InstructionBuilder::new_allocate_local(1)); // placeholder instruction
-        let placeholder_index = self.program.instructions.len() as Instruction - 1;
+        self.set_location(block.line, &block.filename);
+        self.begin_local_scope();
+
+        // a function frame reserves the slots of all its blocks up front,
+        // blocks outside of functions allocate their own slots while they run
+        let allocates_locals = self.enclosing_functions.is_empty();
+        let placeholder_index = if allocates_locals {
+            // placeholder instruction
+            Some(self.add_instruction(InstructionBuilder::new_allocate_local(1)))
+        } else {
+            None
+        };
 
         for statement in &block.statements {
             self.execute(statement);
         }
 
-        let indices = self.local_variable_indices.pop().unwrap();
+        let indices = self.end_local_scope();
         let num_locals = indices.len();
-        self.program.instructions[placeholder_index as usize] =
-            Self::generate_local_memory_instruction(true, num_locals as Instruction);
-        self.program
-            .instructions
-            .push(Self::generate_local_memory_instruction(
-                false,
-                num_locals as Instruction,
-            ));
 
-        self.scope -= 1;
+        if let Some(placeholder_index) = placeholder_index {
+            self.program.instructions[placeholder_index as usize] =
+                Self::generate_local_memory_instruction(true, num_locals as Instruction);
+            self.program
+                .instructions
+                .push(Self::generate_local_memory_instruction(
+                    false,
+                    num_locals as Instruction,
+                ));
+        }
+
         self.local_variable_count -= num_locals as u32;
 
         let source_line = block.line;
@@ -590,72 +2003,18 @@ impl StatementVisitor for BytecodeGenerator {
         &mut self,
         function_statement: &nova_tw::language::function::FunctionStatement,
     ) -> Self::Output {
-        let jump_index = self.add_instruction(0 as Instruction); // placeholder instruction
-        self.scope += 1;
-        self.local_variable_indices.push(HashMap::new());
-
-        let current_instruction_index = self.program.instructions.len() as Instruction;
-        let function_immutable = NovaObject::NovaFunction(NovaFunction {
-            name: Box::new(function_statement.name.object.to_string()),
-            address: current_instruction_index,
-            arity: function_statement.parameters.len() as Instruction,
-            is_method: false,
-            number_of_locals: 0,
-        });
+        self.set_location(function_statement.line, &function_statement.filename);
 
-        let string_immutable =
-            NovaObject::String(Box::new(function_statement.name.object.to_string()));
+        let function_name = function_statement.name.object.to_string();
+        self.function_signatures.insert(
+            function_name.clone(),
+            FunctionSignature::new(function_statement),
+        );
 
+        let string_immutable = NovaObject::String(Box::new(function_name.clone()));
         let _ = self.get_immutable_index(&string_immutable);
-        let function_index = self.get_immutable_index(&function_immutable);
-
-        //self.add_instruction(InstructionBuilder::new_call_indirect_instruction(number_of_parameters, function_name_index));
-        let mut parameter_locals = Vec::new();
-
-        // loop through the parameter list and allocate local variables
-        for parameter in &function_statement.parameters {
-            let index = self.allocate_local(parameter.object.to_string().as_str());
-            parameter_locals.push(index);
-        }
-
-        /* let place_holder =
-        self.add_instruction(InstructionBuilder::new_allocate_local(1 as Instruction)); */
-
-        for (register_index, &local_index) in parameter_locals.iter().enumerate() {
-            self.add_instruction(InstructionBuilder::new_store_local(
-                register_index as Instruction,
-                local_index,
-            ));
-            self.temp_stack.pop();
-        }
-
-        for statement in function_statement.body.statements.iter() {
-            self.execute(statement);
-        }
-
-        let indices = self.local_variable_indices.pop().unwrap();
-        let num_locals = indices.len() as Instruction;
-
-        if let NovaObject::NovaFunction(fuction) =
-            &mut self.program.immutables[function_index as usize]
-        {
-            fuction.number_of_locals = num_locals;
-        }
-
-        /* self.program.instructions[place_holder as usize] =
-            InstructionBuilder::new_allocate_local(num_locals as Instruction);
-        self.add_instruction(InstructionBuilder::new_deallocate_local(
-            num_locals as Instruction,
-        )); */
-
-        self.add_instruction(InstructionBuilder::new_return_none_instruction());
-        self.scope -= 1;
-        self.local_variable_count -= num_locals as u32;
 
-        let current = self.program.instructions.len() as Instruction;
-        self.program.instructions[jump_index as usize] =
-            InstructionBuilder::new_jump_instruction(current - jump_index, true);
-        // restore temp_stack to the way it was before function call.
+        self.generate_function(function_statement, function_name);
 
         let source_line = function_statement.line;
         let source_file = function_statement.filename.clone();
@@ -671,13 +2030,12 @@ impl StatementVisitor for BytecodeGenerator {
         return_statement: &Option<(nova_tw::language::Expression, usize, String)>,
     ) -> Self::Output {
         if let Some(value) = return_statement {
+            self.set_location(value.1, &value.2);
             let expression = &value.0;
-            self.evaluate(expression);
-            self.check_call_and_load_return();
-
-            let source = self.temp_stack.len() as Instruction - 1;
+            let value_register = self.evaluate(expression);
+            let source = self.use_register(value_register);
             self.add_instruction(InstructionBuilder::new_return_value(source));
-            self.temp_stack.pop();
+            self.registers.release(value_register);
             let source_line = value.1;
             let source_file = value.2.clone();
             self.reference_source_lines_and_filename(
@@ -685,27 +2043,28 @@ impl StatementVisitor for BytecodeGenerator {
                 source_line,
                 source_file,
             );
+            self.after_return = true;
             return;
         }
 
         self.add_instruction(InstructionBuilder::new_return_none_instruction());
+        self.after_return = true;
     }
 
     fn visit_var_declaration(
         &mut self,
         var_declaration: &nova_tw::language::declaration::VariableDeclaration,
     ) -> Self::Output {
-        let mut initialized = false;
-        if let Some(initializer) = &var_declaration.initializer {
-            self.evaluate(initializer);
-            initialized = true;
-            self.check_call_and_load_return();
-        }
+        self.set_location(var_declaration.line, &var_declaration.filename);
+
+        let initializer = var_declaration.initializer.as_ref().map(|initializer| {
+            let value = self.evaluate(initializer);
+            self.use_register(value)
+        });
 
         let name_str = var_declaration.name.object.to_string();
         if self.scope == 0 {
             // global scope
-
             let name = NovaObject::String(Box::new(name_str.clone()));
 
             let name_index = self.get_immutable_index(&name);
@@ -714,9 +2073,7 @@ impl StatementVisitor for BytecodeGenerator {
                 .push(InstructionBuilder::new_define_global_indirect(name_index));
             self.global_variables.insert(name_str, name_index);
 
-            if initialized {
-                let source = self.temp_stack.len() as Instruction - 1;
-                self.temp_stack.pop();
+            if let Some(source) = initializer {
                 self.program
                     .instructions
                     .push(InstructionBuilder::new_store_global_indirect(
@@ -733,10 +2090,28 @@ impl StatementVisitor for BytecodeGenerator {
             return;
         }
 
+        if self.get_local_index(name_str.as_str()).is_some() {
+            let redeclared = self
+                .local_variable_indices
+                .last()
+                .is_some_and(|scope| scope.contains_key(&name_str));
+
+            let message = if redeclared {
+                format!(
+                    "local variable '{}' is redeclared in the same scope",
+                    name_str
+                )
+            } else {
+                format!(
+                    "local variable '{}' shadows a variable in an outer scope",
+                    name_str
+                )
+            };
+            self.generate_warning(WarningKind::ShadowedVariable, message);
+        }
+
         let index = self.allocate_local(name_str.as_str());
-        if initialized {
-            let source = self.temp_stack.len() as Instruction - 1;
-            self.temp_stack.pop();
+        if let Some(source) = initializer {
             self.program
                 .instructions
                 .push(InstructionBuilder::new_store_local(source, index));
@@ -747,7 +2122,10 @@ impl StatementVisitor for BytecodeGenerator {
         &mut self,
         expression_statement: &(nova_tw::language::Expression, usize, String),
     ) -> Self::Output {
-        self.evaluate(&expression_statement.0);
+        self.set_location(expression_statement.1, &expression_statement.2);
+        if !self.generate_local_step(&expression_statement.0) {
+            self.evaluate(&expression_statement.0);
+        }
 
         let source_line = expression_statement.1;
         let source_file = expression_statement.2.clone();
@@ -769,3 +2147,173 @@ impl StatementVisitor for BytecodeGenerator {
         todo!()
     }
 }
+
+/// the opcode of a comparison operator and whether its result is negated after it
+fn comparison_opcode(operator: &TokenType) -> Option<(OpCode, bool)> {
+    match operator {
+        TokenType::Less => Some((OpCode::Less, false)),
+        TokenType::LessEqual => Some((OpCode::LessEqual, false)),
+        TokenType::Greater => Some((OpCode::LessEqual, true)),
+        TokenType::GreaterEqual => Some((OpCode::Less, true)),
+        TokenType::EqualEqual => Some((OpCode::Equal, false)),
+        TokenType::NotEqual => Some((OpCode::Equal, true)),
+        _ => None,
+    }
+}
+
+/// a comparison whose left operand is another comparison without parentheses around it
+fn is_chained_comparison(binary: &nova_tw::language::binary::Binary) -> bool {
+    comparison_opcode(&binary.operator.token_type).is_some()
+        && matches!(&binary.left, Expression::Binary(left)
+            if comparison_opcode(&left.operator.token_type).is_some())
+}
+
+/// the native an interpolated `${expression}` is written with
+const INTERPOLATION_NATIVE: &str = "format";
+
+/// a piece of a string literal, text or the source of an interpolated `${expression}`
+#[derive(Debug, PartialEq)]
+enum InterpolationPart {
+    Text(String),
+    Expression(String),
+}
+
+/// split a string literal at its `${expression}` parts. `$${` writes a literal `${`
+fn interpolation_parts(string: &str) -> Result<Vec<InterpolationPart>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = string;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            text.push_str(&rest[..start - 1]);
+            text.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        text.push_str(&rest[..start]);
+        let Some(length) = rest[start + 2..].find('}') else {
+            return Err(format!("unterminated '${{' in string \"{}\"", string));
+        };
+
+        let source = rest[start + 2..start + 2 + length].trim();
+        if source.is_empty() {
+            return Err(format!("empty '${{}}' in string \"{}\"", string));
+        }
+
+        if !text.is_empty() {
+            parts.push(InterpolationPart::Text(std::mem::take(&mut text)));
+        }
+        parts.push(InterpolationPart::Expression(source.to_string()));
+        rest = &rest[start + 3 + length..];
+    }
+
+    text.push_str(rest);
+    if !text.is_empty() || parts.is_empty() {
+        parts.push(InterpolationPart::Text(text));
+    }
+
+    Ok(parts)
+}
+
+/// the text of a string literal without interpolated parts
+fn plain_string(parts: &[InterpolationPart]) -> Option<String> {
+    match parts {
+        [InterpolationPart::Text(text)] => Some(text.clone()),
+        _ => None,
+    }
+}
+
+/// parse the source of an interpolated part, which has to be a single expression
+fn parse_interpolated(source: &str) -> Result<Expression, String> {
+    let invalid = |reason: String| format!("invalid interpolation '${{{}}}': {}", source, reason);
+
+    let tokens = Scanner::new()
+        .scan_tokens_with_filename(source, "")
+        .map_err(|error| invalid(error.to_string()))?;
+    let mut statements = AstParser::new(tokens)
+        .parse_ast()
+        .map_err(|error| invalid(error.to_string()))?;
+
+    match statements.pop() {
+        Some(Statement::Expression((expression, _, _))) if statements.is_empty() => Ok(expression),
+        _ => Err(invalid("expected an expression".to_string())),
+    }
+}
+
+/// whether the statement, or anything nested in it, assigns or declares the name
+fn statement_assigns(statement: &Statement, name: &str) -> bool {
+    match statement {
+        Statement::None => false,
+        Statement::If(if_statement) => {
+            expression_assigns(&if_statement.condition, name)
+                || statement_assigns(&if_statement.then_branch, name)
+                || if_statement
+                    .else_branch
+                    .as_ref()
+                    .is_some_and(|else_branch| statement_assigns(else_branch, name))
+        }
+        Statement::While(while_loop) => {
+            expression_assigns(&while_loop.condition, name)
+                || statement_assigns(&while_loop.body, name)
+        }
+        Statement::Block(block) => block
+            .statements
+            .iter()
+            .any(|statement| statement_assigns(statement, name)),
+        Statement::Function(function) => {
+            function.name.object.to_string() == name
+                || function
+                    .body
+                    .statements
+                    .iter()
+                    .any(|statement| statement_assigns(statement, name))
+        }
+        Statement::Return(value) => value
+            .as_ref()
+            .is_some_and(|(expression, _, _)| expression_assigns(expression, name)),
+        Statement::VarDeclaration(declaration) => {
+            declaration.name.object.to_string() == name
+                || declaration
+                    .initializer
+                    .as_ref()
+                    .is_some_and(|initializer| expression_assigns(initializer, name))
+        }
+        Statement::Expression((expression, _, _)) => expression_assigns(expression, name),
+        // cannot be looked into
+        Statement::Class(_) | Statement::Include(_) => true,
+    }
+}
+
+fn expression_assigns(expression: &Expression, name: &str) -> bool {
+    match expression {
+        Expression::Assign(assign) => {
+            assign.name.object.to_string() == name || expression_assigns(&assign.value, name)
+        }
+        Expression::Binary(binary) => {
+            expression_assigns(&binary.left, name) || expression_assigns(&binary.right, name)
+        }
+        Expression::Unary(unary) => expression_assigns(&unary.right, name),
+        Expression::Grouping(grouping) => expression_assigns(&grouping.expression, name),
+        Expression::Call(call) => {
+            expression_assigns(&call.callee, name)
+                || call
+                    .arguments
+                    .iter()
+                    .any(|argument| expression_assigns(argument, name))
+        }
+        Expression::Get(get) => expression_assigns(&get.object, name),
+        Expression::Set(set) => {
+            expression_assigns(&set.object, name) || expression_assigns(&set.value, name)
+        }
+        Expression::Literal(_) | Expression::Variable(_) => false,
+    }
+}
+
+/// the frontend hands every numeric literal over as a float, hex and binary ones included.
+/// whole numbers that fit an Int64 are loaded as integers, anything else stays a float
+fn integer_literal(number: f64) -> Option<i64> {
+    let in_range = number >= i64::MIN as f64 && number < i64::MAX as f64;
+    (number.fract() == 0.0 && in_range).then_some(number as i64)
+}
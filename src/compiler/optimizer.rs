@@ -0,0 +1,635 @@
+//! optimization passes on generated bytecode. the passes only rewrite jumps and drop
+//! instructions that can never run or do nothing, so they work on programs from any frontend
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::fmt::Display;
+
+use crate::{
+    bytecode::OpCode,
+    debug::instruction_width,
+    instruction::{instruction_decoder, Instruction, InstructionBuilder},
+    object::NovaObject,
+    program::Program,
+};
+
+/// longest jump that fits in the address field of an instruction
+const MAX_JUMP_OFFSET: usize = u16::MAX as usize;
+
+/// which passes run when compiling.
+/// O0 runs none, O1 folds constants, lowers math calls, local steps and counting loops and
+/// runs the peephole passes,
+/// O2 also removes dead code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    O0,
+    O1,
+    #[default]
+    O2,
+}
+
+impl OptLevel {
+    /// parse a command line flag such as `-O1`
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "-O0" => Some(Self::O0),
+            "-O1" => Some(Self::O1),
+            "-O2" => Some(Self::O2),
+            _ => None,
+        }
+    }
+
+    /// evaluate arithmetic on literals and constants while generating code
+    pub fn folds_constants(self) -> bool {
+        self >= Self::O1
+    }
+
+    /// replace calls to the `sqrt`, `abs`, `floor` and `ceil` natives with their opcodes
+    pub fn lowers_math_calls(self) -> bool {
+        self >= Self::O1
+    }
+
+    /// compile `x = x + n` and `x = x - n` on a local to `Inc` and `Dec`, the only forms of a
+    /// step the frontend parses
+    pub fn lowers_local_steps(self) -> bool {
+        self >= Self::O1
+    }
+
+    /// compile `while` loops that count a local up or down to `ForPrep` and `ForLoop`
+    pub fn fuses_counting_loops(self) -> bool {
+        self >= Self::O1
+    }
+
+    /// thread jumps, drop jumps to the next instruction and moves of a register onto itself
+    pub fn runs_peephole(self) -> bool {
+        self >= Self::O1
+    }
+
+    /// fold conditions on constant booleans and remove unreachable code
+    pub fn eliminates_dead_code(self) -> bool {
+        self >= Self::O2
+    }
+}
+
+/// what the optimizer changed, counted in instructions unless stated otherwise
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptStats {
+    /// expressions replaced by their value
+    pub constants_folded: usize,
+    /// calls to math natives replaced by their opcode
+    pub math_calls_lowered: usize,
+    /// local updates compiled to `Inc` or `Dec`
+    pub local_steps_lowered: usize,
+    /// `while` loops compiled to `ForPrep` and `ForLoop`, counted in loops
+    pub counting_loops_fused: usize,
+    pub jumps_threaded: usize,
+    pub jumps_removed: usize,
+    pub moves_removed: usize,
+    pub conditions_folded: usize,
+    pub unreachable_removed: usize,
+    /// program size in words before and after the bytecode passes
+    pub words_before: usize,
+    pub words_after: usize,
+}
+
+impl Display for OptStats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "constants folded: {}", self.constants_folded)?;
+        writeln!(f, "math calls lowered: {}", self.math_calls_lowered)?;
+        writeln!(f, "local steps lowered: {}", self.local_steps_lowered)?;
+        writeln!(f, "counting loops fused: {}", self.counting_loops_fused)?;
+        writeln!(f, "jumps threaded: {}", self.jumps_threaded)?;
+        writeln!(f, "jumps removed: {}", self.jumps_removed)?;
+        writeln!(f, "moves removed: {}", self.moves_removed)?;
+        writeln!(f, "conditions folded: {}", self.conditions_folded)?;
+        writeln!(
+            f,
+            "unreachable instructions removed: {}",
+            self.unreachable_removed
+        )?;
+        write!(
+            f,
+            "program size: {} -> {} words",
+            self.words_before, self.words_after
+        )
+    }
+}
+
+/// run the bytecode passes enabled by the level and add what they changed to the stats.
+/// function addresses, the line table and the local symbols are moved along with the instructions
+pub fn optimize(program: &mut Program, level: OptLevel, stats: &mut OptStats) {
+    stats.words_before = program.instructions.len();
+    stats.words_after = program.instructions.len();
+
+    if !level.runs_peephole() && !level.eliminates_dead_code() {
+        return;
+    }
+
+    let entries: Vec<usize> = program
+        .immutables
+        .iter()
+        .filter_map(|immutable| match immutable {
+            NovaObject::NovaFunction(function) => Some(function.address as usize),
+            _ => None,
+        })
+        .collect();
+
+    let Some(mut branches) = Branches::new(&program.instructions, entries) else {
+        return;
+    };
+
+    if level.eliminates_dead_code() {
+        stats.conditions_folded += branches.fold_constant_conditions();
+    }
+
+    if level.runs_peephole() {
+        stats.jumps_threaded += branches.thread_jumps();
+        stats.moves_removed += branches.remove_redundant_moves();
+    }
+
+    if level.eliminates_dead_code() {
+        stats.unreachable_removed += branches.remove_unreachable();
+    }
+
+    if level.runs_peephole() {
+        stats.jumps_removed += branches.remove_jumps_to_next();
+    }
+
+    let kept_before = branches.kept_before();
+    let new_index = |index: usize| kept_before[index.min(kept_before.len() - 1)] as Instruction;
+
+    for immutable in Arc::make_mut(&mut program.immutables).iter_mut() {
+        if let NovaObject::NovaFunction(function) = immutable {
+            function.address = new_index(function.address as usize);
+        }
+    }
+
+    for local in program.local_symbols.iter_mut() {
+        local.start = new_index(local.start) as usize;
+        local.end = new_index(local.end) as usize;
+    }
+
+    // the last instruction of a line may be gone, it then ends at the last one kept before it
+    // unless that already ends another line, in which case nothing of the line is left
+    let line_ends: Vec<(usize, bool)> = program
+        .line_definitions
+        .iter()
+        .map(|line_definition| {
+            let last_instruction = line_definition.last_instruction;
+            let kept = !branches.is_removed(last_instruction);
+            let end = new_index(last_instruction + 1) as usize;
+            (end.saturating_sub(1), kept)
+        })
+        .collect();
+
+    let mut line_ends_iter = line_ends.iter();
+    program.line_definitions.retain_mut(|line_definition| {
+        let &(end, kept) = line_ends_iter.next().unwrap();
+        line_definition.last_instruction = end;
+        kept || !line_ends.contains(&(end, true))
+    });
+
+    program.instructions = branches.compact(&kept_before);
+    stats.words_after = program.instructions.len();
+}
+
+/// instructions along with where each jump lands, words that are replaced by
+/// `NoInstruction` are dropped when the program is compacted
+struct Branches {
+    instructions: Vec<Instruction>,
+    /// words that begin an instruction, the rest are operands of the one before them
+    starts: Vec<bool>,
+    /// absolute target of every jump
+    targets: Vec<Option<usize>>,
+    /// addresses of the functions, they are entered from outside any jump
+    entries: Vec<usize>,
+}
+
+impl Branches {
+    /// None if a jump leaves the program, which the pass can not reason about
+    fn new(instructions: &[Instruction], entries: Vec<usize>) -> Option<Self> {
+        let mut starts = vec![false; instructions.len()];
+        let mut targets = vec![None; instructions.len()];
+
+        let mut index = 0;
+        while index < instructions.len() {
+            starts[index] = true;
+            let instruction = instructions[index];
+
+            if opcode_of(instruction) == OpCode::Jump as Instruction {
+                let offset = instruction_decoder::decode_immutable_address_small(instruction);
+                let forward = instruction_decoder::decode_destination_register(instruction) != 0;
+                let target = if forward {
+                    index + offset as usize
+                } else {
+                    index.checked_sub(offset as usize)?
+                };
+
+                if target > instructions.len() {
+                    return None;
+                }
+                targets[index] = Some(target);
+            }
+
+            index += instruction_width(instruction);
+        }
+
+        Some(Self {
+            instructions: instructions.to_vec(),
+            starts,
+            targets,
+            entries,
+        })
+    }
+
+    fn opcode(&self, index: usize) -> Option<Instruction> {
+        match self.starts.get(index) {
+            Some(true) => Some(opcode_of(self.instructions[index])),
+            _ => None,
+        }
+    }
+
+    fn is_jump(&self, index: usize) -> bool {
+        self.opcode(index) == Some(OpCode::Jump as Instruction)
+    }
+
+    /// a conditional jump is always followed by the jump it takes, after its operand words
+    fn is_conditional(&self, index: usize) -> bool {
+        let takes_next_jump = self
+            .opcode(index)
+            .and_then(|opcode| OpCode::try_from(opcode).ok())
+            .is_some_and(|opcode| opcode.takes_next_jump());
+        takes_next_jump && self.is_jump(index + instruction_width(self.instructions[index]))
+    }
+
+    /// the conditional jump that takes the jump at the index, if any
+    fn conditional_before(&self, index: usize) -> Option<usize> {
+        (index.saturating_sub(2)..index)
+            .find(|&conditional| self.is_conditional(conditional))
+            .filter(|&conditional| {
+                conditional + instruction_width(self.instructions[conditional]) == index
+            })
+    }
+
+    /// whether the instruction can be reached other than from the one before it
+    fn is_target(&self, index: usize) -> bool {
+        self.targets
+            .iter()
+            .flatten()
+            .chain(&self.entries)
+            .any(|&target| target == index)
+    }
+
+    fn is_removed(&self, index: usize) -> bool {
+        self.opcode(index) == Some(OpCode::NoInstruction as Instruction)
+    }
+
+    fn remove(&mut self, index: usize) {
+        let width = instruction_width(self.instructions[index]);
+        for word in index..(index + width).min(self.instructions.len()) {
+            self.instructions[word] = no_instruction();
+            self.starts[word] = true;
+            self.targets[word] = None;
+        }
+    }
+
+    /// `LoadBool` followed by `JumpFalse` on the same register either always or never jumps,
+    /// unless another path can reach the `JumpFalse` with a different value
+    fn fold_constant_conditions(&mut self) -> usize {
+        let mut folded = 0;
+        let mut previous = None;
+        for index in 0..self.instructions.len() {
+            if !self.starts[index] {
+                continue;
+            }
+
+            let load = previous.replace(index);
+            let Some(load) = load else {
+                continue;
+            };
+
+            if self.opcode(index) != Some(OpCode::JumpFalse as Instruction)
+                || !self.is_conditional(index)
+                || self.opcode(load) != Some(OpCode::LoadBool as Instruction)
+            {
+                continue;
+            }
+
+            let condition = instruction_decoder::decode_source_register_1(self.instructions[index]);
+            let loaded = instruction_decoder::decode_destination_register(self.instructions[load]);
+            let reachable_elsewhere = self.is_target(index) || self.is_target(index + 1);
+
+            if condition != loaded || reachable_elsewhere {
+                continue;
+            }
+
+            let value =
+                instruction_decoder::decode_immutable_address_small(self.instructions[load]);
+            if value == 0 {
+                // always falsy, only the jump is left
+                self.remove(index);
+            } else {
+                self.remove(index);
+                self.remove(index + 1);
+            }
+            folded += 1;
+        }
+
+        folded
+    }
+
+    /// point jumps that land on another jump straight at its target
+    fn thread_jumps(&mut self) -> usize {
+        let mut threaded = 0;
+        for index in 0..self.instructions.len() {
+            let Some(mut target) = self.targets[index] else {
+                continue;
+            };
+
+            // a chain longer than the program is a loop of jumps
+            for _ in 0..self.instructions.len() {
+                let next = match self.targets.get(target) {
+                    Some(&Some(next)) if self.is_jump(target) => next,
+                    _ => break,
+                };
+
+                if next == target || next.abs_diff(index) > MAX_JUMP_OFFSET {
+                    break;
+                }
+                target = next;
+            }
+
+            if self.targets[index] != Some(target) {
+                self.targets[index] = Some(target);
+                threaded += 1;
+            }
+        }
+
+        threaded
+    }
+
+    /// drop moves of a register onto itself
+    fn remove_redundant_moves(&mut self) -> usize {
+        let mut removed = 0;
+        for index in 0..self.instructions.len() {
+            if self.opcode(index) != Some(OpCode::Move as Instruction) {
+                continue;
+            }
+
+            let instruction = self.instructions[index];
+            if instruction_decoder::decode_destination_register(instruction)
+                == instruction_decoder::decode_source_register_1(instruction)
+            {
+                self.remove(index);
+                removed += 1;
+            }
+        }
+
+        removed
+    }
+
+    fn remove_unreachable(&mut self) -> usize {
+        let mut removed = 0;
+        let mut reachable = vec![false; self.instructions.len()];
+        let mut pending: Vec<usize> = core::iter::once(0).chain(self.entries.clone()).collect();
+
+        while let Some(index) = pending.pop() {
+            if index >= self.instructions.len() || reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+
+            let opcode = opcode_of(self.instructions[index]);
+            if let Some(target) = self.targets[index] {
+                pending.push(target);
+            } else if self.is_conditional(index) {
+                let jump = index + instruction_width(self.instructions[index]);
+                pending.push(jump);
+                pending.push(jump + 1);
+            } else if opcode != OpCode::Halt as Instruction
+                && opcode != OpCode::ReturnNone as Instruction
+                && opcode != OpCode::ReturnVal as Instruction
+            {
+                pending.push(index + instruction_width(self.instructions[index]));
+            }
+        }
+
+        for (index, reachable) in reachable.into_iter().enumerate() {
+            if self.starts[index] && !reachable && !self.is_removed(index) {
+                self.remove(index);
+                removed += 1;
+            }
+        }
+
+        removed
+    }
+
+    /// a jump with only removed instructions before its target does nothing.
+    /// a conditional jump goes with it, since it skips the word after it
+    fn remove_jumps_to_next(&mut self) -> usize {
+        let mut removed = 0;
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for index in 0..self.instructions.len() {
+                let Some(target) = self.targets[index] else {
+                    continue;
+                };
+
+                let jumps_to_next =
+                    target > index && (index + 1..target).all(|word| self.is_removed(word));
+                if !jumps_to_next {
+                    continue;
+                }
+
+                // a counting loop steps its index, it cannot go
+                match self.conditional_before(index) {
+                    Some(conditional)
+                        if self.opcode(conditional) == Some(OpCode::ForLoop as Instruction) =>
+                    {
+                        continue
+                    }
+                    Some(conditional) => self.remove(conditional),
+                    None => {}
+                }
+                self.remove(index);
+                removed += 1;
+                changed = true;
+            }
+        }
+
+        removed
+    }
+
+    /// number of words kept before each index, which is the new index of a kept word
+    fn kept_before(&self) -> Vec<usize> {
+        let mut kept_before = Vec::with_capacity(self.instructions.len() + 1);
+        let mut kept = 0;
+        for index in 0..self.instructions.len() {
+            kept_before.push(kept);
+            if !self.is_removed(index) {
+                kept += 1;
+            }
+        }
+        kept_before.push(kept);
+        kept_before
+    }
+
+    /// drop removed instructions and re-encode every jump
+    fn compact(&self, kept_before: &[usize]) -> Vec<Instruction> {
+        let mut instructions = Vec::with_capacity(kept_before[kept_before.len() - 1]);
+        for index in 0..self.instructions.len() {
+            if self.is_removed(index) {
+                continue;
+            }
+
+            let instruction = match self.targets[index] {
+                Some(target) => {
+                    let from = kept_before[index];
+                    let to = kept_before[target];
+                    if to > from {
+                        InstructionBuilder::new_jump_instruction((to - from) as Instruction, true)
+                    } else {
+                        InstructionBuilder::new_jump_instruction((from - to) as Instruction, false)
+                    }
+                }
+                None => self.instructions[index],
+            };
+            instructions.push(instruction);
+        }
+
+        instructions
+    }
+}
+
+fn opcode_of(instruction: Instruction) -> Instruction {
+    instruction_decoder::decode_opcode(instruction)
+}
+
+fn no_instruction() -> Instruction {
+    InstructionBuilder::new()
+        .add_opcode(OpCode::NoInstruction)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        bytecode::OpCode,
+        instruction::{Instruction, InstructionBuilder},
+        object::{NovaFunction, NovaObject},
+        program::{LineDefinition, Program},
+    };
+
+    use super::{optimize, OptLevel, OptStats};
+
+    fn optimize_fully(program: &mut Program) -> OptStats {
+        let mut stats = OptStats::default();
+        optimize(program, OptLevel::O2, &mut stats);
+        stats
+    }
+
+    fn jump(offset: Instruction, forward: bool) -> Instruction {
+        InstructionBuilder::new_jump_instruction(offset, forward)
+    }
+
+    fn load_int(register: Instruction) -> Instruction {
+        InstructionBuilder::new()
+            .add_opcode(OpCode::LoadInt32)
+            .add_destination_register(register)
+            .build()
+    }
+
+    #[test]
+    fn test_thread_jumps() {
+        let mut program = Program {
+            instructions: vec![
+                InstructionBuilder::new_jump_false_instruction(0),
+                jump(3, true),
+                InstructionBuilder::new_print_instruction(0, true),
+                jump(1, true),
+                jump(2, true),
+                InstructionBuilder::new_print_instruction(0, true),
+                InstructionBuilder::new_halt_instruction(),
+            ],
+            ..Default::default()
+        };
+
+        optimize_fully(&mut program);
+
+        let expected = vec![
+            InstructionBuilder::new_jump_false_instruction(0),
+            jump(2, true),
+            InstructionBuilder::new_print_instruction(0, true),
+            InstructionBuilder::new_halt_instruction(),
+        ];
+        assert_eq!(program.instructions, expected);
+    }
+
+    #[test]
+    fn test_fold_constant_conditions() {
+        let branch = |value: Instruction| Program {
+            instructions: vec![
+                InstructionBuilder::new_load_bool(0, value),
+                InstructionBuilder::new_jump_false_instruction(0),
+                jump(3, true),
+                load_int(1),
+                // operand word that looks like a jump
+                jump(1, false),
+                InstructionBuilder::new_print_instruction(1, true),
+                InstructionBuilder::new_halt_instruction(),
+            ],
+            immutables: Arc::new(
+                vec![NovaObject::NovaFunction(NovaFunction {
+                    name: Box::new("f".to_string()),
+                    address: 5,
+                    arity: 0,
+                    default_parameters: 0,
+                    is_variadic: false,
+                    is_method: false,
+                    number_of_locals: 0,
+                })]
+                .into(),
+            ),
+            line_definitions: vec![LineDefinition {
+                last_instruction: 4,
+                source_line: 1,
+                source_file: "test.nova".to_string(),
+                columns: None,
+            }],
+            ..Default::default()
+        };
+
+        let mut never_taken = branch(1);
+        optimize_fully(&mut never_taken);
+        assert_eq!(
+            never_taken.instructions,
+            vec![
+                InstructionBuilder::new_load_bool(0, 1),
+                load_int(1),
+                jump(1, false),
+                InstructionBuilder::new_print_instruction(1, true),
+                InstructionBuilder::new_halt_instruction(),
+            ]
+        );
+
+        let mut always_taken = branch(0);
+        optimize_fully(&mut always_taken);
+        assert_eq!(
+            always_taken.instructions,
+            vec![
+                InstructionBuilder::new_load_bool(0, 0),
+                InstructionBuilder::new_print_instruction(1, true),
+                InstructionBuilder::new_halt_instruction(),
+            ]
+        );
+
+        let NovaObject::NovaFunction(function) = &always_taken.immutables[0] else {
+            unreachable!()
+        };
+        assert_eq!(function.address, 1);
+        assert_eq!(always_taken.line_definitions[0].last_instruction, 0);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::instruction::Instruction;
+
+/// number of registers an instruction can address
+pub const GENERAL_REGISTERS: usize = 16;
+
+/// a temporary value produced while generating an expression.
+/// where it lives is decided by the allocator and can change until it is released
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualRegister(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Register(Instruction),
+    /// stored in a spill slot, counted from the first free local slot
+    Spilled(Instruction),
+    /// result of the last call, still in the return register
+    Return,
+}
+
+/// linear scan allocation of expression temporaries onto the general registers.
+/// values are handed out the lowest free register, and when none is left the oldest
+/// value is spilled since it is the one used last when walking an expression tree
+#[derive(Default)]
+pub struct RegisterAllocator {
+    /// location of every virtual register, None once it is released
+    locations: Vec<Option<Location>>,
+    /// virtual register held by each general register
+    registers: [Option<VirtualRegister>; GENERAL_REGISTERS],
+    spill_slots: Vec<bool>,
+    /// values needed by the instruction being generated, they can not be spilled
+    pinned: Vec<VirtualRegister>,
+    /// values numbered below this were created outside the code that may be jumped over
+    region_start: usize,
+}
+
+impl RegisterAllocator {
+    pub fn new_value(&mut self, location: Location) -> VirtualRegister {
+        let value = VirtualRegister(self.locations.len());
+        self.locations.push(Some(location));
+
+        if let Location::Register(register) = location {
+            self.registers[register as usize] = Some(value);
+        }
+
+        value
+    }
+
+    pub fn location(&self, value: VirtualRegister) -> Option<Location> {
+        self.locations.get(value.0).copied().flatten()
+    }
+
+    pub fn move_to(&mut self, value: VirtualRegister, location: Location) {
+        self.clear_location(value);
+        self.locations[value.0] = Some(location);
+
+        if let Location::Register(register) = location {
+            self.registers[register as usize] = Some(value);
+        }
+    }
+
+    pub fn release(&mut self, value: VirtualRegister) {
+        self.clear_location(value);
+        self.pinned.retain(|pinned| *pinned != value);
+        if let Some(location) = self.locations.get_mut(value.0) {
+            *location = None;
+        }
+    }
+
+    fn clear_location(&mut self, value: VirtualRegister) {
+        match self.location(value) {
+            Some(Location::Register(register)) => self.registers[register as usize] = None,
+            Some(Location::Spilled(slot)) => self.spill_slots[slot as usize] = false,
+            _ => {}
+        }
+    }
+
+    pub fn free_register(&self) -> Option<Instruction> {
+        self.registers
+            .iter()
+            .position(|value| value.is_none())
+            .map(|register| register as Instruction)
+    }
+
+    pub fn value_in(&self, register: Instruction) -> Option<VirtualRegister> {
+        self.registers[register as usize]
+    }
+
+    pub fn allocate_spill_slot(&mut self) -> Instruction {
+        let slot = match self.spill_slots.iter().position(|used| !used) {
+            Some(slot) => slot,
+            None => {
+                self.spill_slots.push(false);
+                self.spill_slots.len() - 1
+            }
+        };
+
+        self.spill_slots[slot] = true;
+        slot as Instruction
+    }
+
+    /// the value to spill when a register is needed, None if every value is in use
+    pub fn spill_candidate(&self) -> Option<VirtualRegister> {
+        self.registers
+            .iter()
+            .flatten()
+            .filter(|value| value.0 >= self.region_start && !self.pinned.contains(value))
+            .min_by_key(|value| value.0)
+            .copied()
+    }
+
+    /// the value waiting in the return register, if any
+    pub fn pending_return(&self) -> Option<VirtualRegister> {
+        self.locations
+            .iter()
+            .position(|location| *location == Some(Location::Return))
+            .map(VirtualRegister)
+    }
+
+    pub fn pin(&mut self, value: VirtualRegister) {
+        self.pinned.push(value);
+    }
+
+    pub fn unpin(&mut self, value: VirtualRegister) {
+        if let Some(index) = self.pinned.iter().position(|pinned| *pinned == value) {
+            self.pinned.remove(index);
+        }
+    }
+
+    /// start code that may be skipped at runtime, values from before it keep their location
+    /// until the matching `end_region` so both paths agree on where they are
+    pub fn begin_region(&mut self) -> usize {
+        core::mem::replace(&mut self.region_start, self.locations.len())
+    }
+
+    pub fn end_region(&mut self, region_start: usize) {
+        self.region_start = region_start;
+    }
+
+    /// forget every value, used once a statement is done
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Location, RegisterAllocator};
+
+    #[test]
+    fn test_spill_candidate_respects_pins_and_regions() {
+        let mut allocator = RegisterAllocator::default();
+
+        let first = allocator.new_value(Location::Register(0));
+        let second = allocator.new_value(Location::Register(1));
+        assert_eq!(allocator.free_register(), Some(2));
+        assert_eq!(allocator.spill_candidate(), Some(first));
+
+        allocator.pin(first);
+        assert_eq!(allocator.spill_candidate(), Some(second));
+
+        let region = allocator.begin_region();
+        assert_eq!(allocator.spill_candidate(), None);
+
+        let third = allocator.new_value(Location::Register(2));
+        assert_eq!(allocator.spill_candidate(), Some(third));
+        allocator.end_region(region);
+
+        let slot = allocator.allocate_spill_slot();
+        allocator.move_to(second, Location::Spilled(slot));
+        assert_eq!(allocator.free_register(), Some(1));
+
+        allocator.release(second);
+        assert_eq!(allocator.allocate_spill_slot(), slot);
+    }
+}
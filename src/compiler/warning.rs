@@ -0,0 +1,41 @@
+use alloc::{format, string::String};
+use core::fmt::Display;
+
+use crate::diagnostics::{Diagnostic, Palette, Style};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WarningKind {
+    /// a local variable that is declared but never read
+    UnusedLocal,
+    /// statements that follow a return in the same block
+    UnreachableCode,
+    /// a local variable declared with the same name as one already in scope
+    ShadowedVariable,
+}
+
+/// a non fatal diagnostic produced while generating bytecode
+#[derive(Debug, Clone)]
+pub struct CompileWarning {
+    pub kind: WarningKind,
+    pub message: String,
+    pub line: usize,
+    pub file: String,
+}
+
+impl Diagnostic for CompileWarning {
+    fn render_with(&self, palette: Palette) -> String {
+        format!(
+            "{}: {} on line [{}] in file '{}'",
+            palette.paint(Style::Warning, "warning"),
+            self.message,
+            self.line,
+            self.file
+        )
+    }
+}
+
+impl Display for CompileWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.render_with(Palette::plain()))
+    }
+}
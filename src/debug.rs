@@ -1,33 +1,62 @@
+use alloc::{format, string::String};
+
 use crate::{
     bytecode::OpCode,
     instruction::{instruction_decoder, Instruction},
 };
 
+#[cfg(not(feature = "minimal"))]
+use alloc::string::ToString;
+
+#[cfg(not(feature = "minimal"))]
+use crate::{
+    bytecode::HOST_OPCODES,
+    instruction::{FOR_DESCENDING, FOR_INCLUSIVE, FOR_LIMIT_IMMEDIATE},
+};
+
+/// the instruction word as it is, the `minimal` feature leaves out the mnemonics
+#[cfg(feature = "minimal")]
+pub fn debug_instruction(instructions: &[Instruction], instruction_pointer: u64) -> String {
+    format!("{:#010x}", instructions[instruction_pointer as usize])
+}
+
+#[cfg(not(feature = "minimal"))]
 pub fn debug_instruction(instructions: &[Instruction], instruction_pointer: u64) -> String {
     let instruction = instructions[instruction_pointer as usize];
 
-    let opcode = instruction_decoder::decode_opcode(instruction);
+    let opcode = match OpCode::try_from(instruction_decoder::decode_opcode(instruction)) {
+        Ok(opcode) => opcode,
+        Err(error) if HOST_OPCODES.contains(&error.0) => {
+            return binary_op(&format!("HOST {}", error.0), instruction);
+        }
+        Err(error) => return error.to_string(),
+    };
 
     //print!("(dbg[{}]) ", instruction_pointer);
     match opcode {
-        x if x == OpCode::NoInstruction as u32 => "NOINSTRUCTION".to_string(),
+        OpCode::NoInstruction => "NOINSTRUCTION".to_string(),
         // System Interrupt
-        x if x == OpCode::Halt as u32 => "HALT".to_string(),
+        OpCode::Halt => "HALT".to_string(),
 
         // Binary Operations
-        x if x == OpCode::Add as u32 => binary_op("ADD", instruction),
-        x if x == OpCode::Sub as u32 => binary_op("SUB", instruction),
-        x if x == OpCode::Mul as u32 => binary_op("MUL", instruction),
-        x if x == OpCode::Div as u32 => binary_op("DIV", instruction),
-        x if x == OpCode::Pow as u32 => binary_op("POW", instruction),
-        x if x == OpCode::Mod as u32 => binary_op("MOD", instruction),
+        OpCode::Add => binary_op("ADD", instruction),
+        OpCode::Sub => binary_op("SUB", instruction),
+        OpCode::Mul => binary_op("MUL", instruction),
+        OpCode::Div => binary_op("DIV", instruction),
+        OpCode::Pow => binary_op("POW", instruction),
+        OpCode::Mod => binary_op("MOD", instruction),
 
         // Register Manipulation
-        x if x == OpCode::LoadK as u32 => load_constant_to_register(instruction),
+        OpCode::LoadK => load_constant_to_register(instruction),
+
+        OpCode::LoadBool => load_bool_to_register(instruction),
 
-        x if x == OpCode::LoadBool as u32 => load_bool_to_register(instruction),
+        OpCode::LoadNil => {
+            let destination = instruction_decoder::decode_destination_register(instruction);
+            format!("LOADNIL {}", destination)
+        }
 
-        x if x == OpCode::LoadFloat32 as u32 => {
+        OpCode::LoadFloat32 => {
             let destination_register =
                 instruction_decoder::decode_destination_register(instruction);
             let number = instructions[instruction_pointer as usize + 1];
@@ -35,7 +64,7 @@ pub fn debug_instruction(instructions: &[Instruction], instruction_pointer: u64)
             load_float32_to_register(destination_register, number)
         }
 
-        x if x == OpCode::LoadFloat64 as u32 => {
+        OpCode::LoadFloat64 => {
             let destination_register =
                 instruction_decoder::decode_destination_register(instruction);
             let first_half = instructions[instruction_pointer as usize + 1];
@@ -45,7 +74,7 @@ pub fn debug_instruction(instructions: &[Instruction], instruction_pointer: u64)
             load_float64_to_register(destination_register, number)
         }
 
-        x if x == OpCode::LoadInt32 as u32 => {
+        OpCode::LoadInt32 => {
             let destination_register =
                 instruction_decoder::decode_destination_register(instruction);
             let number = instructions[instruction_pointer as usize + 1];
@@ -53,7 +82,7 @@ pub fn debug_instruction(instructions: &[Instruction], instruction_pointer: u64)
             format!("LOADINT32 {} {}", destination_register, number)
         }
 
-        x if x == OpCode::LoadInt64 as u32 => {
+        OpCode::LoadInt64 => {
             let destination_register =
                 instruction_decoder::decode_destination_register(instruction);
             let first_half = instructions[instruction_pointer as usize + 1];
@@ -63,90 +92,115 @@ pub fn debug_instruction(instructions: &[Instruction], instruction_pointer: u64)
             format!("LOADINT64 {} {}", destination_register, number)
         }
 
-        x if x == OpCode::Move as u32 => move_register(instruction),
+        OpCode::Move => move_register(instruction),
 
         // Variable Manipulation
-        x if x == OpCode::DefineGlobalIndirect as u32 => {
+        OpCode::DefineGlobalIndirect => {
             let address = instruction_decoder::decode_immutable_address_small(instruction);
 
             format!("DEFINEGLOBALINDIRECT {}", address)
         }
 
-        x if x == OpCode::StoreGlobalIndirect as u32 => {
+        OpCode::StoreGlobalIndirect => {
             let source1 = instruction_decoder::decode_source_register_1(instruction);
             let address = instruction_decoder::decode_immutable_address_small(instruction);
 
             format!("STOREGLOBALINDIRECT {} {}", source1, address)
         }
 
-        x if x == OpCode::LoadGlobalIndirect as u32 => {
+        OpCode::LoadGlobalIndirect => {
             let destination = instruction_decoder::decode_destination_register(instruction);
             let address = instruction_decoder::decode_immutable_address_small(instruction);
 
             format!("LOADGLOBALINDIRECT {} {}", destination, address)
         }
 
-        x if x == OpCode::LoadGlobal as u32 => {
+        OpCode::LoadGlobal => {
             let destination = instruction_decoder::decode_destination_register(instruction);
             let address = instruction_decoder::decode_immutable_address_small(instruction);
 
             format!("LOADGLOBAL {} {}", destination, address)
         }
 
-        x if x == OpCode::AllocateLocal as u32 => {
+        OpCode::AllocateLocal => {
             let number = instruction_decoder::decode_immutable_address_small(instruction);
 
             format!("ALLOCATELOCAL {}", number)
         }
 
-        x if x == OpCode::DeallocateLocal as u32 => {
+        OpCode::DeallocateLocal => {
             let number = instruction_decoder::decode_immutable_address_small(instruction);
             format!("DEALLOCATELOCAL {}", number)
         }
 
-        x if x == OpCode::StoreLocal as u32 => {
+        OpCode::StoreLocal => {
             let source1 = instruction_decoder::decode_source_register_1(instruction);
             let address = instruction_decoder::decode_immutable_address_small(instruction);
 
             format!("STORELOCAL {} {}", source1, address)
         }
 
-        x if x == OpCode::LoadLocal as u32 => {
+        OpCode::LoadLocal => {
             let destination = instruction_decoder::decode_destination_register(instruction);
             let address = instruction_decoder::decode_immutable_address_small(instruction);
 
             format!("LOADLOCAL {} {}", destination, address)
         }
 
-        // Control flow
-        x if x == OpCode::Less as u32 => {
-            let source1 = instruction_decoder::decode_source_register_1(instruction);
-            let source2 = instruction_decoder::decode_source_register_2(instruction);
-
-            format!("LESS {} {}", source1, source2)
+        OpCode::ForPrep | OpCode::ForLoop => {
+            let name = if opcode == OpCode::ForPrep {
+                "FORPREP"
+            } else {
+                "FORLOOP"
+            };
+            let flags = instruction_decoder::decode_destination_register(instruction);
+            let step = instruction_decoder::decode_source_register_1(instruction);
+            let address = instruction_decoder::decode_immutable_address_small(instruction);
+            let operand = instructions[instruction_pointer as usize + 1];
+
+            let test = match (flags & FOR_DESCENDING != 0, flags & FOR_INCLUSIVE != 0) {
+                (false, false) => "<",
+                (false, true) => "<=",
+                (true, false) => ">",
+                (true, true) => ">=",
+            };
+            let limit = if flags & FOR_LIMIT_IMMEDIATE != 0 {
+                (operand as i32).to_string()
+            } else {
+                format!("local {}", operand)
+            };
+
+            format!("{} {} {} {} step {}", name, address, test, limit, step)
         }
 
-        x if x == OpCode::LessEqual as u32 => {
-            let source1 = instruction_decoder::decode_source_register_1(instruction);
-            let source2 = instruction_decoder::decode_source_register_2(instruction);
+        OpCode::Inc | OpCode::Dec => {
+            let name = if opcode == OpCode::Inc { "INC" } else { "DEC" };
+            let address = instruction_decoder::decode_immutable_address_small(instruction);
+            let amount = instruction_decoder::decode_immediate_byte(instruction);
 
-            format!("LESSEQUAL {} {}", source1, source2)
+            format!("{} {} {}", name, address, amount)
         }
 
-        x if x == OpCode::Equal as u32 => {
+        // Control flow
+        OpCode::Less => binary_op("LESS", instruction),
+
+        OpCode::LessEqual => binary_op("LESSEQUAL", instruction),
+
+        OpCode::Equal => binary_op("EQUAL", instruction),
+
+        OpCode::JumpFalse => {
             let source1 = instruction_decoder::decode_source_register_1(instruction);
-            let source2 = instruction_decoder::decode_source_register_2(instruction);
 
-            format!("EQUAL {} {}", source1, source2)
+            format!("JUMPFALSE {}", source1)
         }
 
-        x if x == OpCode::JumpFalse as u32 => {
+        OpCode::JumpNone => {
             let source1 = instruction_decoder::decode_source_register_1(instruction);
 
-            format!("JUMPFALSE {}", source1)
+            format!("JUMPNONE {}", source1)
         }
 
-        x if x == OpCode::Jump as u32 => {
+        OpCode::Jump => {
             let offset = instruction_decoder::decode_immutable_address_small(instruction);
             let direction = instruction_decoder::decode_destination_register(instruction);
 
@@ -157,9 +211,9 @@ pub fn debug_instruction(instructions: &[Instruction], instruction_pointer: u64)
             )
         }
 
-        x if x == OpCode::NewFrame as u32 => "NEWFRAME".to_string(),
+        OpCode::NewFrame => "NEWFRAME".to_string(),
 
-        x if x == OpCode::Invoke as u32 => {
+        OpCode::Invoke => {
             let parameter_start = instruction_decoder::decode_destination_register(instruction);
             let parameters = instruction_decoder::decode_source_register_1(instruction);
             let invoke_register = instruction_decoder::decode_source_register_2(instruction);
@@ -170,48 +224,78 @@ pub fn debug_instruction(instructions: &[Instruction], instruction_pointer: u64)
             )
         }
 
-        x if x == OpCode::ReturnNone as u32 => "RETURN_NONE".to_string(),
+        OpCode::ReturnNone => "RETURN_NONE".to_string(),
 
-        x if x == OpCode::ReturnVal as u32 => {
+        OpCode::ReturnVal => {
             let source = instruction_decoder::decode_source_register_1(instruction);
             format!("RETURN_VAL {}", source)
         }
 
-        x if x == OpCode::LoadReturn as u32 => {
+        OpCode::LoadReturn => {
             let destination = instruction_decoder::decode_destination_register(instruction);
             format!("LOADRETURN {}", destination)
         }
 
         // IO
-        x if x == OpCode::Print as u32 => {
+        OpCode::Print => {
             let source = instruction_decoder::decode_source_register_1(instruction);
             format!("PRINT {}", source)
         }
 
         // Logical
-        x if x == OpCode::And as u32 => {
+        OpCode::And => {
             let source1 = instruction_decoder::decode_source_register_1(instruction);
             let source2 = instruction_decoder::decode_source_register_2(instruction);
 
             format!("AND {} {}", source1, source2)
         }
 
-        x if x == OpCode::Not as u32 => {
+        OpCode::Or => {
+            let source1 = instruction_decoder::decode_source_register_1(instruction);
+            let source2 = instruction_decoder::decode_source_register_2(instruction);
+
+            format!("OR {} {}", source1, source2)
+        }
+
+        OpCode::Not => {
             let source1 = instruction_decoder::decode_source_register_1(instruction);
 
             format!("NOT {}", source1)
         }
 
-        x if x == OpCode::Neg as u32 => {
+        OpCode::Neg => {
             let source1 = instruction_decoder::decode_source_register_1(instruction);
 
             format!("NEGATE {}", source1)
         }
 
-        _ => format!("Unsupported opcode instruction ({:#x})", opcode),
+        // Math
+        OpCode::Sqrt => unary_op("SQRT", instruction),
+        OpCode::Abs => unary_op("ABS", instruction),
+        OpCode::Floor => unary_op("FLOOR", instruction),
+        OpCode::Ceil => unary_op("CEIL", instruction),
+        OpCode::Pos => unary_op("POSITIVE", instruction),
+
+        _ => format!("Unsupported opcode instruction ({:?})", opcode),
+    }
+}
+
+/// number of words an instruction occupies, including the operand words that follow it
+pub fn instruction_width(instruction: Instruction) -> usize {
+    match OpCode::try_from(instruction_decoder::decode_opcode(instruction)) {
+        Ok(opcode) => 1 + opcode.operand_words(),
+        Err(_) => 1,
     }
 }
 
+#[cfg(not(feature = "minimal"))]
+fn unary_op(name: &str, instruction: Instruction) -> String {
+    let source_register_1 = instruction_decoder::decode_source_register_1(instruction);
+
+    format!("{} {}", name, source_register_1)
+}
+
+#[cfg(not(feature = "minimal"))]
 fn binary_op(name: &str, instruction: Instruction) -> String {
     let destination_register = instruction_decoder::decode_destination_register(instruction);
     let source_register_1 = instruction_decoder::decode_source_register_1(instruction);
@@ -223,6 +307,7 @@ fn binary_op(name: &str, instruction: Instruction) -> String {
     )
 }
 
+#[cfg(not(feature = "minimal"))]
 fn load_constant_to_register(instruction: Instruction) -> String {
     let destination_register = instruction_decoder::decode_destination_register(instruction);
     let immutable_address = instruction_decoder::decode_immutable_address_small(instruction);
@@ -230,6 +315,7 @@ fn load_constant_to_register(instruction: Instruction) -> String {
     format!("LOADK {} {}", destination_register, immutable_address)
 }
 
+#[cfg(not(feature = "minimal"))]
 fn load_bool_to_register(instruction: Instruction) -> String {
     let destination = instruction_decoder::decode_destination_register(instruction);
     let boolean = instruction_decoder::decode_immutable_address_small(instruction);
@@ -241,14 +327,17 @@ fn load_bool_to_register(instruction: Instruction) -> String {
     )
 }
 
+#[cfg(not(feature = "minimal"))]
 fn load_float32_to_register(destination: Instruction, number: f32) -> String {
     format!("LOADFLOAT32 {} {}", destination, number)
 }
 
+#[cfg(not(feature = "minimal"))]
 fn load_float64_to_register(destination: Instruction, number: f64) -> String {
     format!("LOADFLOAT64 {} {}", destination, number)
 }
 
+#[cfg(not(feature = "minimal"))]
 fn move_register(instruction: Instruction) -> String {
     let destination = instruction_decoder::decode_destination_register(instruction);
     let source = instruction_decoder::decode_source_register_1(instruction);
@@ -0,0 +1,171 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::fmt::Display;
+
+#[cfg(feature = "std")]
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+
+use crate::program::ColumnSpan;
+
+const RESET: &str = "\x1b[0m";
+
+/// what a painted part of a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// red, the label and underline of an error
+    Error,
+    /// yellow, the label and underline of a warning
+    Warning,
+    /// cyan, locations and the gutter around source lines
+    Note,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Error => "\x1b[1;31m",
+            Style::Warning => "\x1b[1;33m",
+            Style::Note => "\x1b[36m",
+        }
+    }
+}
+
+/// when the command line tools color their diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// color when stderr is a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// `--color=auto`, `--color=always`, `--color=never` or `--no-color`
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "--color=auto" => Some(ColorChoice::Auto),
+            "--color=always" => Some(ColorChoice::Always),
+            "--color=never" | "--no-color" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    /// the last color flag among the arguments wins
+    pub fn from_args(arguments: &[String]) -> Self {
+        arguments
+            .iter()
+            .rev()
+            .find_map(|argument| Self::from_flag(argument))
+            .unwrap_or_default()
+    }
+
+    /// the palette for diagnostics written to stderr
+    #[cfg(feature = "std")]
+    pub fn palette(self) -> Palette {
+        self.palette_for(io::stderr())
+    }
+
+    /// a flag beats `NO_COLOR`, which only turns off the automatic coloring
+    #[cfg(feature = "std")]
+    pub fn palette_for(self, stream: impl IsTerminal) -> Palette {
+        let color = match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+                !no_color && stream.is_terminal()
+            }
+        };
+
+        Palette { color }
+    }
+}
+
+/// paints the parts of a diagnostic with ANSI colors, or leaves them plain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Palette {
+    color: bool,
+}
+
+impl Palette {
+    pub fn plain() -> Self {
+        Self { color: false }
+    }
+
+    pub fn colored() -> Self {
+        Self { color: true }
+    }
+
+    pub fn is_colored(self) -> bool {
+        self.color
+    }
+
+    pub fn paint(self, style: Style, text: impl Display) -> String {
+        if self.color {
+            format!("{}{}{}", style.code(), text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// an error, warning or lint reported to the user. `Display` renders it with `Palette::plain`
+pub trait Diagnostic {
+    fn render_with(&self, palette: Palette) -> String;
+}
+
+/// append the source line between gutters, underlined at the columns or as a whole, e.g.
+///
+/// ```text
+///   |
+/// 2 | b := not a
+///   | ^^^^^^^^^^
+/// ```
+pub(crate) fn write_excerpt(
+    rendered: &mut String,
+    palette: Palette,
+    style: Style,
+    line: usize,
+    snippet: &str,
+    columns: Option<ColumnSpan>,
+) {
+    let gutter = " ".repeat(line.to_string().len());
+    let bar = palette.paint(Style::Note, "|");
+
+    // the indentation is copied so tabs line up with the source
+    let indentation = &snippet[..snippet.len() - snippet.trim_start().len()];
+    let underline = match columns {
+        Some(columns) => {
+            let padding: String = snippet
+                .chars()
+                .chain(core::iter::repeat(' '))
+                .take(columns.start.saturating_sub(1))
+                .map(|character| if character == '\t' { '\t' } else { ' ' })
+                .collect();
+            format!(
+                "{}{}",
+                padding,
+                palette.paint(style, "^".repeat(columns.width()))
+            )
+        }
+        None => {
+            let width = snippet.trim().chars().count().max(1);
+            format!("{}{}", indentation, palette.paint(style, "^".repeat(width)))
+        }
+    };
+
+    rendered.push_str(&format!("\n{} {}", gutter, bar));
+    rendered.push_str(&format!(
+        "\n{} {} {}",
+        palette.paint(Style::Note, line),
+        bar,
+        snippet.trim_end()
+    ));
+    rendered.push_str(&format!("\n{} {} {}", gutter, bar, underline));
+}
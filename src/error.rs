@@ -0,0 +1,226 @@
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{error::Error, fmt::Display};
+
+pub use crate::compiler::error::{CompileError, CompileErrorKind};
+use crate::{
+    collections::Map,
+    diagnostics::{write_excerpt, Diagnostic, Palette, Style},
+    instruction::Instruction,
+    program::{ColumnSpan, VerifyError},
+};
+
+/// a position in the source, used to locate runtime errors
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    /// the text of the source line, when the file could be read
+    pub snippet: Option<String>,
+    /// the part of the line the failing instruction was compiled from
+    pub columns: Option<ColumnSpan>,
+}
+
+impl SourceLocation {
+    /// append the location followed by its source line underlined, e.g.
+    ///
+    /// ```text
+    /// On line [2] in file 'main.nova'
+    ///   |
+    /// 2 | print(missing)
+    ///   | ^^^^^^^^^^^^^^
+    /// ```
+    fn write_excerpt(&self, rendered: &mut String, palette: Palette, prefix: &str) {
+        let location = format!("{} line [{}] in file '{}'", prefix, self.line, self.file);
+        rendered.push_str(&format!("\n{}", palette.paint(Style::Note, location)));
+
+        // without columns the whole statement is underlined
+        match &self.snippet {
+            Some(snippet) if !snippet.trim().is_empty() => write_excerpt(
+                rendered,
+                palette,
+                Style::Error,
+                self.line,
+                snippet,
+                self.columns,
+            ),
+            _ => {}
+        }
+    }
+}
+
+/// source files read while building an error, each read at most once and only when an error
+/// needs a line from it
+#[derive(Default)]
+pub(crate) struct SourceExcerpts {
+    files: Map<String, Option<String>>,
+}
+
+/// the source file, excerpts are left out without the `std` feature
+#[cfg(feature = "std")]
+fn read_source(file: &str) -> Option<String> {
+    std::fs::read_to_string(file).ok()
+}
+
+#[cfg(not(feature = "std"))]
+fn read_source(_: &str) -> Option<String> {
+    None
+}
+
+impl SourceExcerpts {
+    /// the text of the 1 based line, none when the file cannot be read
+    pub(crate) fn line(&mut self, file: &str, line: usize) -> Option<String> {
+        if file.is_empty() || line == 0 {
+            return None;
+        }
+
+        let source = self
+            .files
+            .entry(file.to_string())
+            .or_insert_with(|| read_source(file));
+
+        source
+            .as_deref()?
+            .lines()
+            .nth(line - 1)
+            .map(|line| line.to_string())
+    }
+}
+
+/// what stopped the virtual machine
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmErrorKind {
+    /// an error raised by an instruction or a native function, described by the message
+    Runtime,
+    /// the word at `pc` is not an instruction the virtual machine runs, either a reserved opcode
+    /// or a jump into the middle of a wide instruction. usually a hand built or corrupted program
+    IllegalInstruction {
+        pc: usize,
+        /// the raw instruction word
+        instruction: Instruction,
+        opcode: u32,
+        /// disassembly of the words around `pc`, the illegal one marked with `->`
+        listing: Vec<String>,
+    },
+    /// `start_vm` was called while the machine was running, only possible when an earlier run
+    /// unwound with a panic and left its frames behind. `reset` makes the machine usable again
+    Reentered,
+}
+
+/// an error raised while the virtual machine was running a program
+#[derive(Debug, Clone)]
+pub struct VmError {
+    pub kind: VmErrorKind,
+    pub message: String,
+    /// where the error was raised, if the program has line information for it
+    pub location: Option<SourceLocation>,
+    /// the call sites leading to the error, most recent call first
+    pub call_stack: Vec<SourceLocation>,
+}
+
+impl Diagnostic for VmError {
+    fn render_with(&self, palette: Palette) -> String {
+        let mut rendered = format!(
+            "{} '{}' Most recent call first",
+            palette.paint(Style::Error, "Error:"),
+            self.message
+        );
+
+        if let Some(location) = &self.location {
+            location.write_excerpt(&mut rendered, palette, "On");
+        }
+
+        for call_site in &self.call_stack {
+            call_site.write_excerpt(&mut rendered, palette, "Called from");
+        }
+
+        if let VmErrorKind::IllegalInstruction { listing, .. } = &self.kind {
+            for line in listing {
+                rendered.push_str(&format!("\n{}", line));
+            }
+        }
+
+        rendered
+    }
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.render_with(Palette::plain()))
+    }
+}
+
+/// any error produced while compiling or running nova code
+#[derive(Debug, Clone)]
+pub enum NovaError {
+    Compile(CompileError),
+    /// boxed, the stack trace makes it much larger than a compile error
+    Runtime(Box<VmError>),
+}
+
+impl Diagnostic for NovaError {
+    fn render_with(&self, palette: Palette) -> String {
+        match self {
+            NovaError::Compile(error) => error.render_with(palette),
+            NovaError::Runtime(error) => error.render_with(palette),
+        }
+    }
+}
+
+impl Display for NovaError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NovaError::Compile(error) => write!(f, "{}", error),
+            NovaError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<CompileError> for NovaError {
+    fn from(error: CompileError) -> Self {
+        NovaError::Compile(error)
+    }
+}
+
+impl From<VmError> for NovaError {
+    fn from(error: VmError) -> Self {
+        NovaError::Runtime(Box::new(error))
+    }
+}
+
+/// why `VirtualMachine::replace_function` left the function as it was
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplaceFunctionError {
+    /// there is no global nova function with the name to replace
+    UnknownFunction(String),
+    /// the fragment does not define a function with the name
+    MissingFromFragment(String),
+    /// the fragment did not pass `Program::verify`
+    Verify(VerifyError),
+}
+
+impl Display for ReplaceFunctionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReplaceFunctionError::UnknownFunction(name) => {
+                write!(f, "'{}' is not a nova function", name)
+            }
+            ReplaceFunctionError::MissingFromFragment(name) => {
+                write!(f, "the fragment does not define '{}'", name)
+            }
+            ReplaceFunctionError::Verify(error) => write!(f, "invalid fragment: {}", error),
+        }
+    }
+}
+
+impl Error for ReplaceFunctionError {}
+
+impl From<VerifyError> for ReplaceFunctionError {
+    fn from(error: VerifyError) -> Self {
+        ReplaceFunctionError::Verify(error)
+    }
+}
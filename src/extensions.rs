@@ -0,0 +1,163 @@
+//! opcodes added by the host. a domain specific engine registers a handler for a number in
+//! `HOST_OPCODES` and builds instructions with it, which the dispatcher hands to the handler
+//! instead of raising an illegal instruction
+
+use alloc::string::{String, ToString};
+use core::fmt::Display;
+
+use crate::{
+    bytecode::HOST_OPCODES,
+    instruction::{Instruction, InstructionBuilder},
+    object::NativeFunctionPointer,
+};
+
+/// an opcode of the host. the handler is called like a native function with the two source
+/// registers as its arguments, its result goes to the destination register
+#[derive(Debug, Clone)]
+pub struct HostOpCode {
+    pub name: String,
+    pub handler: NativeFunctionPointer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtensionError {
+    /// the number is not in `HOST_OPCODES`
+    OutOfRange(u32),
+    /// another opcode is registered with the number
+    Taken { number: u32, name: String },
+}
+
+impl Display for ExtensionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ExtensionError::OutOfRange(number) => write!(
+                f,
+                "opcode {} is not a host opcode, those are {} to {}",
+                number,
+                HOST_OPCODES.start(),
+                HOST_OPCODES.end()
+            ),
+            ExtensionError::Taken { number, name } => {
+                write!(f, "opcode {} is already registered as {}", number, name)
+            }
+        }
+    }
+}
+
+impl core::error::Error for ExtensionError {}
+
+/// the opcodes a host registered, given to `VirtualMachine::with_extensions`
+#[derive(Debug, Clone, Default)]
+pub struct Extensions {
+    opcodes: [Option<HostOpCode>; 4],
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// handle the opcode number with the function, the number has to be free and in
+    /// `HOST_OPCODES`
+    pub fn register(
+        &mut self,
+        number: u32,
+        name: &str,
+        handler: NativeFunctionPointer,
+    ) -> Result<(), ExtensionError> {
+        if !HOST_OPCODES.contains(&number) {
+            return Err(ExtensionError::OutOfRange(number));
+        }
+
+        let slot = &mut self.opcodes[(number - HOST_OPCODES.start()) as usize];
+        if let Some(opcode) = slot {
+            return Err(ExtensionError::Taken {
+                number,
+                name: opcode.name.clone(),
+            });
+        }
+
+        *slot = Some(HostOpCode {
+            name: name.to_string(),
+            handler,
+        });
+        Ok(())
+    }
+
+    /// the opcode registered with the number
+    pub fn get(&self, number: u32) -> Option<&HostOpCode> {
+        let index = number.checked_sub(*HOST_OPCODES.start())?;
+        self.opcodes.get(index as usize)?.as_ref()
+    }
+
+    pub fn is_registered(&self, number: u32) -> bool {
+        self.get(number).is_some()
+    }
+
+    /// the registered opcodes with their numbers
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &HostOpCode)> {
+        HOST_OPCODES
+            .zip(self.opcodes.iter())
+            .filter_map(|(number, opcode)| Some((number, opcode.as_ref()?)))
+    }
+}
+
+/// an instruction running the host opcode with the number, laid out like a binary operation
+pub fn new_host_instruction(
+    number: u32,
+    destination: Instruction,
+    source1: Instruction,
+    source2: Instruction,
+) -> Instruction {
+    let registers = InstructionBuilder::new()
+        .add_destination_register(destination)
+        .add_source_register_1(source1)
+        .add_source_register_2(source2)
+        .build();
+    number << 26 | registers
+}
+
+#[cfg(test)]
+mod extensions_tests {
+    use crate::{
+        bytecode::HOST_OPCODES,
+        natives::{NativeArguments, NativeContext},
+        object::{NativeResult, NovaObject},
+    };
+
+    use super::{ExtensionError, Extensions};
+
+    fn none(_: &mut NativeContext, _: NativeArguments) -> NativeResult {
+        Ok(NovaObject::None)
+    }
+
+    #[test]
+    fn test_register() {
+        let mut extensions = Extensions::new();
+        assert_eq!(extensions.register(61, "first", none), Ok(()));
+        assert_eq!(
+            extensions.register(61, "second", none),
+            Err(ExtensionError::Taken {
+                number: 61,
+                name: "first".to_string()
+            })
+        );
+        assert_eq!(
+            extensions.register(59, "coroutine", none),
+            Err(ExtensionError::OutOfRange(59))
+        );
+        assert_eq!(
+            extensions.register(HOST_OPCODES.end() + 1, "past", none),
+            Err(ExtensionError::OutOfRange(64))
+        );
+
+        assert!(extensions.is_registered(61));
+        assert!(!extensions.is_registered(60));
+        assert!(!extensions.is_registered(3));
+        let names: Vec<(u32, &str)> = extensions
+            .iter()
+            .map(|(number, opcode)| (number, opcode.name.as_str()))
+            .collect();
+        assert_eq!(names, vec![(61, "first")]);
+    }
+}
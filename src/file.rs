@@ -2,14 +2,25 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::{
     error::Error,
     fmt::Display,
-    fs::{self, File},
-    io::{BufReader, Write},
+    fs,
+    io::{Read, Write},
+    path::Path,
+    sync::Arc,
 };
 
+#[cfg(feature = "mmap")]
+mod mapped;
+mod migration;
+
+#[cfg(feature = "mmap")]
+pub use mapped::{map_program_file, MappedCode, MappedProgram};
+
 use crate::{
+    bytecode::HOST_OPCODES,
+    immutables::{ImmutableSource, Immutables},
     instruction::Instruction,
-    object::{NovaFunction, NovaObject},
-    program::Program,
+    object::{NovaFunction, NovaMap, NovaObject},
+    program::{verify_function, ColumnSpan, LineDefinition, LocalSymbol, Program},
     version,
 };
 
@@ -29,43 +40,183 @@ impl Error for FileError {}
 pub struct Metadata {
     version_major: Instruction,
     version_minor: Instruction,
+    layout: u32,
     instruction_count: Instruction,
     immutables_count: Instruction,
 }
 
+/// numbers in the file are little endian
+pub const LAYOUT_LITTLE_ENDIAN: u32 = 1;
+
+/// float immutables are IEEE 754 binary64
+pub const LAYOUT_IEEE754_DOUBLE: u32 = 1 << 1;
+
+/// float operand words are IEEE 754 binary32
+pub const LAYOUT_IEEE754_SINGLE: u32 = 1 << 2;
+
+/// instructions are 32 bit words
+pub const LAYOUT_WORD_32: u32 = 1 << 3;
+
+/// the instructions and immutables are deflate compressed, which needs the `compression`
+/// feature to read and write. not part of `LAYOUT`, files may or may not have it
+pub const LAYOUT_DEFLATE: u32 = 1 << 4;
+
+/// counts changes to the format that the flags do not describe, kept in the top byte of the
+/// layout. files of older versions are upgraded by the decoders in `migration`
+pub const FORMAT_VERSION: u32 = 2;
+
+/// what a program file assumes about its numbers, written after the version. a reader only
+/// accepts the layout it writes, so a file from another layout is refused rather than run
+pub const LAYOUT: u32 = FORMAT_VERSION << 24
+    | LAYOUT_LITTLE_ENDIAN
+    | LAYOUT_IEEE754_DOUBLE
+    | LAYOUT_IEEE754_SINGLE
+    | LAYOUT_WORD_32;
+
+/// starts the optional debug info after the immutables, `NDBG` in little endian
+const DEBUG_INFO_MARKER: u32 = u32::from_le_bytes(*b"NDBG");
+
+/// starts the signature added by `sign_program_bytes` at the end of the file, `NSIG` in
+/// little endian
+const SIGNATURE_MARKER: u32 = u32::from_le_bytes(*b"NSIG");
+
+/// size of an HMAC-SHA256 signature
+const SIGNATURE_LENGTH: usize = 32;
+
+/// starts a debug info sidecar written by `write_debug_info_file`, `NSYM` in little endian
+const SIDECAR_MARKER: u32 = u32::from_le_bytes(*b"NSYM");
+
+/// extension of the debug info sidecar of a `.nvc` file
+pub const SIDECAR_EXTENSION: &str = "nvd";
+
+/// stands for the top level code where a local symbol names its function
+const NO_FUNCTION: u32 = u32::MAX;
+
+/// starts the globals written by `write_globals`, `NGLB` in little endian
+const GLOBALS_MARKER: u32 = u32::from_le_bytes(*b"NGLB");
+
+/// lists and maps nested deeper than this are not saved with the globals
+const MAX_VALUE_DEPTH: usize = 256;
+
+#[repr(u8)]
+enum ValueKind {
+    None,
+    Bool,
+    Int64,
+    Float64,
+    String,
+    List,
+    Map,
+}
+
 #[repr(u8)]
 enum ImmutableKind {
     String,
     NovaFunction,
+    Int64,
+    Float64,
 }
 
 pub fn write_program_file(path: &str, program: &Program) -> Result<(), Box<dyn Error>> {
-    let mut buffer = Vec::new();
-    let version_major = version::major();
-    let version_minor = version::minor();
-    let instruction_count = program.instructions.len() as Instruction;
-    let immutables_count = program.immutables.len() as Instruction;
+    let buffer = write_program_bytes(program)?;
 
-    let metadata = Metadata {
-        version_major,
-        version_minor,
-        instruction_count,
-        immutables_count,
-    };
+    let mut file = fs::File::create(path)?;
+    file.write_all(&buffer)?;
+
+    Ok(())
+}
+
+/// like `write_program_file`, followed by the line table and local symbols for debuggers
+pub fn write_program_file_with_debug_info(
+    path: &str,
+    program: &Program,
+) -> Result<(), Box<dyn Error>> {
+    let buffer = write_program_bytes_with_debug_info(program)?;
+
+    let mut file = fs::File::create(path)?;
+    file.write_all(&buffer)?;
+
+    Ok(())
+}
+
+/// serialize the program in the same format as `write_program_file_with_debug_info`
+pub fn write_program_bytes_with_debug_info(program: &Program) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut buffer = write_program_bytes(program)?;
+    write_debug_info(program, &mut buffer)?;
 
-    write_metadata(metadata, &mut buffer)?;
+    Ok(buffer)
+}
+
+/// serialize the program in the same format as `write_program_file`
+pub fn write_program_bytes(program: &Program) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    write_metadata(metadata(program, LAYOUT), &mut buffer)?;
     write_instructions(program, &mut buffer)?;
     write_immutables(program, &mut buffer)?;
 
+    Ok(buffer)
+}
+
+/// like `write_program_file`, with the instructions and immutables deflate compressed. the
+/// debug info is added when asked for and left uncompressed
+#[cfg(feature = "compression")]
+pub fn write_compressed_program_file(
+    path: &str,
+    program: &Program,
+    debug_info: bool,
+) -> Result<(), Box<dyn Error>> {
+    let buffer = write_compressed_program_bytes(program, debug_info)?;
+
     let mut file = fs::File::create(path)?;
     file.write_all(&buffer)?;
 
     Ok(())
 }
 
+/// serialize the program in the same format as `write_compressed_program_file`. the
+/// compressed sections follow the header with their compressed and original sizes
+#[cfg(feature = "compression")]
+pub fn write_compressed_program_bytes(
+    program: &Program,
+    debug_info: bool,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    use flate2::{write::DeflateEncoder, Compression};
+
+    let mut sections = Vec::new();
+    write_instructions(program, &mut sections)?;
+    write_immutables(program, &mut sections)?;
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&sections)?;
+    let compressed = encoder.finish()?;
+
+    let mut buffer = Vec::new();
+    write_metadata(metadata(program, LAYOUT | LAYOUT_DEFLATE), &mut buffer)?;
+    buffer.write_u64::<LittleEndian>(compressed.len() as u64)?;
+    buffer.write_u64::<LittleEndian>(sections.len() as u64)?;
+    buffer.write_all(&compressed)?;
+
+    if debug_info {
+        write_debug_info(program, &mut buffer)?;
+    }
+
+    Ok(buffer)
+}
+
+fn metadata(program: &Program, layout: u32) -> Metadata {
+    Metadata {
+        version_major: version::major(),
+        version_minor: version::minor(),
+        layout,
+        instruction_count: program.instructions.len() as Instruction,
+        immutables_count: program.immutables.len() as Instruction,
+    }
+}
+
 fn write_metadata(metadata: Metadata, buffer: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
     buffer.write_u32::<LittleEndian>(metadata.version_major)?;
     buffer.write_u32::<LittleEndian>(metadata.version_minor)?;
+    buffer.write_u32::<LittleEndian>(metadata.layout)?;
     buffer.write_u32::<LittleEndian>(metadata.instruction_count)?;
     buffer.write_u32::<LittleEndian>(metadata.immutables_count)?;
 
@@ -97,16 +248,29 @@ fn write_immutables(program: &Program, buffer: &mut Vec<u8>) -> Result<(), Box<d
                 buffer.write_u8(function.arity as u8)?;
                 buffer.write_u32::<LittleEndian>(function.number_of_locals)?;
                 buffer.write_u8(function.is_method as u8)?;
+                buffer.write_u8(function.default_parameters as u8)?;
+                buffer.write_u8(function.is_variadic as u8)?;
                 let length = function.name.len();
                 buffer.write_u64::<LittleEndian>(length as u64)?;
                 let bytes = function.name.as_bytes();
                 buffer.write_all(bytes)?;
             }
 
+            NovaObject::Int64(value) => {
+                buffer.write_u8(ImmutableKind::Int64 as u8)?; // write a type
+                buffer.write_i64::<LittleEndian>(*value)?;
+            }
+
+            NovaObject::Float64(value) => {
+                buffer.write_u8(ImmutableKind::Float64 as u8)?; // write a type
+                buffer.write_f64::<LittleEndian>(*value)?;
+            }
+
             NovaObject::None
-            | NovaObject::Float64(_)
+            | NovaObject::Bool(_)
             | NovaObject::NativeFunction(_)
-            | NovaObject::Int64(_) => {
+            | NovaObject::List(_)
+            | NovaObject::Map(_) => {
                 continue;
             }
         }
@@ -115,17 +279,205 @@ fn write_immutables(program: &Program, buffer: &mut Vec<u8>) -> Result<(), Box<d
     Ok(())
 }
 
+fn write_debug_info(program: &Program, buffer: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+    buffer.write_u32::<LittleEndian>(DEBUG_INFO_MARKER)?;
+
+    buffer.write_u32::<LittleEndian>(program.line_definitions.len() as u32)?;
+    for line_definition in &program.line_definitions {
+        buffer.write_u64::<LittleEndian>(line_definition.last_instruction as u64)?;
+        buffer.write_u64::<LittleEndian>(line_definition.source_line as u64)?;
+        write_string(&line_definition.source_file, buffer)?;
+
+        // columns are 1 based, a start of 0 stands for no columns
+        let columns = line_definition
+            .columns
+            .unwrap_or(ColumnSpan { start: 0, end: 0 });
+        buffer.write_u64::<LittleEndian>(columns.start as u64)?;
+        buffer.write_u64::<LittleEndian>(columns.end as u64)?;
+    }
+
+    buffer.write_u32::<LittleEndian>(program.local_symbols.len() as u32)?;
+    for local in &program.local_symbols {
+        let function = local
+            .function
+            .map_or(NO_FUNCTION, |function| function as u32);
+        buffer.write_u32::<LittleEndian>(function)?;
+        buffer.write_u32::<LittleEndian>(local.slot)?;
+        buffer.write_u64::<LittleEndian>(local.start as u64)?;
+        buffer.write_u64::<LittleEndian>(local.end as u64)?;
+        write_string(&local.name, buffer)?;
+    }
+
+    Ok(())
+}
+
+fn write_string(string: &str, buffer: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+    buffer.write_u64::<LittleEndian>(string.len() as u64)?;
+    buffer.write_all(string.as_bytes())?;
+
+    Ok(())
+}
+
+/// read a program file, with the debug info of its sidecar when the file has none and a
+/// sidecar made for it is next to it. a sidecar of another program is ignored
 pub fn read_program_file(path: &str) -> Result<Program, Box<dyn Error>> {
-    let file = fs::File::open(path)?;
-    //let mut buffer = Vec::new();
-    //file.read(&mut buffer);
+    let source: ImmutableSource = Arc::new(fs::read(path)?);
+    let mut program = read_program((*source).as_ref(), Some(&source))?;
+
+    if program.line_definitions.is_empty() && program.local_symbols.is_empty() {
+        if let Ok(sidecar) = fs::read(sidecar_path(path)) {
+            let _ = read_debug_info_bytes(&sidecar, &mut program);
+        }
+    }
 
-    let mut reader = BufReader::new(file);
+    Ok(program)
+}
+
+/// the path of the debug info sidecar of a program file, `main.nvc` has `main.nvd`
+pub fn sidecar_path(path: &str) -> String {
+    Path::new(path)
+        .with_extension(SIDECAR_EXTENSION)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// write the line table and local symbols of the program to a sidecar, leaving the program
+/// file small. the sidecar names the instructions it was made for, so it is only ever joined
+/// with them
+pub fn write_debug_info_file(path: &str, program: &Program) -> Result<(), Box<dyn Error>> {
+    let buffer = write_debug_info_bytes(program)?;
+
+    let mut file = fs::File::create(path)?;
+    file.write_all(&buffer)?;
+
+    Ok(())
+}
+
+/// serialize the debug info in the same format as `write_debug_info_file`
+pub fn write_debug_info_bytes(program: &Program) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    buffer.write_u32::<LittleEndian>(SIDECAR_MARKER)?;
+    buffer.write_u32::<LittleEndian>(program.instructions.len() as u32)?;
+    buffer.write_u64::<LittleEndian>(instructions_fingerprint(&program.instructions))?;
+    write_debug_info(program, &mut buffer)?;
+
+    Ok(buffer)
+}
+
+/// give the program the debug info of a sidecar, failing when the sidecar was made for other
+/// instructions
+pub fn read_debug_info_bytes(bytes: &[u8], program: &mut Program) -> Result<(), Box<dyn Error>> {
+    let mut reader = bytes;
+    if reader.read_u32::<LittleEndian>()? != SIDECAR_MARKER {
+        return Err(Box::new(FileError {
+            description: "not a debug info sidecar".to_string(),
+        }));
+    }
+
+    let instruction_count = reader.read_u32::<LittleEndian>()?;
+    let fingerprint = reader.read_u64::<LittleEndian>()?;
+    if instruction_count as usize != program.instructions.len()
+        || fingerprint != instructions_fingerprint(&program.instructions)
+    {
+        return Err(Box::new(FileError {
+            description: "the debug info was made for another program".to_string(),
+        }));
+    }
+
+    if reader.read_u32::<LittleEndian>()? != DEBUG_INFO_MARKER {
+        return Err(Box::new(FileError {
+            description: "the sidecar holds no debug info".to_string(),
+        }));
+    }
+
+    let (line_definitions, local_symbols) = read_debug_info(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(Box::new(FileError {
+            description: format!("{} unexpected bytes after the debug info", reader.len()),
+        }));
+    }
+
+    program.line_definitions = line_definitions;
+    program.local_symbols = local_symbols;
+    Ok(())
+}
+
+/// FNV-1a of the instruction words, which stays the same between builds unlike the std hashers
+fn instructions_fingerprint(instructions: &[Instruction]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for byte in instructions
+        .iter()
+        .flat_map(|instruction| instruction.to_le_bytes())
+    {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    hash
+}
+
+/// deserialize a program written by `write_program_file`, checking the version, the layout and
+/// that the bytes hold exactly the instructions and immutables the header announces, and the
+/// debug info if there is any. files of older format versions are upgraded
+pub fn read_program_bytes(bytes: &[u8]) -> Result<Program, Box<dyn Error>> {
+    read_program(bytes, None)
+}
+
+/// read the program in the bytes, which are those of the source when there is one. the strings
+/// of a source are decoded when they are first used
+fn read_program(bytes: &[u8], source: Option<&ImmutableSource>) -> Result<Program, Box<dyn Error>> {
+    // version 1 files may be too short for a layout word, they hold no instructions then
+    let layout = bytes.get(8..12).map_or(0, |layout| {
+        u32::from_le_bytes([layout[0], layout[1], layout[2], layout[3]])
+    });
+    let format_version = migration::format_version(layout);
+    if format_version < FORMAT_VERSION {
+        let program = migration::decode(format_version, bytes)?;
+        program.verify()?;
+        return Ok(program);
+    }
+
+    let mut reader = bytes;
     let metadata = read_metadata(&mut reader)?;
+    check_version(&metadata)?;
+    check_layout(metadata.layout & !LAYOUT_DEFLATE)?;
+
+    let (instructions, immutables) = if metadata.layout & LAYOUT_DEFLATE != 0 {
+        let (instructions, immutables) = read_compressed_sections(&mut reader, &metadata)?;
+        (instructions, immutables.into())
+    } else {
+        let instructions = read_instructions(&mut reader, metadata.instruction_count)?;
+        let immutables = match source {
+            Some(source) => {
+                let start = bytes.len() - reader.len();
+                let (immutables, end) = scan_immutables(source, start, metadata.immutables_count)?;
+                reader = &bytes[end..];
+                immutables
+            }
+            None => read_immutables(&mut reader, metadata.immutables_count)?.into(),
+        };
+        (instructions, immutables)
+    };
+
+    let (line_definitions, local_symbols) = read_trailer(reader)?;
+
+    let program = Program {
+        instructions,
+        immutables: Arc::new(immutables),
+        line_definitions,
+        local_symbols,
+    };
+    // which host opcodes are registered is only known to the virtual machine loading it
+    program.verify_with_host_opcodes(|number| HOST_OPCODES.contains(&number))?;
+
+    Ok(program)
+}
+
+fn check_version(metadata: &Metadata) -> Result<(), Box<dyn Error>> {
     let version_major = version::major();
     let version_minor = version::minor();
 
-    if metadata.version_major > version_major {
+    if metadata.version_major > version_major || metadata.version_minor > version_minor {
         return Err(Box::new(FileError {
             description: format!(
                 "Version of bytecode {}.{} higher than supported {}.{}",
@@ -134,41 +486,266 @@ pub fn read_program_file(path: &str) -> Result<Program, Box<dyn Error>> {
         }));
     }
 
-    if metadata.version_minor > version_minor {
+    Ok(())
+}
+
+/// the debug info after the immutables, if there is any, and an optional signature. anything
+/// else after them is an error
+fn read_trailer(
+    mut reader: &[u8],
+) -> Result<(Vec<LineDefinition>, Vec<LocalSymbol>), Box<dyn Error>> {
+    let mut line_definitions = Vec::new();
+    let mut local_symbols = Vec::new();
+    if reader.starts_with(&DEBUG_INFO_MARKER.to_le_bytes()) {
+        reader = &reader[4..];
+        (line_definitions, local_symbols) = read_debug_info(&mut reader)?;
+    }
+
+    // only `read_signed_program_bytes` checks the signature
+    if reader.len() == 4 + SIGNATURE_LENGTH && reader.starts_with(&SIGNATURE_MARKER.to_le_bytes()) {
+        reader = &[];
+    }
+
+    if !reader.is_empty() {
+        return Err(Box::new(FileError {
+            description: format!("{} unexpected bytes after the program", reader.len()),
+        }));
+    }
+
+    Ok((line_definitions, local_symbols))
+}
+
+fn check_layout(layout: u32) -> Result<(), Box<dyn Error>> {
+    if layout == LAYOUT {
+        return Ok(());
+    }
+
+    let description = if layout.swap_bytes() == LAYOUT {
+        "the bytecode was written with big endian numbers, only little endian is supported"
+            .to_string()
+    } else if layout & !LAYOUT == 0 && layout >> 24 == LAYOUT >> 24 {
+        format!(
+            "the bytecode layout {:#010x} lacks assumptions of the supported {:#010x}",
+            layout, LAYOUT
+        )
+    } else {
+        format!(
+            "the bytecode layout {:#010x} is not supported, expected {:#010x}",
+            layout, LAYOUT
+        )
+    };
+
+    Err(Box::new(FileError { description }))
+}
+
+/// add an HMAC-SHA256 signature of the bytes, a serialized program, made with the key. a host
+/// that reads its scripts with `read_signed_program_bytes` and the same key refuses any that
+/// were changed after signing
+#[cfg(feature = "signing")]
+pub fn sign_program_bytes(bytes: &[u8], key: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    use hmac::Mac;
+
+    let mut mac = program_mac(key)?;
+    mac.update(bytes);
+
+    let mut signed = bytes.to_vec();
+    signed.write_u32::<LittleEndian>(SIGNATURE_MARKER)?;
+    signed.write_all(&mac.finalize().into_bytes())?;
+
+    Ok(signed)
+}
+
+/// read a file written by `sign_program_bytes`, see `read_signed_program_bytes`
+#[cfg(feature = "signing")]
+pub fn read_signed_program_file(path: &str, key: &[u8]) -> Result<Program, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    read_signed_program_bytes(&bytes, key)
+}
+
+/// deserialize a program signed by `sign_program_bytes`, refusing it when it has no signature
+/// or the signature was not made with the key over these bytes
+#[cfg(feature = "signing")]
+pub fn read_signed_program_bytes(bytes: &[u8], key: &[u8]) -> Result<Program, Box<dyn Error>> {
+    use hmac::Mac;
+
+    let Some(split) = bytes.len().checked_sub(4 + SIGNATURE_LENGTH) else {
+        return Err(Box::new(FileError {
+            description: "the bytecode is not signed".to_string(),
+        }));
+    };
+
+    let (program, signature) = bytes.split_at(split);
+    let Some(signature) = signature.strip_prefix(&SIGNATURE_MARKER.to_le_bytes()) else {
+        return Err(Box::new(FileError {
+            description: "the bytecode is not signed".to_string(),
+        }));
+    };
+
+    let mut mac = program_mac(key)?;
+    mac.update(program);
+    if mac.verify_slice(signature).is_err() {
+        return Err(Box::new(FileError {
+            description: "the bytecode signature does not match, it was changed or signed with \
+                another key"
+                .to_string(),
+        }));
+    }
+
+    read_program_bytes(bytes)
+}
+
+#[cfg(feature = "signing")]
+fn program_mac(key: &[u8]) -> Result<hmac::Hmac<sha2::Sha256>, Box<dyn Error>> {
+    use hmac::Mac;
+
+    hmac::Hmac::new_from_slice(key).map_err(|err| -> Box<dyn Error> {
+        Box::new(FileError {
+            description: err.to_string(),
+        })
+    })
+}
+
+#[cfg(feature = "compression")]
+fn read_compressed_sections(
+    reader: &mut &[u8],
+    metadata: &Metadata,
+) -> Result<(Vec<Instruction>, Vec<NovaObject>), Box<dyn Error>> {
+    use flate2::read::DeflateDecoder;
+
+    let compressed_length = read_usize(reader)?;
+    let length = reader.read_u64::<LittleEndian>()?;
+    if compressed_length > reader.len() {
+        return Err(Box::new(FileError {
+            description: "the file ends in the middle of the compressed sections".to_string(),
+        }));
+    }
+
+    let (compressed, rest) = reader.split_at(compressed_length);
+    *reader = rest;
+
+    // reading one byte more than announced tells a longer stream apart
+    let mut sections = Vec::new();
+    DeflateDecoder::new(compressed)
+        .take(length.saturating_add(1))
+        .read_to_end(&mut sections)?;
+
+    if sections.len() as u64 != length {
         return Err(Box::new(FileError {
             description: format!(
-                "Version of bytecode {}.{} higher than supported {}.{}",
-                metadata.version_major, metadata.version_minor, version_major, version_minor
+                "the compressed sections hold {} bytes, the header announces {}",
+                sections.len(),
+                length
+            ),
+        }));
+    }
+
+    let mut sections = sections.as_slice();
+    let instructions = read_instructions(&mut sections, metadata.instruction_count)?;
+    let immutables = read_immutables(&mut sections, metadata.immutables_count)?;
+    if !sections.is_empty() {
+        return Err(Box::new(FileError {
+            description: format!(
+                "{} unexpected bytes after the compressed sections",
+                sections.len()
             ),
         }));
     }
 
-    let instructions = read_instructions(&mut reader, metadata.instruction_count)?;
-    let immutables = read_immutables(&mut reader, metadata.immutables_count)?;
+    Ok((instructions, immutables))
+}
 
-    Ok(Program {
-        instructions,
-        immutables,
-        ..Default::default()
+#[cfg(not(feature = "compression"))]
+fn read_compressed_sections(
+    _reader: &mut &[u8],
+    _metadata: &Metadata,
+) -> Result<(Vec<Instruction>, Vec<NovaObject>), Box<dyn Error>> {
+    Err(Box::new(FileError {
+        description: "the bytecode is compressed, reading it needs the compression feature"
+            .to_string(),
+    }))
+}
+
+fn read_debug_info(
+    reader: &mut impl Read,
+) -> Result<(Vec<LineDefinition>, Vec<LocalSymbol>), Box<dyn Error>> {
+    let line_count = reader.read_u32::<LittleEndian>()?;
+    let mut line_definitions = Vec::new();
+    for _ in 0..line_count {
+        let last_instruction = read_usize(reader)?;
+        let source_line = read_usize(reader)?;
+        let source_file = read_string(reader)?;
+        let start = read_usize(reader)?;
+        let end = read_usize(reader)?;
+        line_definitions.push(LineDefinition {
+            last_instruction,
+            source_line,
+            source_file,
+            columns: (start != 0).then_some(ColumnSpan { start, end }),
+        });
+    }
+
+    let local_count = reader.read_u32::<LittleEndian>()?;
+    let mut local_symbols = Vec::new();
+    for _ in 0..local_count {
+        let function = reader.read_u32::<LittleEndian>()?;
+        let slot = reader.read_u32::<LittleEndian>()?;
+        let start = read_usize(reader)?;
+        let end = read_usize(reader)?;
+        let name = read_string(reader)?;
+        local_symbols.push(LocalSymbol {
+            name,
+            function: (function != NO_FUNCTION).then_some(function as usize),
+            slot,
+            start,
+            end,
+        });
+    }
+
+    Ok((line_definitions, local_symbols))
+}
+
+/// a size written as 64 bits, which may not fit this platform
+fn read_usize(reader: &mut impl Read) -> Result<usize, Box<dyn Error>> {
+    let value = reader.read_u64::<LittleEndian>()?;
+    usize::try_from(value).map_err(|_| -> Box<dyn Error> {
+        Box::new(FileError {
+            description: format!("{} is too large for this platform", value),
+        })
     })
 }
 
-fn read_metadata(reader: &mut BufReader<File>) -> Result<Metadata, Box<dyn Error>> {
+fn read_string(reader: &mut impl Read) -> Result<String, Box<dyn Error>> {
+    let length = reader.read_u64::<LittleEndian>()?;
+    let mut bytes = Vec::new();
+    reader.by_ref().take(length).read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 != length {
+        return Err(Box::new(FileError {
+            description: "the file ends in the middle of a string".to_string(),
+        }));
+    }
+
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn read_metadata(reader: &mut impl Read) -> Result<Metadata, Box<dyn Error>> {
     let version_major = reader.read_u32::<LittleEndian>()?;
     let version_minor = reader.read_u32::<LittleEndian>()?;
+    let layout = reader.read_u32::<LittleEndian>()?;
     let instruction_count = reader.read_u32::<LittleEndian>()?;
     let immutables_count = reader.read_u32::<LittleEndian>()?;
 
     Ok(Metadata {
         version_major,
         version_minor,
+        layout,
         instruction_count,
         immutables_count,
     })
 }
 
 pub fn read_instructions(
-    reader: &mut BufReader<File>,
+    reader: &mut impl Read,
     instruction_count: u32,
 ) -> Result<Vec<u32>, Box<dyn Error>> {
     let mut instructions = Vec::new();
@@ -181,7 +758,7 @@ pub fn read_instructions(
 }
 
 pub fn read_immutables(
-    reader: &mut BufReader<File>,
+    reader: &mut impl Read,
     immutables_count: u32,
 ) -> Result<Vec<NovaObject>, Box<dyn Error>> {
     let mut immutables = Vec::new();
@@ -190,14 +767,7 @@ pub fn read_immutables(
 
         match immutable_kind {
             x if x == ImmutableKind::String as u8 => {
-                let length = reader.read_u64::<LittleEndian>()?;
-                let mut str_buffer = Vec::with_capacity(length as usize);
-                for _ in 0..length {
-                    let byte = reader.read_u8()?;
-                    str_buffer.push(byte);
-                }
-
-                let string = String::from_utf8(str_buffer)?;
+                let string = read_string(reader)?;
                 immutables.push(NovaObject::String(Box::new(string)))
             }
 
@@ -206,22 +776,31 @@ pub fn read_immutables(
                 let arity = reader.read_u8()? as Instruction;
                 let number_of_locals = reader.read_u32::<LittleEndian>()?;
                 let is_method = reader.read_u8()? != 0;
-                let length = reader.read_u64::<LittleEndian>()?;
-                let mut str_buffer = Vec::with_capacity(length as usize);
-                for _ in 0..length {
-                    let byte = reader.read_u8()?;
-                    str_buffer.push(byte);
-                }
-
-                let name = Box::new(String::from_utf8(str_buffer)?);
+                let default_parameters = reader.read_u8()? as Instruction;
+                let is_variadic = reader.read_u8()? != 0;
+                let name = Box::new(read_string(reader)?);
 
-                immutables.push(NovaObject::NovaFunction(NovaFunction {
+                let function = NovaFunction {
                     name,
                     address,
                     arity,
+                    default_parameters,
+                    is_variadic,
                     is_method,
                     number_of_locals,
-                }))
+                };
+                verify_function(immutables.len(), &function)?;
+                immutables.push(NovaObject::NovaFunction(function))
+            }
+
+            x if x == ImmutableKind::Int64 as u8 => {
+                let value = reader.read_i64::<LittleEndian>()?;
+                immutables.push(NovaObject::Int64(value))
+            }
+
+            x if x == ImmutableKind::Float64 as u8 => {
+                let value = reader.read_f64::<LittleEndian>()?;
+                immutables.push(NovaObject::Float64(value))
             }
 
             _ => {
@@ -238,13 +817,201 @@ pub fn read_immutables(
     Ok(immutables)
 }
 
+/// read the immutables from `start` of the source like `read_immutables`, only checking the
+/// strings and leaving them in the source until they are used. returns where they end
+fn scan_immutables(
+    source: &ImmutableSource,
+    start: usize,
+    immutables_count: u32,
+) -> Result<(Immutables, usize), Box<dyn Error>> {
+    let bytes = (**source).as_ref();
+    let mut immutables = Immutables::with_source(source.clone());
+    let mut reader = &bytes[start..];
+
+    for _ in 0..immutables_count {
+        if reader.first() != Some(&(ImmutableKind::String as u8)) {
+            for immutable in read_immutables(&mut reader, 1)? {
+                immutables.push(immutable);
+            }
+            continue;
+        }
+
+        reader = &reader[1..];
+        let length = read_usize(&mut reader)?;
+        if length > reader.len() {
+            return Err(Box::new(FileError {
+                description: "the file ends in the middle of a string".to_string(),
+            }));
+        }
+
+        let text = bytes.len() - reader.len();
+        immutables.push_text(text..text + length)?;
+        reader = &reader[length..];
+    }
+
+    Ok((immutables, bytes.len() - reader.len()))
+}
+
+/// whether `write_globals` can save the value, functions and values holding them cannot be
+/// and neither can lists and maps nested deeper than the reader accepts
+pub fn is_saveable(value: &NovaObject) -> bool {
+    let mut pending = vec![(value, 0)];
+
+    while let Some((value, depth)) = pending.pop() {
+        match value {
+            NovaObject::NovaFunction(_) | NovaObject::NativeFunction(_) => return false,
+            NovaObject::List(_) | NovaObject::Map(_) if depth >= MAX_VALUE_DEPTH => return false,
+            NovaObject::List(list) => pending.extend(list.iter().map(|item| (item, depth + 1))),
+            NovaObject::Map(map) => pending.extend(map.values().map(|item| (item, depth + 1))),
+            _ => {}
+        }
+    }
+
+    true
+}
+
+/// serialize named values, they must all be `is_saveable`
+pub fn write_globals(
+    globals: &[(String, NovaObject)],
+    output: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    buffer.write_u32::<LittleEndian>(GLOBALS_MARKER)?;
+    buffer.write_u32::<LittleEndian>(globals.len() as u32)?;
+
+    for (name, value) in globals {
+        write_string(name, &mut buffer)?;
+        write_value(value, &mut buffer)?;
+    }
+
+    output.write_all(&buffer)?;
+    Ok(())
+}
+
+fn write_value(value: &NovaObject, buffer: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+    match value {
+        NovaObject::None => buffer.write_u8(ValueKind::None as u8)?,
+        NovaObject::Bool(bool) => {
+            buffer.write_u8(ValueKind::Bool as u8)?;
+            buffer.write_u8(*bool as u8)?;
+        }
+        NovaObject::Int64(value) => {
+            buffer.write_u8(ValueKind::Int64 as u8)?;
+            buffer.write_i64::<LittleEndian>(*value)?;
+        }
+        NovaObject::Float64(value) => {
+            buffer.write_u8(ValueKind::Float64 as u8)?;
+            buffer.write_f64::<LittleEndian>(*value)?;
+        }
+        NovaObject::String(string) => {
+            buffer.write_u8(ValueKind::String as u8)?;
+            write_string(string, buffer)?;
+        }
+        NovaObject::List(list) => {
+            buffer.write_u8(ValueKind::List as u8)?;
+            buffer.write_u64::<LittleEndian>(list.len() as u64)?;
+            for item in list.iter() {
+                write_value(item, buffer)?;
+            }
+        }
+        NovaObject::Map(map) => {
+            buffer.write_u8(ValueKind::Map as u8)?;
+            buffer.write_u64::<LittleEndian>(map.len() as u64)?;
+            for (key, item) in map.iter() {
+                write_string(key, buffer)?;
+                write_value(item, buffer)?;
+            }
+        }
+        NovaObject::NovaFunction(_) | NovaObject::NativeFunction(_) => {
+            return Err(Box::new(FileError {
+                description: format!("cannot save the function '{}'", value),
+            }))
+        }
+    }
+
+    Ok(())
+}
+
+/// deserialize the named values written by `write_globals`
+pub fn read_globals(input: &mut impl Read) -> Result<Vec<(String, NovaObject)>, Box<dyn Error>> {
+    if input.read_u32::<LittleEndian>()? != GLOBALS_MARKER {
+        return Err(Box::new(FileError {
+            description: "not a file of saved globals".to_string(),
+        }));
+    }
+
+    let count = input.read_u32::<LittleEndian>()?;
+    let mut globals = Vec::new();
+    for _ in 0..count {
+        let name = read_string(input)?;
+        let value = read_value(input, 0)?;
+        globals.push((name, value));
+    }
+
+    Ok(globals)
+}
+
+fn read_value(input: &mut impl Read, depth: usize) -> Result<NovaObject, Box<dyn Error>> {
+    let kind = input.read_u8()?;
+
+    let value = match kind {
+        x if x == ValueKind::None as u8 => NovaObject::None,
+        x if x == ValueKind::Bool as u8 => NovaObject::Bool(input.read_u8()? != 0),
+        x if x == ValueKind::Int64 as u8 => NovaObject::Int64(input.read_i64::<LittleEndian>()?),
+        x if x == ValueKind::Float64 as u8 => {
+            NovaObject::Float64(input.read_f64::<LittleEndian>()?)
+        }
+        x if x == ValueKind::String as u8 => NovaObject::String(Box::new(read_string(input)?)),
+        x if (x == ValueKind::List as u8 || x == ValueKind::Map as u8)
+            && depth >= MAX_VALUE_DEPTH =>
+        {
+            return Err(Box::new(FileError {
+                description: format!("values nested deeper than {}", MAX_VALUE_DEPTH),
+            }))
+        }
+        x if x == ValueKind::List as u8 => {
+            let length = input.read_u64::<LittleEndian>()?;
+            let mut list = Vec::new();
+            for _ in 0..length {
+                list.push(read_value(input, depth + 1)?);
+            }
+            NovaObject::List(Box::new(list))
+        }
+        x if x == ValueKind::Map as u8 => {
+            let length = input.read_u64::<LittleEndian>()?;
+            let mut map = NovaMap::new();
+            for _ in 0..length {
+                let key = read_string(input)?;
+                map.insert(key, read_value(input, depth + 1)?);
+            }
+            NovaObject::Map(Box::new(map))
+        }
+        _ => {
+            return Err(Box::new(FileError {
+                description: format!("Cannot read value kind {:?} from file", kind),
+            }))
+        }
+    };
+
+    Ok(value)
+}
+
 #[cfg(test)]
 mod file_tests {
+    use std::sync::Arc;
+
     use crate::{
-        bytecode::OpCode, instruction::InstructionBuilder, object::NovaObject, program::Program,
+        bytecode::OpCode,
+        instruction::InstructionBuilder,
+        object::NovaObject,
+        program::{ColumnSpan, LineDefinition, LocalSymbol, Program},
     };
 
-    use super::{read_program_file, write_program_file};
+    use super::{
+        read_debug_info_bytes, read_program_bytes, read_program_file, sidecar_path,
+        write_debug_info_bytes, write_debug_info_file, write_program_bytes,
+        write_program_bytes_with_debug_info, write_program_file, LAYOUT, LAYOUT_IEEE754_SINGLE,
+    };
 
     #[test]
     fn test_write_and_read() {
@@ -252,11 +1019,240 @@ mod file_tests {
         write_program_file("test.nvc", &program).unwrap();
         let r_program = read_program_file("test.nvc").unwrap();
         assert_eq!(program.instructions, r_program.instructions);
+
+        // the string stays in the file's bytes until it is used
+        assert!(!r_program.immutables.is_decoded(0));
+        assert!(r_program.immutables.is_decoded(1));
+        assert_eq!(program.immutables, r_program.immutables);
+        assert!(r_program.immutables.is_decoded(0));
+    }
+
+    #[test]
+    fn test_read_bytes() {
+        let program = get_program();
+        let bytes = write_program_bytes(&program).unwrap();
+
+        let r_program = read_program_bytes(&bytes).unwrap();
+        assert_eq!(program.instructions, r_program.instructions);
+        assert_eq!(program.immutables, r_program.immutables);
+
+        assert!(read_program_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(read_program_bytes(&trailing).is_err());
+    }
+
+    #[test]
+    fn test_debug_info() {
+        let mut program = get_program();
+        program.line_definitions.push(LineDefinition {
+            last_instruction: 4,
+            source_line: 2,
+            source_file: "main.nova".to_string(),
+            columns: Some(ColumnSpan { start: 3, end: 9 }),
+        });
+        program.local_symbols = vec![
+            LocalSymbol {
+                name: "total".to_string(),
+                function: None,
+                slot: 0,
+                start: 0,
+                end: 12,
+            },
+            LocalSymbol {
+                name: "x".to_string(),
+                function: Some(3),
+                slot: 1,
+                start: 4,
+                end: 8,
+            },
+        ];
+
+        let bytes = write_program_bytes_with_debug_info(&program).unwrap();
+        let r_program = read_program_bytes(&bytes).unwrap();
+        assert_eq!(program.instructions, r_program.instructions);
+        assert_eq!(r_program.line_definitions.len(), 1);
+        assert_eq!(r_program.line_definitions[0].source_file, "main.nova");
+        assert_eq!(
+            r_program.line_definitions[0].columns,
+            Some(ColumnSpan { start: 3, end: 9 })
+        );
+        assert_eq!(program.local_symbols, r_program.local_symbols);
+
+        assert!(read_program_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // without debug info the tables are left empty
+        let r_program = read_program_bytes(&write_program_bytes(&program).unwrap()).unwrap();
+        assert!(r_program.line_definitions.is_empty());
+        assert!(r_program.local_symbols.is_empty());
+    }
+
+    #[test]
+    fn test_debug_info_sidecar() {
+        let mut program = get_program();
+        program.line_definitions.push(LineDefinition {
+            last_instruction: 12,
+            source_line: 3,
+            source_file: "main.nova".to_string(),
+            columns: None,
+        });
+
+        assert_eq!(sidecar_path("build/main.nvc"), "build/main.nvd");
+        write_program_file("test_sidecar.nvc", &program).unwrap();
+        write_debug_info_file("test_sidecar.nvd", &program).unwrap();
+        let r_program = read_program_file("test_sidecar.nvc").unwrap();
+        std::fs::remove_file("test_sidecar.nvc").unwrap();
+        std::fs::remove_file("test_sidecar.nvd").unwrap();
+        assert_eq!(r_program.line_definitions.len(), 1);
+        assert_eq!(r_program.line_definitions[0].source_line, 3);
+
+        // the sidecar only joins the instructions it was made for
+        let sidecar = write_debug_info_bytes(&program).unwrap();
+        let mut other = get_program();
+        other.instructions[0] += 1;
+        let Err(err) = read_debug_info_bytes(&sidecar, &mut other) else {
+            panic!("the sidecar of another program was read")
+        };
+        assert_eq!(
+            err.to_string(),
+            "the debug info was made for another program"
+        );
+        assert!(other.line_definitions.is_empty());
+
+        assert!(read_debug_info_bytes(&sidecar[..sidecar.len() - 1], &mut get_program()).is_err());
+    }
+
+    #[test]
+    fn test_layout() {
+        let bytes = write_program_bytes(&get_program()).unwrap();
+        assert_eq!(bytes[8..12], LAYOUT.to_le_bytes());
+
+        let with_layout = |layout: u32| {
+            let mut bytes = bytes.clone();
+            bytes[8..12].copy_from_slice(&layout.to_le_bytes());
+            match read_program_bytes(&bytes) {
+                Ok(_) => panic!("layout {:#x} was accepted", layout),
+                Err(err) => err.to_string(),
+            }
+        };
+
+        assert_eq!(
+            with_layout(LAYOUT.swap_bytes()),
+            "the bytecode was written with big endian numbers, only little endian is supported"
+        );
+        assert_eq!(
+            with_layout(LAYOUT & !LAYOUT_IEEE754_SINGLE),
+            "the bytecode layout 0x0200000b lacks assumptions of the supported 0x0200000f"
+        );
+        assert_eq!(
+            with_layout(LAYOUT + (1 << 24)),
+            "the bytecode layout 0x0300000f is not supported, expected 0x0200000f"
+        );
+        assert_eq!(
+            with_layout(LAYOUT | 1 << 5),
+            "the bytecode layout 0x0200002f is not supported, expected 0x0200000f"
+        );
+
+        // a string longer than the file is an error, not an allocation
+        let mut program = get_program();
+        program.immutables = Arc::new(vec![NovaObject::String(Box::new("ab".to_string()))].into());
+        let mut bytes = write_program_bytes(&program).unwrap();
+        let length = bytes.len() - 10;
+        bytes[length..length + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(read_program_bytes(&bytes).is_err());
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn test_signed() {
+        use super::{read_signed_program_bytes, sign_program_bytes};
+
+        let key = b"host key";
+        let bytes = write_program_bytes_with_debug_info(&get_program()).unwrap();
+        let signed = sign_program_bytes(&bytes, key).unwrap();
+
+        let r_program = read_signed_program_bytes(&signed, key).unwrap();
+        assert_eq!(get_program().instructions, r_program.instructions);
+        // the signature does not stop an unchecked read
+        assert!(read_program_bytes(&signed).is_ok());
+
+        let message = |result: Result<Program, Box<dyn std::error::Error>>| match result {
+            Ok(_) => panic!("the program was accepted"),
+            Err(err) => err.to_string(),
+        };
+
+        let mut tampered = signed.clone();
+        tampered[20] ^= 1;
+        assert_eq!(
+            message(read_signed_program_bytes(&tampered, key)),
+            "the bytecode signature does not match, it was changed or signed with another key"
+        );
+        assert_eq!(
+            message(read_signed_program_bytes(&signed, b"other key")),
+            "the bytecode signature does not match, it was changed or signed with another key"
+        );
+        assert_eq!(
+            message(read_signed_program_bytes(&bytes, key)),
+            "the bytecode is not signed"
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed() {
+        use super::write_compressed_program_bytes;
+
+        let mut program = get_program();
+        Arc::make_mut(&mut program.immutables)
+            .push(NovaObject::String(Box::new("nova ".repeat(200))));
+        program.local_symbols.push(LocalSymbol {
+            name: "total".to_string(),
+            function: None,
+            slot: 0,
+            start: 0,
+            end: 12,
+        });
+
+        let plain = write_program_bytes(&program).unwrap();
+        let bytes = write_compressed_program_bytes(&program, true).unwrap();
+        assert!(bytes.len() * 2 < plain.len(), "{} bytes", bytes.len());
+
+        let r_program = read_program_bytes(&bytes).unwrap();
+        assert_eq!(program.instructions, r_program.instructions);
         assert_eq!(program.immutables, r_program.immutables);
+        assert_eq!(program.local_symbols, r_program.local_symbols);
+
+        let bytes = write_compressed_program_bytes(&program, false).unwrap();
+        assert!(read_program_bytes(&bytes).is_ok());
+        assert!(read_program_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // the original size is the second word after the header
+        let mut wrong_size = bytes.clone();
+        wrong_size[28] ^= 1;
+        assert!(read_program_bytes(&wrong_size).is_err());
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn test_compressed_needs_feature() {
+        let mut bytes = write_program_bytes(&get_program()).unwrap();
+        bytes[8..12].copy_from_slice(&(LAYOUT | super::LAYOUT_DEFLATE).to_le_bytes());
+        let Err(err) = read_program_bytes(&bytes) else {
+            panic!("a compressed file was read")
+        };
+        assert_eq!(
+            err.to_string(),
+            "the bytecode is compressed, reading it needs the compression feature"
+        );
     }
 
     fn get_program() -> Program {
-        let immutables = vec![NovaObject::String(Box::new("I am Timothy".to_string()))];
+        let immutables = vec![
+            NovaObject::String(Box::new("I am Timothy".to_string())),
+            NovaObject::Int64(-7),
+            NovaObject::Float64(2.5),
+        ];
 
         let instructions = vec![
             InstructionBuilder::new_load_float32_instruction(0),
@@ -275,7 +1271,7 @@ mod file_tests {
         ];
         Program {
             instructions,
-            immutables,
+            immutables: Arc::new(immutables.into()),
             ..Default::default()
         }
     }
@@ -0,0 +1,175 @@
+//! programs run straight from a memory mapped file. the instruction section of a file is
+//! already the little endian words the virtual machine executes, so it is used in place
+//! instead of being copied into a vector, and a large program starts without reading all of it.
+//! the strings are decoded from the mapping when they are first used
+
+use memmap2::Mmap;
+use std::{
+    error::Error,
+    fs,
+    mem::{align_of, size_of},
+    sync::Arc,
+};
+
+use crate::{
+    immutables::ImmutableSource,
+    instruction::Instruction,
+    program::{ImmutablePool, LineDefinition, LocalSymbol, Program},
+};
+
+use super::{
+    check_layout, check_version, migration, read_metadata, read_trailer, scan_immutables,
+    FileError, FORMAT_VERSION, LAYOUT_DEFLATE,
+};
+
+/// the instruction section of a mapped program file
+pub struct MappedCode {
+    map: Arc<Mmap>,
+    start: usize,
+    length: usize,
+}
+
+impl MappedCode {
+    /// the instructions in the mapping, which holds `length` words from `start` on
+    fn new(map: Arc<Mmap>, start: usize, length: usize) -> Result<Self, Box<dyn Error>> {
+        let fits = start
+            .checked_add(length * size_of::<Instruction>())
+            .is_some_and(|end| end <= map.len());
+        let aligned = (map.as_ptr() as usize + start).is_multiple_of(align_of::<Instruction>());
+
+        if !fits || !aligned || cfg!(target_endian = "big") {
+            return Err(Box::new(FileError {
+                description: "the instructions cannot be used from the mapped file".to_string(),
+            }));
+        }
+
+        Ok(Self { map, start, length })
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        // safety: `new` checked the words are inside the mapping, aligned and in the byte order
+        // of the machine, and the mapping lives as long as self
+        unsafe {
+            std::slice::from_raw_parts(
+                self.map.as_ptr().add(self.start) as *const Instruction,
+                self.length,
+            )
+        }
+    }
+}
+
+/// a program whose instructions stay in the mapped file, loaded with
+/// `VirtualMachine::try_load_mapped_program`. the file must not change while it is mapped
+pub struct MappedProgram {
+    pub code: MappedCode,
+    pub immutables: ImmutablePool,
+    pub line_definitions: Vec<LineDefinition>,
+    pub local_symbols: Vec<LocalSymbol>,
+}
+
+impl MappedProgram {
+    pub fn instructions(&self) -> &[Instruction] {
+        self.code.instructions()
+    }
+
+    /// copy the program out of the mapping
+    pub fn to_program(&self) -> Program {
+        Program {
+            instructions: self.instructions().to_vec(),
+            immutables: self.immutables.clone(),
+            line_definitions: self.line_definitions.clone(),
+            local_symbols: self.local_symbols.clone(),
+        }
+    }
+}
+
+/// map a program file written by `write_program_file`. the debug info and the immutables
+/// other than strings are read, the instructions are used where they are and verified when
+/// the program is loaded.
+/// compressed files and files of older format versions cannot be mapped, `read_program_file`
+/// reads those. the debug info of a sidecar is not picked up
+pub fn map_program_file(path: &str) -> Result<MappedProgram, Box<dyn Error>> {
+    let file = fs::File::open(path)?;
+    // safety: the mapping is only sound while nobody changes the file, which the callers
+    // promise as documented on `MappedProgram`
+    let map = Arc::new(unsafe { Mmap::map(&file)? });
+
+    let layout = map.get(8..12).map_or(0, |layout| {
+        u32::from_le_bytes([layout[0], layout[1], layout[2], layout[3]])
+    });
+    if migration::format_version(layout) < FORMAT_VERSION {
+        return Err(Box::new(FileError {
+            description: "files of older format versions cannot be mapped".to_string(),
+        }));
+    }
+
+    let mut reader = &map[..];
+    let metadata = read_metadata(&mut reader)?;
+    check_version(&metadata)?;
+    if metadata.layout & LAYOUT_DEFLATE != 0 {
+        return Err(Box::new(FileError {
+            description: "compressed files cannot be mapped".to_string(),
+        }));
+    }
+    check_layout(metadata.layout)?;
+
+    let start = map.len() - reader.len();
+    let length = metadata.instruction_count as usize;
+    let section = length * size_of::<Instruction>();
+    if reader.len() < section {
+        return Err(Box::new(FileError {
+            description: format!("the file is too short for {} instructions", length),
+        }));
+    }
+
+    // the strings stay in the mapping as well until they are used
+    let source: ImmutableSource = map.clone();
+    let (immutables, end) = scan_immutables(&source, start + section, metadata.immutables_count)?;
+    let (line_definitions, local_symbols) = read_trailer(&map[end..])?;
+
+    Ok(MappedProgram {
+        code: MappedCode::new(map, start, length)?,
+        immutables: Arc::new(immutables),
+        line_definitions,
+        local_symbols,
+    })
+}
+
+#[cfg(test)]
+mod mapped_tests {
+    use std::{env, fs, process};
+
+    use crate::file::{write_program_bytes_with_debug_info, write_program_file};
+
+    use super::map_program_file;
+
+    #[test]
+    fn test_map_program_file() {
+        let program = crate::nova_asm! {
+            load_int 0, 40;
+            load_string 1, "answer";
+            halt;
+        };
+
+        let path = env::temp_dir().join(format!("nova_mapped_{}.nvc", process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, write_program_bytes_with_debug_info(&program).unwrap()).unwrap();
+
+        let mapped = map_program_file(path).unwrap();
+        assert_eq!(mapped.instructions(), &program.instructions[..]);
+        assert_eq!(mapped.immutables, program.immutables);
+
+        // the header announces more instructions than the file holds
+        let mut bytes = fs::read(path).unwrap();
+        bytes[12] = 0xff;
+        fs::write(path, &bytes).unwrap();
+        assert!(map_program_file(path).is_err());
+
+        write_program_file(path, &program).unwrap();
+        assert_eq!(
+            map_program_file(path).unwrap().to_program().instructions,
+            program.instructions
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
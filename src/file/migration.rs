@@ -0,0 +1,173 @@
+//! decoders for the program files of older format versions. each reads the bytes of its
+//! version and upgrades them to the current `Program`, so files keep loading as the format
+//! changes. a change to the format bumps `FORMAT_VERSION` and adds the decoder of the version
+//! it replaces here
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::{error::Error, sync::Arc};
+
+use crate::{
+    bytecode::OpCode,
+    instruction::{instruction_decoder, Instruction},
+    object::{NovaFunction, NovaObject},
+    program::Program,
+};
+
+use super::{read_instructions, read_string, FileError, FORMAT_VERSION};
+
+type Decode = fn(&[u8]) -> Result<Program, Box<dyn Error>>;
+
+/// reads the files of one older format version
+pub(super) struct Decoder {
+    pub version: u32,
+    pub decode: Decode,
+}
+
+/// the decoders of the older format versions, the current one is read by `read_program_bytes`
+pub(super) const DECODERS: &[Decoder] = &[Decoder {
+    version: 1,
+    decode: decode_version_1,
+}];
+
+/// the format version of a file with the layout word. files of version 1 have none, the word
+/// is their instruction count, which is far too small to reach the top byte
+pub(super) fn format_version(layout: u32) -> u32 {
+    match layout >> 24 {
+        0 => 1,
+        version => version,
+    }
+}
+
+/// decode a file of an older format version, failing for versions without a decoder
+pub(super) fn decode(version: u32, bytes: &[u8]) -> Result<Program, Box<dyn Error>> {
+    match DECODERS.iter().find(|decoder| decoder.version == version) {
+        Some(decoder) => (decoder.decode)(bytes),
+        None => Err(Box::new(FileError {
+            description: format!(
+                "bytecode format version {} cannot be read, this build reads version {}",
+                version, FORMAT_VERSION
+            ),
+        })),
+    }
+}
+
+/// opcodes after `Equal` in version 1 were one lower, `JumpNone` came in before `Halt`
+const VERSION_1_HALT: Instruction = 43;
+
+/// version 1 has no layout word, only strings and functions as immutables, functions without
+/// default parameters or variadic arguments, and no debug info
+fn decode_version_1(bytes: &[u8]) -> Result<Program, Box<dyn Error>> {
+    let mut reader = bytes;
+    let _version_major = reader.read_u32::<LittleEndian>()?;
+    let _version_minor = reader.read_u32::<LittleEndian>()?;
+    let instruction_count = reader.read_u32::<LittleEndian>()?;
+    let immutables_count = reader.read_u32::<LittleEndian>()?;
+
+    let mut instructions = read_instructions(&mut reader, instruction_count)?;
+    upgrade_version_1_opcodes(&mut instructions)?;
+
+    let mut immutables = Vec::new();
+    for _ in 0..immutables_count {
+        let immutable = match reader.read_u8()? {
+            0 => NovaObject::String(Box::new(read_string(&mut reader)?)),
+            1 => {
+                let address = reader.read_u32::<LittleEndian>()?;
+                let arity = reader.read_u8()? as Instruction;
+                let number_of_locals = reader.read_u32::<LittleEndian>()?;
+                let is_method = reader.read_u8()? != 0;
+                let name = Box::new(read_string(&mut reader)?);
+
+                NovaObject::NovaFunction(NovaFunction {
+                    name,
+                    address,
+                    arity,
+                    default_parameters: 0,
+                    is_variadic: false,
+                    is_method,
+                    number_of_locals,
+                })
+            }
+            kind => {
+                return Err(Box::new(FileError {
+                    description: format!("Cannot read immutable_kind {:?} from file", kind),
+                }))
+            }
+        };
+
+        immutables.push(immutable);
+    }
+
+    if !reader.is_empty() {
+        return Err(Box::new(FileError {
+            description: format!("{} unexpected bytes after the program", reader.len()),
+        }));
+    }
+
+    Ok(Program {
+        instructions,
+        immutables: Arc::new(immutables.into()),
+        ..Default::default()
+    })
+}
+
+/// renumber the opcodes, stepping over operand words, which are not instructions
+fn upgrade_version_1_opcodes(instructions: &mut [Instruction]) -> Result<(), Box<dyn Error>> {
+    let mut index = 0;
+    while index < instructions.len() {
+        let instruction = instructions[index];
+        let opcode = match instruction_decoder::decode_opcode(instruction) {
+            VERSION_1_HALT => OpCode::Halt,
+            opcode if opcode < VERSION_1_HALT => OpCode::try_from(opcode)?,
+            opcode => {
+                return Err(Box::new(FileError {
+                    description: format!(
+                        "invalid opcode {} in version 1 bytecode at {}",
+                        opcode, index
+                    ),
+                }))
+            }
+        };
+
+        instructions[index] = (instruction & 0x03FF_FFFF) | (opcode as Instruction) << 26;
+        index += 1 + opcode.operand_words();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod migration_tests {
+    use crate::{bytecode::OpCode, file::read_program_bytes, object::NovaObject};
+
+    use super::format_version;
+
+    const VERSION_1: &[u8] = include_bytes!("../../test_code/bytecode/v1.nvc");
+
+    #[test]
+    fn test_version_1() {
+        assert_eq!(
+            format_version(u32::from_le_bytes(VERSION_1[8..12].try_into().unwrap())),
+            1
+        );
+
+        let program = read_program_bytes(VERSION_1).unwrap();
+        let last = *program.instructions.last().unwrap();
+        assert_eq!(last >> 26, OpCode::Halt as u32);
+
+        let add = program
+            .immutables
+            .iter()
+            .find_map(|immutable| match immutable {
+                NovaObject::NovaFunction(function) => Some(function),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(add.name.as_str(), "add");
+        assert_eq!(
+            (add.arity, add.default_parameters, add.is_variadic),
+            (2, 0, false)
+        );
+
+        assert!(read_program_bytes(&VERSION_1[..VERSION_1.len() - 1]).is_err());
+    }
+}
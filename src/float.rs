@@ -0,0 +1,31 @@
+//! the float functions of `std` for builds without it, from `libm`
+
+pub(crate) trait Float {
+    fn powf(self, exponent: Self) -> Self;
+    fn sqrt(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn fract(self) -> Self;
+}
+
+impl Float for f64 {
+    fn powf(self, exponent: Self) -> Self {
+        libm::pow(self, exponent)
+    }
+
+    fn sqrt(self) -> Self {
+        libm::sqrt(self)
+    }
+
+    fn floor(self) -> Self {
+        libm::floor(self)
+    }
+
+    fn ceil(self) -> Self {
+        libm::ceil(self)
+    }
+
+    fn fract(self) -> Self {
+        self - libm::trunc(self)
+    }
+}
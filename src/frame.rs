@@ -1,32 +1,52 @@
 use crate::register::{Register, RegisterID};
 
-#[derive(Debug, Clone)]
+/// number of general registers saved by a frame, the only ones a callee writes to
+pub const SAVED_REGISTERS: usize = RegisterID::R15 as usize;
+
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Frame {
     pub is_main: bool,
-    pub registers: [Register; RegisterID::RMax as usize + 1],
+    /// the caller's general registers, restored when the frame is dropped
+    pub registers: [Register; SAVED_REGISTERS],
+    /// address of the instruction after the invoke
+    pub return_address: u64,
+    /// the caller's local offset
+    pub local_offset: u64,
+    /// address of the called function
+    pub function: u64,
+    /// length of the locals when the frame was entered. dropping the frame truncates them back
+    /// to it, which also frees the slots of blocks the function returned out of
+    pub locals_start: u64,
 }
 
 impl Frame {
     #[inline(always)]
     pub fn new(
-        registers: [Register; RegisterID::RMax as usize + 1],
+        registers: [Register; SAVED_REGISTERS],
+        return_address: u64,
+        local_offset: u64,
+        function: u64,
+        locals_start: u64,
         is_main: bool,
     ) -> Self {
         Self {
             is_main,
             registers,
+            return_address,
+            local_offset,
+            function,
+            locals_start,
         }
     }
 
     #[inline(always)]
     pub fn empty(is_main: bool) -> Self {
-        let registers = [Register::default(); RegisterID::RMax as usize + 1];
-        Self::new(registers, is_main)
+        let registers = [Register::default(); SAVED_REGISTERS];
+        Self::new(registers, 0, 0, 0, 0, is_main)
     }
 
     #[inline(always)]
     pub fn main() -> Self {
-        let registers = [Register::default(); RegisterID::RMax as usize + 1];
-        Self::new(registers, true)
+        Self::empty(true)
     }
 }
@@ -0,0 +1,27 @@
+use crate::{error::NovaError, program::Program};
+
+/// turns source text into a program the virtual machine can run.
+/// implement this to target the vm from another syntax without depending on nova_tw
+pub trait Frontend {
+    fn compile(&self, source: &str, filename: &str) -> Result<Program, NovaError>;
+}
+
+/// the default frontend, compiling nova_tw syntax
+#[cfg(feature = "nova_tw")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NovaFrontend;
+
+#[cfg(feature = "nova_tw")]
+impl Frontend for NovaFrontend {
+    /// only the first error is returned, use `compiler::compile_with_warnings` to get all of them
+    fn compile(&self, source: &str, filename: &str) -> Result<Program, NovaError> {
+        match crate::compiler::compile_with_warnings(source, filename) {
+            Ok((program, _)) => Ok(program),
+            Err(errors) => Err(errors
+                .into_iter()
+                .next()
+                .map(NovaError::Compile)
+                .expect("compilation failed without an error")),
+        }
+    }
+}
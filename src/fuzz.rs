@@ -0,0 +1,313 @@
+//! entry points for fuzzing the loader, the verifier and the virtual machine with arbitrary
+//! bytes. the `fuzz` feature turns on the `checked` one, so a program reaching past the end
+//! of its registers, locals or memory raises an error instead of corrupting the host.
+//! the cargo-fuzz targets calling these live in `fuzz/`
+
+use std::{io, sync::Arc};
+
+use crate::{
+    bytecode::BYTECODE_COUNT,
+    error::VmError,
+    file,
+    instruction::{Instruction, InstructionBuilder},
+    machine::VirtualMachine,
+    object::{NovaFunction, NovaObject},
+    profiler::Profiler,
+    program::{Program, VerifyError},
+};
+
+/// instructions an untrusted program may run by default before it is stopped
+pub const DEFAULT_FUEL: u64 = 100_000;
+
+/// constants `program_from_bytes` makes at most
+const MAX_CONSTANTS: u8 = 16;
+/// length of the longest string constant `program_from_bytes` makes
+const MAX_STRING_LENGTH: usize = 16;
+/// locals of the functions `program_from_bytes` makes at most, past their parameters
+const MAX_FUNCTION_SLOTS: u8 = 8;
+/// parameters of the functions `program_from_bytes` makes at most, more than the registers
+/// hold so the verifier sees functions it has to refuse
+const MAX_FUNCTION_ARITY: u8 = 24;
+/// the opcode is in the top bits of an instruction word
+const OPCODE_SHIFT: u32 = 26;
+
+/// how a run of `run_untrusted` ended
+#[derive(Debug, Clone)]
+pub enum UntrustedOutcome {
+    /// the verifier refused the program, nothing ran
+    Rejected(VerifyError),
+    /// the loader could not read the bytes as a program
+    Unreadable(String),
+    /// the program halted
+    Finished,
+    /// the program raised an error, out of bounds accesses included
+    Failed(Box<VmError>),
+    /// the program used up its fuel without halting
+    OutOfFuel,
+}
+
+/// stops a run after a number of instructions
+#[derive(Debug, Clone, Copy)]
+pub struct Fuel {
+    remaining: u64,
+    initial: u64,
+}
+
+impl Fuel {
+    pub fn new(instructions: u64) -> Self {
+        Self {
+            remaining: instructions,
+            initial: instructions,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+impl Profiler for Fuel {
+    #[inline(always)]
+    fn executed(&mut self, _: Instruction, _: usize, _: usize) {
+        self.remaining = self.remaining.saturating_sub(1);
+    }
+
+    fn interrupt(&mut self) -> Option<String> {
+        self.is_empty()
+            .then(|| format!("Ran out of fuel after {} instructions", self.initial))
+    }
+}
+
+/// verify the program and run it with `DEFAULT_FUEL`, see `run_untrusted_with_fuel`
+pub fn run_untrusted(program: Program) -> UntrustedOutcome {
+    run_untrusted_with_fuel(program, DEFAULT_FUEL)
+}
+
+/// verify the program and run it in a fresh virtual machine for at most `fuel` instructions.
+/// no native functions are loaded and nothing is printed, so the program can only touch the
+/// machine itself
+pub fn run_untrusted_with_fuel(program: Program, fuel: u64) -> UntrustedOutcome {
+    let mut vm = VirtualMachine::new();
+    vm.set_output(Box::new(io::sink()));
+
+    if let Err(error) = vm.try_load_program(program) {
+        return UntrustedOutcome::Rejected(error);
+    }
+
+    let mut fuel = Fuel::new(fuel);
+    if vm.start_vm_with_profiler(0, &mut fuel) == 0 {
+        return UntrustedOutcome::Finished;
+    }
+
+    match vm.take_error() {
+        _ if fuel.is_empty() => UntrustedOutcome::OutOfFuel,
+        Some(error) => UntrustedOutcome::Failed(Box::new(error)),
+        None => UntrustedOutcome::Finished,
+    }
+}
+
+/// read the bytes like a `.nvc` file and run the program like `run_untrusted`
+pub fn load_untrusted(bytes: &[u8]) -> UntrustedOutcome {
+    match file::read_program_bytes(bytes) {
+        Ok(program) => run_untrusted(program),
+        Err(error) => UntrustedOutcome::Unreadable(error.to_string()),
+    }
+}
+
+/// turn arbitrary bytes into a program, for fuzzers without structured input. the first byte
+/// is the number of constants, each one a tag byte followed by its value, and the rest are
+/// instruction words ending in a halt. the opcodes wrap around to known ones so most inputs
+/// get past the verifier, and functions point into the instructions so calling one runs
+/// something
+pub fn program_from_bytes(data: &[u8]) -> Program {
+    let mut bytes = ByteSource { data, position: 0 };
+
+    let constants = bytes.next_u8() % (MAX_CONSTANTS + 1);
+    let mut immutables: Vec<NovaObject> = (0..constants)
+        .map(|_| constant_from_bytes(&mut bytes))
+        .collect();
+
+    let mut instructions: Vec<Instruction> = bytes
+        .rest()
+        .chunks(4)
+        .map(|word| {
+            let mut padded = [0; 4];
+            padded[..word.len()].copy_from_slice(word);
+            let word = u32::from_le_bytes(padded);
+            let opcode = (word >> OPCODE_SHIFT) % BYTECODE_COUNT;
+            (word & !(Instruction::MAX << OPCODE_SHIFT)) | opcode << OPCODE_SHIFT
+        })
+        .collect();
+    instructions.push(InstructionBuilder::new_halt_instruction());
+
+    for immutable in immutables.iter_mut() {
+        if let NovaObject::NovaFunction(function) = immutable {
+            function.address %= instructions.len() as Instruction;
+        }
+    }
+
+    Program {
+        instructions,
+        immutables: Arc::new(immutables.into()),
+        ..Default::default()
+    }
+}
+
+fn constant_from_bytes(bytes: &mut ByteSource) -> NovaObject {
+    match bytes.next_u8() % 6 {
+        0 => NovaObject::None,
+        1 => NovaObject::Bool(bytes.next_u8() % 2 == 1),
+        2 => NovaObject::Int64(bytes.next_u64() as i64),
+        3 => NovaObject::Float64(f64::from_bits(bytes.next_u64())),
+        4 => {
+            let length = bytes.next_u8() as usize % (MAX_STRING_LENGTH + 1);
+            let text: Vec<u8> = (0..length).map(|_| bytes.next_u8()).collect();
+            NovaObject::String(Box::new(String::from_utf8_lossy(&text).into_owned()))
+        }
+        _ => {
+            let arity = (bytes.next_u8() % MAX_FUNCTION_ARITY) as Instruction;
+            NovaObject::NovaFunction(NovaFunction {
+                name: Box::new(format!("f{}", bytes.position)),
+                address: bytes.next_u32(),
+                arity,
+                default_parameters: bytes.next_u8() as Instruction % (arity + 2),
+                is_variadic: bytes.next_u8() % 2 == 1,
+                is_method: false,
+                number_of_locals: arity + (bytes.next_u8() % MAX_FUNCTION_SLOTS) as Instruction,
+            })
+        }
+    }
+}
+
+/// reads the input front to back, zeros once it runs out
+struct ByteSource<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl ByteSource<'_> {
+    fn next_u8(&mut self) -> u8 {
+        let byte = self.data.get(self.position).copied().unwrap_or(0);
+        self.position += 1;
+        byte
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        u32::from_le_bytes([0; 4].map(|_| self.next_u8()))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        u64::from_le_bytes([0; 8].map(|_| self.next_u8()))
+    }
+
+    fn rest(&self) -> &[u8] {
+        self.data.get(self.position..).unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod fuzz_tests {
+    use std::sync::Arc;
+
+    use crate::{
+        file,
+        instruction::InstructionBuilder,
+        object::{NovaFunction, NovaObject},
+        program::{Program, VerifyError},
+    };
+
+    use super::{load_untrusted, program_from_bytes, run_untrusted, UntrustedOutcome};
+
+    #[test]
+    fn test_untrusted_programs() {
+        // an endless loop runs out of fuel
+        let program = crate::nova_asm! {
+            start:
+            jump start;
+        };
+        assert!(matches!(
+            run_untrusted(program),
+            UntrustedOutcome::OutOfFuel
+        ));
+
+        let program = Program {
+            instructions: vec![0xfc00_0000],
+            ..Default::default()
+        };
+        assert!(matches!(
+            run_untrusted(program),
+            UntrustedOutcome::Rejected(_)
+        ));
+
+        let program = Program {
+            instructions: vec![InstructionBuilder::new_halt_instruction()],
+            ..Default::default()
+        };
+        let bytes = file::write_program_bytes(&program).unwrap();
+        assert!(matches!(load_untrusted(&bytes), UntrustedOutcome::Finished));
+        assert!(matches!(
+            load_untrusted(&bytes[..bytes.len() / 2]),
+            UntrustedOutcome::Unreadable(_)
+        ));
+    }
+
+    #[test]
+    fn test_oversized_arity() {
+        let function = |arity, is_variadic| {
+            let function = NovaFunction {
+                name: Box::new("f".to_string()),
+                address: 0,
+                arity,
+                default_parameters: 0,
+                is_variadic,
+                is_method: false,
+                number_of_locals: arity,
+            };
+            Program {
+                instructions: vec![InstructionBuilder::new_halt_instruction()],
+                immutables: Arc::new(vec![NovaObject::NovaFunction(function)].into()),
+                ..Default::default()
+            }
+        };
+
+        // the extra arguments of a variadic function take a register of their own
+        for (arity, is_variadic) in [(200, false), (17, false), (16, true)] {
+            assert!(matches!(
+                run_untrusted(function(arity, is_variadic)),
+                UntrustedOutcome::Rejected(VerifyError::TooManyParameters { immutable: 0 })
+            ));
+
+            let bytes = file::write_program_bytes(&function(arity, is_variadic)).unwrap();
+            assert!(matches!(
+                load_untrusted(&bytes),
+                UntrustedOutcome::Unreadable(_)
+            ));
+        }
+
+        for (arity, is_variadic) in [(16, false), (15, true)] {
+            assert!(matches!(
+                run_untrusted(function(arity, is_variadic)),
+                UntrustedOutcome::Finished
+            ));
+        }
+    }
+
+    #[test]
+    fn test_random_programs_do_not_panic() {
+        // a fixed linear congruential generator so failures reproduce
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        for length in 0..2000 {
+            let data: Vec<u8> = (0..length % 129)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6_364_136_223_846_793_005)
+                        .wrapping_add(1_442_695_040_888_963_407);
+                    (state >> 56) as u8
+                })
+                .collect();
+
+            run_untrusted(program_from_bytes(&data));
+            load_untrusted(&data);
+        }
+    }
+}
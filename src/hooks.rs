@@ -0,0 +1,89 @@
+use alloc::boxed::Box;
+
+use crate::{error::VmError, host::HostOutput};
+
+/// a nova function entering or leaving, given to the function hooks
+#[derive(Debug, Clone, Copy)]
+pub struct FunctionEvent<'a> {
+    pub name: &'a str,
+    /// address of the function's first instruction
+    pub address: u64,
+    /// number of frames while the function runs, counting the main frame
+    pub depth: usize,
+}
+
+type ErrorHook = Box<dyn FnMut(&VmError)>;
+type PrintHook = Box<dyn FnMut(&str)>;
+type FunctionHook = Box<dyn FnMut(FunctionEvent)>;
+
+/// callbacks the virtual machine makes at significant points of a run, set with
+/// `VirtualMachine::set_hooks`. a hook that is not set costs a branch
+#[derive(Default)]
+pub struct VmHooks {
+    on_error: Option<ErrorHook>,
+    on_print: Option<PrintHook>,
+    on_function_enter: Option<FunctionHook>,
+    on_function_exit: Option<FunctionHook>,
+}
+
+impl VmHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// called with the error a run stopped on, before it is printed
+    pub fn on_error(mut self, hook: impl FnMut(&VmError) + 'static) -> Self {
+        self.on_error = Some(Box::new(hook));
+        self
+    }
+
+    /// called with the text of every print, from the print instruction or a native function
+    pub fn on_print(mut self, hook: impl FnMut(&str) + 'static) -> Self {
+        self.on_print = Some(Box::new(hook));
+        self
+    }
+
+    /// called once the frame of a nova function is pushed, before its first instruction
+    pub fn on_function_enter(mut self, hook: impl FnMut(FunctionEvent) + 'static) -> Self {
+        self.on_function_enter = Some(Box::new(hook));
+        self
+    }
+
+    /// called once a nova function returned and its frame is popped
+    pub fn on_function_exit(mut self, hook: impl FnMut(FunctionEvent) + 'static) -> Self {
+        self.on_function_exit = Some(Box::new(hook));
+        self
+    }
+
+    #[inline(always)]
+    pub(crate) fn watches_functions(&self) -> bool {
+        self.on_function_enter.is_some() || self.on_function_exit.is_some()
+    }
+
+    pub(crate) fn error(&mut self, error: &VmError) {
+        if let Some(hook) = &mut self.on_error {
+            hook(error);
+        }
+    }
+
+    /// write the text to the output, telling the print hook about it
+    pub(crate) fn print(&mut self, output: &mut dyn HostOutput, text: &str) {
+        output.write_text(text);
+
+        if let Some(hook) = &mut self.on_print {
+            hook(text);
+        }
+    }
+
+    pub(crate) fn function_enter(&mut self, event: FunctionEvent) {
+        if let Some(hook) = &mut self.on_function_enter {
+            hook(event);
+        }
+    }
+
+    pub(crate) fn function_exit(&mut self, event: FunctionEvent) {
+        if let Some(hook) = &mut self.on_function_exit {
+            hook(event);
+        }
+    }
+}
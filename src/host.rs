@@ -0,0 +1,72 @@
+//! what the virtual machine needs from the platform it runs on. with the `std` feature every
+//! `std::io::Write` is an output and scripts print to stdout by default, without it the host
+//! provides the output, on a microcontroller a uart or a log buffer
+
+use crate::trace::{TraceEvent, TraceSink};
+
+/// where the text scripts print goes, set with `VirtualMachine::set_output`
+pub trait HostOutput {
+    /// write the text, failures are the host's to handle, a script cannot react to them
+    fn write_text(&mut self, text: &str);
+
+    /// called when a run ends, so buffered text is not held back
+    fn flush_text(&mut self) {}
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> HostOutput for W {
+    fn write_text(&mut self, text: &str) {
+        let _ = self.write_all(text.as_bytes());
+    }
+
+    fn flush_text(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// drops everything written to it, the output and trace sink of a virtual machine without
+/// the `std` feature until the host sets its own
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Discard;
+
+impl HostOutput for Discard {
+    fn write_text(&mut self, _: &str) {}
+}
+
+impl TraceSink for Discard {
+    fn trace(&mut self, _: TraceEvent) {}
+}
+
+#[cfg(test)]
+mod host_tests {
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    use crate::{machine::VirtualMachine, nova_asm};
+
+    use super::HostOutput;
+
+    /// an output like a host without `std` would provide
+    struct Buffer(Rc<RefCell<String>>);
+
+    impl HostOutput for Buffer {
+        fn write_text(&mut self, text: &str) {
+            self.0.borrow_mut().push_str(text);
+        }
+    }
+
+    #[test]
+    fn test_host_output() {
+        let printed = Rc::new(RefCell::new(String::new()));
+        let mut vm = VirtualMachine::new();
+        vm.set_output(Box::new(Buffer(printed.clone())));
+        vm.load_program(nova_asm! {
+            load_int 0, 42;
+            println 0;
+            halt;
+        });
+
+        assert_eq!(vm.start_vm(0), 0);
+        assert_eq!(printed.borrow().as_str(), "42\n");
+    }
+}
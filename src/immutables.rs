@@ -0,0 +1,252 @@
+//! the immutables of a program. those read from a file keep the text of their strings in the
+//! file's bytes until an instruction or a global lookup first uses them, so loading a large
+//! program does not allocate every string it could print
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt::Debug,
+    ops::{Index, IndexMut, Range},
+};
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+use crate::object::NovaObject;
+
+/// bytes holding the text of strings not decoded yet, a file read into memory or mapped
+pub type ImmutableSource = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// without `std` there is no file to read strings from, every immutable is pushed decoded
+#[cfg(not(feature = "std"))]
+#[derive(Clone)]
+struct OnceLock<T>(T);
+
+#[cfg(not(feature = "std"))]
+impl<T> OnceLock<T> {
+    fn get(&self) -> Option<&T> {
+        Some(&self.0)
+    }
+
+    fn get_mut(&mut self) -> Option<&mut T> {
+        Some(&mut self.0)
+    }
+
+    fn get_or_init(&self, _: impl FnOnce() -> T) -> &T {
+        &self.0
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<T> From<T> for OnceLock<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+#[derive(Clone)]
+struct Slot {
+    object: OnceLock<NovaObject>,
+    /// where the text of a string still to decode is in the source
+    text: Option<Range<usize>>,
+}
+
+/// the immutables by index, decoding the strings of a source on first use
+#[derive(Clone, Default)]
+pub struct Immutables {
+    slots: Vec<Slot>,
+    source: Option<ImmutableSource>,
+}
+
+impl Immutables {
+    /// no immutables yet, the strings pushed with `push_text` are read from the source
+    #[cfg(feature = "file")]
+    pub(crate) fn with_source(source: ImmutableSource) -> Self {
+        Self {
+            slots: Vec::new(),
+            source: Some(source),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn contains(&self, object: &NovaObject) -> bool {
+        self.iter().any(|immutable| immutable == object)
+    }
+
+    pub fn push(&mut self, object: NovaObject) {
+        self.slots.push(Slot {
+            object: OnceLock::from(object),
+            text: None,
+        });
+    }
+
+    /// add a string whose text is at the range of the source, checking it is utf-8 but
+    /// decoding it only when it is first used. the range has to be inside the source
+    #[cfg(feature = "file")]
+    pub(crate) fn push_text(&mut self, text: Range<usize>) -> Result<(), core::str::Utf8Error> {
+        if let Some(source) = &self.source {
+            core::str::from_utf8(&(**source).as_ref()[text.clone()])?;
+        }
+
+        self.slots.push(Slot {
+            object: OnceLock::new(),
+            text: Some(text),
+        });
+        Ok(())
+    }
+
+    /// the immutable at the index, decoding it if it was not yet
+    pub fn get(&self, index: usize) -> Option<&NovaObject> {
+        let slot = self.slots.get(index)?;
+        Some(slot.object.get_or_init(|| self.decode(slot)))
+    }
+
+    /// whether the immutable at the index was used or never needed decoding
+    pub fn is_decoded(&self, index: usize) -> bool {
+        self.slots
+            .get(index)
+            .is_some_and(|slot| slot.object.get().is_some())
+    }
+
+    /// every immutable, decoding those that were not yet
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &NovaObject> + ExactSizeIterator {
+        (0..self.len()).map(|index| &self[index])
+    }
+
+    /// the immutables decoded so far with their indices, the functions and numbers always are
+    pub fn iter_decoded(&self) -> impl Iterator<Item = (usize, &NovaObject)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.object.get().map(|object| (index, object)))
+    }
+
+    /// the immutables to change in place, all decoded first
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut NovaObject> {
+        self.decode_all();
+        self.slots
+            .iter_mut()
+            .filter_map(|slot| slot.object.get_mut())
+    }
+
+    /// the index of the last string with the text, without decoding strings to compare them
+    pub fn rposition_string(&self, text: &str) -> Option<usize> {
+        self.slots
+            .iter()
+            .rposition(|slot| match (slot.object.get(), &slot.text) {
+                (Some(NovaObject::String(string)), _) => **string == *text,
+                (None, Some(range)) => self.text(range) == text.as_bytes(),
+                _ => false,
+            })
+    }
+
+    fn decode_all(&mut self) {
+        for index in 0..self.len() {
+            self.get(index);
+        }
+    }
+
+    fn text(&self, range: &Range<usize>) -> &[u8] {
+        match &self.source {
+            Some(source) => &(**source).as_ref()[range.clone()],
+            None => &[],
+        }
+    }
+
+    fn decode(&self, slot: &Slot) -> NovaObject {
+        let text = slot.text.as_ref().map_or(&[][..], |range| self.text(range));
+        NovaObject::String(Box::new(String::from_utf8_lossy(text).into_owned()))
+    }
+}
+
+impl Index<usize> for Immutables {
+    type Output = NovaObject;
+
+    fn index(&self, index: usize) -> &NovaObject {
+        match self.get(index) {
+            Some(object) => object,
+            None => panic!("immutable {} out of range, there are {}", index, self.len()),
+        }
+    }
+}
+
+impl IndexMut<usize> for Immutables {
+    fn index_mut(&mut self, index: usize) -> &mut NovaObject {
+        self.get(index);
+        match self
+            .slots
+            .get_mut(index)
+            .and_then(|slot| slot.object.get_mut())
+        {
+            Some(object) => object,
+            None => panic!("immutable {} out of range", index),
+        }
+    }
+}
+
+impl From<Vec<NovaObject>> for Immutables {
+    fn from(objects: Vec<NovaObject>) -> Self {
+        let mut immutables = Self::default();
+        for object in objects {
+            immutables.push(object);
+        }
+
+        immutables
+    }
+}
+
+impl PartialEq for Immutables {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl PartialEq<Vec<NovaObject>> for Immutables {
+    fn eq(&self, other: &Vec<NovaObject>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Debug for Immutables {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(all(test, feature = "file"))]
+mod immutables_tests {
+    use std::sync::Arc;
+
+    use crate::object::NovaObject;
+
+    use super::Immutables;
+
+    #[test]
+    fn test_lazy_strings() {
+        let mut immutables = Immutables::with_source(Arc::new(b"printlnnova".to_vec()));
+        immutables.push(NovaObject::Int64(7));
+        immutables.push_text(0..7).unwrap();
+        immutables.push_text(7..11).unwrap();
+        assert!(immutables.is_decoded(0));
+        assert!(!immutables.is_decoded(1));
+
+        // looking for a name compares the bytes
+        assert_eq!(immutables.rposition_string("nova"), Some(2));
+        assert!(!immutables.is_decoded(2));
+
+        assert_eq!(
+            immutables[2],
+            NovaObject::String(Box::new("nova".to_string()))
+        );
+        assert!(immutables.is_decoded(2) && !immutables.is_decoded(1));
+        assert_eq!(immutables.iter_decoded().count(), 2);
+
+        let mut invalid = Immutables::with_source(Arc::new(vec![0xff]));
+        assert!(invalid.push_text(0..1).is_err());
+    }
+}
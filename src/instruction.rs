@@ -2,6 +2,13 @@ use crate::bytecode::OpCode;
 
 pub type Instruction = u32;
 
+/// `ForPrep` and `ForLoop` run while the index is at the limit too, `<=` rather than `<`
+pub const FOR_INCLUSIVE: Instruction = 1;
+/// `ForPrep` and `ForLoop` count down, subtracting the step and testing `>` or `>=`
+pub const FOR_DESCENDING: Instruction = 2;
+/// the operand word of `ForPrep` and `ForLoop` is the limit itself rather than its local slot
+pub const FOR_LIMIT_IMMEDIATE: Instruction = 4;
+
 pub struct InstructionBuilder {
     instruction: Instruction,
 }
@@ -63,6 +70,14 @@ impl InstructionBuilder {
             .build()
     }
 
+    /// one of the math opcodes, `Sqrt`, `Abs`, `Floor` or `Ceil`, working on the register in place
+    pub fn new_math_instruction(opcode: OpCode, register: Instruction) -> Instruction {
+        InstructionBuilder::new()
+            .add_opcode(opcode)
+            .add_source_register_1(register)
+            .build()
+    }
+
     pub fn new_jump_instruction(offset: Instruction, forward: bool) -> Instruction {
         InstructionBuilder::new()
             .add_opcode(OpCode::Jump)
@@ -78,6 +93,13 @@ impl InstructionBuilder {
             .build()
     }
 
+    pub fn new_jump_none_instruction(source1: Instruction) -> Instruction {
+        InstructionBuilder::new()
+            .add_opcode(OpCode::JumpNone)
+            .add_source_register_1(source1)
+            .build()
+    }
+
     pub fn new_load_constant_instruction(
         destination: Instruction,
         constant_index: Instruction,
@@ -166,6 +188,31 @@ impl InstructionBuilder {
             .build()
     }
 
+    /// `Inc` or `Dec` of the local slot by an amount below 256
+    pub fn new_step_local(opcode: OpCode, slot: Instruction, amount: Instruction) -> Instruction {
+        InstructionBuilder::new()
+            .add_opcode(opcode)
+            .add_immediate_byte(amount)
+            .add_address_small(slot)
+            .build()
+    }
+
+    /// `ForPrep` or `ForLoop` of the index in the local slot, stepping by 1 to 15. the flags
+    /// are kept in the destination field and the limit goes in the word after it
+    pub fn new_for_instruction(
+        opcode: OpCode,
+        slot: Instruction,
+        step: Instruction,
+        flags: Instruction,
+    ) -> Instruction {
+        InstructionBuilder::new()
+            .add_opcode(opcode)
+            .add_destination_register(flags)
+            .add_source_register_1(step)
+            .add_address_small(slot)
+            .build()
+    }
+
     pub fn new_load_float32_instruction(destination: Instruction) -> Instruction {
         InstructionBuilder::new()
             .add_opcode(OpCode::LoadFloat32)
@@ -230,6 +277,13 @@ impl InstructionBuilder {
         self
     }
 
+    /// a byte stored where the destination and first source registers go
+    pub fn add_immediate_byte(mut self, byte: Instruction) -> Self {
+        let shifted = byte << 18;
+        self.instruction += shifted;
+        self
+    }
+
     pub fn add_address_small(mut self, address: Instruction) -> Self {
         self.instruction += address;
         self
@@ -288,6 +342,13 @@ pub mod instruction_decoder {
         instruction & 0xF
     }
 
+    #[inline(always)]
+    pub fn decode_immediate_byte(instruction: Instruction) -> Instruction {
+        let instruction = instruction >> 18;
+
+        instruction & 0xFF
+    }
+
     #[inline(always)]
     pub fn decode_immutable_address_small(instruction: Instruction) -> Instruction {
         // take only lower 16 bits
@@ -1,15 +1,56 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod api;
+pub mod assembler;
+#[cfg(feature = "std")]
+pub mod bench;
 pub mod bytecode;
+pub mod capabilities;
 pub mod compiler;
 pub mod debug;
+pub mod diagnostics;
+pub mod error;
+pub mod extensions;
+#[cfg(feature = "file")]
 pub mod file;
 pub mod frame;
+pub mod frontend;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod hooks;
+pub mod host;
+pub mod immutables;
 pub mod instruction;
+pub mod limits;
+pub mod lint;
 pub mod machine;
+#[cfg(feature = "matrix")]
+pub mod matrix;
 pub mod natives;
+pub mod number_format;
 pub mod object;
+pub mod prelude;
+pub mod profiler;
 pub mod program;
 pub mod register;
+#[cfg(all(feature = "nova_tw", feature = "natives"))]
+pub mod stdlib;
+pub mod tools;
+pub mod trace;
 pub mod version;
+#[cfg(feature = "std")]
+pub mod watch;
 
 mod cache;
+mod collections;
+#[cfg(not(feature = "std"))]
+mod float;
+#[cfg(all(
+    feature = "nova_tw",
+    feature = "natives",
+    feature = "file",
+    not(feature = "minimal")
+))]
 mod tests;
@@ -0,0 +1,65 @@
+use alloc::{format, string::String};
+
+use crate::object::NovaObject;
+
+/// caps on the size of the values a script builds, set with
+/// `VirtualMachine::set_resource_limits`. a script going over one stops with an error instead
+/// of exhausting memory. there are no limits by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResourceLimits {
+    /// the longest string in bytes
+    pub max_string_length: Option<usize>,
+    /// the most values a list, or entries a map, can hold
+    pub max_collection_length: Option<usize>,
+}
+
+impl ResourceLimits {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_string_length(mut self, length: usize) -> Self {
+        self.max_string_length = Some(length);
+        self
+    }
+
+    pub fn with_max_collection_length(mut self, length: usize) -> Self {
+        self.max_collection_length = Some(length);
+        self
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_string_length.is_none() && self.max_collection_length.is_none()
+    }
+
+    /// the error for a value over the limits. the values inside a list or map are not looked
+    /// at, they were checked when they were made
+    pub fn check(&self, value: &NovaObject) -> Result<(), String> {
+        match value {
+            NovaObject::String(string) => self.check_string_length(string.len()),
+            NovaObject::List(list) => self.check_collection_length("list", list.len()),
+            NovaObject::Map(map) => self.check_collection_length("map", map.len()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_string_length(&self, length: usize) -> Result<(), String> {
+        match self.max_string_length {
+            Some(max) if length > max => Err(format!(
+                "String of {} bytes is longer than the limit of {}",
+                length, max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_collection_length(&self, kind: &str, length: usize) -> Result<(), String> {
+        match self.max_collection_length {
+            Some(max) if length > max => Err(format!(
+                "A {} of {} values is longer than the limit of {}",
+                kind, length, max
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Display;
+
+use crate::{
+    bytecode::OpCode,
+    debug::instruction_width,
+    diagnostics::{Diagnostic, Palette, Style},
+    error::SourceLocation,
+    instruction::{instruction_decoder, Instruction, FOR_LIMIT_IMMEDIATE},
+    object::NovaObject,
+    program::{Program, VerifyError},
+};
+
+/// registers whose values are tracked by the lint, one bit each
+pub(crate) type RegisterSet = u16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    /// the program failed `Program::verify`, nothing else is checked
+    Invalid(VerifyError),
+    /// a jump lands on the operand words of a multi-word load
+    JumpIntoOperand { target: usize },
+    /// a jump lands outside the program
+    JumpOutOfProgram,
+    /// instructions no path from the start or a function entry reaches
+    Unreachable { count: usize },
+    /// a register read before any path wrote to it
+    UninitializedRead { register: Instruction },
+    /// a local variable slot that is stored but never loaded in the same function
+    UnusedLocal { slot: Instruction },
+}
+
+/// a problem found by `lint`, at the instruction `index`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub severity: Severity,
+    pub kind: LintKind,
+    pub index: usize,
+    /// the source line of the instruction, when the program has line information
+    pub location: Option<SourceLocation>,
+}
+
+impl Diagnostic for Lint {
+    fn render_with(&self, palette: Palette) -> String {
+        let severity = match self.severity {
+            Severity::Warning => palette.paint(Style::Warning, "warning"),
+            Severity::Error => palette.paint(Style::Error, "error"),
+        };
+
+        let message = match &self.kind {
+            LintKind::Invalid(error) => error.to_string(),
+            LintKind::JumpIntoOperand { target } => {
+                format!(
+                    "jump into the operands of the load before instruction {}",
+                    target
+                )
+            }
+            LintKind::JumpOutOfProgram => "jump out of the program".to_string(),
+            LintKind::Unreachable { count } => format!("{} unreachable instruction(s)", count),
+            LintKind::UninitializedRead { register } => {
+                format!("register {} is read before it is written", register)
+            }
+            LintKind::UnusedLocal { slot } => {
+                format!("local {} is stored but never loaded", slot)
+            }
+        };
+
+        let mut rendered = format!("{}: {} at instruction {}", severity, message, self.index);
+        if let Some(location) = &self.location {
+            rendered.push_str(&format!(" (line {} in '{}')", location.line, location.file));
+        }
+
+        rendered
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.render_with(Palette::plain()))
+    }
+}
+
+/// check a program for mistakes the virtual machine would only find at runtime, or not at all.
+/// the program is verified first, the other checks only run on verified programs
+pub fn lint(program: &Program) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    if let Err(error) = program.verify() {
+        let index = match error {
+            VerifyError::InvalidOpCode { index, .. }
+            | VerifyError::Truncated { index }
+            | VerifyError::ReservedOpCode { index, .. } => index,
+            // at the start of the function
+            VerifyError::TooManyDefaults { immutable }
+            | VerifyError::TooManyParameters { immutable } => {
+                match &program.immutables[immutable] {
+                    NovaObject::NovaFunction(function) => function.address as usize,
+                    _ => 0,
+                }
+            }
+        };
+        lints.push(lint_at(
+            program,
+            Severity::Error,
+            LintKind::Invalid(error),
+            index,
+        ));
+        return lints;
+    }
+
+    let flow = Flow::new(program);
+
+    for (index, target) in flow.jumps.iter().copied() {
+        let kind = match target {
+            Some(target) if target < flow.starts.len() => {
+                if flow.starts[target] {
+                    continue;
+                }
+                LintKind::JumpIntoOperand { target }
+            }
+            _ => LintKind::JumpOutOfProgram,
+        };
+        lints.push(lint_at(program, Severity::Error, kind, index));
+    }
+
+    let mut reachable = vec![false; program.instructions.len()];
+    for &(entry, _) in &flow.entries {
+        let region = flow.reachable_from(entry);
+        for &index in &region {
+            reachable[index] = true;
+        }
+
+        for (index, slot) in unused_locals(program, &region) {
+            lints.push(lint_at(
+                program,
+                Severity::Warning,
+                LintKind::UnusedLocal { slot },
+                index,
+            ));
+        }
+    }
+
+    let mut index = 0;
+    while index < program.instructions.len() {
+        if reachable[index] || is_padding(program.instructions[index]) {
+            index += instruction_width(program.instructions[index]);
+            continue;
+        }
+
+        let first = index;
+        let mut count = 0;
+        while index < program.instructions.len() && !reachable[index] {
+            count += 1;
+            index += instruction_width(program.instructions[index]);
+        }
+
+        // the return the generator adds to every function, after one written in the source
+        let implicit_return = opcode_of(program.instructions[first]) == Some(OpCode::ReturnNone);
+        if count > 1 || !implicit_return {
+            let kind = LintKind::Unreachable { count };
+            lints.push(lint_at(program, Severity::Warning, kind, first));
+        }
+    }
+
+    for (index, register) in flow.uninitialized_reads() {
+        let kind = LintKind::UninitializedRead { register };
+        lints.push(lint_at(program, Severity::Warning, kind, index));
+    }
+
+    lints.sort_by_key(|lint| lint.index);
+    lints
+}
+
+fn lint_at(program: &Program, severity: Severity, kind: LintKind, index: usize) -> Lint {
+    Lint {
+        severity,
+        kind,
+        index,
+        location: source_location(program, index),
+    }
+}
+
+/// line definitions end at the last instruction of a statement, so the first one ending at or
+/// after the instruction is the statement holding it
+fn source_location(program: &Program, index: usize) -> Option<SourceLocation> {
+    program
+        .line_definitions
+        .iter()
+        .filter(|line_definition| line_definition.last_instruction >= index)
+        .min_by_key(|line_definition| line_definition.last_instruction)
+        .map(|line_definition| SourceLocation {
+            file: line_definition.source_file.clone(),
+            line: line_definition.source_line,
+            snippet: None,
+            columns: line_definition.columns,
+        })
+}
+
+fn opcode_of(instruction: Instruction) -> Option<OpCode> {
+    OpCode::try_from(instruction_decoder::decode_opcode(instruction)).ok()
+}
+
+/// words left behind by removed instructions are not worth reporting
+fn is_padding(instruction: Instruction) -> bool {
+    opcode_of(instruction) == Some(OpCode::NoInstruction)
+}
+
+/// the control flow of a verified program
+struct Flow {
+    instructions: Vec<Instruction>,
+    /// words that begin an instruction, the rest are operands of the one before them
+    starts: Vec<bool>,
+    /// every jump with its target, None when it would be before the start of the program
+    jumps: Vec<(usize, Option<usize>)>,
+    /// the start of the program and the function addresses, with the registers holding
+    /// arguments when they are entered
+    entries: Vec<(usize, RegisterSet)>,
+}
+
+impl Flow {
+    fn new(program: &Program) -> Self {
+        let instructions = &program.instructions;
+        let mut starts = vec![false; instructions.len()];
+        let mut jumps = Vec::new();
+
+        let mut index = 0;
+        while index < instructions.len() {
+            starts[index] = true;
+            let instruction = instructions[index];
+
+            if opcode_of(instruction) == Some(OpCode::Jump) {
+                let offset = instruction_decoder::decode_immutable_address_small(instruction);
+                let forward = instruction_decoder::decode_destination_register(instruction) != 0;
+                let target = if forward {
+                    Some(index + offset as usize)
+                } else {
+                    index.checked_sub(offset as usize)
+                };
+                jumps.push((index, target));
+            }
+
+            index += instruction_width(instruction);
+        }
+
+        let mut entries = vec![(0, 0)];
+        for immutable in program.immutables.iter() {
+            if let NovaObject::NovaFunction(function) = immutable {
+                let parameters = function.arity + function.is_variadic as Instruction;
+                let arguments = (0..parameters.min(RegisterSet::BITS))
+                    .fold(0, |set: RegisterSet, register| set | 1 << register);
+                entries.push((function.address as usize, arguments));
+            }
+        }
+
+        Self {
+            instructions: instructions.clone(),
+            starts,
+            jumps,
+            entries,
+        }
+    }
+
+    /// the instructions that can run right after the one at `index`
+    fn successors(&self, index: usize) -> Vec<usize> {
+        let instruction = self.instructions[index];
+        let successors = match opcode_of(instruction) {
+            Some(OpCode::Halt | OpCode::ReturnNone | OpCode::ReturnVal) => vec![],
+            Some(OpCode::Jump) => self
+                .jumps
+                .iter()
+                .find(|(jump, _)| *jump == index)
+                .and_then(|(_, target)| *target)
+                .into_iter()
+                .collect(),
+            // a conditional jump runs the jump after it or skips it
+            Some(opcode) if opcode.takes_next_jump() => {
+                let jump = index + instruction_width(instruction);
+                vec![jump, jump + 1]
+            }
+            _ => vec![index + instruction_width(instruction)],
+        };
+
+        successors
+            .into_iter()
+            .filter(|&successor| self.starts.get(successor) == Some(&true))
+            .collect()
+    }
+
+    /// instructions reachable from the entry without following calls, in program order
+    fn reachable_from(&self, entry: usize) -> Vec<usize> {
+        let mut reached = vec![false; self.starts.len()];
+        let mut pending = vec![entry];
+
+        while let Some(index) = pending.pop() {
+            if self.starts.get(index) != Some(&true) || reached[index] {
+                continue;
+            }
+            reached[index] = true;
+            pending.extend(self.successors(index));
+        }
+
+        (0..reached.len()).filter(|&index| reached[index]).collect()
+    }
+
+    /// reads of registers that no path from an entry writes first. the registers that may
+    /// have been written are carried forward until nothing changes
+    fn uninitialized_reads(&self) -> Vec<(usize, Instruction)> {
+        let mut written: Vec<Option<RegisterSet>> = vec![None; self.instructions.len()];
+        let mut pending = Vec::new();
+        for &(entry, arguments) in &self.entries {
+            if entry < written.len() {
+                written[entry] = Some(written[entry].unwrap_or(0) | arguments);
+                pending.push(entry);
+            }
+        }
+
+        while let Some(index) = pending.pop() {
+            let Some(before) = written[index] else {
+                continue;
+            };
+            let (_, writes) = register_effects(self.instructions[index]);
+            let after = before | writes;
+
+            for successor in self.successors(index) {
+                let current = written[successor];
+                let merged = current.unwrap_or(0) | after;
+                if current != Some(merged) {
+                    written[successor] = Some(merged);
+                    pending.push(successor);
+                }
+            }
+        }
+
+        let mut reads = Vec::new();
+        for (index, written) in written.into_iter().enumerate() {
+            let Some(written) = written else {
+                continue;
+            };
+            let (read, _) = register_effects(self.instructions[index]);
+            for register in 0..RegisterSet::BITS {
+                if read & !written & (1 << register) != 0 {
+                    reads.push((index, register));
+                }
+            }
+        }
+
+        reads
+    }
+}
+
+/// the first store to every local slot of the region that no instruction in it loads
+fn unused_locals(program: &Program, region: &[usize]) -> Vec<(usize, Instruction)> {
+    let mut stored: Vec<(usize, Instruction)> = Vec::new();
+    let mut loaded: Vec<Instruction> = Vec::new();
+
+    for &index in region {
+        let instruction = program.instructions[index];
+        let slot = instruction_decoder::decode_immutable_address_small(instruction);
+
+        match opcode_of(instruction) {
+            Some(OpCode::StoreLocal) if !stored.iter().any(|(_, stored)| *stored == slot) => {
+                stored.push((index, slot))
+            }
+            Some(OpCode::LoadLocal | OpCode::Inc | OpCode::Dec) => loaded.push(slot),
+            Some(OpCode::ForPrep | OpCode::ForLoop) => {
+                loaded.push(slot);
+                let flags = instruction_decoder::decode_destination_register(instruction);
+                if flags & FOR_LIMIT_IMMEDIATE == 0 {
+                    loaded.push(program.instructions[index + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    stored.retain(|(_, slot)| !loaded.contains(slot));
+    stored
+}
+
+/// the registers an instruction reads and writes. calls restore the registers of the caller,
+/// so only the arguments and the function being called count as reads
+pub(crate) fn register_effects(instruction: Instruction) -> (RegisterSet, RegisterSet) {
+    let destination: RegisterSet =
+        1 << instruction_decoder::decode_destination_register(instruction);
+    let source_1: RegisterSet = 1 << instruction_decoder::decode_source_register_1(instruction);
+    let source_2: RegisterSet = 1 << instruction_decoder::decode_source_register_2(instruction);
+
+    match opcode_of(instruction) {
+        Some(OpCode::Move) => (source_1, destination),
+        Some(
+            OpCode::LoadK
+            | OpCode::LoadNil
+            | OpCode::LoadBool
+            | OpCode::LoadInt32
+            | OpCode::LoadInt64
+            | OpCode::LoadFloat32
+            | OpCode::LoadFloat64
+            | OpCode::LoadReturn
+            | OpCode::LoadGlobalIndirect
+            | OpCode::LoadGlobal
+            | OpCode::LoadLocal,
+        ) => (0, destination),
+        Some(
+            OpCode::Add
+            | OpCode::Sub
+            | OpCode::Mul
+            | OpCode::Div
+            | OpCode::Mod
+            | OpCode::Pow
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Equal,
+        ) => (source_1 | source_2, destination),
+        // operate in place, storing a local clears the register
+        Some(
+            OpCode::Neg
+            | OpCode::Pos
+            | OpCode::Not
+            | OpCode::Sqrt
+            | OpCode::Abs
+            | OpCode::Floor
+            | OpCode::Ceil
+            | OpCode::StoreLocal,
+        ) => (source_1, source_1),
+        Some(
+            OpCode::JumpFalse
+            | OpCode::JumpNone
+            | OpCode::StoreGlobalIndirect
+            | OpCode::Print
+            | OpCode::ReturnVal,
+        ) => (source_1, 0),
+        Some(OpCode::Invoke) => {
+            let start = instruction_decoder::decode_destination_register(instruction);
+            let arguments = instruction_decoder::decode_source_register_1(instruction);
+            let arguments = (start..(start + arguments).min(RegisterSet::BITS))
+                .fold(0, |set: RegisterSet, register| set | 1 << register);
+            (arguments | source_2, 0)
+        }
+        _ => (0, 0),
+    }
+}
+
+#[cfg(test)]
+mod lint_tests {
+    use crate::{instruction::InstructionBuilder, program::Program};
+
+    use super::{lint, LintKind};
+
+    fn kinds(program: &Program) -> Vec<(usize, LintKind)> {
+        lint(program)
+            .into_iter()
+            .map(|lint| (lint.index, lint.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_dataflow_lints() {
+        let program = crate::nova_asm! {
+            allocate_local 2;
+            load_int 0, 1;
+            store_local 0, 0;
+            store_local 0, 1;
+            add 2, 1, 0;
+            load_local 3, 1;
+            halt;
+            println 3;
+        };
+
+        assert_eq!(
+            kinds(&program),
+            vec![
+                (3, LintKind::UnusedLocal { slot: 0 }),
+                (5, LintKind::UninitializedRead { register: 1 }),
+                (8, LintKind::Unreachable { count: 1 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jump_lints() {
+        let mut program = crate::nova_asm! {
+            load_int 0, 7;
+            halt;
+        };
+        // lands on the number loaded by the first instruction
+        program
+            .instructions
+            .push(InstructionBuilder::new_jump_instruction(2, false));
+        program
+            .instructions
+            .push(InstructionBuilder::new_jump_instruction(9, true));
+
+        assert_eq!(
+            kinds(&program),
+            vec![
+                (3, LintKind::JumpIntoOperand { target: 1 }),
+                (3, LintKind::Unreachable { count: 2 }),
+                (4, LintKind::JumpOutOfProgram),
+            ]
+        );
+
+        program.instructions[0] = 0xfc00_0000;
+        assert!(matches!(
+            kinds(&program).as_slice(),
+            [(0, LintKind::Invalid(_))]
+        ));
+    }
+}
@@ -1,43 +1,135 @@
-pub mod bytecode_execution;
-pub mod memory_management;
-pub mod program_management;
-pub mod register_management;
-pub mod garbage_collection;
-
-use std::{ptr::copy_nonoverlapping, sync::{Arc, RwLock}, thread, time::Duration};
+// the execution internals index registers, locals and memory unchecked, they are only safe
+// with the state the virtual machine keeps and stay inside the crate
+pub(crate) mod bytecode_execution;
+pub(crate) mod code;
+pub(crate) mod memory_management;
+pub(crate) mod program_management;
+pub(crate) mod register_management;
+pub(crate) mod stack;
+pub(crate) mod garbage_collection;
+pub mod heap_inspection;
+mod unchecked;
+#[cfg(feature = "checked")]
+mod bounds_check;
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+#[cfg(all(feature = "std", not(feature = "gc_always")))]
+use std::{sync::RwLock, thread, time::Duration};
 
-use memory_management::{allocate_global, create_global, set_global_value, store_object_in_memory};
+use memory_management::{
+    define_global, freeze_global, load_object_from_memory, set_global_value,
+    store_object_in_memory, undefine_global,
+};
 use program_management::{check_error, emit_error_with_message, get_next_instruction};
-use register_management::get_register;
+use register_management::{get_register, nova_object_into_register, package_register_into_nova_object};
+use heap_inspection::HeapStats;
+#[cfg(feature = "heap_sites")]
+use heap_inspection::{AllocationSites, HeapReport};
+use stack::{Frames, Locals};
+use code::Code;
+use bytecode_execution::arithmetic_operations::MathOp;
 
 use crate::{
     bytecode::{OpCode, BYTECODE_LOOKUP_TABLE},
     cache::MemoryCache,
-    frame::Frame,
+    capabilities::Capabilities,
+    limits::ResourceLimits,
+    debug::{debug_instruction, instruction_width},
+    diagnostics::Palette,
+    error::{ReplaceFunctionError, SourceExcerpts, SourceLocation, VmError, VmErrorKind},
+    extensions::Extensions,
+    frame::{Frame, SAVED_REGISTERS},
+    hooks::VmHooks,
+    host::{Discard, HostOutput},
+    immutables::Immutables,
     instruction::{instruction_decoder, Instruction, InstructionBuilder},
+    number_format::FloatFormat,
+    prelude::Prelude,
     object::{
-        MappedMemory, NativeFunction, NovaCallable, NovaFunctionID, NovaObject, RegisterValueKind,
+        FrozenGlobals, MappedMemory, NativeFunction, NativeResult, NovaCallable, NovaFunctionID, NovaObject,
+        RegisterValueKind,
     },
-    program::{LineDefinition, Program},
+    program::{ImmutablePool, LineDefinition, LocalSymbol, Program, VerifyError},
     register::{Register, RegisterID},
+    profiler::{NoProfiler, Profiler},
+    trace::TraceSink,
 };
 
 #[cfg(feature = "debug")]
-use crate::debug::debug_instruction;
+use crate::trace::TraceEvent;
+#[cfg(feature = "file")]
+use crate::file;
+#[cfg(feature = "mmap")]
+use crate::{file::MappedProgram, program};
+#[cfg(feature = "file")]
+use std::{error::Error, io::{Read, Write}};
+#[cfg(feature = "std")]
+use crate::{diagnostics::Diagnostic, trace::ConsoleTrace};
+#[cfg(all(feature = "nova_tw", feature = "natives"))]
+use crate::stdlib::StdlibError;
 
 const PC_START: Instruction = 0x0;
 
-pub struct VirtualMachineData<'a> {
-    pub instructions: &'a mut Vec<Instruction>,
-    pub immutables: &'a mut Vec<NovaObject>,
+/// how deep native functions can call back into nova code that calls native functions again,
+/// every level takes rust stack
+pub const MAX_NATIVE_CALL_DEPTH: u32 = 128;
+
+/// instructions between garbage collections without the `std` feature, which has no timer
+#[cfg(all(not(feature = "std"), not(feature = "gc_always")))]
+const GC_INSTRUCTION_INTERVAL: u32 = 1 << 16;
+
+#[cfg(feature = "std")]
+fn default_output() -> Box<dyn HostOutput> {
+    Box::new(std::io::stdout())
+}
+
+#[cfg(not(feature = "std"))]
+fn default_output() -> Box<dyn HostOutput> {
+    Box::new(Discard)
+}
+
+#[cfg(feature = "std")]
+fn default_trace() -> Box<dyn TraceSink> {
+    Box::new(ConsoleTrace)
+}
+
+#[cfg(not(feature = "std"))]
+fn default_trace() -> Box<dyn TraceSink> {
+    Box::new(Discard)
+}
+
+pub(crate) struct VirtualMachineData<'a> {
+    pub instructions: &'a [Instruction],
+    pub immutables: &'a Immutables,
     pub registers: &'a mut [Register; RegisterID::RMax as usize + 1],
     pub running: &'a mut bool,
     pub memory: &'a mut Vec<NovaObject>,
-    pub frames: &'a mut Vec<Frame>,
-    pub locals: &'a mut Vec<Register>,
+    pub frames: &'a mut Frames,
+    pub locals: &'a mut Locals,
     pub globals: &'a mut Vec<Register>,
     pub identifiers: &'a mut MappedMemory,
+    pub frozen_globals: &'a mut FrozenGlobals,
     pub mem_cache: &'a mut MemoryCache,
+    pub natives: &'a [NativeFunction],
+    pub extensions: &'a Extensions,
+    pub float_format: FloatFormat,
+    pub capabilities: Capabilities,
+    pub limits: ResourceLimits,
+    pub coercion: Coercion,
+    /// number of native functions calling back into nova code around this run
+    pub native_depth: u32,
+    pub output: &'a mut dyn HostOutput,
+    pub trace: &'a mut dyn TraceSink,
+    pub hooks: &'a mut VmHooks,
+    #[cfg(feature = "heap_sites")]
+    pub heap_sites: &'a mut AllocationSites,
 }
 
 #[inline(always)]
@@ -60,18 +152,65 @@ fn offset_immutable_address(instruction: Instruction, offset: Instruction) -> In
     instruction
 }
 
+/// what `add` does with a string and a number. the other arithmetic instructions have no
+/// meaning for strings and raise an error in both modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Coercion {
+    /// the number is appended to the string, or the string to the number, written the way
+    /// `+` always wrote it
+    #[default]
+    Coercive,
+    /// adding a string and a number is an error, `format()` converts the number explicitly
+    Strict,
+}
+
+/// a global namespace of the virtual machine, see `VirtualMachine::create_context`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContextId(usize);
+
+impl ContextId {
+    /// the namespace a virtual machine starts in
+    pub const MAIN: ContextId = ContextId(0);
+}
+
 pub struct VirtualMachine {
-    instructions: Vec<Instruction>,
-    immutables: Vec<NovaObject>,
+    instructions: Code,
+    /// shared with the loaded program until another one is appended
+    immutables: ImmutablePool,
     registers: [Register; RegisterID::RMax as usize + 1],
     running: bool,
+    /// set while `start_vm` runs, still set when a run unwound with a panic
+    entered: bool,
     memory: Vec<NovaObject>,
-    frames: Vec<Frame>,
-    locals: Vec<Register>,
+    frames: Frames,
+    locals: Locals,
     globals: Vec<Register>,
     identifiers: MappedMemory,
+    /// the names of every context by id, the current one is in `identifiers` and empty here
+    contexts: Vec<MappedMemory>,
+    context: ContextId,
+    frozen_globals: FrozenGlobals,
     mem_cache: MemoryCache,
     line_definitions: Vec<LineDefinition>,
+    /// names of the local slots of the loaded programs, moved to where they were loaded
+    local_symbols: Vec<LocalSymbol>,
+    output: Box<dyn HostOutput>,
+    last_error: Option<VmError>,
+    /// native functions loaded so far, loaded again after a reset
+    natives: Vec<NativeFunction>,
+    /// standard library modules loaded so far, loaded again after a reset
+    #[cfg(all(feature = "nova_tw", feature = "natives"))]
+    stdlib: Vec<&'static Program>,
+    extensions: Extensions,
+    trace: Box<dyn TraceSink>,
+    float_format: FloatFormat,
+    capabilities: Capabilities,
+    limits: ResourceLimits,
+    coercion: Coercion,
+    hooks: VmHooks,
+    palette: Palette,
+    #[cfg(feature = "heap_sites")]
+    heap_sites: AllocationSites,
 }
 
 impl Default for VirtualMachine {
@@ -82,46 +221,236 @@ impl Default for VirtualMachine {
 
 impl VirtualMachine {
     pub fn new() -> Self {
-        let mut frames = Vec::with_capacity(32);
+        let mut frames = Frames::with_capacity(32);
         frames.push(Frame::main());
 
         Self {
-            instructions: Vec::new(),
-            immutables: Vec::new(),
+            instructions: Code::default(),
+            immutables: ImmutablePool::default(),
             registers: [Register::default(); RegisterID::RMax as usize + 1],
             running: false,
+            entered: false,
             memory: Vec::with_capacity(32),
             frames,
-            locals: Vec::with_capacity(32),
+            locals: Locals::with_capacity(32),
             globals: Vec::with_capacity(32),
             identifiers: MappedMemory::default(),
+            contexts: vec![MappedMemory::default()],
+            context: ContextId::MAIN,
+            frozen_globals: FrozenGlobals::default(),
             mem_cache: MemoryCache::default(),
             line_definitions: Vec::new(),
+            local_symbols: Vec::new(),
+            output: default_output(),
+            last_error: None,
+            natives: Vec::new(),
+            #[cfg(all(feature = "nova_tw", feature = "natives"))]
+            stdlib: Vec::new(),
+            extensions: Extensions::new(),
+            trace: default_trace(),
+            float_format: FloatFormat::default(),
+            capabilities: Capabilities::none(),
+            limits: ResourceLimits::unlimited(),
+            coercion: Coercion::default(),
+            hooks: VmHooks::new(),
+            palette: Palette::plain(),
+            #[cfg(feature = "heap_sites")]
+            heap_sites: AllocationSites::default(),
+        }
+    }
+
+    /// a virtual machine running the host opcodes as well, programs using one that is not
+    /// registered do not load
+    pub fn with_extensions(extensions: Extensions) -> Self {
+        let mut vm = Self::new();
+        vm.extensions = extensions;
+        vm
+    }
+
+    /// a virtual machine with the natives and standard library modules of the prelude loaded
+    pub fn with_prelude(prelude: Prelude) -> Self {
+        let mut vm = Self::new();
+        vm.load_prelude(prelude);
+        vm
+    }
+
+    /// a virtual machine with every native and the whole standard library loaded, see `stdlib`
+    #[cfg(all(feature = "nova_tw", feature = "natives"))]
+    pub fn with_stdlib() -> Self {
+        Self::with_prelude(Prelude::all())
+    }
+
+    /// load the natives and then the standard library modules of the prelude. the library only
+    /// defines functions so none of it has to run, a program loaded after it starts at the
+    /// instruction count from before it was loaded. panics if a module of the library does not
+    /// compile, see `try_load_prelude`
+    pub fn load_prelude(&mut self, prelude: Prelude) {
+        #[cfg(all(feature = "nova_tw", feature = "natives"))]
+        if let Err(error) = self.try_load_prelude(prelude) {
+            panic!("cannot load the prelude: {}", error);
         }
+
+        #[cfg(not(all(feature = "nova_tw", feature = "natives")))]
+        self.load_natives(prelude.native_functions());
+    }
+
+    /// load the prelude like `load_prelude`, nothing is loaded if a standard library module
+    /// does not compile
+    #[cfg(all(feature = "nova_tw", feature = "natives"))]
+    pub fn try_load_prelude(&mut self, prelude: Prelude) -> Result<(), StdlibError> {
+        let programs = prelude.stdlib_programs()?;
+        self.load_natives(prelude.native_functions());
+        self.load_stdlib(programs);
+
+        Ok(())
+    }
+
+    #[cfg(all(feature = "nova_tw", feature = "natives"))]
+    fn load_stdlib(&mut self, programs: Vec<&'static Program>) {
+        for program in programs {
+            self.load_program(program.clone());
+            self.stdlib.push(program);
+        }
+    }
+
+    /// redirect everything the running program prints (print instructions and native functions)
+    /// into a different sink, stdout is used by default. without the `std` feature nothing is
+    /// printed until the host sets an output
+    pub fn set_output(&mut self, output: Box<dyn HostOutput>) {
+        self.output = output;
+    }
+
+    /// choose how printing writes floats, `FloatFormat::Rounded` by default
+    pub fn set_float_format(&mut self, float_format: FloatFormat) {
+        self.float_format = float_format;
+    }
+
+    /// get called back on errors, prints and nova function calls, replacing the hooks set before
+    pub fn set_hooks(&mut self, hooks: VmHooks) {
+        self.hooks = hooks;
+    }
+
+    /// choose what scripts may do outside the virtual machine, nothing by default
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    /// cap the length of the strings, lists and maps scripts build, no limits by default
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+
+    /// choose whether adding a string and a number converts the number, `Coercion::Coercive`
+    /// by default
+    pub fn set_coercion(&mut self, coercion: Coercion) {
+        self.coercion = coercion;
+    }
+
+    /// color the runtime errors printed to stderr, they are plain by default
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// receive the execution trace in a different sink, it is only emitted when the `debug`
+    /// feature is enabled. `ConsoleTrace` is used by default
+    pub fn set_trace_sink(&mut self, trace: Box<dyn TraceSink>) {
+        self.trace = trace;
     }
 
     pub fn load_natives(&mut self, native_functions: Vec<NativeFunction>) {
         for native_function in native_functions {
+            self.natives.push(native_function.clone());
+            self.load_callable(NovaCallable::NativeFunction(&native_function));
+        }
+    }
+
+    /// add a global namespace with the natives loaded so far bound in it. programs loaded while
+    /// it is the current context define their globals and functions there, so scripts in
+    /// different contexts cannot see or overwrite each other's globals. the natives, immutables
+    /// and heap are shared. natives loaded later are only bound in the current context
+    pub fn create_context(&mut self) -> ContextId {
+        let context = ContextId(self.contexts.len());
+        self.contexts.push(MappedMemory::default());
+
+        let current = self.context;
+        self.switch_context(context);
+        for native_function in self.natives.clone() {
             self.load_callable(NovaCallable::NativeFunction(&native_function));
         }
+        self.switch_context(current);
+
+        context
+    }
+
+    /// make the context the one programs are loaded and run in, false if there is no such context
+    pub fn switch_context(&mut self, context: ContextId) -> bool {
+        if context.0 >= self.contexts.len() {
+            return false;
+        }
+
+        core::mem::swap(&mut self.identifiers, &mut self.contexts[self.context.0]);
+        core::mem::swap(&mut self.identifiers, &mut self.contexts[context.0]);
+        self.context = context;
+
+        // the same name is at another location in the new context
+        self.mem_cache.invalidate_all();
+        true
+    }
+
+    pub fn current_context(&self) -> ContextId {
+        self.context
+    }
+
+    /// forget every loaded program along with the globals, memory and frames it left behind.
+    /// the native functions, the standard library, the host opcodes, the output, the trace sink, the hooks, the float format, the capabilities, the resource limits, the coercion and the palette are kept, so a fresh program can be loaded and run
+    pub fn reset(&mut self) {
+        let output = core::mem::replace(&mut self.output, Box::new(Discard));
+        let trace = core::mem::replace(&mut self.trace, Box::new(Discard));
+        let natives = core::mem::take(&mut self.natives);
+        let extensions = core::mem::take(&mut self.extensions);
+        let hooks = core::mem::take(&mut self.hooks);
+        #[cfg(all(feature = "nova_tw", feature = "natives"))]
+        let stdlib = core::mem::take(&mut self.stdlib);
+
+        let float_format = self.float_format;
+        let capabilities = self.capabilities;
+        let limits = self.limits;
+        let coercion = self.coercion;
+        let palette = self.palette;
+
+        *self = Self::new();
+        self.output = output;
+        self.trace = trace;
+        self.float_format = float_format;
+        self.capabilities = capabilities;
+        self.limits = limits;
+        self.coercion = coercion;
+        self.hooks = hooks;
+        self.palette = palette;
+        self.extensions = extensions;
+        self.load_natives(natives);
+        #[cfg(all(feature = "nova_tw", feature = "natives"))]
+        self.load_stdlib(stdlib);
     }
 
     /// load a native function into virtual machine
     /// the function name is stored in the identifiers map together with an address to a global location.
-    /// the global location points to a memory address containing a NovaObject wrapping the NativeFunction
+    /// the global location points to a memory address containing a NovaObject wrapping the NativeFunction,
+    /// tagged with the index of the native in the natives table so invoking it skips the memory
     #[inline(always)]
     fn load_callable(&mut self, callable: NovaCallable) {
-        let global_location = allocate_global(&mut self.globals);
-        let name = callable.get_name().to_string();
-        create_global(&mut self.identifiers, name, global_location);
+        let global_location =
+            define_global(&mut self.identifiers, &mut self.globals, callable.get_name());
         let nova_object = callable.as_object();
         let memory_location = store_object_in_memory(&mut self.memory, nova_object);
 
         let global_value = match callable {
             NovaCallable::NovaFunction(nova_function) => {
-                let name_address = self.immutables.len() as u32 - 1;
-                let nova_function_id =
-                    NovaFunctionID::from_nova_function(nova_function, name_address);
+                // the name is interned before the function, but not always right before it
+                let name_address = self.immutables.rposition_string(&nova_function.name);
+                let nova_function_id = name_address.and_then(|name_address| {
+                    NovaFunctionID::from_nova_function(nova_function, name_address as u32)
+                });
 
                 if let Some(nova_function_id) = nova_function_id {
                     let function_address = nova_function.address as u64;
@@ -134,6 +463,22 @@ impl VirtualMachine {
                 }
             }
 
+            NovaCallable::NativeFunction(native_function) => {
+                // natives in the table are invoked without loading their object
+                let index = self
+                    .natives
+                    .iter()
+                    .rposition(|native| native.name == native_function.name);
+
+                match index {
+                    Some(index) => Register::new(
+                        RegisterValueKind::NativeFunctionID(index as u32),
+                        memory_location as u64,
+                    ),
+                    None => Register::new(RegisterValueKind::MemAddress, memory_location as u64),
+                }
+            }
+
             _ => Register::new(RegisterValueKind::MemAddress, memory_location as u64),
         };
 
@@ -144,31 +489,237 @@ impl VirtualMachine {
         self.instructions.len() as u32
     }
 
+    /// panics if the program does not pass `Program::verify`, use `try_load_program`
+    /// for programs that were not produced by the compiler
     pub fn load_program(&mut self, program: Program) {
+        if let Err(error) = self.try_load_program(program) {
+            panic!("cannot load program: {}", error);
+        }
+    }
+
+    /// load the program after checking its instructions, nothing is loaded if verification fails
+    pub fn try_load_program(&mut self, program: Program) -> Result<(), VerifyError> {
+        program.verify_with_host_opcodes(|number| self.extensions.is_registered(number))?;
+        self.append_program(&program, |_| true);
+
+        Ok(())
+    }
+
+    /// load a program mapped with `file::map_program_file` after checking its instructions. a
+    /// machine without code runs them from the mapping, otherwise they are copied after the
+    /// loaded code like `try_load_program` does
+    #[cfg(feature = "mmap")]
+    pub fn try_load_mapped_program(&mut self, program: MappedProgram) -> Result<(), VerifyError> {
+        program::verify_instructions(program.instructions(), |number| {
+            self.extensions.is_registered(number)
+        })?;
+
+        if !self.instructions.is_empty() || !self.immutables.is_empty() {
+            self.append_program(&program.to_program(), |_| true);
+            return Ok(());
+        }
+
+        self.instructions = Code::Mapped(program.code);
+        self.immutables = program.immutables;
+        self.line_definitions.extend(program.line_definitions);
+        self.local_symbols.extend(program.local_symbols);
+        self.bind_callables(0, |_| true);
+
+        Ok(())
+    }
+
+    /// swap the body of the global nova function for the one the fragment defines, leaving every
+    /// other global as it is. the new body is added after the loaded code and the global bound to
+    /// it, so calls still running finish in the old body. none of the fragment's top level code
+    /// runs and its other functions are not bound
+    pub fn replace_function(&mut self, name: &str, fragment: Program) -> Result<(), ReplaceFunctionError> {
+        let current = self
+            .identifiers
+            .get(name)
+            .and_then(|&location| self.globals.get(location as usize))
+            .map(|&global| register_management::register_as_nova_object(global, &self.memory, &self.immutables));
+        if !matches!(current.as_deref(), Some(NovaObject::NovaFunction(_))) {
+            return Err(ReplaceFunctionError::UnknownFunction(name.to_string()));
+        }
+
+        let defines_function = fragment.immutables.iter().any(|immutable| {
+            matches!(immutable, NovaObject::NovaFunction(function) if *function.name == name && !function.is_lambda())
+        });
+        if !defines_function {
+            return Err(ReplaceFunctionError::MissingFromFragment(name.to_string()));
+        }
+
+        fragment.verify_with_host_opcodes(|number| self.extensions.is_registered(number))?;
+        self.append_program(&fragment, |callable| callable == name);
+
+        Ok(())
+    }
+
+    /// add the verified program after the loaded code, binding the callables `bind` accepts to
+    /// globals. immutable and function addresses are moved to where the program is placed
+    fn append_program(&mut self, program: &Program, bind: impl Fn(&str) -> bool) {
         let immutable_offset = self.immutables.len() as Instruction;
         let instruction_offset = self.instructions.len();
 
-        for &instruction in &program.instructions {
-            //self.instruction_count += 1;
-            // TODO: check validity of opcodes;
-            let instruction = offset_immutable_address(instruction, immutable_offset);
-            self.instructions.push(instruction);
+        // only instruction words are moved, the operand words after them are data
+        let instructions = self.instructions.to_mut();
+        for decoded in program.iter_instructions() {
+            let instruction = offset_immutable_address(decoded.instruction, immutable_offset);
+            instructions.push(instruction);
+            instructions.extend_from_slice(decoded.operands);
         }
 
-        for immutable in &program.immutables {
-            if immutable.is_callable() {
-                let callable = immutable.as_callable();
-                self.load_callable(callable);
-            }
+        // a machine without code shares the program's pool, its functions are already in place.
+        // otherwise the pool is copied the first time another program is appended to it
+        if self.immutables.is_empty() && instruction_offset == 0 {
+            self.immutables = program.immutables.clone();
+        } else {
+            let immutables = Arc::make_mut(&mut self.immutables);
+            for immutable in program.immutables.iter() {
+                let mut immutable = immutable.clone();
+                if let NovaObject::NovaFunction(function) = &mut immutable {
+                    function.address += instruction_offset as Instruction;
+                }
 
-            self.immutables.push(immutable.clone());
+                immutables.push(immutable);
+            }
         }
 
+        self.bind_callables(immutable_offset as usize, bind);
+
         for line_definition in &program.line_definitions {
             let mut line_definition = line_definition.clone();
             line_definition.last_instruction += instruction_offset;
             self.line_definitions.push(line_definition)
         }
+
+        for local_symbol in &program.local_symbols {
+            let mut local_symbol = local_symbol.clone();
+            local_symbol.function = local_symbol.function.map(|function| function + immutable_offset as usize);
+            local_symbol.start += instruction_offset;
+            local_symbol.end += instruction_offset;
+            self.local_symbols.push(local_symbol);
+        }
+    }
+
+    /// bind the callables the immutables hold from `first` on that `bind` accepts to globals
+    fn bind_callables(&mut self, first: usize, bind: impl Fn(&str) -> bool) {
+        // strings not decoded yet cannot be callables
+        let pool = self.immutables.clone();
+        for (_, immutable) in pool.iter_decoded().filter(|(index, _)| *index >= first) {
+            let is_lambda = matches!(immutable, NovaObject::NovaFunction(function) if function.is_lambda());
+            if immutable.is_callable() && !is_lambda {
+                let callable = immutable.as_callable();
+                if bind(callable.get_name()) {
+                    self.load_callable(callable);
+                }
+            }
+        }
+    }
+
+    /// every heap slot as `(address, object, live)`, live being whether a register, frame or
+    /// variable still refers to it so a garbage collection would keep it. freed slots hold None
+    pub fn heap_iter(&self) -> impl Iterator<Item = (usize, &NovaObject, bool)> {
+        let mut live = vec![false; self.memory.len()];
+        let live_objects = garbage_collection::live_objects(
+            &self.registers,
+            &self.frames,
+            &self.locals,
+            &self.globals,
+            self.memory.len(),
+        );
+        for address in live_objects {
+            if let Some(live) = live.get_mut(address) {
+                *live = true;
+            }
+        }
+
+        self.memory
+            .iter()
+            .enumerate()
+            .map(move |(address, object)| (address, object, live[address]))
+    }
+
+    /// remove the global variable so later programs can no longer refer to it,
+    /// false if there is no global with the name
+    pub fn undefine_global(&mut self, name: &str) -> bool {
+        self.unfreeze_global(name);
+        undefine_global(
+            &mut self.identifiers,
+            &mut self.globals,
+            &mut self.mem_cache,
+            name,
+        )
+    }
+
+    /// write the globals holding data to the output, for `load_globals` to restore in a later run.
+    /// functions are left out, they come back with the program defining them, and so are values
+    /// holding functions
+    #[cfg(feature = "file")]
+    pub fn save_globals(&self, output: &mut impl Write) -> Result<(), Box<dyn Error>> {
+        let mut globals: Vec<(String, NovaObject)> = self
+            .identifiers
+            .iter()
+            .filter_map(|(name, &location)| {
+                let global = *self.globals.get(location as usize)?;
+                let value = register_management::register_as_nova_object(global, &self.memory, &self.immutables);
+                file::is_saveable(&value).then(|| (name.clone(), value.into_owned()))
+            })
+            .collect();
+        globals.sort_by(|(name, _), (other, _)| name.cmp(other));
+
+        file::write_globals(&globals, output)
+    }
+
+    /// define the globals written by `save_globals`, replacing the values of globals that are
+    /// already defined. returns how many were loaded
+    #[cfg(feature = "file")]
+    pub fn load_globals(&mut self, input: &mut impl Read) -> Result<usize, Box<dyn Error>> {
+        let globals = file::read_globals(input)?;
+
+        for (name, value) in &globals {
+            let location = define_global(&mut self.identifiers, &mut self.globals, name);
+            let value = nova_object_into_register(&mut self.memory, value.clone());
+            set_global_value(&mut self.globals, location, value);
+        }
+
+        Ok(globals.len())
+    }
+
+    /// make the global read only, scripts assigning or redefining it stop with an error. for
+    /// configuration and native modules scripts must not overwrite. false if there is no
+    /// global with the name
+    pub fn freeze_global(&mut self, name: &str) -> bool {
+        freeze_global(&self.identifiers, &mut self.frozen_globals, name)
+    }
+
+    /// let scripts write to the global again, false if it was not frozen
+    pub fn unfreeze_global(&mut self, name: &str) -> bool {
+        match self.identifiers.get(name) {
+            Some(location) => self.frozen_globals.remove(location),
+            None => false,
+        }
+    }
+
+    /// forget where every global was found, for embedders that move globals around.
+    /// the next access looks each name up again
+    pub fn invalidate_global_cache(&mut self) {
+        self.mem_cache.invalidate_all();
+    }
+
+    /// number of heap objects per kind and how many of them are live
+    pub fn heap_stats(&self) -> HeapStats {
+        HeapStats::from_heap(self.heap_iter())
+    }
+
+    /// the live heap objects grouped by the instruction that allocated them, with the source
+    /// line of each site and the age of its oldest object
+    #[cfg(feature = "heap_sites")]
+    pub fn heap_report(&self) -> HeapReport {
+        HeapReport::from_heap(self.heap_iter(), &self.heap_sites, |site| {
+            self.get_source_line_definition(site)
+                .map(|line| format!("{}:{}", line.source_file, line.source_line))
+        })
     }
 
     #[inline(always)]
@@ -183,66 +734,169 @@ impl VirtualMachine {
         let _freed_memory = garbage_collection::clean_all_dead_objects(&mut vm_data.memory, &live_objects);
     }
 
-    #[inline(always)]
-    fn print_error(&self) {
+    /// describe the error in the error register, along with where it happened
+    fn build_error(&self) -> VmError {
         let register = get_register(&self.registers, RegisterID::RERR as Instruction);
 
+        let mut message = String::new();
         if let RegisterValueKind::MemAddress = register.kind {
             let address = register.value;
-            let object = &self.memory[address as usize];
-            eprint!("Error: ");
-
-            if let NovaObject::String(string) = object {
-                eprint!("'{}'", string)
+            if let NovaObject::String(string) = &self.memory[address as usize] {
+                message = string.to_string();
             }
-            eprintln!(" Most recent call first");
         }
 
+        // the program counter is already past the instruction that raised the error
         let program_counter = self.registers[RegisterID::RPC as usize].value as usize;
-
-        let line_definition = self.get_source_line_definition(program_counter);
-
-        if let Some(line_definition) = line_definition {
-            eprintln!(
-                "On line [{}] in file '{}'",
-                line_definition.source_line, line_definition.source_file
-            );
+        let mut excerpts = SourceExcerpts::default();
+        let location =
+            self.get_source_location(program_counter.saturating_sub(1), &mut excerpts);
+
+        let mut call_stack = Vec::new();
+        if self.frames.len() > 1 {
+            for frame in self.frames[1..].iter().rev() {
+                // the return address points to the instruction after the invoke
+                let program_counter = (frame.return_address as usize).saturating_sub(1);
+
+                if let Some(location) = self.get_source_location(program_counter, &mut excerpts) {
+                    call_stack.push(location);
+                }
+            }
         }
 
-        if self.frames.len() <= 1 {
-            return;
+        VmError {
+            kind: error_kind(&self.instructions, &self.extensions, program_counter.saturating_sub(1)),
+            message,
+            location,
+            call_stack,
         }
+    }
 
-        let frames = self.frames.iter().as_slice()[1..].iter().rev();
-
-        for frame in frames {
-            let program_counter = frame.registers[RegisterID::RRTN as usize].value as usize - 2; // I don't understand why 2, but it works
+    /// the error raised by the last run of the virtual machine, if any
+    pub fn take_error(&mut self) -> Option<VmError> {
+        self.last_error.take()
+    }
 
-            let line_definition = self.get_source_line_definition(program_counter);
+    /// the named local variables of the innermost frame at the instruction the machine stopped on,
+    /// to inspect a run that raised an error. only programs compiled with local symbols have names
+    pub fn local_variables(&self) -> Vec<(String, NovaObject)> {
+        let Some(frame) = self.frames.last() else {
+            return Vec::new();
+        };
 
-            if let Some(line_definition) = line_definition {
-                eprintln!(
-                    "Called from line [{}] in file '{}'",
-                    line_definition.source_line, line_definition.source_file
-                );
+        let function = if frame.is_main {
+            None
+        } else {
+            let function = self.immutables.iter().position(|immutable| {
+                matches!(immutable, NovaObject::NovaFunction(function) if function.address as u64 == frame.function)
+            });
+            if function.is_none() {
+                return Vec::new();
             }
-        }
-    }
+            function
+        };
 
-    fn get_source_line_definition(&self, program_counter: usize) -> Option<&LineDefinition> {
-        let mut maximum_line_definition = self.line_definitions.get(0);
+        // the program counter is already past the instruction that stopped the machine
+        let instruction = (self.registers[RegisterID::RPC as usize].value as usize).saturating_sub(1);
+        let local_offset = self.registers[RegisterID::RLO as usize].value as usize;
+
+        let mut symbols: Vec<&LocalSymbol> = self
+            .local_symbols
+            .iter()
+            .filter(|local| local.function == function && local.is_in_scope(instruction))
+            .collect();
+        symbols.sort_by_key(|local| local.slot);
+
+        symbols
+            .into_iter()
+            .filter_map(|local| {
+                let register = *self.locals.get(local_offset + local.slot as usize)?;
+                let value = register_management::register_as_nova_object(register, &self.memory, &self.immutables);
+                Some((local.name.clone(), value.into_owned()))
+            })
+            .collect()
+    }
 
-        for line_definition in self.line_definitions.iter() {
-            if line_definition.last_instruction <= program_counter {
-                maximum_line_definition = Some(line_definition);
+    /// name and source location of the nova function starting at the address, for reports
+    pub fn describe_function(&self, address: usize) -> String {
+        let function = self.immutables.iter().find_map(|immutable| match immutable {
+            NovaObject::NovaFunction(function) if function.address as usize == address => {
+                Some(function)
             }
+            _ => None,
+        });
+
+        let name = match function {
+            Some(function) => function.name.to_string(),
+            None => format!("<function at {}>", address),
+        };
+
+        // line definitions end at the last instruction of a statement, so the first one
+        // ending inside the function is its first statement
+        let first_line = self
+            .line_definitions
+            .iter()
+            .filter(|line_definition| line_definition.last_instruction >= address)
+            .min_by_key(|line_definition| (line_definition.last_instruction, line_definition.source_line));
+
+        match first_line {
+            Some(line) => format!("{} ({}:{})", name, line.source_file, line.source_line),
+            None => name,
         }
+    }
+
+    fn get_source_location(
+        &self,
+        instruction: usize,
+        excerpts: &mut SourceExcerpts,
+    ) -> Option<SourceLocation> {
+        self.get_source_line_definition(instruction)
+            .map(|line_definition| SourceLocation {
+                file: line_definition.source_file.clone(),
+                line: line_definition.source_line,
+                snippet: excerpts.line(&line_definition.source_file, line_definition.source_line),
+                columns: line_definition.columns,
+            })
+    }
 
-        return maximum_line_definition;
+    /// line definitions end at the last instruction of a statement, so the first one ending at or
+    /// after the instruction is the statement holding it
+    fn get_source_line_definition(&self, instruction: usize) -> Option<&LineDefinition> {
+        self.line_definitions
+            .iter()
+            .filter(|line_definition| line_definition.last_instruction >= instruction)
+            .min_by_key(|line_definition| line_definition.last_instruction)
+            .or_else(|| self.line_definitions.last())
     }
 
+    /// run the loaded code from the offset, 0 when it finished and 1 when it raised an error,
+    /// which `take_error` returns. the machine cannot be started again while it is running:
+    /// native functions call back into it with `NativeContext::call` instead. a run that
+    /// unwound with a panic leaves the machine half way through, starting it fails with a
+    /// `VmErrorKind::Reentered` error until it is `reset`
     pub fn start_vm(&mut self, offset: Instruction) -> u32 {
+        self.start_vm_with_profiler(offset, &mut NoProfiler)
+    }
+
+    /// run like `start_vm`, telling the profiler about every executed instruction
+    pub fn start_vm_with_profiler<P: Profiler>(&mut self, offset: Instruction, profiler: &mut P) -> u32 {
+        if self.entered {
+            let error = VmError {
+                kind: VmErrorKind::Reentered,
+                message: "Cannot start the virtual machine while it is already running".to_string(),
+                location: None,
+                call_stack: Vec::new(),
+            };
+            self.hooks.error(&error);
+            #[cfg(feature = "std")]
+            eprintln!("{}", error.render_with(self.palette));
+            self.last_error = Some(error);
+            return 1;
+        }
+
+        self.entered = true;
         self.running = true;
+        self.last_error = None;
         let program_counter = Register {
             kind: RegisterValueKind::MemAddress,
             value: (offset + PC_START) as u64,
@@ -252,40 +906,77 @@ impl VirtualMachine {
 
         let mut virtual_machine_data = VirtualMachineData {
             registers: &mut self.registers,
-            instructions: &mut self.instructions,
-            immutables: &mut self.immutables,
+            instructions: &self.instructions,
+            immutables: &self.immutables,
             running: &mut self.running,
             memory: &mut self.memory,
             frames: &mut self.frames,
             locals: &mut self.locals,
             globals: &mut self.globals,
             identifiers: &mut self.identifiers,
+            frozen_globals: &mut self.frozen_globals,
             mem_cache: &mut self.mem_cache,
+            natives: &self.natives,
+            extensions: &self.extensions,
+            float_format: self.float_format,
+            capabilities: self.capabilities,
+            limits: self.limits,
+            coercion: self.coercion,
+            native_depth: 0,
+            output: self.output.as_mut(),
+            trace: self.trace.as_mut(),
+            hooks: &mut self.hooks,
+            #[cfg(feature = "heap_sites")]
+            heap_sites: &mut self.heap_sites,
         };
 
-        
-        #[cfg(not(feature = "gc_always"))]
+        // what the host stored since the last run was not allocated by an instruction
+        #[cfg(feature = "heap_sites")]
+        {
+            virtual_machine_data.heap_sites.enter(None);
+            virtual_machine_data.heap_sites.tag(virtual_machine_data.memory.len());
+        }
+
+        #[cfg(all(feature = "std", not(feature = "gc_always")))]
         let run_gc = Arc::new(RwLock::new(false));
-        #[cfg(not(feature = "gc_always"))]
+        #[cfg(all(feature = "std", not(feature = "gc_always")))]
         let run_gc0 = run_gc.clone();
         
-        #[cfg(not(feature = "gc_always"))]
+        #[cfg(all(feature = "std", not(feature = "gc_always")))]
         let gc_delta_time: u64 = 30; // millis
         
+        // without threads the collection runs after a number of instructions instead of a timer
+        #[cfg(all(not(feature = "std"), not(feature = "gc_always")))]
+        let mut instructions_since_gc: u32 = 0;
 
-        #[cfg(not(feature = "gc_always"))]
+        #[cfg(all(feature = "std", not(feature = "gc_always")))]
         thread::spawn(move || {
             
-            loop {
+            // the run holds the other reference, once it is over the timer stops
+            while Arc::strong_count(&run_gc0) > 1 {
                 thread::sleep(Duration::from_millis(gc_delta_time));
                 let mut value_lock = run_gc0.write().unwrap();
                 *value_lock = true;
             }
         });
 
+        let mut exit_code = 0;
+
         while *virtual_machine_data.running {
+            // the trace reads the instruction, so it only runs once the bounds are checked
+            #[cfg(feature = "checked")]
+            if !bounds_check::check_next_instruction(&mut virtual_machine_data) {
+                exit_code = 1;
+                break;
+            }
+
             #[cfg(feature = "debug")]
-            debug(&virtual_machine_data);
+            debug(&mut virtual_machine_data);
+
+            #[cfg(feature = "heap_sites")]
+            virtual_machine_data.heap_sites.enter(Some(
+                virtual_machine_data.registers[RegisterID::RPC as usize].value as usize,
+            ));
 
             let instruction = get_next_instruction(
                 virtual_machine_data.registers,
@@ -293,18 +984,39 @@ impl VirtualMachine {
             );
 
             Self::execute_instruction(instruction, &mut virtual_machine_data);
+            #[cfg(feature = "heap_sites")]
+            virtual_machine_data.heap_sites.tag(virtual_machine_data.memory.len());
+            profiler.executed(
+                instruction,
+                virtual_machine_data.registers[RegisterID::RPC as usize].value as usize,
+                virtual_machine_data.frames.len(),
+            );
 
             if check_error(virtual_machine_data.registers) {
-                self.print_error();
-                self.clear_error();
-                return 1;
+                exit_code = 1;
+                break;
+            }
+
+            if let Some(message) = profiler.interrupt() {
+                emit_error_with_message(virtual_machine_data.registers, virtual_machine_data.memory, &message);
+                exit_code = 1;
+                break;
             }
 
             #[cfg(feature = "gc_always")]
             Self::trigger_garbage_collection(&mut virtual_machine_data);
 
 
-            #[cfg(not(feature = "gc_always"))]
+            #[cfg(all(not(feature = "std"), not(feature = "gc_always")))]
+            {
+                instructions_since_gc += 1;
+                if instructions_since_gc == GC_INSTRUCTION_INTERVAL {
+                    Self::trigger_garbage_collection(&mut virtual_machine_data);
+                    instructions_since_gc = 0;
+                }
+            }
+
+            #[cfg(all(feature = "std", not(feature = "gc_always")))]
             {
                 let mut should_run_gc = run_gc.write().unwrap();
                 if *should_run_gc {
@@ -313,7 +1025,21 @@ impl VirtualMachine {
                 }
             }
         }
-        0
+
+        profiler.finish();
+        virtual_machine_data.output.flush_text();
+
+        if exit_code != 0 {
+            let error = self.build_error();
+            self.hooks.error(&error);
+            #[cfg(feature = "std")]
+            eprintln!("{}", error.render_with(self.palette));
+            self.last_error = Some(error);
+            self.clear_error();
+        }
+
+        self.entered = false;
+        exit_code
     }
 
     #[inline(always)]
@@ -323,7 +1049,18 @@ impl VirtualMachine {
     ) {
         let opcode = instruction_decoder::decode_opcode(instruction);
 
-        let opcode = unsafe { *BYTECODE_LOOKUP_TABLE.get_unchecked(opcode as usize) };
+        // verified programs only hold valid opcodes, but a jump can still land on the data word
+        // of a wide instruction
+        let Some(&opcode) = BYTECODE_LOOKUP_TABLE.get(opcode as usize) else {
+            match virtual_machine_data.extensions.get(opcode) {
+                Some(host) => {
+                    let handler = host.handler;
+                    bytecode_execution::host_opcode(instruction, virtual_machine_data, handler);
+                }
+                None => Self::raise_illegal_instruction(instruction, virtual_machine_data),
+            }
+            return;
+        };
 
         match opcode {
             OpCode::NoInstruction => {}
@@ -336,6 +1073,21 @@ impl VirtualMachine {
             OpCode::Neg => {
                 bytecode_execution::negate(instruction, virtual_machine_data);
             }
+            OpCode::Pos => {
+                bytecode_execution::positive(instruction, virtual_machine_data);
+            }
+            OpCode::Sqrt => {
+                bytecode_execution::math(MathOp::Sqrt, instruction, virtual_machine_data);
+            }
+            OpCode::Abs => {
+                bytecode_execution::math(MathOp::Abs, instruction, virtual_machine_data);
+            }
+            OpCode::Floor => {
+                bytecode_execution::math(MathOp::Floor, instruction, virtual_machine_data);
+            }
+            OpCode::Ceil => {
+                bytecode_execution::math(MathOp::Ceil, instruction, virtual_machine_data);
+            }
 
             // Binary Operations
             OpCode::Add => {
@@ -431,6 +1183,22 @@ impl VirtualMachine {
                 bytecode_execution::load_local(instruction, virtual_machine_data);
             }
 
+            OpCode::ForPrep => {
+                bytecode_execution::for_prep(instruction, virtual_machine_data);
+            }
+
+            OpCode::ForLoop => {
+                bytecode_execution::for_loop(instruction, virtual_machine_data);
+            }
+
+            OpCode::Inc => {
+                bytecode_execution::step_local(instruction, virtual_machine_data, false);
+            }
+
+            OpCode::Dec => {
+                bytecode_execution::step_local(instruction, virtual_machine_data, true);
+            }
+
             // Logical tests
             OpCode::Less => {
                 bytecode_execution::less(instruction, virtual_machine_data);
@@ -457,6 +1225,10 @@ impl VirtualMachine {
                 bytecode_execution::jump(instruction, virtual_machine_data);
             }
 
+            OpCode::JumpNone => {
+                bytecode_execution::jump_if_none(instruction, virtual_machine_data);
+            }
+
             OpCode::Invoke => {
                 bytecode_execution::invoke(instruction, virtual_machine_data);
             }
@@ -474,64 +1246,196 @@ impl VirtualMachine {
             // IO
             OpCode::Print => bytecode_execution::print(instruction, virtual_machine_data),
 
-            _ => emit_error_with_message(
-                virtual_machine_data.registers,
-                virtual_machine_data.memory,
-                &format!("Unsupported opcode instruction ({:?})", opcode),
-            ),
+            // reserved opcodes
+            _ => Self::raise_illegal_instruction(instruction, virtual_machine_data),
         }
     }
+
+    /// the error is described further by `build_error`, which finds the instruction at the
+    /// program counter
+    #[cold]
+    fn raise_illegal_instruction(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
+        let opcode = instruction_decoder::decode_opcode(instruction);
+        let message = match OpCode::try_from(opcode) {
+            Ok(opcode) => format!("Illegal instruction {:#010x}, {:?} is reserved", instruction, opcode),
+            Err(_) => format!("Illegal instruction {:#010x}, invalid opcode {:#x}", instruction, opcode),
+        };
+
+        emit_error_with_message(virtual_machine_data.registers, virtual_machine_data.memory, &message);
+    }
 }
 
-#[allow(dead_code)]
-fn print_vec_of_registers(registers: &[Register]) {
-    println!("[");
-    for (index, register) in registers.iter().enumerate() {
-        println!("\t[{}] {}, ", index, register)
+/// words disassembled on each side of an illegal instruction
+const ILLEGAL_INSTRUCTION_CONTEXT: usize = 2;
+
+/// the disassembly of the word, or nothing when its operands would run past the end. the
+/// listing can start in the middle of a wide instruction, on an operand that reads as one
+fn listing_of(instructions: &[Instruction], address: usize) -> String {
+    if address + instruction_width(instructions[address]) > instructions.len() {
+        return String::new();
+    }
+
+    debug_instruction(instructions, address as u64).trim_end().to_string()
+}
+
+/// the kind of error raised by the instruction at `pc`, an illegal instruction always raises one
+fn error_kind(instructions: &[Instruction], extensions: &Extensions, pc: usize) -> VmErrorKind {
+    let Some(&instruction) = instructions.get(pc) else {
+        return VmErrorKind::Runtime;
+    };
+
+    let opcode = instruction_decoder::decode_opcode(instruction);
+    let legal = OpCode::try_from(opcode).is_ok_and(|opcode| !opcode.is_reserved())
+        || extensions.is_registered(opcode);
+    if legal {
+        return VmErrorKind::Runtime;
+    }
+
+    let start = pc.saturating_sub(ILLEGAL_INSTRUCTION_CONTEXT);
+    let end = (pc + ILLEGAL_INSTRUCTION_CONTEXT + 1).min(instructions.len());
+    let listing = (start..end)
+        .map(|address| {
+            let marker = if address == pc { "->" } else { "  " };
+            format!(
+                "{} [{}]: {:#010x} {}",
+                marker,
+                address,
+                instructions[address],
+                listing_of(instructions, address)
+            )
+        })
+        .collect();
+
+    VmErrorKind::IllegalInstruction {
+        pc,
+        instruction,
+        opcode,
+        listing,
     }
-    println!("]");
 }
 
 #[inline(always)]
-fn array_copy<T>(
-    source: &[T],
-    source_index: usize,
-    destination: &mut [T],
-    destination_index: usize,
-    length: usize,
-) {
-    unsafe {
-        let src = source.as_ptr().offset(source_index as isize);
-        let dest = destination.as_mut_ptr().offset(destination_index as isize);
-
-        copy_nonoverlapping(src, dest, length);
+/// call a function from inside a native function and run it until it returns. the caller's
+/// registers are put back afterwards, except when the call fails and the error is left for the
+/// native function to pass on. the garbage collector and the profiler do not run during the call
+pub(crate) fn call_function(
+    virtual_machine_data: &mut VirtualMachineData,
+    callee: Register,
+    arguments: Vec<NovaObject>,
+) -> NativeResult {
+    if arguments.len() > SAVED_REGISTERS {
+        return Err(format!(
+            "Cannot call a function with more than {} arguments from a native function",
+            SAVED_REGISTERS
+        ));
+    }
+
+    let arguments: Vec<Register> = arguments
+        .into_iter()
+        .map(|argument| nova_object_into_register(virtual_machine_data.memory, argument))
+        .collect();
+    call_function_with_registers(virtual_machine_data, callee, &arguments)
+}
+
+/// call a function like `call_function` with arguments that are already in registers, objects
+/// on the heap are passed by reference the way instructions pass them
+pub(crate) fn call_function_with_registers(
+    virtual_machine_data: &mut VirtualMachineData,
+    callee: Register,
+    arguments: &[Register],
+) -> NativeResult {
+    if virtual_machine_data.native_depth >= MAX_NATIVE_CALL_DEPTH {
+        return Err(format!(
+            "Too many nested calls from native functions, the limit is {}",
+            MAX_NATIVE_CALL_DEPTH
+        ));
+    }
+
+    let saved_registers = *virtual_machine_data.registers;
+    let argument_number = arguments.len() as u32;
+    virtual_machine_data.registers[..arguments.len()].copy_from_slice(arguments);
+
+    // the arguments belong to the instruction that called the native function
+    #[cfg(feature = "heap_sites")]
+    let native_site = virtual_machine_data.heap_sites.site();
+    #[cfg(feature = "heap_sites")]
+    virtual_machine_data.heap_sites.tag(virtual_machine_data.memory.len());
+
+    let depth = virtual_machine_data.frames.len();
+    bytecode_execution::invoke_callable(virtual_machine_data, callee, 0, argument_number);
+
+    while !check_error(virtual_machine_data.registers)
+        && *virtual_machine_data.running
+        && virtual_machine_data.frames.len() > depth
+    {
+        #[cfg(feature = "checked")]
+        if !bounds_check::check_next_instruction(virtual_machine_data) {
+            break;
+        }
+
+        #[cfg(feature = "debug")]
+        debug(virtual_machine_data);
+
+        #[cfg(feature = "heap_sites")]
+        virtual_machine_data.heap_sites.enter(Some(
+            virtual_machine_data.registers[RegisterID::RPC as usize].value as usize,
+        ));
+
+        let instruction = get_next_instruction(
+            virtual_machine_data.registers,
+            virtual_machine_data.instructions,
+        );
+
+        VirtualMachine::execute_instruction(instruction, virtual_machine_data);
+        #[cfg(feature = "heap_sites")]
+        virtual_machine_data.heap_sites.tag(virtual_machine_data.memory.len());
+    }
+
+    // the result is allocated on behalf of the native function's caller again
+    #[cfg(feature = "heap_sites")]
+    virtual_machine_data.heap_sites.enter(native_site);
+
+    if check_error(virtual_machine_data.registers) {
+        let error = get_register(virtual_machine_data.registers, RegisterID::RERR as Instruction);
+        return match load_object_from_memory(virtual_machine_data.memory, error.value) {
+            NovaObject::String(message) => Err(message.to_string()),
+            _ => Err(String::new()),
+        };
     }
+
+    let result = package_register_into_nova_object(
+        virtual_machine_data.registers,
+        virtual_machine_data.memory,
+        virtual_machine_data.immutables,
+        RegisterID::RRTN as Instruction,
+    );
+    *virtual_machine_data.registers = saved_registers;
+
+    Ok(result)
 }
 
 #[cfg(feature = "debug")]
-pub fn debug(vm: &VirtualMachineData) {
+pub(crate) fn debug(vm: &mut VirtualMachineData) {
     #[cfg(feature = "dbg_code")]
     {
-        let current = vm.registers[RegisterID::RPC as usize].value;
-        println!(
-            "[{}]: {}",
-            current,
-            debug_instruction(
-                &vm.instructions,
-                vm.registers[RegisterID::RPC as usize].value,
-            )
-        );
+        let address = vm.registers[RegisterID::RPC as usize].value;
+        let listing = debug_instruction(vm.instructions, address);
+        vm.trace.trace(TraceEvent::Instruction {
+            address,
+            instruction: vm.instructions[address as usize],
+            listing: &listing,
+        });
     }
     #[cfg(feature = "verbose")]
-    print_register_values(vm);
+    vm.trace.trace(TraceEvent::Registers(vm.registers));
     #[cfg(feature = "dbg_global")]
-    print_globals(vm);
+    vm.trace.trace(TraceEvent::Globals(vm.globals));
     #[cfg(feature = "dbg_local")]
-    print_locals(vm);
+    vm.trace.trace(TraceEvent::Locals(vm.locals));
     #[cfg(feature = "dbg_global")]
-    print_identifiers(vm);
+    vm.trace.trace(TraceEvent::Identifiers(vm.identifiers));
     #[cfg(feature = "dbg_memory")]
-    print_memory(vm);
+    vm.trace.trace(TraceEvent::Memory(vm.memory));
     #[cfg(feature = "dbg_step")]
     wait_for_input();
 }
@@ -544,67 +1448,98 @@ fn wait_for_input() {
     let _ = io::stdin().read_line(&mut buffer);
 }
 
-#[cfg(feature = "verbose")]
-fn print_register_values(vm: &VirtualMachineData) {
-    println!("{:=^30}", "Registers");
-    for register_index in 0..RegisterID::RMax as usize + 1 {
-        let register = vm.registers[register_index];
-        println!("==> R{:<2}: {}", register_index, register);
+#[cfg(all(test, feature = "nova_tw"))]
+mod tests {
+    use std::{io, sync::Arc};
+
+    use crate::{
+        compiler,
+        machine::VirtualMachine,
+        natives,
+    };
+
+    /// load the source after what the virtual machine already ran, like the repl does
+    fn run_line(vm: &mut VirtualMachine, source: &str) -> u32 {
+        let offset = vm.get_instruction_count();
+        vm.load_program(compiler::compile(source, "").unwrap());
+        vm.start_vm(offset)
     }
-    println!("{:=^30}", "");
-}
 
-#[cfg(feature = "dbg_memory")]
-fn print_memory(vm: &VirtualMachineData) {
-    println!("{:=^30}", "Heap");
-    print_vec_of_objects(&vm.memory);
-    println!("{:=^30}", "");
-}
-
-#[cfg(feature = "dbg_global")]
-fn print_globals(vm: &VirtualMachineData) {
-    println!("{:=^30}", "Globals");
-    print_vec_of_registers(&vm.globals);
-    println!("{:=^30}", "");
-}
+    #[test]
+    fn test_redefine_and_undefine_global() {
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(io::sink()));
+
+        assert_eq!(run_line(&mut vm, "x := 1\n"), 0);
+        let globals = vm.globals.len();
+        assert_eq!(run_line(&mut vm, "x := 2\nassert_equal(x, 2)\n"), 0);
+        assert_eq!(vm.globals.len(), globals);
+
+        // the first load caches where x lives, undef has to forget it
+        assert_eq!(run_line(&mut vm, "println(x)\nassert(undef(\"x\"))\nprintln(x)\n"), 1);
+        assert_eq!(
+            vm.take_error().unwrap().message,
+            "Cannot find global named: x"
+        );
+        assert!(!vm.undefine_global("x"));
+    }
 
-#[cfg(feature = "dbg_local")]
-fn print_locals(vm: &VirtualMachineData) {
-    println!("{:=^30}", "Locals");
-    print_vec_of_registers(&vm.locals);
-    println!("{:=^30}", "");
-}
+    #[test]
+    fn test_early_return_frees_block_locals() {
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(io::sink()));
+
+        // the return skips the deallocation at the end of the loop and if blocks
+        let source = "fn find(limit)\ni := 0\nwhile i < 10\nif i == limit\nhit := i\nreturn hit\nend\ni = i + 1\nend\nend\nj := 0\nwhile j < 5\nfind(j)\nj = j + 1\nend\n";
+        assert_eq!(run_line(&mut vm, source), 0);
+        assert!(vm.locals.is_empty());
+    }
 
-#[cfg(feature = "dbg_global")]
-fn print_identifiers(vm: &VirtualMachineData) {
-    println!("{:=^30}", "Identifiers");
-    println!("==> {:?}", &vm.identifiers);
-    println!("{:=^30}", "");
-}
+    #[test]
+    fn test_shared_immutables() {
+        let program = compiler::compile("fn greet(name)\nreturn \"hi \" + name\nend\nassert_equal(greet(\"a\"), \"hi a\")\n", "").unwrap();
+
+        let mut first = VirtualMachine::new();
+        let mut second = VirtualMachine::new();
+        for vm in [&mut first, &mut second] {
+            vm.load_natives(natives::common_native_functions());
+            vm.set_output(Box::new(io::sink()));
+            vm.load_program(program.clone());
+            assert!(Arc::ptr_eq(&vm.immutables, &program.immutables));
+            assert_eq!(vm.start_vm(0), 0);
+        }
 
-#[allow(dead_code)]
-fn print_vec_of_objects(objects: &[NovaObject]) {
-    println!("[");
-    for (index, object) in objects.iter().enumerate() {
-        println!("\t[{}] {}", index, object);
+        // a repl line copies the pool of its machine only
+        assert_eq!(run_line(&mut first, "assert_equal(greet(\"b\"), \"hi b\")\n"), 0);
+        assert!(!Arc::ptr_eq(&first.immutables, &program.immutables));
+        assert!(first.immutables.len() > program.immutables.len());
+        assert!(Arc::ptr_eq(&second.immutables, &program.immutables));
     }
-    println!("]");
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::machine::array_copy;
 
+    #[cfg(feature = "mmap")]
     #[test]
-    fn test_array_copy() {
-        let src = [1, 2, 3, 4, 5, 6];
-        let mut dest = [0; 6];
-
-        //println!("Source: {:?}", src);
-        //println!("Destination before = {:?}", dest);
-        array_copy(&src, 2, &mut dest, 0, 3);
-        //println!("Destination after = {:?}", dest);
-        let expected_dest = [3, 4, 5, 0, 0, 0];
-        assert_eq!(expected_dest, dest);
+    fn test_mapped_program() {
+        use std::{env, fs, process};
+
+        use crate::{file, machine::code::Code};
+
+        let program = compiler::compile("fn add(a, b)\nreturn a + b\nend\nassert_equal(add(40, 2), 42)\n", "").unwrap();
+        let path = env::temp_dir().join(format!("nova_load_mapped_{}.nvc", process::id()));
+        let path = path.to_str().unwrap();
+        file::write_program_file(path, &program).unwrap();
+
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(io::sink()));
+        vm.try_load_mapped_program(file::map_program_file(path).unwrap()).unwrap();
+        assert!(matches!(vm.instructions, Code::Mapped(_)));
+        assert_eq!(vm.start_vm(0), 0);
+
+        // a repl line after it copies the instructions out of the mapping
+        assert_eq!(run_line(&mut vm, "assert_equal(add(1, 2), 3)\n"), 0);
+        assert!(matches!(vm.instructions, Code::Owned(_)));
+        fs::remove_file(path).unwrap();
     }
 }
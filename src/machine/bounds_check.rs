@@ -0,0 +1,203 @@
+use alloc::{format, string::String};
+
+use crate::{
+    bytecode::OpCode,
+    debug::instruction_width,
+    instruction::{instruction_decoder, Instruction},
+    lint::{register_effects, RegisterSet},
+    object::RegisterValueKind,
+    register::RegisterID,
+};
+
+use super::{program_management::emit_error_with_message, VirtualMachineData};
+
+/// check the instruction at the program counter before it runs, with the `checked` feature.
+/// the execution internals index registers, locals, globals and memory unchecked, a corrupted
+/// program or a bug in the machine raises an error here instead of reading out of bounds.
+/// false when an error was raised
+pub(crate) fn check_next_instruction(virtual_machine_data: &mut VirtualMachineData) -> bool {
+    let error = match next_instruction_error(virtual_machine_data) {
+        Some(error) => error,
+        None => return true,
+    };
+
+    emit_error_with_message(
+        virtual_machine_data.registers,
+        virtual_machine_data.memory,
+        &format!("Out of bounds: {}", error),
+    );
+    false
+}
+
+fn next_instruction_error(virtual_machine_data: &VirtualMachineData) -> Option<String> {
+    let registers = &virtual_machine_data.registers;
+    let pc = registers[RegisterID::RPC as usize].value as usize;
+    let instructions = virtual_machine_data.instructions.len();
+
+    let Some(&instruction) = virtual_machine_data.instructions.get(pc) else {
+        return Some(format!(
+            "the program counter {} is past the last of {} instructions",
+            pc, instructions
+        ));
+    };
+
+    if pc + instruction_width(instruction) > instructions {
+        return Some(format!(
+            "the operand words of the instruction at {} are past the last of {} instructions",
+            pc, instructions
+        ));
+    }
+
+    let (reads, _) = register_effects(instruction);
+    for register in (0..RegisterSet::BITS).filter(|register| reads & (1 << register) != 0) {
+        if let Some(error) = register_error(virtual_machine_data, register) {
+            return Some(error);
+        }
+    }
+
+    if let Some(error) = jump_error(virtual_machine_data.instructions, pc, instruction) {
+        return Some(error);
+    }
+
+    operand_error(virtual_machine_data, instruction)
+}
+
+/// a jump landing outside the instructions. the conditional jumps take the jump after them
+fn jump_error(instructions: &[Instruction], pc: usize, instruction: Instruction) -> Option<String> {
+    let (index, jump) = match OpCode::try_from(instruction_decoder::decode_opcode(instruction)) {
+        Ok(OpCode::Jump) => (pc, instruction),
+        Ok(opcode) if opcode.takes_next_jump() => {
+            match instructions.get(pc + 1 + opcode.operand_words()) {
+                Some(&jump) => (pc + 1 + opcode.operand_words(), jump),
+                None => {
+                    return Some(format!(
+                        "the conditional jump at {} has no jump after it",
+                        pc
+                    ))
+                }
+            }
+        }
+        _ => return None,
+    };
+
+    let offset = instruction_decoder::decode_immutable_address_small(jump) as usize;
+    let target = match instruction_decoder::decode_destination_register(jump) {
+        0 => index.checked_sub(offset),
+        _ if offset == 0 => None,
+        _ => Some(index + offset),
+    };
+
+    match target {
+        Some(target) if target < instructions.len() => None,
+        _ => Some(format!(
+            "the jump at {} by {} lands outside the {} instructions",
+            index,
+            offset,
+            instructions.len()
+        )),
+    }
+}
+
+/// a register pointing at memory or a constant that is not there
+fn register_error(virtual_machine_data: &VirtualMachineData, register: u32) -> Option<String> {
+    let value = virtual_machine_data.registers[register as usize];
+    let (pool, length) = match value.kind {
+        RegisterValueKind::StrMem | RegisterValueKind::MemAddress => {
+            ("memory", virtual_machine_data.memory.len())
+        }
+        RegisterValueKind::StrImm | RegisterValueKind::ImmAddress => {
+            ("constant", virtual_machine_data.immutables.len())
+        }
+        _ => return None,
+    };
+
+    (value.value as usize >= length).then(|| {
+        format!(
+            "register r{} points at {} address {}, there are {}",
+            register, pool, value.value, length
+        )
+    })
+}
+
+/// the local, global or constant an instruction names directly, if it is not there
+fn operand_error(
+    virtual_machine_data: &VirtualMachineData,
+    instruction: Instruction,
+) -> Option<String> {
+    let operand = instruction_decoder::decode_immutable_address_small(instruction) as usize;
+    let local_offset = virtual_machine_data.registers[RegisterID::RLO as usize].value as usize;
+    let locals = virtual_machine_data.locals.len();
+
+    match OpCode::try_from(instruction_decoder::decode_opcode(instruction)) {
+        Ok(
+            OpCode::LoadLocal
+            | OpCode::StoreLocal
+            | OpCode::Inc
+            | OpCode::Dec
+            | OpCode::ForPrep
+            | OpCode::ForLoop,
+        ) if local_offset + operand >= locals => Some(format!(
+            "local slot {} of the frame at {} is past the last of {} locals",
+            operand, local_offset, locals
+        )),
+        Ok(OpCode::DeallocateLocal) if operand > locals.saturating_sub(local_offset) => {
+            Some(format!(
+                "cannot free {} locals, the frame at {} has {}",
+                operand,
+                local_offset,
+                locals.saturating_sub(local_offset)
+            ))
+        }
+        Ok(OpCode::LoadGlobal) if operand >= virtual_machine_data.globals.len() => Some(format!(
+            "global {} is past the last of {} globals",
+            operand,
+            virtual_machine_data.globals.len()
+        )),
+        Ok(
+            OpCode::LoadK
+            | OpCode::DefineGlobalIndirect
+            | OpCode::StoreGlobalIndirect
+            | OpCode::LoadGlobalIndirect,
+        ) if operand >= virtual_machine_data.immutables.len() => Some(format!(
+            "constant {} is past the last of {} constants",
+            operand,
+            virtual_machine_data.immutables.len()
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod bounds_check_tests {
+    use crate::{host::Discard, machine::VirtualMachine};
+
+    fn run_error(program: crate::program::Program) -> String {
+        let mut vm = VirtualMachine::new();
+        vm.set_output(Box::new(Discard));
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+        vm.take_error().unwrap().message
+    }
+
+    #[test]
+    fn test_out_of_bounds_accesses_raise_errors() {
+        let program = crate::nova_asm! {
+            allocate_local 1;
+            load_local 0, 3;
+            halt;
+        };
+        assert_eq!(
+            run_error(program),
+            "Out of bounds: local slot 3 of the frame at 0 is past the last of 1 locals"
+        );
+
+        // running off the end without a halt
+        let program = crate::nova_asm! {
+            load_int 0, 1;
+        };
+        assert_eq!(
+            run_error(program),
+            "Out of bounds: the program counter 2 is past the last of 2 instructions"
+        );
+    }
+}
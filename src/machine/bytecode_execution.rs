@@ -1,20 +1,38 @@
-mod arithmetic_operations;
+pub(crate) mod arithmetic_operations;
+mod operator_overloads;
 mod string_operations;
 
-use arithmetic_operations::{op_float_float, op_float_int, op_int_float, op_int_int, ArithmeticOp};
-use string_operations::{add_num_str, add_str_num};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{cmp::Ordering, fmt::Write};
+
+use arithmetic_operations::{
+    op_float_float, op_float_int, op_int_float, op_int_int, op_math, ArithmeticOp, MathOp,
+};
+use operator_overloads::{call_overload, may_overload, Overload};
+use string_operations::{add_num_str, add_str_num, store_string};
 
 use crate::{
     bytecode::OpCode,
-    instruction::{instruction_decoder, Instruction},
-    object::{NovaCallable, NovaFunctionIDLabelled, NovaObject, RegisterValueKind},
+    hooks::FunctionEvent,
+    immutables::Immutables,
+    instruction::{
+        instruction_decoder, Instruction, FOR_DESCENDING, FOR_INCLUSIVE, FOR_LIMIT_IMMEDIATE,
+    },
+    natives::{NativeArguments, NativeContext},
+    object::{
+        NativeFunctionPointer, NovaCallable, NovaFunctionIDLabelled, NovaObject, RegisterValueKind,
+    },
     register::{Register, RegisterID},
 };
 
 use super::{
-    array_copy,
     memory_management::{
-        allocate_global, allocate_local_variables, create_global, load_global_value,
+        allocate_local_variables, define_global, is_frozen, load_global_value,
         load_object_from_memory, set_global_value, store_object_in_memory,
     },
     program_management::{
@@ -22,10 +40,11 @@ use super::{
     },
     register_management::{
         clear_register, compare_registers, get_register, is_truthy, load_f64_to_register,
-        load_i64_to_register, package_register_into_nova_object,
-        set_value_in_register,
+        load_i64_to_register, nova_object_into_register, package_register_into_nova_object,
+        register_as_nova_object, set_value_in_register,
     },
-    VirtualMachineData,
+    stack::{Room, MAX_FRAMES, MAX_LOCALS},
+    unchecked, Coercion, VirtualMachineData,
 };
 
 #[inline(always)]
@@ -36,7 +55,24 @@ pub fn invoke(instruction: Instruction, virtual_machine_data: &mut VirtualMachin
     let argument_number = instruction_decoder::decode_source_register_1(instruction);
 
     let register = get_register(*registers, invoke_register);
+    invoke_callable(
+        virtual_machine_data,
+        register,
+        argument_start,
+        argument_number,
+    );
+}
 
+/// call the function held by the register with the arguments in the registers starting at
+/// `argument_start`. a native function returns straight away, a nova function has its frame
+/// pushed and runs from the next instruction
+#[inline(always)]
+pub fn invoke_callable(
+    virtual_machine_data: &mut VirtualMachineData,
+    register: Register,
+    argument_start: u32,
+    argument_number: u32,
+) {
     if let RegisterValueKind::NovaFunctionID(nova_function_id) = register.kind {
         let function_address = register.value;
         invoke_nova_function_id_labelled(
@@ -49,16 +85,30 @@ pub fn invoke(instruction: Instruction, virtual_machine_data: &mut VirtualMachin
         return;
     }
 
+    if let RegisterValueKind::NativeFunctionID(index) = register.kind {
+        let function = virtual_machine_data.natives[index as usize].function;
+        invoke_native_function(
+            virtual_machine_data,
+            function,
+            argument_start,
+            argument_number,
+        );
+        return;
+    }
+
     let registers = &mut virtual_machine_data.registers;
     let memory = &mut virtual_machine_data.memory;
     let immutables = &mut virtual_machine_data.immutables;
 
-    if register.kind != RegisterValueKind::MemAddress {
-        emit_error_with_message(*registers, *memory, "Function not found");
-        return;
-    }
-
-    let nova_object = load_object_from_memory(*memory, register.value);
+    // lambdas are referenced straight from the immutables
+    let nova_object = match register.kind {
+        RegisterValueKind::MemAddress => load_object_from_memory(*memory, register.value),
+        RegisterValueKind::ImmAddress => &immutables[register.value as usize],
+        _ => {
+            emit_error_with_message(*registers, *memory, "Function not found");
+            return;
+        }
+    };
 
     let callable = match nova_object {
         NovaObject::NovaFunction(nova_function) => NovaCallable::NovaFunction(nova_function),
@@ -73,6 +123,8 @@ pub fn invoke(instruction: Instruction, virtual_machine_data: &mut VirtualMachin
             let nova_function_id = NovaFunctionIDLabelled {
                 name_address: 0,
                 arity: function.arity,
+                default_parameters: function.default_parameters,
+                is_variadic: function.is_variadic,
                 is_method: function.is_method,
                 number_of_locals: function.number_of_locals,
             };
@@ -88,59 +140,133 @@ pub fn invoke(instruction: Instruction, virtual_machine_data: &mut VirtualMachin
         }
 
         NovaCallable::NativeFunction(function) => {
-            let mut source_index = argument_start;
-            let source_end = argument_start + argument_number;
-
-            let mut arguments = Vec::new();
+            let function = function.function;
+            invoke_native_function(
+                virtual_machine_data,
+                function,
+                argument_start,
+                argument_number,
+            );
+        }
 
-            while source_index < source_end {
-                let object =
-                    package_register_into_nova_object(*registers, memory, immutables, source_index);
-                arguments.push(object);
-                source_index += 1;
-            }
+        NovaCallable::None => {
+            emit_error_with_message(*registers, *memory, "Called a None Value");
+        }
+    }
+}
 
-            let result = (function.function)(arguments);
+#[inline(always)]
+fn invoke_native_function(
+    virtual_machine_data: &mut VirtualMachineData,
+    function: NativeFunctionPointer,
+    argument_start: u32,
+    argument_number: u32,
+) {
+    // the arguments are copied out so the native function can call back into the machine
+    let argument_registers = *virtual_machine_data.registers;
+    let source_index = argument_start as usize;
+    let source_end = (argument_start + argument_number) as usize;
+    run_native_function(
+        virtual_machine_data,
+        function,
+        &argument_registers[source_index..source_end],
+        RegisterID::RRTN as Instruction,
+    );
+}
 
-            if let Err(error) = result {
-                emit_error_with_message(*registers, *memory, &error);
-                return;
-            }
+/// run an opcode the host registered, see `extensions::Extensions`. the handler gets the two
+/// source registers and its result goes to the destination
+pub fn host_opcode(
+    instruction: Instruction,
+    virtual_machine_data: &mut VirtualMachineData,
+    handler: NativeFunctionPointer,
+) {
+    let destination = instruction_decoder::decode_destination_register(instruction);
+    let source_register_1 = instruction_decoder::decode_source_register_1(instruction);
+    let source_register_2 = instruction_decoder::decode_source_register_2(instruction);
 
-            let result = result.unwrap();
+    let argument_registers = [
+        get_register(virtual_machine_data.registers, source_register_1),
+        get_register(virtual_machine_data.registers, source_register_2),
+    ];
+    run_native_function(
+        virtual_machine_data,
+        handler,
+        &argument_registers,
+        destination,
+    );
+}
 
-            match result {
-                NovaObject::Float64(value) => {
-                    let register = Register::new(RegisterValueKind::Float64, value.to_bits());
-                    set_value_in_register(*registers, RegisterID::RRTN as Instruction, register);
-                }
+/// call the native function with the registers as its arguments, storing its result in the
+/// destination register or raising its error
+#[inline(always)]
+fn run_native_function(
+    virtual_machine_data: &mut VirtualMachineData,
+    function: NativeFunctionPointer,
+    argument_registers: &[Register],
+    destination: Instruction,
+) {
+    let arguments = NativeArguments::new(
+        argument_registers,
+        virtual_machine_data.memory,
+        virtual_machine_data.immutables,
+    );
 
-                NovaObject::Int64(value) => {
-                    let register = Register::new(RegisterValueKind::Int64, value as u64);
-                    set_value_in_register(*registers, RegisterID::RRTN as Instruction, register);
-                }
+    let mut context = NativeContext {
+        output: &mut *virtual_machine_data.output,
+        identifiers: &mut *virtual_machine_data.identifiers,
+        frozen_globals: &mut *virtual_machine_data.frozen_globals,
+        globals: &mut *virtual_machine_data.globals,
+        mem_cache: &mut *virtual_machine_data.mem_cache,
+        float_format: virtual_machine_data.float_format,
+        capabilities: virtual_machine_data.capabilities,
+        limits: virtual_machine_data.limits,
+        coercion: virtual_machine_data.coercion,
+        instructions: virtual_machine_data.instructions,
+        registers: &mut *virtual_machine_data.registers,
+        running: &mut *virtual_machine_data.running,
+        frames: &mut *virtual_machine_data.frames,
+        locals: &mut *virtual_machine_data.locals,
+        natives: virtual_machine_data.natives,
+        extensions: virtual_machine_data.extensions,
+        trace: &mut *virtual_machine_data.trace,
+        hooks: &mut *virtual_machine_data.hooks,
+        native_depth: virtual_machine_data.native_depth,
+        #[cfg(feature = "heap_sites")]
+        heap_sites: &mut *virtual_machine_data.heap_sites,
+    };
+    let result = function(&mut context, arguments);
 
-                NovaObject::None => {
-                    set_value_in_register(
-                        *registers,
-                        RegisterID::RRTN as Instruction,
-                        Register::empty(),
-                    );
-                }
+    let registers = &mut virtual_machine_data.registers;
+    let memory = &mut virtual_machine_data.memory;
 
-                _ => {
-                    let memory_location = store_object_in_memory(*memory, result);
-                    let register =
-                        Register::new(RegisterValueKind::MemAddress, memory_location as u64);
-                    set_value_in_register(*registers, RegisterID::RRTN as Instruction, register);
-                }
-            }
+    // natives like push grow the lists and maps they are given in place, so those are checked
+    // along with the result
+    let limits = virtual_machine_data.limits;
+    if !limits.is_unlimited() {
+        let exceeded = argument_registers
+            .iter()
+            .filter(|register| register.kind == RegisterValueKind::MemAddress)
+            .filter_map(|register| memory.get(register.value as usize))
+            .chain(result.as_ref().ok())
+            .find_map(|object| limits.check(object).err());
+
+        if let Some(error) = exceeded {
+            emit_error_with_message(*registers, *memory, &error);
+            return;
         }
+    }
 
-        NovaCallable::None => {
-            emit_error_with_message(*registers, *memory, "Called a None Value");
+    let result = match result {
+        Ok(result) => result,
+        Err(error) => {
+            emit_error_with_message(*registers, *memory, &error);
+            return;
         }
-    }
+    };
+
+    let register = nova_object_into_register(memory, result);
+    set_value_in_register(*registers, destination, register);
 }
 
 #[inline(always)]
@@ -158,28 +284,75 @@ fn invoke_nova_function_id_labelled(
 
     let function = nova_function_id;
 
-    if argument_number != function.arity {
+    // verified programs never have more defaults than parameters
+    let Some(required_arguments) = function.arity.checked_sub(function.default_parameters) else {
+        let name = function_name(virtual_machine_data.immutables, function_address);
+        emit_error_with_message(
+            *registers,
+            memory,
+            &format!("Invalid parameters for function '{}'", name),
+        );
+        return;
+    };
+
+    let too_many = argument_number > function.arity && !function.is_variadic;
+    if argument_number < required_arguments || too_many {
+        let required = if function.default_parameters == 0 {
+            function.arity.to_string()
+        } else {
+            format!("{} to {}", required_arguments, function.arity)
+        };
+
+        let name = function_name(virtual_machine_data.immutables, function_address);
         emit_error_with_message(
             *registers,
             *memory,
             &format!(
-                "Not enough function arguments.\n{} are required\n{} were provided",
-                function.arity, argument_number
+                "{} arguments for function '{}'.\n{} are required\n{} were provided",
+                if argument_number < required_arguments {
+                    "Not enough"
+                } else {
+                    "Too many"
+                },
+                name,
+                required,
+                argument_number
             ),
         );
         return;
     }
 
+    if !frames.has_room(1) || !locals.has_room(function.number_of_locals as usize) {
+        stack_overflow(virtual_machine_data);
+        return;
+    }
+
+    // arguments past the named parameters are collected before the caller's registers are saved
+    let variadic_arguments = if function.is_variadic {
+        let extra_start = argument_start + function.arity.min(argument_number);
+        let extra_end = argument_start + argument_number;
+        let immutables = &virtual_machine_data.immutables;
+
+        let list = (extra_start..extra_end)
+            .map(|source| package_register_into_nova_object(*registers, memory, immutables, source))
+            .collect::<Vec<NovaObject>>();
+
+        let address = store_object_in_memory(memory, NovaObject::List(Box::new(list)));
+        Some(Register::new(RegisterValueKind::MemAddress, address as u64))
+    } else {
+        None
+    };
+
     let num_locals = function.number_of_locals;
-    new_frame(*registers, *frames, *locals, num_locals);
+    new_frame(*registers, *frames, *locals, num_locals, function_address);
     let old_frame = frames.last().unwrap();
 
     let source_index = argument_start as usize;
-    let source_end = (argument_start + argument_number) as usize;
+    let source_end = (argument_start + argument_number.min(function.arity)) as usize;
     let destination_index = 0;
     let length = source_end - source_index;
 
-    array_copy(
+    unchecked::copy(
         &old_frame.registers,
         source_index,
         *registers,
@@ -187,39 +360,90 @@ fn invoke_nova_function_id_labelled(
         length,
     );
 
-    unsafe {
-        registers.get_unchecked_mut(RegisterID::RPC as usize).value = function_address as u64;
+    // parameters that were not passed start as None so their defaults get applied
+    let passed = argument_number.min(function.arity) as usize;
+    for register in registers[passed..function.arity as usize].iter_mut() {
+        *register = Register::empty();
+    }
+
+    if let Some(variadic_arguments) = variadic_arguments {
+        set_value_in_register(*registers, function.arity, variadic_arguments);
+    }
+
+    registers[RegisterID::RPC as usize].value = function_address as u64;
+
+    if virtual_machine_data.hooks.watches_functions() {
+        virtual_machine_data.hooks.function_enter(FunctionEvent {
+            name: function_name(virtual_machine_data.immutables, function_address),
+            address: function_address,
+            depth: virtual_machine_data.frames.len(),
+        });
     }
 }
 
+/// drop the frame of the returning function, telling the exit hook about it
+#[inline(always)]
+fn return_from_function(virtual_machine_data: &mut VirtualMachineData) {
+    let frames = &mut virtual_machine_data.frames;
+    let exiting = match frames.last() {
+        Some(frame) if !frame.is_main && virtual_machine_data.hooks.watches_functions() => {
+            Some((frame.function, frames.len()))
+        }
+        _ => None,
+    };
+
+    drop_frame(
+        virtual_machine_data.registers,
+        frames,
+        virtual_machine_data.locals,
+        virtual_machine_data.running,
+    );
+
+    if let Some((address, depth)) = exiting {
+        virtual_machine_data.hooks.function_exit(FunctionEvent {
+            name: function_name(virtual_machine_data.immutables, address),
+            address,
+            depth,
+        });
+    }
+}
+
+/// name of the nova function starting at the address, only looked up when reporting errors
+/// or calling hooks
+fn function_name(immutables: &Immutables, function_address: u64) -> &str {
+    immutables
+        .iter()
+        .find_map(|immutable| match immutable {
+            NovaObject::NovaFunction(function) if function.address as u64 == function_address => {
+                Some(function.name.as_str())
+            }
+            _ => None,
+        })
+        .unwrap_or("<unknown>")
+}
+
 #[inline(always)]
 pub fn return_none(_: Instruction, virtual_machine_data: &mut VirtualMachineData) {
     let registers = &mut virtual_machine_data.registers;
-    let frames = &mut virtual_machine_data.frames;
-    let locals = &mut virtual_machine_data.locals;
-    let running_state = &mut virtual_machine_data.running;
 
     set_value_in_register(
         *registers,
         RegisterID::RRTN as Instruction,
         Register::empty(),
     );
-    drop_frame(*registers, *frames, *locals, *running_state);
+    return_from_function(virtual_machine_data);
 }
 
 #[inline(always)]
 pub fn return_val(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
     let registers = &mut virtual_machine_data.registers;
-    let frames = &mut virtual_machine_data.frames;
-    let locals = &mut virtual_machine_data.locals;
-    let running_state = &mut virtual_machine_data.running;
 
     let value_source = instruction_decoder::decode_source_register_1(instruction);
     let value_register = get_register(*registers, value_source);
 
     set_value_in_register(*registers, RegisterID::RRTN as Instruction, value_register);
 
-    drop_frame(*registers, *frames, *locals, *running_state);
+    return_from_function(virtual_machine_data);
 }
 
 #[inline(always)]
@@ -228,7 +452,7 @@ pub fn load_return(instruction: Instruction, virtual_machine_data: &mut VirtualM
 
     let destination = instruction_decoder::decode_destination_register(instruction);
 
-    let return_register = unsafe { *registers.get_unchecked(RegisterID::RRTN as usize) };
+    let return_register = registers[RegisterID::RRTN as usize];
     set_value_in_register(*registers, destination, return_register);
 }
 
@@ -237,6 +461,9 @@ pub fn print(instruction: Instruction, virtual_machine_data: &mut VirtualMachine
     let registers = &mut virtual_machine_data.registers;
     let memory = &mut virtual_machine_data.memory;
     let immutables = &mut virtual_machine_data.immutables;
+    let float_format = virtual_machine_data.float_format;
+    // the text is written in one piece so the print hook sees whole prints
+    let mut output = String::new();
 
     let source = instruction_decoder::decode_source_register_1(instruction);
     let newline = instruction_decoder::decode_destination_register(instruction);
@@ -245,35 +472,48 @@ pub fn print(instruction: Instruction, virtual_machine_data: &mut VirtualMachine
 
     match register.kind {
         RegisterValueKind::Int64 => {
-            print!("{}", register.value as i64)
+            let _ = write!(output, "{}", register.value as i64);
         }
         RegisterValueKind::Float64 => {
-            print!("{}", f64::from_bits(register.value))
+            let value = f64::from_bits(register.value);
+            let _ = write!(
+                output,
+                "{}",
+                float_format.display(&NovaObject::Float64(value))
+            );
         }
         RegisterValueKind::None => {
-            print!("None")
+            let _ = write!(output, "None");
         }
         RegisterValueKind::Bool => {
-            print!("{}", register.value == 1)
+            let _ = write!(output, "{}", register.value == 1);
         }
-        RegisterValueKind::MemAddress => {
+        RegisterValueKind::MemAddress
+        | RegisterValueKind::StrMem
+        | RegisterValueKind::NativeFunctionID(_) => {
             let address = register.value;
             let object = load_object_from_memory(*memory, address);
-            print!("{}", object);
+            let _ = write!(output, "{}", float_format.display(object));
         }
 
-        RegisterValueKind::ImmAddress => {
+        RegisterValueKind::ImmAddress | RegisterValueKind::StrImm => {
             let immutable = &immutables[register.value as usize];
-            print!("{}", immutable);
+            let _ = write!(output, "{}", float_format.display(immutable));
         }
 
-        RegisterValueKind::NovaFunctionID(_) => todo!(),
-        RegisterValueKind::StrMem => todo!(),
-        RegisterValueKind::StrImm => todo!(),
+        RegisterValueKind::NovaFunctionID(_) => {
+            let function =
+                package_register_into_nova_object(*registers, memory, immutables, source);
+            let _ = write!(output, "{}", function);
+        }
     }
     if newline == 1 {
-        println!()
+        output.push('\n');
     }
+
+    virtual_machine_data
+        .hooks
+        .print(virtual_machine_data.output, &output);
 }
 
 #[inline(always)]
@@ -298,11 +538,55 @@ pub fn negate(instruction: Instruction, virtual_machine_data: &mut VirtualMachin
     emit_error_with_message(*registers, *memory, "Cannot negate non float32 value");
 }
 
+/// unary plus keeps a number as it is and raises an error for any other value
+#[inline(always)]
+pub fn positive(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
+    let registers = &mut virtual_machine_data.registers;
+    let memory = &mut virtual_machine_data.memory;
+    let immutables = &virtual_machine_data.immutables;
+
+    let source = instruction_decoder::decode_source_register_1(instruction);
+    let register = get_register(*registers, source);
+    if let RegisterValueKind::Int64 | RegisterValueKind::Float64 = register.kind {
+        return;
+    }
+
+    let kind = register_as_nova_object(register, memory, immutables).kind_name();
+    emit_error_with_message(
+        *registers,
+        memory,
+        &format!("Unary plus needs a number, found {}", kind),
+    );
+}
+
+#[inline(always)]
+pub fn math(op: MathOp, instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
+    let registers = &mut virtual_machine_data.registers;
+    let memory = &mut virtual_machine_data.memory;
+
+    let source = instruction_decoder::decode_source_register_1(instruction);
+    let destination = source; // operate in place like negate
+
+    let register = get_register(*registers, source);
+    if let Some(register) = op_math(op, register) {
+        set_value_in_register(*registers, destination, register);
+        return;
+    }
+
+    emit_error_with_message(
+        *registers,
+        memory,
+        &format!("Function '{}()' needs a number", op.name()),
+    );
+}
+
 #[inline(always)]
 pub fn add(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
     let registers = &mut virtual_machine_data.registers;
     let memory = &mut virtual_machine_data.memory;
     let immutables = &mut virtual_machine_data.immutables;
+    let limits = virtual_machine_data.limits;
+    let coercive = virtual_machine_data.coercion == Coercion::Coercive;
 
     let destination_register = instruction_decoder::decode_destination_register(instruction);
     let source_register_1 = instruction_decoder::decode_source_register_1(instruction);
@@ -334,55 +618,49 @@ pub fn add(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
             return;
         }
 
-        (RegisterValueKind::StrImm, RegisterValueKind::Float64 | RegisterValueKind::Int64) => {
+        (RegisterValueKind::StrImm, RegisterValueKind::Float64 | RegisterValueKind::Int64)
+            if coercive =>
+        {
             let string1object = &immutables[register_1.value as usize];
             let object = add_str_num(string1object, register_2);
-            let address = store_object_in_memory(*memory, object) as u64;
-            let register = Register::new(RegisterValueKind::StrMem, address);
-            set_value_in_register(*registers, destination_register, register);
+            store_string(*registers, memory, limits, destination_register, object);
             return;
         }
 
-        (RegisterValueKind::Float64 | RegisterValueKind::Int64, RegisterValueKind::StrImm) => {
-            let string2_object = &immutables[register_1.value as usize];
+        (RegisterValueKind::Float64 | RegisterValueKind::Int64, RegisterValueKind::StrImm)
+            if coercive =>
+        {
+            let string2_object = &immutables[register_2.value as usize];
             let object = add_num_str(register_1, string2_object);
-            let address = store_object_in_memory(*memory, object) as u64;
-            let register = Register::new(RegisterValueKind::StrMem, address);
-            set_value_in_register(*registers, destination_register, register);
+            store_string(*registers, memory, limits, destination_register, object);
             return;
         }
 
-        (RegisterValueKind::StrMem, RegisterValueKind::Float64 | RegisterValueKind::Int64) => {
+        (RegisterValueKind::StrMem, RegisterValueKind::Float64 | RegisterValueKind::Int64)
+            if coercive =>
+        {
             let string1object = &memory[register_1.value as usize];
             let object = add_str_num(string1object, register_2);
-            let address = store_object_in_memory(*memory, object) as u64;
-            let register = Register::new(RegisterValueKind::StrMem, address);
-            set_value_in_register(*registers, destination_register, register);
+            store_string(*registers, memory, limits, destination_register, object);
             return;
         }
 
-        (RegisterValueKind::Float64 | RegisterValueKind::Int64, RegisterValueKind::StrMem) => {
-            let string2_object = &memory[register_1.value as usize];
+        (RegisterValueKind::Float64 | RegisterValueKind::Int64, RegisterValueKind::StrMem)
+            if coercive =>
+        {
+            let string2_object = &memory[register_2.value as usize];
             let object = add_num_str(register_1, string2_object);
-            let address = store_object_in_memory(*memory, object) as u64;
-            let register = Register::new(RegisterValueKind::StrMem, address);
-            set_value_in_register(*registers, destination_register, register);
+            store_string(*registers, memory, limits, destination_register, object);
             return;
         }
 
         (RegisterValueKind::StrMem, RegisterValueKind::StrMem) => {
-            let mut string1 = unsafe {
-                memory.get_unchecked(register_1.value as usize).to_string()
-            };
-            let string2 = unsafe {
-                memory.get_unchecked(register_2.value as usize).to_string()
-            };
+            let mut string1 = unchecked::get(memory, register_1.value as usize).to_string();
+            let string2 = unchecked::get(memory, register_2.value as usize).to_string();
 
             string1.push_str(&string2);
             let object = NovaObject::String(Box::new(string1));
-            let address = store_object_in_memory(*memory, object) as u64;
-            let register = Register::new(RegisterValueKind::StrMem, address);
-            set_value_in_register(*registers, destination_register, register);
+            store_string(*registers, memory, limits, destination_register, object);
             return;
         }
 
@@ -392,44 +670,57 @@ pub fn add(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
 
             string1.push_str(&string2);
             let object = NovaObject::String(Box::new(string1));
-            let address = store_object_in_memory(*memory, object) as u64;
-            let register = Register::new(RegisterValueKind::StrMem, address);
-            set_value_in_register(*registers, destination_register, register);
+            store_string(*registers, memory, limits, destination_register, object);
             return;
         }
 
         (RegisterValueKind::StrImm, RegisterValueKind::StrMem) => {
             let mut string1 = immutables[register_1.value as usize].to_string();
-            let string2 = unsafe {
-                memory.get_unchecked(register_2.value as usize).to_string()
-            };
+            let string2 = unchecked::get(memory, register_2.value as usize).to_string();
 
             string1.push_str(&string2);
             let object = NovaObject::String(Box::new(string1));
-            let address = store_object_in_memory(*memory, object) as u64;
-            let register = Register::new(RegisterValueKind::StrMem, address);
-            set_value_in_register(*registers, destination_register, register);
+            store_string(*registers, memory, limits, destination_register, object);
             return;
         }
 
         (RegisterValueKind::StrMem, RegisterValueKind::StrImm) => {
-            let mut string1 = unsafe {
-                memory.get_unchecked(register_1.value as usize).to_string()
-            };
+            let mut string1 = unchecked::get(memory, register_1.value as usize).to_string();
             let string2 = immutables[register_2.value as usize].to_string();
 
             string1.push_str(&string2);
             let object = NovaObject::String(Box::new(string1));
-            let address = store_object_in_memory(*memory, object) as u64;
-            let register = Register::new(RegisterValueKind::StrMem, address);
-            set_value_in_register(*registers, destination_register, register);
+            store_string(*registers, memory, limits, destination_register, object);
             return;
         }
-        
+
+        (
+            RegisterValueKind::StrImm | RegisterValueKind::StrMem,
+            RegisterValueKind::Float64 | RegisterValueKind::Int64,
+        )
+        | (
+            RegisterValueKind::Float64 | RegisterValueKind::Int64,
+            RegisterValueKind::StrImm | RegisterValueKind::StrMem,
+        ) => emit_error_with_message(
+            *registers,
+            memory,
+            "cannot add a string and a number in strict mode, convert the number with format()",
+        ),
+
         (_, _) => {
+            if overloaded(
+                virtual_machine_data,
+                Overload::Add,
+                destination_register,
+                register_1,
+                register_2,
+            ) {
+                return;
+            }
+
             emit_error_with_message(
-                *registers,
-                *memory,
+                virtual_machine_data.registers,
+                virtual_machine_data.memory,
                 &format!("cannot add {:?} to {:?}", register_1.kind, register_2.kind),
             )
         }
@@ -439,7 +730,6 @@ pub fn add(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
 #[inline(always)]
 pub fn sub(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
     let registers = &mut virtual_machine_data.registers;
-    let memory = &mut virtual_machine_data.memory;
 
     let destination_register = instruction_decoder::decode_destination_register(instruction);
     let source_register_1 = instruction_decoder::decode_source_register_1(instruction);
@@ -453,28 +743,37 @@ pub fn sub(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
         (RegisterValueKind::Float64, RegisterValueKind::Float64) => {
             let result = op_float_float(ArithmeticOp::Sub, register_1, register_2);
             set_value_in_register(*registers, destination_register, result);
-            
         }
         (RegisterValueKind::Int64, RegisterValueKind::Int64) => {
             let result = op_int_int(ArithmeticOp::Sub, register_1, register_2);
             set_value_in_register(*registers, destination_register, result);
-            
         }
         (RegisterValueKind::Int64, RegisterValueKind::Float64) => {
             let result = op_int_float(ArithmeticOp::Sub, register_1, register_2);
             set_value_in_register(*registers, destination_register, result);
-            
         }
         (RegisterValueKind::Float64, RegisterValueKind::Int64) => {
             let result = op_float_int(ArithmeticOp::Sub, register_1, register_2);
             set_value_in_register(*registers, destination_register, result);
-            
         }
         _ => {
+            if overloaded(
+                virtual_machine_data,
+                Overload::Sub,
+                destination_register,
+                register_1,
+                register_2,
+            ) {
+                return;
+            }
+
             emit_error_with_message(
-                *registers,
-                *memory,
-                &format!("cannot subtract {:?} to {:?}", register_1.kind, register_2.kind),
+                virtual_machine_data.registers,
+                virtual_machine_data.memory,
+                &format!(
+                    "cannot subtract {:?} to {:?}",
+                    register_1.kind, register_2.kind
+                ),
             );
         }
     }
@@ -483,7 +782,6 @@ pub fn sub(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
 #[inline(always)]
 pub fn mul(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
     let registers = &mut virtual_machine_data.registers;
-    let memory = &mut virtual_machine_data.memory;
 
     let destination_register = instruction_decoder::decode_destination_register(instruction);
     let source_register_1 = instruction_decoder::decode_source_register_1(instruction);
@@ -515,10 +813,23 @@ pub fn mul(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
             return;
         }
         _ => {
+            if overloaded(
+                virtual_machine_data,
+                Overload::Mul,
+                destination_register,
+                register_1,
+                register_2,
+            ) {
+                return;
+            }
+
             emit_error_with_message(
-                *registers,
-                *memory,
-                &format!("cannot multiply {:?} with {:?}", register_1.kind, register_2.kind),
+                virtual_machine_data.registers,
+                virtual_machine_data.memory,
+                &format!(
+                    "cannot multiply {:?} with {:?}",
+                    register_1.kind, register_2.kind
+                ),
             );
         }
     }
@@ -543,6 +854,9 @@ pub fn div(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
             set_value_in_register(*registers, destination_register, result);
             return;
         }
+        (RegisterValueKind::Int64, RegisterValueKind::Int64) if register_2.value == 0 => {
+            emit_error_with_message(*registers, memory, "Division by zero");
+        }
         (RegisterValueKind::Int64, RegisterValueKind::Int64) => {
             let result = op_int_int(ArithmeticOp::Div, register_1, register_2);
             set_value_in_register(*registers, destination_register, result);
@@ -559,10 +873,23 @@ pub fn div(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
             return;
         }
         _ => {
+            if overloaded(
+                virtual_machine_data,
+                Overload::Div,
+                destination_register,
+                register_1,
+                register_2,
+            ) {
+                return;
+            }
+
             emit_error_with_message(
-                *registers,
-                *memory,
-                &format!("cannot divide {:?} with {:?}", register_1.kind, register_2.kind),
+                virtual_machine_data.registers,
+                virtual_machine_data.memory,
+                &format!(
+                    "cannot divide {:?} with {:?}",
+                    register_1.kind, register_2.kind
+                ),
             );
         }
     }
@@ -571,7 +898,6 @@ pub fn div(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
 #[inline(always)]
 pub fn pow(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
     let registers = &mut virtual_machine_data.registers;
-    let memory = &mut virtual_machine_data.memory;
 
     let destination_register = instruction_decoder::decode_destination_register(instruction);
     let source_register_1 = instruction_decoder::decode_source_register_1(instruction);
@@ -603,10 +929,23 @@ pub fn pow(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
             return;
         }
         _ => {
+            if overloaded(
+                virtual_machine_data,
+                Overload::Pow,
+                destination_register,
+                register_1,
+                register_2,
+            ) {
+                return;
+            }
+
             emit_error_with_message(
-                *registers,
-                *memory,
-                &format!("cannot find power of {:?} to {:?}", register_1.kind, register_2.kind),
+                virtual_machine_data.registers,
+                virtual_machine_data.memory,
+                &format!(
+                    "cannot find power of {:?} to {:?}",
+                    register_1.kind, register_2.kind
+                ),
             );
         }
     }
@@ -631,6 +970,9 @@ pub fn modulus(instruction: Instruction, virtual_machine_data: &mut VirtualMachi
             set_value_in_register(*registers, destination_register, result);
             return;
         }
+        (RegisterValueKind::Int64, RegisterValueKind::Int64) if register_2.value == 0 => {
+            emit_error_with_message(*registers, memory, "Modulo by zero");
+        }
         (RegisterValueKind::Int64, RegisterValueKind::Int64) => {
             let result = op_int_int(ArithmeticOp::Mod, register_1, register_2);
             set_value_in_register(*registers, destination_register, result);
@@ -647,29 +989,81 @@ pub fn modulus(instruction: Instruction, virtual_machine_data: &mut VirtualMachi
             return;
         }
         _ => {
+            if overloaded(
+                virtual_machine_data,
+                Overload::Mod,
+                destination_register,
+                register_1,
+                register_2,
+            ) {
+                return;
+            }
+
             emit_error_with_message(
-                *registers,
-                *memory,
-                &format!("cannot find modulus of {:?} to {:?}", register_1.kind, register_2.kind),
+                virtual_machine_data.registers,
+                virtual_machine_data.memory,
+                &format!(
+                    "cannot find modulus of {:?} to {:?}",
+                    register_1.kind, register_2.kind
+                ),
             );
         }
     }
 }
 
+/// store the result of the function a record overloads the operator with in the destination,
+/// or raise its error. false when neither operand overloads the operator
+#[inline(always)]
+fn overloaded(
+    virtual_machine_data: &mut VirtualMachineData,
+    overload: Overload,
+    destination: Instruction,
+    first: Register,
+    second: Register,
+) -> bool {
+    match call_overload(virtual_machine_data, overload, first, second) {
+        None => false,
+        Some(Ok(result)) => {
+            let register = nova_object_into_register(virtual_machine_data.memory, result);
+            set_value_in_register(virtual_machine_data.registers, destination, register);
+            true
+        }
+        Some(Err(error)) => {
+            emit_error_with_message(
+                virtual_machine_data.registers,
+                virtual_machine_data.memory,
+                &error,
+            );
+            true
+        }
+    }
+}
+
 #[inline(always)]
 /// compares if first register is less than second register
 pub fn less(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
-    let registers = &mut virtual_machine_data.registers;
-    let memory = &mut virtual_machine_data.memory;
-    let immutables = &mut virtual_machine_data.immutables;
-
     let source1 = instruction_decoder::decode_source_register_1(instruction);
     let source2 = instruction_decoder::decode_source_register_2(instruction);
 
     let destination = instruction_decoder::decode_destination_register(instruction);
 
-    let register1 = get_register(*registers, source1);
-    let register2 = get_register(*registers, source2);
+    let register1 = get_register(virtual_machine_data.registers, source1);
+    let register2 = get_register(virtual_machine_data.registers, source2);
+    if may_overload(register1, register2)
+        && overloaded(
+            virtual_machine_data,
+            Overload::Less,
+            destination,
+            register1,
+            register2,
+        )
+    {
+        return;
+    }
+
+    let registers = &mut virtual_machine_data.registers;
+    let memory = &mut virtual_machine_data.memory;
+    let immutables = &mut virtual_machine_data.immutables;
 
     let less = compare_registers(
         *registers,
@@ -694,17 +1088,28 @@ pub fn less(instruction: Instruction, virtual_machine_data: &mut VirtualMachineD
 #[inline(always)]
 /// compares if first register is less than or equal to second register
 pub fn less_or_equal(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
-    let registers = &mut virtual_machine_data.registers;
-    let memory = &mut virtual_machine_data.memory;
-    let immutables = &mut virtual_machine_data.immutables;
-
     let source1 = instruction_decoder::decode_source_register_1(instruction);
     let source2 = instruction_decoder::decode_source_register_2(instruction);
 
     let destination = instruction_decoder::decode_destination_register(instruction);
 
-    let register1 = get_register(*registers, source1);
-    let register2 = get_register(*registers, source2);
+    let register1 = get_register(virtual_machine_data.registers, source1);
+    let register2 = get_register(virtual_machine_data.registers, source2);
+    if may_overload(register1, register2)
+        && overloaded(
+            virtual_machine_data,
+            Overload::LessEqual,
+            destination,
+            register1,
+            register2,
+        )
+    {
+        return;
+    }
+
+    let registers = &mut virtual_machine_data.registers;
+    let memory = &mut virtual_machine_data.memory;
+    let immutables = &mut virtual_machine_data.immutables;
 
     let less = compare_registers(
         *registers,
@@ -729,17 +1134,28 @@ pub fn less_or_equal(instruction: Instruction, virtual_machine_data: &mut Virtua
 #[inline(always)]
 /// compares if first register is less than or equal to second register
 pub fn equal(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
-    let registers = &mut virtual_machine_data.registers;
-    let memory = &mut virtual_machine_data.memory;
-    let immutables = &mut virtual_machine_data.immutables;
-
     let source1 = instruction_decoder::decode_source_register_1(instruction);
     let source2 = instruction_decoder::decode_source_register_2(instruction);
 
     let destination = instruction_decoder::decode_destination_register(instruction);
 
-    let register1 = get_register(*registers, source1);
-    let register2 = get_register(*registers, source2);
+    let register1 = get_register(virtual_machine_data.registers, source1);
+    let register2 = get_register(virtual_machine_data.registers, source2);
+    if may_overload(register1, register2)
+        && overloaded(
+            virtual_machine_data,
+            Overload::Equal,
+            destination,
+            register1,
+            register2,
+        )
+    {
+        return;
+    }
+
+    let registers = &mut virtual_machine_data.registers;
+    let memory = &mut virtual_machine_data.memory;
+    let immutables = &mut virtual_machine_data.immutables;
 
     let equal = compare_registers(
         *registers,
@@ -766,10 +1182,15 @@ pub fn not(instruction: Instruction, virtual_machine_data: &mut VirtualMachineDa
     let registers = &mut virtual_machine_data.registers;
 
     let source = instruction_decoder::decode_source_register_1(instruction);
-    let mut register = get_register(*registers, source);
+    let register = get_register(*registers, source);
 
-    let is_true = is_truthy(register);
-    register.value = if is_true { 0 } else { 1 };
+    // the result is always a bool, whatever kind of value was negated
+    let is_true = is_truthy(
+        register,
+        virtual_machine_data.memory,
+        virtual_machine_data.immutables,
+    );
+    let register = Register::new(RegisterValueKind::Bool, !is_true as u64);
 
     set_value_in_register(*registers, source, register);
 }
@@ -782,7 +1203,11 @@ pub fn jump_if_false(instruction: Instruction, virtual_machine_data: &mut Virtua
     let source = instruction_decoder::decode_source_register_1(instruction);
 
     let register = get_register(*registers, source);
-    let truthy = is_truthy(register);
+    let truthy = is_truthy(
+        register,
+        virtual_machine_data.memory,
+        virtual_machine_data.immutables,
+    );
 
     let jump_instruction = get_next_instruction(*registers, instructions);
 
@@ -791,6 +1216,23 @@ pub fn jump_if_false(instruction: Instruction, virtual_machine_data: &mut Virtua
     }
 }
 
+#[inline(always)]
+pub fn jump_if_none(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
+    let registers = &mut virtual_machine_data.registers;
+    let instructions = &virtual_machine_data.instructions;
+
+    let source = instruction_decoder::decode_source_register_1(instruction);
+
+    let register = get_register(*registers, source);
+    let is_none = matches!(register.kind, RegisterValueKind::None);
+
+    let jump_instruction = get_next_instruction(*registers, instructions);
+
+    if is_none {
+        jump(jump_instruction, virtual_machine_data);
+    }
+}
+
 #[inline(always)]
 pub fn jump(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
     let registers = &mut virtual_machine_data.registers;
@@ -818,14 +1260,12 @@ pub fn load_constant_to_register(
 
     let immutable = &immutables[immutable_address as usize];
 
-    let kind = match immutable {
-        NovaObject::String(_) => RegisterValueKind::StrImm,
-        _ => RegisterValueKind::ImmAddress
-    };
-
-    let register = Register {
-        kind,
-        value: immutable_address as u64,
+    // numbers are loaded by value so arithmetic never has to look them up
+    let register = match immutable {
+        NovaObject::Int64(value) => Register::new(RegisterValueKind::Int64, *value as u64),
+        NovaObject::Float64(value) => Register::new(RegisterValueKind::Float64, value.to_bits()),
+        NovaObject::String(_) => Register::new(RegisterValueKind::StrImm, immutable_address as u64),
+        _ => Register::new(RegisterValueKind::ImmAddress, immutable_address as u64),
     };
 
     set_value_in_register(*registers, destination_register, register);
@@ -916,8 +1356,9 @@ pub fn load_bool_to_register(
     let registers = &mut virtual_machine_data.registers;
 
     let destination = instruction_decoder::decode_destination_register(instruction);
-    let boolean = instruction_decoder::decode_immutable_address_small(instruction);
-    let register = Register::new(RegisterValueKind::Float64, boolean as u64);
+    // any non zero operand is true, the same way the optimizer folds it
+    let boolean = instruction_decoder::decode_immutable_address_small(instruction) != 0;
+    let register = Register::new(RegisterValueKind::Bool, boolean as u64);
     set_value_in_register(*registers, destination, register);
 }
 
@@ -930,13 +1371,25 @@ pub fn define_global_indirect(
     let immutables = &mut virtual_machine_data.immutables;
     let identifiers = &mut virtual_machine_data.identifiers;
     let globals = &mut virtual_machine_data.globals;
+    let frozen_globals = &virtual_machine_data.frozen_globals;
 
     let index = instruction_decoder::decode_immutable_address_small(instruction);
     let immutable = immutables[index as usize].clone();
 
     if let NovaObject::String(name) = immutable {
-        let global_location = allocate_global(*globals);
-        create_global(*identifiers, name.to_string(), global_location);
+        let frozen = identifiers
+            .get(name.as_str())
+            .is_some_and(|&address| is_frozen(frozen_globals, address));
+        if frozen {
+            emit_error_with_message(
+                virtual_machine_data.registers,
+                virtual_machine_data.memory,
+                &format!("Cannot redefine frozen global: {}", name),
+            );
+            return;
+        }
+
+        define_global(*identifiers, *globals, &name);
     }
 }
 
@@ -952,25 +1405,36 @@ pub fn store_global_indirect(
     let globals = &mut virtual_machine_data.globals;
     let mem_cache = &mut virtual_machine_data.mem_cache;
     let memory = &mut virtual_machine_data.memory;
+    let frozen_globals = &virtual_machine_data.frozen_globals;
 
     let source = instruction_decoder::decode_source_register_1(instruction);
     let index = instruction_decoder::decode_immutable_address_small(instruction);
 
     let register = get_register(*registers, source);
 
-    if let Some(&address) = mem_cache.get_cache(&(index as usize)) {
+    if let Some(address) = mem_cache.get_cache(&(index as usize)) {
+        if is_frozen(frozen_globals, address as Instruction) {
+            emit_frozen_global_error(*registers, memory, &immutables[index as usize]);
+            return;
+        }
+
         set_global_value(*globals, address as u32, register);
         clear_register(*registers, source);
         return;
     }
 
-    let immutable = unsafe { immutables.get_unchecked(index as usize) };
+    let immutable = &immutables[index as usize];
 
     if let NovaObject::String(name) = immutable {
         let global_address = identifiers.get(name.as_str());
 
         if let Some(&address) = global_address {
             mem_cache.add_cache(index as usize, address as usize);
+            if is_frozen(frozen_globals, address) {
+                emit_frozen_global_error(*registers, memory, immutable);
+                return;
+            }
+
             set_global_value(*globals, address, register);
 
             return;
@@ -993,6 +1457,19 @@ pub fn store_global_indirect(
     clear_register(*registers, source)
 }
 
+#[cold]
+fn emit_frozen_global_error(
+    registers: &mut [Register],
+    memory: &mut Vec<NovaObject>,
+    name: &NovaObject,
+) {
+    emit_error_with_message(
+        registers,
+        memory,
+        &format!("Cannot assign to frozen global: {}", name),
+    );
+}
+
 /// load a value from a global location into a register by first looking up its name in the immutables array
 #[inline(always)]
 pub fn load_global_indirect(
@@ -1009,12 +1486,12 @@ pub fn load_global_indirect(
     let destination = instruction_decoder::decode_destination_register(instruction);
     let index = instruction_decoder::decode_immutable_address_small(instruction);
 
-    if let Some(&address) = mem_cache.get_cache(&(index as usize)) {
+    if let Some(address) = mem_cache.get_cache(&(index as usize)) {
         load_global_value(*registers, *globals, destination, address as u32);
         return;
     }
 
-    let immutable = unsafe { immutables.get_unchecked(index as usize) };
+    let immutable = &immutables[index as usize];
 
     if let NovaObject::String(name) = immutable {
         let global_address = identifiers.get(name.as_str());
@@ -1046,16 +1523,33 @@ pub fn allocate_locals(instruction: Instruction, virtual_machine_data: &mut Virt
     let locals = &mut virtual_machine_data.locals;
     // number of variables
     let number = instruction_decoder::decode_immutable_address_small(instruction);
+    if !locals.has_room(number as usize) {
+        stack_overflow(virtual_machine_data);
+        return;
+    }
+
     allocate_local_variables(*locals, number);
 }
 
+/// raised when a call or a block needs more frames or locals than a `minimal` build keeps
+fn stack_overflow(virtual_machine_data: &mut VirtualMachineData) {
+    emit_error_with_message(
+        virtual_machine_data.registers,
+        virtual_machine_data.memory,
+        &format!(
+            "Stack overflow, at most {} frames and {} locals fit",
+            MAX_FRAMES, MAX_LOCALS
+        ),
+    );
+}
+
 #[inline(always)]
 pub fn deallocate_locals(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
     let locals = &mut virtual_machine_data.locals;
     // number of variables
     let number = instruction_decoder::decode_immutable_address_small(instruction) as usize;
 
-    locals.drain(locals.len() - number..);
+    locals.truncate(locals.len() - number);
 }
 
 #[inline(always)]
@@ -1067,15 +1561,211 @@ pub fn store_local(instruction: Instruction, virtual_machine_data: &mut VirtualM
     let address = instruction_decoder::decode_immutable_address_small(instruction);
 
     let register = get_register(*registers, source);
-    let local_offset = unsafe { registers.get_unchecked(RegisterID::RLO as usize).value };
-    unsafe {
-        let local = locals.get_unchecked_mut((address + local_offset as u32) as usize);
+    let local_offset = registers[RegisterID::RLO as usize].value;
+    let local = unchecked::get_mut(locals, (address + local_offset as u32) as usize);
+    local.value = register.value;
+    local.kind = register.kind;
+
+    clear_register(*registers, source);
+}
+
+/// add the immediate to a local slot in place, or subtract it when `decrement` is set. the
+/// slot changes the way it would with `add` or `sub` and a register holding the amount
+#[inline(always)]
+pub fn step_local(
+    instruction: Instruction,
+    virtual_machine_data: &mut VirtualMachineData,
+    decrement: bool,
+) {
+    let registers = &mut virtual_machine_data.registers;
+    let locals = &mut virtual_machine_data.locals;
+    let memory = &mut virtual_machine_data.memory;
+
+    let address = instruction_decoder::decode_immutable_address_small(instruction);
+    let amount = instruction_decoder::decode_immediate_byte(instruction);
+    let amount = Register::new(RegisterValueKind::Int64, amount as u64);
+    let op = if decrement {
+        ArithmeticOp::Sub
+    } else {
+        ArithmeticOp::Add
+    };
+
+    let local_offset = get_register(*registers, RegisterID::RLO as u32).value;
+    let slot = (address as u64 + local_offset) as usize;
+    let local = *unchecked::get(locals, slot);
+
+    let result = match local.kind {
+        RegisterValueKind::Int64 => op_int_int(op, local, amount),
+        RegisterValueKind::Float64 => op_float_int(op, local, amount),
+
+        RegisterValueKind::StrImm | RegisterValueKind::StrMem
+            if !decrement && virtual_machine_data.coercion == Coercion::Coercive =>
+        {
+            let string = match local.kind {
+                RegisterValueKind::StrImm => &virtual_machine_data.immutables[local.value as usize],
+                _ => unchecked::get(memory, local.value as usize),
+            };
+            let string = add_str_num(string, amount);
+            if let Err(error) = virtual_machine_data.limits.check(&string) {
+                emit_error_with_message(*registers, memory, &error);
+                return;
+            }
+
+            let address = store_object_in_memory(memory, string) as u64;
+            Register::new(RegisterValueKind::StrMem, address)
+        }
+
+        RegisterValueKind::StrImm | RegisterValueKind::StrMem if !decrement => {
+            emit_error_with_message(
+                *registers,
+                memory,
+                "cannot add a string and a number in strict mode, convert the number with format()",
+            );
+            return;
+        }
+
+        kind => {
+            let overload = if decrement {
+                Overload::Sub
+            } else {
+                Overload::Add
+            };
+            match call_overload(virtual_machine_data, overload, local, amount) {
+                Some(Ok(result)) => nova_object_into_register(virtual_machine_data.memory, result),
+                Some(Err(error)) => {
+                    emit_error_with_message(
+                        virtual_machine_data.registers,
+                        virtual_machine_data.memory,
+                        &error,
+                    );
+                    return;
+                }
+                None => {
+                    let verb = if decrement { "subtract" } else { "add" };
+                    emit_error_with_message(
+                        virtual_machine_data.registers,
+                        virtual_machine_data.memory,
+                        &format!("cannot {} {:?} to {:?}", verb, kind, amount.kind),
+                    );
+                    return;
+                }
+            }
+        }
+    };
+
+    *unchecked::get_mut(virtual_machine_data.locals, slot) = result;
+}
 
-        local.value = register.value;
-        local.kind = register.kind;
+/// the limit of a counting loop, in the operand word itself or in the local slot it names
+#[inline(always)]
+fn loop_limit(
+    flags: Instruction,
+    operand: Instruction,
+    locals: &[Register],
+    local_offset: u64,
+) -> Register {
+    if flags & FOR_LIMIT_IMMEDIATE != 0 {
+        return Register::new(RegisterValueKind::Int64, operand as i32 as i64 as u64);
     }
 
-    clear_register(*registers, source);
+    *unchecked::get(locals, (operand as u64 + local_offset) as usize)
+}
+
+/// whether a counting loop runs again, comparing like `less` and `less_equal` do
+#[inline(always)]
+fn loop_runs(flags: Instruction, index: Register, limit: Register) -> Result<bool, String> {
+    let ordering = match (index.kind, limit.kind) {
+        (RegisterValueKind::Int64, RegisterValueKind::Int64) => {
+            (index.value as i64).partial_cmp(&(limit.value as i64))
+        }
+        (RegisterValueKind::Int64, RegisterValueKind::Float64) => {
+            (index.value as i64 as f64).partial_cmp(&f64::from_bits(limit.value))
+        }
+        (RegisterValueKind::Float64, RegisterValueKind::Int64) => {
+            f64::from_bits(index.value).partial_cmp(&(limit.value as i64 as f64))
+        }
+        (RegisterValueKind::Float64, RegisterValueKind::Float64) => {
+            f64::from_bits(index.value).partial_cmp(&f64::from_bits(limit.value))
+        }
+        _ => {
+            return Err(format!(
+                "a counting loop needs numbers, found {:?} and {:?}",
+                index.kind, limit.kind
+            ))
+        }
+    };
+
+    let inclusive = flags & FOR_INCLUSIVE != 0;
+    Ok(match ordering {
+        Some(Ordering::Less) => flags & FOR_DESCENDING == 0,
+        Some(Ordering::Greater) => flags & FOR_DESCENDING != 0,
+        Some(Ordering::Equal) => inclusive,
+        // NaN never passes a comparison
+        None => false,
+    })
+}
+
+/// test a counting loop before it first runs, reading the limit from the word after the
+/// instruction and taking the jump after that when the loop does not run
+#[inline(always)]
+pub fn for_prep(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
+    let registers = &mut virtual_machine_data.registers;
+    let instructions = &virtual_machine_data.instructions;
+    let locals = &virtual_machine_data.locals;
+
+    let operand = get_next_instruction(*registers, instructions);
+    let jump_instruction = get_next_instruction(*registers, instructions);
+
+    let flags = instruction_decoder::decode_destination_register(instruction);
+    let address = instruction_decoder::decode_immutable_address_small(instruction);
+    let local_offset = get_register(*registers, RegisterID::RLO as u32).value;
+    let index = *unchecked::get(locals, (address as u64 + local_offset) as usize);
+    let limit = loop_limit(flags, operand, locals, local_offset);
+
+    match loop_runs(flags, index, limit) {
+        Ok(true) => {}
+        Ok(false) => jump(jump_instruction, virtual_machine_data),
+        Err(message) => emit_error_with_message(*registers, virtual_machine_data.memory, &message),
+    }
+}
+
+/// step the index of a counting loop and take the jump back to its body while it runs
+#[inline(always)]
+pub fn for_loop(instruction: Instruction, virtual_machine_data: &mut VirtualMachineData) {
+    let registers = &mut virtual_machine_data.registers;
+    let instructions = &virtual_machine_data.instructions;
+    let locals = &mut virtual_machine_data.locals;
+
+    let operand = get_next_instruction(*registers, instructions);
+    let jump_instruction = get_next_instruction(*registers, instructions);
+
+    let flags = instruction_decoder::decode_destination_register(instruction);
+    let step = instruction_decoder::decode_source_register_1(instruction);
+    let step = Register::new(RegisterValueKind::Int64, step as u64);
+    let op = if flags & FOR_DESCENDING != 0 {
+        ArithmeticOp::Sub
+    } else {
+        ArithmeticOp::Add
+    };
+
+    let address = instruction_decoder::decode_immutable_address_small(instruction);
+    let local_offset = get_register(*registers, RegisterID::RLO as u32).value;
+    let slot = (address as u64 + local_offset) as usize;
+    let index = *unchecked::get(locals, slot);
+
+    // the loop was tested by `ForPrep` and only it changes the index
+    let index = match index.kind {
+        RegisterValueKind::Int64 => op_int_int(op, index, step),
+        _ => op_float_int(op, index, step),
+    };
+    *unchecked::get_mut(locals, slot) = index;
+
+    let limit = loop_limit(flags, operand, locals, local_offset);
+    match loop_runs(flags, index, limit) {
+        Ok(true) => jump(jump_instruction, virtual_machine_data),
+        Ok(false) => {}
+        Err(message) => emit_error_with_message(*registers, virtual_machine_data.memory, &message),
+    }
 }
 
 #[inline(always)]
@@ -1086,10 +1776,8 @@ pub fn load_local(instruction: Instruction, virtual_machine_data: &mut VirtualMa
     let destination = instruction_decoder::decode_destination_register(instruction);
     let address = instruction_decoder::decode_immutable_address_small(instruction);
 
-    let register = unsafe {
-        let local_offset = get_register(*registers, RegisterID::RLO as u32).value;
-        *locals.get_unchecked((address as u64 + local_offset) as usize)
-    };
+    let local_offset = get_register(*registers, RegisterID::RLO as u32).value;
+    let register = *unchecked::get(locals, (address as u64 + local_offset) as usize);
 
     set_value_in_register(*registers, destination, register);
 }
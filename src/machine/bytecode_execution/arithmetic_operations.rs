@@ -1,3 +1,5 @@
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
 use crate::{object::RegisterValueKind, register::Register};
 
 pub enum ArithmeticOp {
@@ -47,27 +49,29 @@ pub fn op_int_int(op: ArithmeticOp, register_1: Register, register_2: Register)
     let value_1 = register_1.value as i64;
     let value_2 = register_2.value as i64;
 
+    // integers wrap around like release builds do, division by zero is caught before this
+
     let result = match op {
         ArithmeticOp::Add => {
-            value_1 + value_2
+            value_1.wrapping_add(value_2)
         }
 
         ArithmeticOp::Sub => {
-            value_1 - value_2
+            value_1.wrapping_sub(value_2)
         }
 
         ArithmeticOp::Mul => {
-            value_1 * value_2
+            value_1.wrapping_mul(value_2)
         }
 
         ArithmeticOp::Div => {
-            value_1 / value_2
+            value_1.wrapping_div(value_2)
         }
         ArithmeticOp::Pow => {
             ((value_1 as f64).powf(value_2 as f64)) as i64
         },
         ArithmeticOp::Mod => {
-            value_1 % value_2
+            value_1.wrapping_rem(value_2)
         },
     };
 
@@ -140,3 +144,61 @@ pub fn op_float_int(op: ArithmeticOp, register_1: Register, register_2: Register
     let result = result.to_bits();
     Register::new(RegisterValueKind::Float64, result)
 }
+
+/// the single operand math of the `Sqrt`, `Abs`, `Floor` and `Ceil` opcodes and the natives
+/// of the same name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathOp {
+    Sqrt,
+    Abs,
+    Floor,
+    Ceil,
+}
+
+impl MathOp {
+    pub fn name(self) -> &'static str {
+        match self {
+            MathOp::Sqrt => "sqrt",
+            MathOp::Abs => "abs",
+            MathOp::Floor => "floor",
+            MathOp::Ceil => "ceil",
+        }
+    }
+}
+
+/// None when the register is not a number. the square root is always a float, the rest keep
+/// integers as integers
+#[inline(always)]
+pub fn op_math(op: MathOp, register: Register) -> Option<Register> {
+    match register.kind {
+        RegisterValueKind::Int64 => {
+            let value = register.value as i64;
+
+            let result = match op {
+                MathOp::Sqrt => {
+                    let result = (value as f64).sqrt().to_bits();
+                    return Some(Register::new(RegisterValueKind::Float64, result));
+                }
+                MathOp::Abs => value.wrapping_abs(),
+                MathOp::Floor | MathOp::Ceil => value,
+            };
+
+            Some(Register::new(RegisterValueKind::Int64, result as u64))
+        }
+
+        RegisterValueKind::Float64 => {
+            let value = f64::from_bits(register.value);
+
+            let result = match op {
+                MathOp::Sqrt => value.sqrt(),
+                MathOp::Abs => value.abs(),
+                MathOp::Floor => value.floor(),
+                MathOp::Ceil => value.ceil(),
+            };
+
+            Some(Register::new(RegisterValueKind::Float64, result.to_bits()))
+        }
+
+        _ => None,
+    }
+}
@@ -0,0 +1,109 @@
+//! operators on records. a record is a map, and one holding a function under `__add`, `__sub`,
+//! `__mul`, `__div`, `__mod`, `__pow`, `__eq`, `__lt` or `__le` overloads that operator: when
+//! an operand of the operator is such a record, the function is called with both operands and
+//! its result is the result of the operator. the left operand's function is used before the
+//! right one's. numbers, strings and maps without the function are never affected
+
+use crate::{
+    machine::{self, register_management::nova_object_into_register, VirtualMachineData},
+    object::{NativeResult, NovaObject, RegisterValueKind},
+    register::Register,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overload {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Equal,
+    Less,
+    LessEqual,
+}
+
+impl Overload {
+    fn is_comparison(self) -> bool {
+        matches!(self, Overload::Equal | Overload::Less | Overload::LessEqual)
+    }
+
+    /// the key of the function overloading the operator
+    pub fn method(self) -> &'static str {
+        match self {
+            Overload::Add => "__add",
+            Overload::Sub => "__sub",
+            Overload::Mul => "__mul",
+            Overload::Div => "__div",
+            Overload::Mod => "__mod",
+            Overload::Pow => "__pow",
+            Overload::Equal => "__eq",
+            Overload::Less => "__lt",
+            Overload::LessEqual => "__le",
+        }
+    }
+}
+
+/// the function of the first operand that is a record overloading the operator
+fn find_method(
+    memory: &[NovaObject],
+    overload: Overload,
+    operands: [Register; 2],
+) -> Option<NovaObject> {
+    operands
+        .into_iter()
+        .filter(|operand| operand.kind == RegisterValueKind::MemAddress)
+        .find_map(|operand| match memory.get(operand.value as usize) {
+            Some(NovaObject::Map(map)) => match map.get(overload.method()) {
+                Some(method @ (NovaObject::NovaFunction(_) | NovaObject::NativeFunction(_))) => {
+                    Some(method.clone())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+/// whether an operand could be a record, only then can the operator be overloaded. the
+/// comparisons check this first so numbers never look for a function
+#[inline(always)]
+pub fn may_overload(first: Register, second: Register) -> bool {
+    first.kind == RegisterValueKind::MemAddress || second.kind == RegisterValueKind::MemAddress
+}
+
+/// call the function overloading the operator when an operand is a record defining it,
+/// None when neither does. comparisons give a bool of whether the result is truthy.
+/// `a <= b` falls back on `not (b < a)` for records defining only `__lt`, so that one
+/// function orders them for all four comparisons
+#[inline(always)]
+pub fn call_overload(
+    virtual_machine_data: &mut VirtualMachineData,
+    overload: Overload,
+    first: Register,
+    second: Register,
+) -> Option<NativeResult> {
+    if !may_overload(first, second) {
+        return None;
+    }
+
+    let memory = &*virtual_machine_data.memory;
+    let (method, arguments, negate) = match find_method(memory, overload, [first, second]) {
+        Some(method) => (method, [first, second], false),
+        None if overload == Overload::LessEqual => (
+            find_method(memory, Overload::Less, [first, second])?,
+            [second, first],
+            true,
+        ),
+        None => return None,
+    };
+
+    let callee = nova_object_into_register(virtual_machine_data.memory, method);
+    virtual_machine_data.native_depth += 1;
+    let result = machine::call_function_with_registers(virtual_machine_data, callee, &arguments);
+    virtual_machine_data.native_depth -= 1;
+
+    Some(result.map(|result| match overload.is_comparison() {
+        true => NovaObject::Bool(result.is_truthy() != negate),
+        false => result,
+    }))
+}
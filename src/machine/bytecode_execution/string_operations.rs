@@ -1,8 +1,36 @@
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+
 use crate::{
+    instruction::Instruction,
+    limits::ResourceLimits,
+    machine::{
+        memory_management::store_object_in_memory, program_management::emit_error_with_message,
+        register_management::set_value_in_register,
+    },
     object::{NovaObject, RegisterValueKind},
     register::Register,
 };
 
+/// store a string made by an instruction and point the destination register at it, or raise
+/// an error when it is longer than the limit
+#[inline(always)]
+pub fn store_string(
+    registers: &mut [Register],
+    memory: &mut Vec<NovaObject>,
+    limits: ResourceLimits,
+    destination_register: Instruction,
+    string: NovaObject,
+) {
+    if let Err(error) = limits.check(&string) {
+        emit_error_with_message(registers, memory, &error);
+        return;
+    }
+
+    let address = store_object_in_memory(memory, string) as u64;
+    let register = Register::new(RegisterValueKind::StrMem, address);
+    set_value_in_register(registers, destination_register, register);
+}
+
 #[inline(always)]
 pub fn add_str_num(string1: &NovaObject, number: Register) -> NovaObject {
     let mut string1 = string1.to_string();
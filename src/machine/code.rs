@@ -0,0 +1,48 @@
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+#[cfg(feature = "mmap")]
+use crate::file::MappedCode;
+use crate::instruction::Instruction;
+
+/// the instructions of the virtual machine, its own or those of a mapped program file. the
+/// mapped ones are copied the first time another program is appended after them
+pub(crate) enum Code {
+    Owned(Vec<Instruction>),
+    #[cfg(feature = "mmap")]
+    Mapped(MappedCode),
+}
+
+impl Default for Code {
+    fn default() -> Self {
+        Code::Owned(Vec::new())
+    }
+}
+
+impl Deref for Code {
+    type Target = [Instruction];
+
+    fn deref(&self) -> &[Instruction] {
+        match self {
+            Code::Owned(instructions) => instructions,
+            #[cfg(feature = "mmap")]
+            Code::Mapped(code) => code.instructions(),
+        }
+    }
+}
+
+impl Code {
+    /// the instructions to append to, copied out of the mapping first
+    pub fn to_mut(&mut self) -> &mut Vec<Instruction> {
+        #[cfg(feature = "mmap")]
+        if let Code::Mapped(code) = self {
+            *self = Code::Owned(code.instructions().to_vec());
+        }
+
+        match self {
+            Code::Owned(instructions) => instructions,
+            #[cfg(feature = "mmap")]
+            Code::Mapped(_) => unreachable!("the mapped instructions were copied"),
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{object::NovaObject, register::{Register, RegisterID}};
+use alloc::vec::Vec;
+
+use crate::{frame::Frame, object::{NovaObject, RegisterValueKind}, register::{Register, RegisterID}};
 
 use super::VirtualMachineData;
 
@@ -6,12 +8,30 @@ use super::VirtualMachineData;
 /// and marks those objects as live
 #[inline(always)]
 pub fn mark_all_live_objects(vm_data: &mut VirtualMachineData) -> Vec<usize> {
-    let mut live_object_set = Vec::with_capacity(vm_data.memory.len());
+    live_objects(vm_data.registers, vm_data.frames, vm_data.locals, vm_data.globals, vm_data.memory.len())
+}
+
+/// memory locations referenced from the registers and variables, the objects a collection keeps.
+/// the registers saved by every frame are roots until the frame is dropped, and the return
+/// register keeps the returned value alive until the next return overwrites it, so an object
+/// created by a function survives its frame being dropped
+#[inline(always)]
+pub fn live_objects(
+    registers: &[Register; RegisterID::RMax as usize + 1],
+    frames: &[Frame],
+    locals: &[Register],
+    globals: &[Register],
+    memory_size: usize,
+) -> Vec<usize> {
+    let mut live_object_set = Vec::with_capacity(memory_size);
+
+    mark_live_objects_from_registers(registers, &mut live_object_set);
+    for frame in frames {
+        mark_live_objects_from_variables(&frame.registers, &mut live_object_set);
+    }
+    mark_live_objects_from_variables(locals, &mut live_object_set);
+    mark_live_objects_from_variables(globals, &mut live_object_set);
 
-    mark_live_objects_from_registers(&vm_data.registers, &mut live_object_set);
-    mark_live_objects_from_variables(&vm_data.locals, &mut live_object_set);
-    mark_live_objects_from_variables(&vm_data.globals, &mut live_object_set);
-    
     live_object_set
 }
 
@@ -37,10 +57,15 @@ pub fn clean_all_dead_objects(memory: &mut [NovaObject], live_objects: &Vec<usiz
 #[inline(always)]
 fn mark_live_objects_from_registers(registers: &[Register; RegisterID::RMax as usize + 1], live_object_set: &mut Vec<usize>) {
     registers[0..=RegisterID::R15 as usize].iter().for_each(|register| {
-        if register.kind.is_mem_address() {
+        if references_memory(register) {
             live_object_set.push(register.value as usize);
         }
-    })
+    });
+
+    let return_register = &registers[RegisterID::RRTN as usize];
+    if references_memory(return_register) {
+        live_object_set.push(return_register.value as usize);
+    }
 }
 
 /// retrieve of memory locations referenced in the registers 
@@ -48,9 +73,19 @@ fn mark_live_objects_from_registers(registers: &[Register; RegisterID::RMax as u
 #[inline(always)]
 fn mark_live_objects_from_variables(variables: &[Register], live_object_set: &mut Vec<usize>) {
     variables.iter().for_each(|register| {
-        if register.kind.is_mem_address() {
+        if references_memory(register) {
             live_object_set.push(register.value as usize);
         }
     })
 }
 
+/// whether the register holds the address of an object in memory, strings included
+#[inline(always)]
+fn references_memory(register: &Register) -> bool {
+    matches!(
+        register.kind,
+        RegisterValueKind::MemAddress
+            | RegisterValueKind::StrMem
+            | RegisterValueKind::NativeFunctionID(_)
+    )
+}
@@ -0,0 +1,236 @@
+use alloc::collections::BTreeMap;
+#[cfg(feature = "heap_sites")]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Display;
+
+use crate::object::NovaObject;
+
+/// how many heap objects of one kind there are and how many of them are still referenced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapKindStats {
+    pub count: usize,
+    pub live: usize,
+}
+
+/// the heap summarized per object kind, returned by `VirtualMachine::heap_stats`.
+/// freed slots hold `NovaObject::None` so they are counted under "None"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    pub kinds: BTreeMap<&'static str, HeapKindStats>,
+}
+
+impl HeapStats {
+    /// summarize the `(address, object, live)` entries yielded by `VirtualMachine::heap_iter`
+    pub fn from_heap<'a>(heap: impl Iterator<Item = (usize, &'a NovaObject, bool)>) -> Self {
+        let mut kinds: BTreeMap<&'static str, HeapKindStats> = BTreeMap::new();
+
+        for (_, object, live) in heap {
+            let stats = kinds.entry(object.kind_name()).or_default();
+            stats.count += 1;
+            if live {
+                stats.live += 1;
+            }
+        }
+
+        Self { kinds }
+    }
+
+    /// the stats of every kind added together
+    pub fn total(&self) -> HeapKindStats {
+        self.kinds
+            .values()
+            .fold(HeapKindStats::default(), |total, stats| HeapKindStats {
+                count: total.count + stats.count,
+                live: total.live + stats.live,
+            })
+    }
+}
+
+impl Display for HeapStats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "{:<16}{:>8}{:>8}{:>8}", "kind", "count", "live", "dead")?;
+
+        for (kind, stats) in &self.kinds {
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>8}{:>8}",
+                kind,
+                stats.count,
+                stats.live,
+                stats.count - stats.live
+            )?;
+        }
+
+        let total = self.total();
+        write!(
+            f,
+            "{:<16}{:>8}{:>8}{:>8}",
+            "total",
+            total.count,
+            total.live,
+            total.count - total.live
+        )
+    }
+}
+
+/// when and by which instruction a heap slot was filled, kept with the `heap_sites` feature
+#[cfg(feature = "heap_sites")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+    /// index of the allocating instruction, None for objects stored outside a run, like the
+    /// native functions
+    pub site: Option<usize>,
+    /// instructions the virtual machine had executed when the object was allocated
+    pub time: u64,
+}
+
+/// the allocation of every heap slot. the dispatch loop tells it which instruction runs and
+/// tags the slots the heap grew by afterwards, so nothing allocating has to know about it
+#[cfg(feature = "heap_sites")]
+#[derive(Debug, Default)]
+pub(crate) struct AllocationSites {
+    allocations: Vec<Allocation>,
+    site: Option<usize>,
+    clock: u64,
+}
+
+#[cfg(feature = "heap_sites")]
+impl AllocationSites {
+    /// the instruction at `site` is about to run, `None` outside of a run
+    #[inline(always)]
+    pub fn enter(&mut self, site: Option<usize>) {
+        self.site = site;
+        if site.is_some() {
+            self.clock += 1;
+        }
+    }
+
+    /// the instruction currently running
+    pub fn site(&self) -> Option<usize> {
+        self.site
+    }
+
+    /// give the slots past the ones already tagged to the current instruction
+    #[inline(always)]
+    pub fn tag(&mut self, heap_size: usize) {
+        if heap_size > self.allocations.len() {
+            let allocation = Allocation {
+                site: self.site,
+                time: self.clock,
+            };
+            self.allocations.resize(heap_size, allocation);
+        }
+    }
+
+    pub fn get(&self, address: usize) -> Option<Allocation> {
+        self.allocations.get(address).copied()
+    }
+
+    /// instructions executed so far
+    pub fn now(&self) -> u64 {
+        self.clock
+    }
+}
+
+/// the live objects allocated by one instruction
+#[cfg(feature = "heap_sites")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteStats {
+    /// index of the allocating instruction, None for objects stored outside a run
+    pub site: Option<usize>,
+    /// `file:line` of the instruction, when the program has line definitions
+    pub location: Option<String>,
+    pub live: usize,
+    /// live objects of the site per kind
+    pub kinds: BTreeMap<&'static str, usize>,
+    /// age of the oldest live object in executed instructions
+    pub oldest: u64,
+}
+
+/// the live heap grouped by allocation site, returned by `VirtualMachine::heap_report`. the
+/// sites holding the most live objects come first, so a growing heap points at its source
+#[cfg(feature = "heap_sites")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapReport {
+    pub sites: Vec<SiteStats>,
+}
+
+#[cfg(feature = "heap_sites")]
+impl HeapReport {
+    /// group the live `(address, object, live)` entries of `VirtualMachine::heap_iter` by the
+    /// instruction that allocated them, `location` naming the source of an instruction
+    pub(crate) fn from_heap<'a>(
+        heap: impl Iterator<Item = (usize, &'a NovaObject, bool)>,
+        allocations: &AllocationSites,
+        location: impl Fn(usize) -> Option<String>,
+    ) -> Self {
+        let mut sites: BTreeMap<Option<usize>, SiteStats> = BTreeMap::new();
+
+        for (address, object, live) in heap {
+            if !live {
+                continue;
+            }
+
+            let allocation = allocations.get(address).unwrap_or(Allocation {
+                site: None,
+                time: allocations.now(),
+            });
+            let stats = sites.entry(allocation.site).or_insert_with(|| SiteStats {
+                site: allocation.site,
+                location: allocation.site.and_then(&location),
+                live: 0,
+                kinds: BTreeMap::new(),
+                oldest: 0,
+            });
+
+            stats.live += 1;
+            *stats.kinds.entry(object.kind_name()).or_default() += 1;
+            stats.oldest = stats.oldest.max(allocations.now() - allocation.time);
+        }
+
+        let mut sites: Vec<SiteStats> = sites.into_values().collect();
+        sites.sort_by(|a, b| b.live.cmp(&a.live).then(a.site.cmp(&b.site)));
+
+        Self { sites }
+    }
+}
+
+#[cfg(feature = "heap_sites")]
+impl Display for HeapReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:<10}{:>8}{:>12}  {:<24}kinds",
+            "site", "live", "oldest", "location"
+        )?;
+
+        for stats in &self.sites {
+            let site = match stats.site {
+                Some(site) => site.to_string(),
+                None => "host".to_string(),
+            };
+            let kinds = stats
+                .kinds
+                .iter()
+                .map(|(kind, count)| format!("{} {}", count, kind))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            write!(
+                f,
+                "\n{:<10}{:>8}{:>12}  {:<24}{}",
+                site,
+                stats.live,
+                stats.oldest,
+                stats.location.as_deref().unwrap_or("-"),
+                kinds
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,17 +1,21 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
 use crate::{
+    cache::MemoryCache,
     instruction::Instruction,
-    object::{MappedMemory, NovaObject},
+    object::{FrozenGlobals, MappedMemory, NovaObject},
     register::Register,
 };
 
+use super::{stack::Locals, unchecked};
+
 /// load an object from memory given the memory location
 #[inline(always)]
 pub fn load_object_from_memory(memory: &[NovaObject], address: u64) -> &NovaObject {
-    unsafe {
-        let object = memory.get_unchecked(address as usize);
-        return object;
-    }
-    // &self.memory[address as usize]
+    unchecked::get(memory, address as usize)
 }
 
 /// store a NovaObject in the memory and return its allocated address
@@ -35,14 +39,64 @@ pub fn create_global(identifiers: &mut MappedMemory, name: String, global_locati
     identifiers.insert(name, global_location);
 }
 
+/// make the global bound to the name read only, false if there is no global with the name
+pub fn freeze_global(identifiers: &MappedMemory, frozen: &mut FrozenGlobals, name: &str) -> bool {
+    match identifiers.get(name) {
+        Some(&global_location) => {
+            frozen.insert(global_location);
+            true
+        }
+        None => false,
+    }
+}
+
+/// whether the global location was frozen, checked before every write to a global
+#[inline(always)]
+pub fn is_frozen(frozen: &FrozenGlobals, global_location: Instruction) -> bool {
+    !frozen.is_empty() && frozen.contains(&global_location)
+}
+
+/// the global location bound to the name, reset to None when the name is already defined
+/// so redefining a global reuses its location
+#[inline(always)]
+pub fn define_global(
+    identifiers: &mut MappedMemory,
+    globals: &mut Vec<Register>,
+    name: &str,
+) -> Instruction {
+    if let Some(&global_location) = identifiers.get(name) {
+        set_global_value(globals, global_location, Register::empty());
+        return global_location;
+    }
+
+    let global_location = allocate_global(globals);
+    create_global(identifiers, name.to_string(), global_location);
+    global_location
+}
+
+/// unbind the name from its global location, false if it was not defined.
+/// the location is emptied and forgotten by the cache, but stays allocated
+pub fn undefine_global(
+    identifiers: &mut MappedMemory,
+    globals: &mut [Register],
+    mem_cache: &mut MemoryCache,
+    name: &str,
+) -> bool {
+    let Some(global_location) = identifiers.remove(name) else {
+        return false;
+    };
+
+    set_global_value(globals, global_location, Register::empty());
+    mem_cache.invalidate_address(global_location as usize);
+    true
+}
+
 /// set value of a specified global location
 #[inline(always)]
 pub fn set_global_value(globals: &mut [Register], address: Instruction, new_value: Register) {
-    unsafe {
-        let global = globals.get_unchecked_mut(address as usize);
-        global.kind = new_value.kind;
-        global.value = new_value.value;
-    }
+    let global = unchecked::get_mut(globals, address as usize);
+    global.kind = new_value.kind;
+    global.value = new_value.value;
 }
 
 /// load value from a specified global address
@@ -53,23 +107,13 @@ pub fn load_global_value(
     destination: Instruction,
     global_address: Instruction,
 ) {
-    let value = unsafe { *globals.get_unchecked(global_address as usize) };
-    unsafe {
-        let register = registers.get_unchecked_mut(destination as usize);
-
-        register.kind = value.kind;
-        register.value = value.value;
-    }
+    let value = *unchecked::get(globals, global_address as usize);
+    let register = unchecked::get_mut(registers, destination as usize);
+    register.kind = value.kind;
+    register.value = value.value;
 }
 
 #[inline(always)]
-pub fn allocate_local_variables(locals: &mut Vec<Register>, number_of_locals: Instruction) {
+pub fn allocate_local_variables(locals: &mut Locals, number_of_locals: Instruction) {
     locals.resize(locals.len() + number_of_locals as usize, Default::default())
 }
-
-#[inline(always)]
-pub fn deallocate_local_variables(locals: &mut Vec<Register>, number_of_locals: Instruction) {
-    let number = number_of_locals as usize;
-
-    locals.drain(locals.len() - number..);
-}
@@ -1,23 +1,23 @@
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+
 use crate::{
-    frame::Frame,
+    frame::{Frame, SAVED_REGISTERS},
     instruction::Instruction,
     object::{NovaObject, RegisterValueKind},
     register::{Register, RegisterID},
 };
 
 use super::{
-    memory_management::{
-        allocate_local_variables, deallocate_local_variables, store_object_in_memory,
-    },
+    memory_management::{allocate_local_variables, store_object_in_memory},
     register_management::{clear_registers, get_register, load_memory_address_to_register},
+    stack::{Frames, Locals},
+    unchecked,
 };
 
 #[inline(always)]
 pub fn peek_next_instruction(registers: &[Register], instructions: &[Instruction]) -> Instruction {
-    unsafe {
-        let instruction_pointer = registers.get_unchecked(RegisterID::RPC as usize).value as usize;
-        return *instructions.get_unchecked(instruction_pointer);
-    }
+    let instruction_pointer = registers[RegisterID::RPC as usize].value as usize;
+    *unchecked::get(instructions, instruction_pointer)
 }
 
 #[inline(always)]
@@ -26,26 +26,34 @@ pub fn get_next_instruction(
     instructions: &[Instruction],
 ) -> Instruction {
     let instruction = peek_next_instruction(registers, instructions);
-    unsafe {
-        registers.get_unchecked_mut(RegisterID::RPC as usize).value += 1;
-    }
+    registers[RegisterID::RPC as usize].value += 1;
 
     instruction
 }
 
+/// save the caller's state in a new frame and set up the locals of the function at `function_address`
 #[inline(always)]
 pub fn new_frame(
     registers: &mut [Register; RegisterID::RMax as usize + 1],
-    frames: &mut Vec<Frame>,
-    locals: &mut Vec<Register>,
+    frames: &mut Frames,
+    locals: &mut Locals,
     num_locals: Instruction,
+    function_address: u64,
 ) {
-    //let return_address = unsafe { registers.get_unchecked(RegisterID::RPC as usize).value };
-    //let local_offset = unsafe { registers.get_unchecked(RegisterID::RLO as usize).value };
+    let return_address = registers[RegisterID::RPC as usize].value;
+    let local_offset = registers[RegisterID::RLO as usize].value;
 
-    let old_registers: [Register; RegisterID::RMax as usize + 1] = *registers;
+    let mut saved_registers = [Register::default(); SAVED_REGISTERS];
+    saved_registers.copy_from_slice(&registers[..SAVED_REGISTERS]);
 
-    let frame = Frame::new(old_registers,  false);
+    let frame = Frame::new(
+        saved_registers,
+        return_address,
+        local_offset,
+        function_address,
+        locals.len() as u64,
+        false,
+    );
 
     frames.push(frame);
 
@@ -54,42 +62,31 @@ pub fn new_frame(
     set_local_offset(registers, locals);
 
     allocate_local_variables(locals, num_locals);
-
-    unsafe {
-        let register = registers.get_unchecked_mut(RegisterID::RMax as usize);
-        register.kind = RegisterValueKind::MemAddress;
-        register.value = num_locals as u64;
-    }
 }
 
+/// restore the caller's state. the return register is left as the callee set it, it is a
+/// garbage collection root so the returned object outlives the callee's registers and locals
 #[inline(always)]
 pub fn drop_frame(
     registers: &mut [Register; RegisterID::RMax as usize + 1],
-    frames: &mut Vec<Frame>,
-    locals: &mut Vec<Register>,
+    frames: &mut Frames,
+    locals: &mut Locals,
     running_state: &mut bool,
 ) {
-    let return_value = unsafe { *registers.get_unchecked(RegisterID::RRTN as usize) };
-    let num_locals = unsafe { registers.get_unchecked(RegisterID::RMax as usize).value };
-
-    deallocate_local_variables(locals, num_locals as u32);
-
     let frame = frames.pop();
 
     if let Some(frame) = frame {
+        locals.truncate(frame.locals_start as usize);
+
         if frame.is_main {
             *running_state = false;
             return;
         }
 
-        registers.copy_from_slice(&frame.registers);
-        //array_copy(&frame.registers, 0, registers, 0, registers.len());
+        registers[..SAVED_REGISTERS].copy_from_slice(&frame.registers);
 
-        unsafe {
-            let register = registers.get_unchecked_mut(RegisterID::RRTN as usize);
-            register.kind = return_value.kind;
-            register.value = return_value.value;
-        }
+        registers[RegisterID::RPC as usize].value = frame.return_address;
+        registers[RegisterID::RLO as usize].value = frame.local_offset;
     } else {
         *running_state = false;
     }
@@ -97,9 +94,7 @@ pub fn drop_frame(
 
 #[inline(always)]
 pub fn set_local_offset(registers: &mut [Register], locals: &[Register]) {
-    unsafe {
-        registers.get_unchecked_mut(RegisterID::RLO as usize).value = (locals.len()) as u64;
-    }
+    registers[RegisterID::RLO as usize].value = locals.len() as u64;
 }
 
 #[inline(always)]
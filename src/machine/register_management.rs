@@ -1,7 +1,10 @@
+use alloc::{borrow::Cow, boxed::Box, format, string::ToString, vec::Vec};
+
 use crate::{
     bytecode::OpCode,
+    immutables::Immutables,
     instruction::{instruction_decoder, Instruction},
-    object::{NovaObject, RegisterValueKind},
+    object::{NovaFunction, NovaObject, RegisterValueKind},
     register::Register,
 };
 
@@ -9,7 +12,9 @@ use crate::{
 use crate::register::RegisterID;
 
 use super::{
-    memory_management::load_object_from_memory, program_management::emit_error_with_message,
+    memory_management::{load_object_from_memory, store_object_in_memory},
+    program_management::emit_error_with_message,
+    unchecked,
 };
 
 #[inline(always)]
@@ -35,7 +40,7 @@ pub fn clear_registers(_registers: &mut [Register]) {
 pub fn compare_registers(
     registers: &mut [Register],
     memory: &mut Vec<NovaObject>,
-    immutables: &[NovaObject],
+    immutables: &Immutables,
     op: OpCode,
     first: Register,
     second: Register,
@@ -66,30 +71,9 @@ pub fn compare_registers(
                 return first < (second as f64);
             }
 
-            if first.kind.is_mem_address() && second.kind.is_mem_address() {
-                let first = load_object_from_memory(memory, first.value);
-                let second = load_object_from_memory(memory, second.value);
-
-                return first < second;
-            }
-
-            if first.kind.is_imm_address() && second.kind.is_imm_address() {
-                let first = &immutables[first.value as usize];
-                let second = &immutables[second.value as usize];
-
-                return first < second;
-            }
-
-            if first.kind.is_imm_address() && second.kind.is_mem_address() {
-                let first = &immutables[first.value as usize];
-                let second = load_object_from_memory(memory, second.value);
-
-                return first < second;
-            }
-
-            if first.kind.is_mem_address() && second.kind.is_imm_address() {
-                let first = load_object_from_memory(memory, first.value);
-                let second = &immutables[second.value as usize];
+            if first.kind.is_object_address() && second.kind.is_object_address() {
+                let first = register_as_nova_object(first, memory, immutables);
+                let second = register_as_nova_object(second, memory, immutables);
 
                 return first < second;
             }
@@ -120,23 +104,9 @@ pub fn compare_registers(
                 return first <= (second as f64);
             }
 
-            if first.kind.is_mem_address() && second.kind.is_mem_address() {
-                let first = load_object_from_memory(memory, first.value);
-                let second = load_object_from_memory(memory, second.value);
-
-                return first <= second;
-            }
-
-            if first.kind.is_imm_address() && second.kind.is_mem_address() {
-                let first = &immutables[first.value as usize];
-                let second = load_object_from_memory(memory, second.value);
-
-                return first <= second;
-            }
-
-            if first.kind.is_mem_address() && second.kind.is_imm_address() {
-                let first = load_object_from_memory(memory, first.value);
-                let second = &immutables[second.value as usize];
+            if first.kind.is_object_address() && second.kind.is_object_address() {
+                let first = register_as_nova_object(first, memory, immutables);
+                let second = register_as_nova_object(second, memory, immutables);
 
                 return first <= second;
             }
@@ -155,6 +125,21 @@ pub fn compare_registers(
                 return first == (second as f64);
             }
 
+            // functions are equal by identity, the value of a function id is its address
+            if first.kind.is_nova_function() && second.kind.is_nova_function() {
+                return first.value == second.value;
+            }
+
+            // a string can be in memory or in the immutables and a function can be an id or an
+            // object, so objects are compared wherever they are before the kinds are
+            let is_object = |kind: RegisterValueKind| kind.is_object_address() || kind.is_nova_function();
+            if is_object(first.kind) && is_object(second.kind) {
+                let first = register_as_nova_object(first, memory, immutables);
+                let second = register_as_nova_object(second, memory, immutables);
+
+                return first == second;
+            }
+
             if first.kind != second.kind {
                 return false;
             }
@@ -164,33 +149,14 @@ pub fn compare_registers(
             }
 
             if first.kind.is_float64() && second.kind.is_float64() {
-                return first.value == second.value;
+                return f64::from_bits(first.value) == f64::from_bits(second.value);
             }
 
-            if first.kind.is_int64() && second.kind.is_int64() {
+            if (first.kind.is_int64() && second.kind.is_int64())
+                || (first.kind.is_bool() && second.kind.is_bool())
+            {
                 return first.value == second.value;
             }
-
-            if first.kind.is_mem_address() && second.kind.is_mem_address() {
-                let first = load_object_from_memory(memory, first.value);
-                let second = load_object_from_memory(memory, second.value);
-
-                return first == second;
-            }
-
-            if first.kind.is_imm_address() && second.kind.is_mem_address() {
-                let first = &immutables[first.value as usize];
-                let second = load_object_from_memory(memory, second.value);
-
-                return first == second;
-            }
-
-            if first.kind.is_mem_address() && second.kind.is_imm_address() {
-                let first = load_object_from_memory(memory, first.value);
-                let second = &immutables[second.value as usize];
-
-                return first == second;
-            }
         }
 
         _ => {
@@ -213,7 +179,7 @@ pub fn compare_registers(
 
 #[inline(always)]
 pub fn clear_register(registers: &mut [Register], register_id: Instruction) {
-    let register = unsafe { registers.get_unchecked_mut(register_id as usize) };
+    let register = unchecked::get_mut(registers, register_id as usize);
 
     register.kind = RegisterValueKind::None;
     register.value = 0;
@@ -221,9 +187,7 @@ pub fn clear_register(registers: &mut [Register], register_id: Instruction) {
 
 #[inline(always)]
 pub fn get_register(registers: &[Register], register_id: Instruction) -> Register {
-    unsafe {
-        return *registers.get_unchecked(register_id as usize);
-    }
+    *unchecked::get(registers, register_id as usize)
 }
 
 #[inline(always)]
@@ -232,11 +196,9 @@ pub fn set_value_in_register(
     register_id: Instruction,
     value: Register,
 ) {
-    unsafe {
-        let register = registers.get_unchecked_mut(register_id as usize);
-        register.kind = value.kind;
-        register.value = value.value;
-    }
+    let register = unchecked::get_mut(registers, register_id as usize);
+    register.kind = value.kind;
+    register.value = value.value;
 }
 
 #[inline(always)]
@@ -267,29 +229,87 @@ pub fn load_memory_address_to_register(
 pub fn package_register_into_nova_object(
     registers: &mut [Register],
     memory: &[NovaObject],
-    immutables: &[NovaObject],
+    immutables: &Immutables,
     register_address: Instruction,
 ) -> NovaObject {
     let register = get_register(registers, register_address);
 
+    register_as_nova_object(register, memory, immutables).into_owned()
+}
+
+/// the register holding the object, objects that do not fit in a register are stored in memory
+#[inline(always)]
+pub fn nova_object_into_register(memory: &mut Vec<NovaObject>, object: NovaObject) -> Register {
+    match object {
+        NovaObject::Float64(value) => Register::new(RegisterValueKind::Float64, value.to_bits()),
+        NovaObject::Int64(value) => Register::new(RegisterValueKind::Int64, value as u64),
+        NovaObject::Bool(value) => Register::new(RegisterValueKind::Bool, value as u64),
+        NovaObject::None => Register::empty(),
+        // strings are tagged like the ones made by instructions so they can be concatenated
+        object @ NovaObject::String(_) => {
+            let memory_location = store_object_in_memory(memory, object);
+            Register::new(RegisterValueKind::StrMem, memory_location as u64)
+        }
+        object => {
+            let memory_location = store_object_in_memory(memory, object);
+            Register::new(RegisterValueKind::MemAddress, memory_location as u64)
+        }
+    }
+}
+
+/// the object held by the register, borrowed from memory or the immutables when it lives there
+#[inline(always)]
+pub fn register_as_nova_object<'a>(
+    register: Register,
+    memory: &'a [NovaObject],
+    immutables: &'a Immutables,
+) -> Cow<'a, NovaObject> {
     let value = match register.kind {
         RegisterValueKind::Int64 => NovaObject::Int64(register.value as i64),
         RegisterValueKind::Float64 => NovaObject::Float64(f64::from_bits(register.value)),
         RegisterValueKind::None => NovaObject::None,
-        RegisterValueKind::MemAddress | RegisterValueKind::StrMem => load_object_from_memory(memory, register.value).clone(),
-        RegisterValueKind::ImmAddress | RegisterValueKind::StrImm => immutables[register.value as usize].clone(),
-        RegisterValueKind::Bool => todo!(),
-        RegisterValueKind::NovaFunctionID(_) => todo!(),
+        RegisterValueKind::MemAddress
+        | RegisterValueKind::StrMem
+        | RegisterValueKind::NativeFunctionID(_) => {
+            return Cow::Borrowed(load_object_from_memory(memory, register.value))
+        }
+        RegisterValueKind::ImmAddress | RegisterValueKind::StrImm => {
+            return Cow::Borrowed(&immutables[register.value as usize])
+        }
+        RegisterValueKind::Bool => NovaObject::Bool(register.value == 1),
+        RegisterValueKind::NovaFunctionID(nova_function_id) => {
+            let labelled = nova_function_id.to_labelled();
+            let name = immutables[labelled.name_address as usize].to_string();
+
+            NovaObject::NovaFunction(NovaFunction {
+                name: Box::new(name),
+                address: register.value as Instruction,
+                arity: labelled.arity,
+                default_parameters: labelled.default_parameters,
+                is_variadic: labelled.is_variadic,
+                is_method: labelled.is_method,
+                number_of_locals: labelled.number_of_locals,
+            })
+        }
     };
 
-    value
+    Cow::Owned(value)
 }
 
+/// truthiness of the value in the register, following `NovaObject::is_truthy`.
+/// values held in the register itself are decided without touching memory
 #[inline(always)]
-pub fn is_truthy(register: Register) -> bool {
+pub fn is_truthy(register: Register, memory: &[NovaObject], immutables: &Immutables) -> bool {
     match register.kind {
         RegisterValueKind::None => false,
-        RegisterValueKind::Bool => register.value == 1,
-        _ => true
+        RegisterValueKind::Bool | RegisterValueKind::Int64 => register.value != 0,
+        RegisterValueKind::Float64 => f64::from_bits(register.value) != 0.0,
+        RegisterValueKind::NovaFunctionID(_) | RegisterValueKind::NativeFunctionID(_) => true,
+        RegisterValueKind::MemAddress
+        | RegisterValueKind::ImmAddress
+        | RegisterValueKind::StrMem
+        | RegisterValueKind::StrImm => {
+            register_as_nova_object(register, memory, immutables).is_truthy()
+        }
     }
 }
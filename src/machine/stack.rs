@@ -0,0 +1,166 @@
+//! the frames and locals of the virtual machine. they grow as calls need them, the `minimal`
+//! feature keeps them in arrays of a fixed capacity instead so calls never allocate and the
+//! memory the machine needs is known up front
+
+use alloc::vec::Vec;
+#[cfg(feature = "minimal")]
+use core::ops::{Deref, DerefMut};
+
+use crate::{frame::Frame, register::Register};
+
+/// most frames the `minimal` virtual machine keeps, counting the main frame
+pub const MAX_FRAMES: usize = 32;
+/// most local slots the `minimal` virtual machine keeps over all of its frames
+pub const MAX_LOCALS: usize = 256;
+
+#[cfg(not(feature = "minimal"))]
+pub(crate) type Frames = Vec<Frame>;
+#[cfg(not(feature = "minimal"))]
+pub(crate) type Locals = Vec<Register>;
+
+#[cfg(feature = "minimal")]
+pub(crate) type Frames = FixedStack<Frame, MAX_FRAMES>;
+#[cfg(feature = "minimal")]
+pub(crate) type Locals = FixedStack<Register, MAX_LOCALS>;
+
+/// whether a stack takes more items, checked before a call or a block grows it
+pub(crate) trait Room {
+    fn has_room(&self, additional: usize) -> bool;
+}
+
+impl<T> Room for Vec<T> {
+    #[inline(always)]
+    fn has_room(&self, _: usize) -> bool {
+        true
+    }
+}
+
+/// a stack of at most `N` items kept inline, with the parts of the `Vec` api the machine uses
+#[cfg(feature = "minimal")]
+pub(crate) struct FixedStack<T, const N: usize> {
+    items: [T; N],
+    len: usize,
+}
+
+#[cfg(feature = "minimal")]
+impl<T: Copy + Default, const N: usize> FixedStack<T, N> {
+    /// the capacity is always `N`
+    pub fn with_capacity(_: usize) -> Self {
+        Self {
+            items: [T::default(); N],
+            len: 0,
+        }
+    }
+
+    /// panics when the stack is full, callers check `has_room` first
+    #[inline(always)]
+    pub fn push(&mut self, item: T) {
+        assert!(self.len < N, "stack of {} items is full", N);
+        self.items[self.len] = item;
+        self.len += 1;
+    }
+
+    #[inline(always)]
+    pub fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.items[self.len])
+    }
+
+    #[inline(always)]
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// panics past the capacity, callers check `has_room` first
+    #[inline(always)]
+    pub fn resize(&mut self, len: usize, value: T) {
+        assert!(len <= N, "stack of {} items is full", N);
+        if len > self.len {
+            self.items[self.len..len].fill(value);
+        }
+        self.len = len;
+    }
+}
+
+#[cfg(feature = "minimal")]
+impl<T, const N: usize> Room for FixedStack<T, N> {
+    #[inline(always)]
+    fn has_room(&self, additional: usize) -> bool {
+        N - self.len >= additional
+    }
+}
+
+#[cfg(feature = "minimal")]
+impl<T, const N: usize> Deref for FixedStack<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items[..self.len]
+    }
+}
+
+#[cfg(feature = "minimal")]
+impl<T, const N: usize> DerefMut for FixedStack<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.items[..self.len]
+    }
+}
+
+#[cfg(all(test, feature = "minimal"))]
+mod stack_tests {
+    use crate::{machine::VirtualMachine, nova_asm};
+
+    use super::{FixedStack, Room, MAX_LOCALS};
+
+    #[test]
+    fn test_fixed_stack() {
+        let mut stack: FixedStack<u32, 4> = FixedStack::with_capacity(0);
+        stack.push(1);
+        stack.resize(3, 7);
+        assert_eq!(&stack[..], &[1, 7, 7]);
+        assert!(stack.has_room(1));
+        assert!(!stack.has_room(2));
+
+        stack.truncate(1);
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let recursion = nova_asm! {
+            jump start;
+            forever:
+            load_function 0, "forever", 0, forever;
+            invoke 0, 0;
+            return_none;
+            start:
+            load_function 0, "forever", 0, forever;
+            invoke 0, 0;
+            halt;
+        };
+
+        let mut vm = VirtualMachine::new();
+        vm.load_program(recursion);
+        assert_eq!(vm.start_vm(0), 1);
+        assert!(vm
+            .take_error()
+            .unwrap()
+            .message
+            .starts_with("Stack overflow"));
+
+        let locals = nova_asm! {
+            allocate_local MAX_LOCALS as u32 + 1;
+            halt;
+        };
+
+        let mut vm = VirtualMachine::new();
+        vm.load_program(locals);
+        assert_eq!(vm.start_vm(0), 1);
+        assert!(vm
+            .take_error()
+            .unwrap()
+            .message
+            .starts_with("Stack overflow"));
+    }
+}
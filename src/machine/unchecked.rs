@@ -0,0 +1,87 @@
+//! the slice accesses the execution internals make without bounds checks, gathered here so the
+//! unsafe code of the machine is in one place. the indices come from verified programs and the
+//! state the machine keeps, the `checked` feature checks them before every instruction.
+//! with the `safe_memory` feature every access is checked by rust instead and panics when out
+//! of bounds, for running the machine under miri or chasing a memory bug
+
+/// the element at the index
+#[cfg(not(feature = "safe_memory"))]
+#[inline(always)]
+pub(crate) fn get<T>(slice: &[T], index: usize) -> &T {
+    // safety: the callers only pass indices inside the slice, see the module docs
+    unsafe { slice.get_unchecked(index) }
+}
+
+#[cfg(feature = "safe_memory")]
+#[inline(always)]
+pub(crate) fn get<T>(slice: &[T], index: usize) -> &T {
+    &slice[index]
+}
+
+/// the element at the index, to change in place
+#[cfg(not(feature = "safe_memory"))]
+#[inline(always)]
+pub(crate) fn get_mut<T>(slice: &mut [T], index: usize) -> &mut T {
+    // safety: the callers only pass indices inside the slice, see the module docs
+    unsafe { slice.get_unchecked_mut(index) }
+}
+
+#[cfg(feature = "safe_memory")]
+#[inline(always)]
+pub(crate) fn get_mut<T>(slice: &mut [T], index: usize) -> &mut T {
+    &mut slice[index]
+}
+
+/// copy `length` elements from the source to the destination, starting at the given indices
+#[cfg(not(feature = "safe_memory"))]
+#[inline(always)]
+pub(crate) fn copy<T: Copy>(
+    source: &[T],
+    source_index: usize,
+    destination: &mut [T],
+    destination_index: usize,
+    length: usize,
+) {
+    // safety: both ranges are inside their slices, which cannot overlap as one is borrowed
+    // mutably
+    unsafe {
+        let source = source.as_ptr().add(source_index);
+        let destination = destination.as_mut_ptr().add(destination_index);
+
+        core::ptr::copy_nonoverlapping(source, destination, length);
+    }
+}
+
+#[cfg(feature = "safe_memory")]
+#[inline(always)]
+pub(crate) fn copy<T: Copy>(
+    source: &[T],
+    source_index: usize,
+    destination: &mut [T],
+    destination_index: usize,
+    length: usize,
+) {
+    destination[destination_index..destination_index + length]
+        .copy_from_slice(&source[source_index..source_index + length]);
+}
+
+#[cfg(test)]
+mod unchecked_tests {
+    use super::{copy, get, get_mut};
+
+    #[test]
+    fn test_copy() {
+        let source = [1, 2, 3, 4, 5, 6];
+        let mut destination = [0; 6];
+
+        copy(&source, 2, &mut destination, 0, 3);
+        assert_eq!(destination, [3, 4, 5, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_get() {
+        let mut values = [1, 2, 3];
+        *get_mut(&mut values, 1) = 5;
+        assert_eq!(*get(&values, 1), 5);
+    }
+}
@@ -0,0 +1,417 @@
+//! matrix natives, only with the `matrix` feature. a matrix is a list of rows, each a list of
+//! numbers of the same length, so matrices print and compare like any other list. the natives
+//! copy it into a flat buffer of floats, run a plain loop over it and build the result back
+
+use crate::{
+    natives::{expect_arguments, NativeArguments, NativeContext},
+    object::{NativeFunction, NativeResult, NovaObject},
+};
+
+/// pivots this close to zero make the matrix count as singular
+const SINGULAR_EPSILON: f64 = 1e-12;
+
+/// a matrix copied out of its list of rows, row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub rows: usize,
+    pub columns: usize,
+    pub values: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn filled(rows: usize, columns: usize, value: f64) -> Self {
+        Self {
+            rows,
+            columns,
+            values: vec![value; rows * columns],
+        }
+    }
+
+    pub fn identity(size: usize) -> Self {
+        let mut identity = Self::filled(size, size, 0.0);
+        for index in 0..size {
+            identity.values[index * size + index] = 1.0;
+        }
+        identity
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.values[row * self.columns + column]
+    }
+
+    /// read a list of rows, every row a list of numbers of the same length
+    pub fn from_object(object: &NovaObject) -> Result<Self, String> {
+        let NovaObject::List(rows) = object else {
+            return Err(format!("Expected a matrix, found {}", object));
+        };
+
+        let mut matrix = Self::filled(rows.len(), 0, 0.0);
+        for (index, row) in rows.iter().enumerate() {
+            let NovaObject::List(row) = row else {
+                return Err(format!("Row {} of the matrix is not a list", index));
+            };
+
+            if index == 0 {
+                matrix.columns = row.len();
+            } else if row.len() != matrix.columns {
+                return Err(format!(
+                    "Row {} of the matrix has {} values while the first has {}",
+                    index,
+                    row.len(),
+                    matrix.columns
+                ));
+            }
+
+            for value in row.iter() {
+                matrix.values.push(number_value(value)?);
+            }
+        }
+
+        Ok(matrix)
+    }
+
+    pub fn into_object(self) -> NovaObject {
+        let rows = (0..self.rows)
+            .map(|row| {
+                let row = &self.values[row * self.columns..(row + 1) * self.columns];
+                let row = row.iter().copied().map(NovaObject::Float64).collect();
+                NovaObject::List(Box::new(row))
+            })
+            .collect();
+
+        NovaObject::List(Box::new(rows))
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut transposed = Self::filled(self.columns, self.rows, 0.0);
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                transposed.values[column * self.rows + row] = self.get(row, column);
+            }
+        }
+        transposed
+    }
+
+    pub fn multiply(&self, other: &Self) -> Result<Self, String> {
+        if self.columns != other.rows {
+            return Err(format!(
+                "Cannot multiply a {}x{} matrix by a {}x{} matrix",
+                self.rows, self.columns, other.rows, other.columns
+            ));
+        }
+
+        let mut product = Self::filled(self.rows, other.columns, 0.0);
+        for row in 0..self.rows {
+            for inner in 0..self.columns {
+                let value = self.get(row, inner);
+                let other_row = &other.values[inner * other.columns..(inner + 1) * other.columns];
+                let product_row =
+                    &mut product.values[row * other.columns..(row + 1) * other.columns];
+                for (product, other) in product_row.iter_mut().zip(other_row) {
+                    *product += value * other;
+                }
+            }
+        }
+
+        Ok(product)
+    }
+
+    /// gauss-jordan elimination with partial pivoting
+    pub fn inverse(&self) -> Result<Self, String> {
+        if self.rows != self.columns {
+            return Err(format!(
+                "Only square matrices have an inverse, this one is {}x{}",
+                self.rows, self.columns
+            ));
+        }
+
+        let size = self.rows;
+        let mut left = self.clone();
+        let mut inverse = Self::identity(size);
+
+        for column in 0..size {
+            let pivot = (column..size)
+                .max_by(|&a, &b| {
+                    let a = left.get(a, column).abs();
+                    let b = left.get(b, column).abs();
+                    a.total_cmp(&b)
+                })
+                .unwrap();
+
+            if left.get(pivot, column).abs() < SINGULAR_EPSILON {
+                return Err("The matrix is singular and has no inverse".to_string());
+            }
+
+            left.swap_rows(pivot, column);
+            inverse.swap_rows(pivot, column);
+
+            let scale = left.get(column, column);
+            left.scale_row(column, 1.0 / scale);
+            inverse.scale_row(column, 1.0 / scale);
+
+            for row in (0..size).filter(|&row| row != column) {
+                let factor = left.get(row, column);
+                if factor != 0.0 {
+                    left.subtract_row(row, column, factor);
+                    inverse.subtract_row(row, column, factor);
+                }
+            }
+        }
+
+        Ok(inverse)
+    }
+
+    fn swap_rows(&mut self, first: usize, second: usize) {
+        if first != second {
+            for column in 0..self.columns {
+                self.values.swap(
+                    first * self.columns + column,
+                    second * self.columns + column,
+                );
+            }
+        }
+    }
+
+    fn scale_row(&mut self, row: usize, scale: f64) {
+        for value in &mut self.values[row * self.columns..(row + 1) * self.columns] {
+            *value *= scale;
+        }
+    }
+
+    /// row -= factor * source
+    fn subtract_row(&mut self, row: usize, source: usize, factor: f64) {
+        for column in 0..self.columns {
+            let value = self.get(source, column);
+            self.values[row * self.columns + column] -= factor * value;
+        }
+    }
+}
+
+fn number_value(value: &NovaObject) -> Result<f64, String> {
+    match value {
+        NovaObject::Int64(value) => Ok(*value as f64),
+        NovaObject::Float64(value) => Ok(*value),
+        value => Err(format!("Matrices hold numbers, found {}", value)),
+    }
+}
+
+/// the natives of the `matrix` feature, added to `common_native_functions`
+pub fn matrix_native_functions() -> Vec<NativeFunction> {
+    vec![
+        matrix_new_native(),
+        matrix_identity_native(),
+        matrix_shape_native(),
+        matrix_get_native(),
+        matrix_set_native(),
+        matrix_mul_native(),
+        matrix_transpose_native(),
+        matrix_inverse_native(),
+    ]
+}
+
+fn matrix_argument(arguments: &NativeArguments, index: usize) -> Result<Matrix, String> {
+    match arguments.get(index) {
+        Some(argument) => Matrix::from_object(&argument),
+        None => Err("Expected a matrix".to_string()),
+    }
+}
+
+fn size_argument(arguments: &NativeArguments, index: usize, name: &str) -> Result<usize, String> {
+    match arguments.get(index).as_deref() {
+        Some(NovaObject::Int64(size)) if *size >= 0 => Ok(*size as usize),
+        _ => Err(format!(
+            "Function '{}()' needs a positive integer as argument {}",
+            name,
+            index + 1
+        )),
+    }
+}
+
+/// the row and column arguments, checked against the size of the matrix
+fn position_arguments(
+    arguments: &NativeArguments,
+    matrix: &Matrix,
+    name: &str,
+) -> Result<(usize, usize), String> {
+    let row = size_argument(arguments, 1, name)?;
+    let column = size_argument(arguments, 2, name)?;
+    if row >= matrix.rows || column >= matrix.columns {
+        return Err(format!(
+            "Position ({}, {}) is outside a {}x{} matrix",
+            row, column, matrix.rows, matrix.columns
+        ));
+    }
+
+    Ok((row, column))
+}
+
+/// `matrix_new(rows, columns, value)` is a matrix filled with the value, 0 if it is left out
+pub fn matrix_new_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 2 && arguments.len() != 3 {
+            return Err(format!(
+                " Incorrect number of arguments for 'matrix_new()', 2 or 3 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        let rows = size_argument(&arguments, 0, "matrix_new")?;
+        let columns = size_argument(&arguments, 1, "matrix_new")?;
+        let value = match arguments.get(2) {
+            Some(value) => number_value(&value)?,
+            None => 0.0,
+        };
+
+        Ok(Matrix::filled(rows, columns, value).into_object())
+    };
+
+    NativeFunction {
+        name: "matrix_new".to_string(),
+        function,
+    }
+}
+
+/// `matrix_identity(size)` is the identity matrix of that size
+pub fn matrix_identity_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "matrix_identity", 1)?;
+        let size = size_argument(&arguments, 0, "matrix_identity")?;
+        Ok(Matrix::identity(size).into_object())
+    };
+
+    NativeFunction {
+        name: "matrix_identity".to_string(),
+        function,
+    }
+}
+
+/// `matrix_shape(matrix)` is the list of its row and column counts
+pub fn matrix_shape_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "matrix_shape", 1)?;
+        let matrix = matrix_argument(&arguments, 0)?;
+        Ok(NovaObject::List(Box::new(vec![
+            NovaObject::Int64(matrix.rows as i64),
+            NovaObject::Int64(matrix.columns as i64),
+        ])))
+    };
+
+    NativeFunction {
+        name: "matrix_shape".to_string(),
+        function,
+    }
+}
+
+/// `matrix_get(matrix, row, column)` is the value at the position, counting from 0
+pub fn matrix_get_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "matrix_get", 3)?;
+        let matrix = matrix_argument(&arguments, 0)?;
+        let (row, column) = position_arguments(&arguments, &matrix, "matrix_get")?;
+        Ok(NovaObject::Float64(matrix.get(row, column)))
+    };
+
+    NativeFunction {
+        name: "matrix_get".to_string(),
+        function,
+    }
+}
+
+/// `matrix_set(matrix, row, column, value)` changes the value at the position in place
+pub fn matrix_set_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "matrix_set", 4)?;
+        let matrix = matrix_argument(&arguments, 0)?;
+        let (row, column) = position_arguments(&arguments, &matrix, "matrix_set")?;
+        let value = number_value(&arguments.get(3).unwrap())?;
+
+        let Some(rows) = arguments.list_mut(0) else {
+            return Err("Function 'matrix_set()' requires a matrix".to_string());
+        };
+        if let NovaObject::List(row) = &mut rows[row] {
+            row[column] = NovaObject::Float64(value);
+        }
+
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "matrix_set".to_string(),
+        function,
+    }
+}
+
+/// `matrix_mul(left, right)` is the matrix product
+pub fn matrix_mul_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "matrix_mul", 2)?;
+        let left = matrix_argument(&arguments, 0)?;
+        let right = matrix_argument(&arguments, 1)?;
+        Ok(left.multiply(&right)?.into_object())
+    };
+
+    NativeFunction {
+        name: "matrix_mul".to_string(),
+        function,
+    }
+}
+
+/// `matrix_transpose(matrix)` swaps the rows and columns
+pub fn matrix_transpose_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "matrix_transpose", 1)?;
+        Ok(matrix_argument(&arguments, 0)?.transpose().into_object())
+    };
+
+    NativeFunction {
+        name: "matrix_transpose".to_string(),
+        function,
+    }
+}
+
+/// `matrix_inverse(matrix)` is the inverse of a square matrix, an error if it is singular
+pub fn matrix_inverse_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "matrix_inverse", 1)?;
+        Ok(matrix_argument(&arguments, 0)?.inverse()?.into_object())
+    };
+
+    NativeFunction {
+        name: "matrix_inverse".to_string(),
+        function,
+    }
+}
+
+#[cfg(test)]
+mod matrix_tests {
+    use super::Matrix;
+
+    fn matrix(rows: usize, columns: usize, values: &[f64]) -> Matrix {
+        Matrix {
+            rows,
+            columns,
+            values: values.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_matrix_kernels() {
+        let a = matrix(2, 3, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(a.transpose(), matrix(3, 2, &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0]));
+        assert_eq!(
+            a.multiply(&a.transpose()).unwrap(),
+            matrix(2, 2, &[14.0, 32.0, 32.0, 77.0])
+        );
+        assert!(a.multiply(&a).is_err());
+
+        let square = matrix(2, 2, &[4.0, 7.0, 2.0, 6.0]);
+        let inverse = square.inverse().unwrap();
+        let product = square.multiply(&inverse).unwrap();
+        for (value, expected) in product.values.iter().zip(&Matrix::identity(2).values) {
+            assert!((value - expected).abs() < 1e-9);
+        }
+
+        assert!(matrix(2, 2, &[1.0, 2.0, 2.0, 4.0]).inverse().is_err());
+        assert!(a.inverse().is_err());
+    }
+}
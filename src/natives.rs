@@ -1,113 +1,208 @@
-use crate::object::{NativeFunction, NovaObject};
-
-pub fn common_native_functions() -> Vec<NativeFunction> {
-    vec![
-        hello_native(),
-        println_native(),
-        print_native(),
-        time_native(),
-    ]
+#[cfg(feature = "natives")]
+mod library;
+
+use alloc::{borrow::Cow, format, vec::Vec};
+
+#[cfg(feature = "heap_sites")]
+use crate::machine::heap_inspection::AllocationSites;
+use crate::{
+    cache::MemoryCache,
+    capabilities::Capabilities,
+    extensions::Extensions,
+    hooks::VmHooks,
+    host::HostOutput,
+    immutables::Immutables,
+    instruction::Instruction,
+    limits::ResourceLimits,
+    machine::{
+        self, memory_management,
+        register_management::register_as_nova_object,
+        stack::{Frames, Locals},
+        Coercion, VirtualMachineData,
+    },
+    number_format::FloatFormat,
+    object::{
+        FrozenGlobals, MappedMemory, NativeFunction, NativeResult, NovaMap, NovaObject,
+        RegisterValueKind,
+    },
+    register::{Register, RegisterID},
+    trace::TraceSink,
+};
+
+#[cfg(feature = "natives")]
+pub use library::*;
+
+/// state of the virtual machine a native function is allowed to touch
+pub struct NativeContext<'a> {
+    /// the output sink of the virtual machine, written to by `print`. writing to it directly
+    /// skips the print hook
+    pub output: &'a mut dyn HostOutput,
+    /// how printed floats are written
+    pub float_format: FloatFormat,
+    /// what the native function may do outside the virtual machine
+    pub capabilities: Capabilities,
+    /// caps on the values the native function builds, checked when it returns
+    pub limits: ResourceLimits,
+    pub(crate) coercion: Coercion,
+    pub(crate) identifiers: &'a mut MappedMemory,
+    pub(crate) frozen_globals: &'a mut FrozenGlobals,
+    pub(crate) globals: &'a mut Vec<Register>,
+    pub(crate) mem_cache: &'a mut MemoryCache,
+    pub(crate) instructions: &'a [Instruction],
+    pub(crate) registers: &'a mut [Register; RegisterID::RMax as usize + 1],
+    pub(crate) running: &'a mut bool,
+    pub(crate) frames: &'a mut Frames,
+    pub(crate) locals: &'a mut Locals,
+    pub(crate) natives: &'a [NativeFunction],
+    pub(crate) extensions: &'a Extensions,
+    pub(crate) trace: &'a mut dyn TraceSink,
+    pub(crate) hooks: &'a mut VmHooks,
+    /// number of native functions calling back into nova code around this one
+    pub(crate) native_depth: u32,
+    #[cfg(feature = "heap_sites")]
+    pub(crate) heap_sites: &'a mut AllocationSites,
 }
 
-pub fn hello_native() -> NativeFunction {
-    let function = |_: Vec<NovaObject>| -> Result<NovaObject, String> {
-        println!("Hello Native Function!!!");
-        Ok(NovaObject::None)
-    };
-
-    NativeFunction {
-        name: "Hello".to_string(),
-        function,
-    }
-}
-
-pub fn print_native() -> NativeFunction {
-    let function = |arguments: Vec<NovaObject>| -> Result<NovaObject, String> {
-        for argument in arguments {
-            print!("{}", argument);
+impl NativeContext<'_> {
+    /// remove a global variable, false if there is no global with the name or it is frozen
+    pub fn undefine_global(&mut self, name: &str) -> bool {
+        let frozen = self.identifiers.get(name).is_some_and(|&global_location| {
+            memory_management::is_frozen(self.frozen_globals, global_location)
+        });
+        if frozen {
+            return false;
         }
 
-        Ok(NovaObject::None)
-    };
-
-    NativeFunction {
-        name: "print".to_string(),
-        function,
+        memory_management::undefine_global(self.identifiers, self.globals, self.mem_cache, name)
     }
-}
 
-pub fn println_native() -> NativeFunction {
-    let function = |arguments: Vec<NovaObject>| -> Result<NovaObject, String> {
-        for argument in arguments {
-            print!("{}", argument);
-        }
-        println!();
+    /// make the global read only, false if there is no global with the name
+    pub fn freeze_global(&mut self, name: &str) -> bool {
+        memory_management::freeze_global(self.identifiers, self.frozen_globals, name)
+    }
 
-        Ok(NovaObject::None)
-    };
+    /// write the text to the output, all printing should go through here so the print hook sees it
+    pub fn print(&mut self, text: &str) {
+        self.hooks.print(self.output, text);
+    }
 
-    NativeFunction {
-        name: "println".to_string(),
-        function,
+    /// call the function passed as the argument at `callee` and wait for its result. objects
+    /// borrowed from the arguments have to be let go of first, the call can move the heap.
+    /// a failed call leaves the machine unwinding, so its error should be returned as it is
+    pub fn call(
+        &mut self,
+        arguments: &mut NativeArguments,
+        callee: usize,
+        values: Vec<NovaObject>,
+    ) -> NativeResult {
+        let callee = *arguments
+            .registers
+            .get(callee)
+            .ok_or_else(|| format!("No argument at {} to call", callee))?;
+
+        let mut virtual_machine_data = VirtualMachineData {
+            instructions: self.instructions,
+            immutables: arguments.immutables,
+            registers: &mut *self.registers,
+            running: &mut *self.running,
+            memory: &mut *arguments.memory,
+            frames: &mut *self.frames,
+            locals: &mut *self.locals,
+            globals: &mut *self.globals,
+            identifiers: &mut *self.identifiers,
+            frozen_globals: &mut *self.frozen_globals,
+            mem_cache: &mut *self.mem_cache,
+            natives: self.natives,
+            extensions: self.extensions,
+            float_format: self.float_format,
+            capabilities: self.capabilities,
+            limits: self.limits,
+            coercion: self.coercion,
+            native_depth: self.native_depth + 1,
+            output: &mut *self.output,
+            trace: &mut *self.trace,
+            hooks: &mut *self.hooks,
+            #[cfg(feature = "heap_sites")]
+            heap_sites: &mut *self.heap_sites,
+        };
+
+        machine::call_function(&mut virtual_machine_data, callee, values)
     }
 }
 
-pub fn time_native() -> NativeFunction {
-    let function = |arguments: Vec<NovaObject>| -> Result<NovaObject, String> {
-        if arguments.len() != 1 {
-            return Err(format!(
-                " Incorrect number of arguments for 'time()', {} needed while {} provided",
-                1,
-                arguments.len()
-            ));
-        }
+/// the arguments of a native call, a view over the caller's registers. an argument is only
+/// turned into a `NovaObject` when it is read, and objects on the heap are borrowed
+pub struct NativeArguments<'a> {
+    registers: &'a [Register],
+    memory: &'a mut Vec<NovaObject>,
+    immutables: &'a Immutables,
+}
 
-        let argument = &arguments[0];
-        if !argument.is_string() {
-            return Err("Function 'time()' requires a string argument".to_string());
+impl<'a> NativeArguments<'a> {
+    pub fn new(
+        registers: &'a [Register],
+        memory: &'a mut Vec<NovaObject>,
+        immutables: &'a Immutables,
+    ) -> Self {
+        Self {
+            registers,
+            memory,
+            immutables,
         }
+    }
 
-        let argument = argument.to_string();
-
-        match argument.as_str() {
-            "milli" => {
-                let epoch = chrono::Utc::now().timestamp_millis();
-                #[cfg(feature = "debug")]
-                println!("epoch = {}", epoch);
-
-                Ok(NovaObject::Int64(epoch as i64))
-            }
-
-            "micro" => {
-                let epoch = chrono::Utc::now().timestamp_micros();
-                #[cfg(feature = "debug")]
-                println!("epoch = {}", epoch);
+    pub fn len(&self) -> usize {
+        self.registers.len()
+    }
 
-                Ok(NovaObject::Int64(epoch as i64))
-            }
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+    }
 
-            "sec" => {
-                let epoch = chrono::Utc::now().timestamp();
-                #[cfg(feature = "debug")]
-                println!("epoch = {}", epoch);
+    pub fn get(&self, index: usize) -> Option<Cow<'_, NovaObject>> {
+        let register = *self.registers.get(index)?;
+        Some(register_as_nova_object(
+            register,
+            self.memory,
+            self.immutables,
+        ))
+    }
 
-                Ok(NovaObject::Int64(epoch as i64))
-            }
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'_, NovaObject>> {
+        self.registers
+            .iter()
+            .map(|register| register_as_nova_object(*register, self.memory, self.immutables))
+    }
 
-            "nano" => {
-                let epoch = chrono::Utc::now().timestamp_nanos_opt().unwrap();
-                #[cfg(feature = "debug")]
-                println!("epoch = {}", epoch);
+    /// the list the argument refers to, changed in place so every variable holding it sees
+    /// the change. None when the argument is not a list on the heap
+    pub fn list_mut(&mut self, index: usize) -> Option<&mut Vec<NovaObject>> {
+        match self.heap_object_mut(index)? {
+            NovaObject::List(list) => Some(list),
+            _ => None,
+        }
+    }
 
-                Ok(NovaObject::Int64(epoch as i64))
-            }
+    /// the map the argument refers to, changed in place like `list_mut`
+    pub fn map_mut(&mut self, index: usize) -> Option<&mut NovaMap> {
+        match self.heap_object_mut(index)? {
+            NovaObject::Map(map) => Some(map),
+            _ => None,
+        }
+    }
 
-            _ => Err(format!("Unknown option: {}", argument)),
+    fn heap_object_mut(&mut self, index: usize) -> Option<&mut NovaObject> {
+        let register = self.registers.get(index)?;
+        if register.kind != RegisterValueKind::MemAddress {
+            return None;
         }
-    };
 
-    NativeFunction {
-        name: "time".to_string(),
-        function,
+        self.memory.get_mut(register.value as usize)
     }
 }
+
+/// the native functions of the library, there are none without the `natives` feature
+#[cfg(not(feature = "natives"))]
+pub fn common_native_functions() -> Vec<NativeFunction> {
+    Vec::new()
+}
@@ -0,0 +1,1775 @@
+//! the native functions scripts call, left out without the `natives` feature
+
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    fmt::Write as _,
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    capabilities::Capability,
+    machine::bytecode_execution::arithmetic_operations::{op_math, MathOp},
+    number_format::{FloatFormat, FormatSpec, IntegerStyle},
+    object::{NativeFunction, NativeResult, NovaMap, NovaObject, RegisterValueKind},
+    register::Register,
+};
+use rustc_hash::FxHasher;
+
+use super::{NativeArguments, NativeContext};
+
+/// every native function of the library, the modules of `prelude::NativeModule` one after
+/// the other
+pub fn common_native_functions() -> Vec<NativeFunction> {
+    let mut natives = core_native_functions();
+    natives.extend(math_native_functions());
+    natives.extend(collection_native_functions());
+    natives.extend(csv_native_functions());
+    natives.extend(hash_native_functions());
+    natives.extend(os_native_functions());
+    natives
+}
+
+/// printing, assertions, formatting and the clock
+pub fn core_native_functions() -> Vec<NativeFunction> {
+    vec![
+        hello_native(),
+        println_native(),
+        print_native(),
+        time_native(),
+        assert_native(),
+        assert_equal_native(),
+        undef_native(),
+        freeze_native(),
+        format_native(),
+    ]
+}
+
+/// numbers and vectors, and matrices with the `matrix` feature
+pub fn math_native_functions() -> Vec<NativeFunction> {
+    #[allow(unused_mut)]
+    let mut natives = vec![
+        min_native(),
+        max_native(),
+        clamp_native(),
+        sqrt_native(),
+        abs_native(),
+        floor_native(),
+        ceil_native(),
+        vec_add_native(),
+        vec_mul_native(),
+        sum_native(),
+        dot_native(),
+    ];
+
+    #[cfg(feature = "matrix")]
+    natives.extend(crate::matrix::matrix_native_functions());
+
+    natives
+}
+
+/// lists, maps and splitting strings
+pub fn collection_native_functions() -> Vec<NativeFunction> {
+    vec![
+        sort_native(),
+        list_native(),
+        push_native(),
+        pop_native(),
+        insert_native(),
+        remove_native(),
+        len_native(),
+        slice_native(),
+        contains_native(),
+        join_native(),
+        map_native(),
+        filter_native(),
+        reduce_native(),
+        each_native(),
+        dict_native(),
+        get_native(),
+        set_native(),
+        keys_native(),
+        values_native(),
+        has_native(),
+        merge_native(),
+        deep_equals_native(),
+        copy_native(),
+        split_native(),
+    ]
+}
+
+pub fn csv_native_functions() -> Vec<NativeFunction> {
+    vec![csv_parse_line_native(), csv_format_line_native()]
+}
+
+/// `hash`, and the digests with the `digest` feature
+pub fn hash_native_functions() -> Vec<NativeFunction> {
+    #[allow(unused_mut)]
+    let mut natives = vec![hash_native()];
+
+    #[cfg(feature = "digest")]
+    natives.extend([md5_native(), sha256_native()]);
+
+    natives
+}
+
+/// the environment of the process, the natives need capabilities
+pub fn os_native_functions() -> Vec<NativeFunction> {
+    vec![
+        os_env_native(),
+        os_set_env_native(),
+        os_platform_native(),
+        os_cwd_native(),
+        os_chdir_native(),
+    ]
+}
+
+pub fn hello_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, _: NativeArguments| -> NativeResult {
+        context.print("Hello Native Function!!!\n");
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "Hello".to_string(),
+        function,
+    }
+}
+
+pub fn print_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        let mut text = String::new();
+        for argument in arguments.iter() {
+            let _ = write!(text, "{}", context.float_format.display(&argument));
+        }
+        context.print(&text);
+
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "print".to_string(),
+        function,
+    }
+}
+
+pub fn println_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        let mut text = String::new();
+        for argument in arguments.iter() {
+            let _ = write!(text, "{}", context.float_format.display(&argument));
+        }
+        text.push('\n');
+        context.print(&text);
+
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "println".to_string(),
+        function,
+    }
+}
+
+pub fn time_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 1 {
+            return Err(format!(
+                " Incorrect number of arguments for 'time()', {} needed while {} provided",
+                1,
+                arguments.len()
+            ));
+        }
+
+        let argument = arguments.get(0).unwrap();
+        if !argument.is_string() {
+            return Err("Function 'time()' requires a string argument".to_string());
+        }
+
+        let argument = argument.to_string();
+
+        match argument.as_str() {
+            "milli" => {
+                let epoch = chrono::Utc::now().timestamp_millis();
+                #[cfg(feature = "debug")]
+                println!("epoch = {}", epoch);
+
+                Ok(NovaObject::Int64(epoch as i64))
+            }
+
+            "micro" => {
+                let epoch = chrono::Utc::now().timestamp_micros();
+                #[cfg(feature = "debug")]
+                println!("epoch = {}", epoch);
+
+                Ok(NovaObject::Int64(epoch as i64))
+            }
+
+            "sec" => {
+                let epoch = chrono::Utc::now().timestamp();
+                #[cfg(feature = "debug")]
+                println!("epoch = {}", epoch);
+
+                Ok(NovaObject::Int64(epoch as i64))
+            }
+
+            "nano" => {
+                let epoch = chrono::Utc::now().timestamp_nanos_opt().unwrap();
+                #[cfg(feature = "debug")]
+                println!("epoch = {}", epoch);
+
+                Ok(NovaObject::Int64(epoch as i64))
+            }
+
+            _ => Err(format!("Unknown option: {}", argument)),
+        }
+    };
+
+    NativeFunction {
+        name: "time".to_string(),
+        function,
+    }
+}
+
+pub fn assert_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.is_empty() || arguments.len() > 2 {
+            return Err(format!(
+                " Incorrect number of arguments for 'assert()', 1 or 2 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        if arguments.get(0).unwrap().is_truthy() {
+            return Ok(NovaObject::None);
+        }
+
+        match arguments.get(1) {
+            Some(message) => Err(format!("Assertion failed: {}", message)),
+            None => Err("Assertion failed".to_string()),
+        }
+    };
+
+    NativeFunction {
+        name: "assert".to_string(),
+        function,
+    }
+}
+
+pub fn assert_equal_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 2 {
+            return Err(format!(
+                " Incorrect number of arguments for 'assert_equal()', {} needed while {} provided",
+                2,
+                arguments.len()
+            ));
+        }
+
+        let left = arguments.get(0).unwrap();
+        let right = arguments.get(1).unwrap();
+
+        if !objects_equal(&left, &right) {
+            return Err(format!(
+                "Assertion failed: left ({}) is not equal to right ({})",
+                left, right
+            ));
+        }
+
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "assert_equal".to_string(),
+        function,
+    }
+}
+
+pub fn undef_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 1 {
+            return Err(format!(
+                " Incorrect number of arguments for 'undef()', {} needed while {} provided",
+                1,
+                arguments.len()
+            ));
+        }
+
+        let name = arguments.get(0).unwrap();
+        if !name.is_string() {
+            return Err("Function 'undef()' requires the name of a global".to_string());
+        }
+
+        Ok(NovaObject::Bool(context.undefine_global(&name.to_string())))
+    };
+
+    NativeFunction {
+        name: "undef".to_string(),
+        function,
+    }
+}
+
+/// `freeze(name)` makes the global read only, false if there is no global with the name
+pub fn freeze_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "freeze", 1)?;
+
+        let name = arguments.get(0).unwrap();
+        if !name.is_string() {
+            return Err("Function 'freeze()' requires the name of a global".to_string());
+        }
+
+        Ok(NovaObject::Bool(context.freeze_global(&name.to_string())))
+    };
+
+    NativeFunction {
+        name: "freeze".to_string(),
+        function,
+    }
+}
+
+/// `format(value)` writes the value like print does. `format(number, ".2")` or
+/// `format(number, 2)` writes it with a fixed number of digits after the point and
+/// `format(number, style)` in the "x", "b", "o" or "e" style
+pub fn format_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.is_empty() || arguments.len() > 2 {
+            return Err(format!(
+                " Incorrect number of arguments for 'format()', 1 or 2 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        let value = arguments.get(0).unwrap();
+        let Some(option) = arguments.get(1) else {
+            let formatted = context.float_format.display(&value).to_string();
+            return Ok(NovaObject::String(Box::new(formatted)));
+        };
+
+        let spec = match option.as_ref() {
+            NovaObject::Int64(digits) if *digits >= 0 => FormatSpec::Precision(*digits as usize),
+            NovaObject::String(spec) => match FormatSpec::parse(spec) {
+                Some(spec) => spec,
+                None => return Err(format!("Unknown format spec: {}", spec)),
+            },
+            _ => {
+                return Err(
+                    "Function 'format()' takes a non negative number of digits or a spec"
+                        .to_string(),
+                )
+            }
+        };
+
+        let formatted = match (spec, value.as_ref()) {
+            (FormatSpec::Precision(digits), NovaObject::Int64(number)) => {
+                FloatFormat::Fixed(digits).format(*number as f64)
+            }
+            (FormatSpec::Precision(digits), NovaObject::Float64(number)) => {
+                FloatFormat::Fixed(digits).format(*number)
+            }
+            (FormatSpec::Style(style), NovaObject::Int64(number)) => style.format(*number),
+            (FormatSpec::Style(IntegerStyle::Scientific), NovaObject::Float64(number)) => {
+                FloatFormat::Scientific.format(*number)
+            }
+            (_, value) => {
+                return Err(format!(
+                    "Function 'format()' cannot write {} with that spec",
+                    value
+                ))
+            }
+        };
+
+        Ok(NovaObject::String(Box::new(formatted)))
+    };
+
+    NativeFunction {
+        name: "format".to_string(),
+        function,
+    }
+}
+
+/// order two values of the same kind. integers and floats compare as numbers, strings by
+/// their text and lists item by item. values of different kinds, and anything else, cannot
+/// be compared
+pub fn compare_objects(first: &NovaObject, second: &NovaObject) -> Result<Ordering, String> {
+    let ordering = match (first, second) {
+        (NovaObject::Int64(first), NovaObject::Int64(second)) => Some(first.cmp(second)),
+        (NovaObject::Int64(first), NovaObject::Float64(second)) => {
+            (*first as f64).partial_cmp(second)
+        }
+        (NovaObject::Float64(first), NovaObject::Int64(second)) => {
+            first.partial_cmp(&(*second as f64))
+        }
+        (NovaObject::Float64(first), NovaObject::Float64(second)) => first.partial_cmp(second),
+        (NovaObject::String(first), NovaObject::String(second)) => Some(first.cmp(second)),
+        (NovaObject::List(first), NovaObject::List(second)) => {
+            for (first, second) in first.iter().zip(second.iter()) {
+                match compare_objects(first, second)? {
+                    Ordering::Equal => continue,
+                    ordering => return Ok(ordering),
+                }
+            }
+
+            Some(first.len().cmp(&second.len()))
+        }
+        _ => None,
+    };
+
+    ordering.ok_or_else(|| {
+        format!(
+            "cannot compare {} to {}",
+            first.kind_name(),
+            second.kind_name()
+        )
+    })
+}
+
+/// the values to pick from, either the arguments or the items of a single list argument
+fn candidates(arguments: &NativeArguments) -> Vec<NovaObject> {
+    if arguments.len() == 1 {
+        if let NovaObject::List(list) = arguments.get(0).unwrap().as_ref() {
+            return list.to_vec();
+        }
+    }
+
+    arguments.iter().map(Cow::into_owned).collect()
+}
+
+/// the value that comes first in the ordering, the earliest one on ties
+fn pick(values: Vec<NovaObject>, name: &str, wanted: Ordering) -> NativeResult {
+    let mut values = values.into_iter();
+    let Some(mut picked) = values.next() else {
+        return Err(format!("Function '{}()' needs at least one value", name));
+    };
+
+    for value in values {
+        if compare_objects(&value, &picked)? == wanted {
+            picked = value;
+        }
+    }
+
+    Ok(picked)
+}
+
+pub fn min_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        pick(candidates(&arguments), "min", Ordering::Less)
+    };
+
+    NativeFunction {
+        name: "min".to_string(),
+        function,
+    }
+}
+
+pub fn max_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        pick(candidates(&arguments), "max", Ordering::Greater)
+    };
+
+    NativeFunction {
+        name: "max".to_string(),
+        function,
+    }
+}
+
+pub fn clamp_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 3 {
+            return Err(format!(
+                " Incorrect number of arguments for 'clamp()', {} needed while {} provided",
+                3,
+                arguments.len()
+            ));
+        }
+
+        let value = arguments.get(0).unwrap();
+        let low = arguments.get(1).unwrap();
+        let high = arguments.get(2).unwrap();
+
+        if compare_objects(&low, &high)? == Ordering::Greater {
+            return Err(format!(
+                "Function 'clamp()' needs the low bound ({}) to not be above the high bound ({})",
+                low, high
+            ));
+        }
+
+        if compare_objects(&value, &low)? == Ordering::Less {
+            return Ok(low.into_owned());
+        }
+
+        if compare_objects(&value, &high)? == Ordering::Greater {
+            return Ok(high.into_owned());
+        }
+
+        Ok(value.into_owned())
+    };
+
+    NativeFunction {
+        name: "clamp".to_string(),
+        function,
+    }
+}
+
+/// the single number argument through the math shared with the `Sqrt`, `Abs`, `Floor` and
+/// `Ceil` opcodes, the optimizer lowers calls to these natives into the opcodes
+fn apply_math(op: MathOp, arguments: &NativeArguments) -> NativeResult {
+    expect_arguments(arguments, op.name(), 1)?;
+
+    let register = match arguments.get(0).as_deref() {
+        Some(NovaObject::Int64(value)) => Register::new(RegisterValueKind::Int64, *value as u64),
+        Some(NovaObject::Float64(value)) => {
+            Register::new(RegisterValueKind::Float64, value.to_bits())
+        }
+        _ => Register::empty(),
+    };
+
+    match op_math(op, register) {
+        Some(result) if result.kind == RegisterValueKind::Int64 => {
+            Ok(NovaObject::Int64(result.value as i64))
+        }
+        Some(result) => Ok(NovaObject::Float64(f64::from_bits(result.value))),
+        None => Err(format!("Function '{}()' needs a number", op.name())),
+    }
+}
+
+pub fn sqrt_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        apply_math(MathOp::Sqrt, &arguments)
+    };
+
+    NativeFunction {
+        name: "sqrt".to_string(),
+        function,
+    }
+}
+
+pub fn abs_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        apply_math(MathOp::Abs, &arguments)
+    };
+
+    NativeFunction {
+        name: "abs".to_string(),
+        function,
+    }
+}
+
+pub fn floor_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        apply_math(MathOp::Floor, &arguments)
+    };
+
+    NativeFunction {
+        name: "floor".to_string(),
+        function,
+    }
+}
+
+pub fn ceil_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        apply_math(MathOp::Ceil, &arguments)
+    };
+
+    NativeFunction {
+        name: "ceil".to_string(),
+        function,
+    }
+}
+
+/// the numbers of a list, kept as integers unless a float is among them
+enum Numbers {
+    Ints(Vec<i64>),
+    Floats(Vec<f64>),
+}
+
+impl Numbers {
+    fn len(&self) -> usize {
+        match self {
+            Numbers::Ints(values) => values.len(),
+            Numbers::Floats(values) => values.len(),
+        }
+    }
+
+    fn into_floats(self) -> Vec<f64> {
+        match self {
+            Numbers::Ints(values) => values.into_iter().map(|value| value as f64).collect(),
+            Numbers::Floats(values) => values,
+        }
+    }
+}
+
+/// the list argument at the index as numbers. with a length, a single number is also accepted
+/// and repeated that many times
+fn numbers_argument(
+    arguments: &NativeArguments,
+    index: usize,
+    name: &str,
+    length: Option<usize>,
+) -> Result<Numbers, String> {
+    let argument = arguments.get(index);
+    let list = match (argument.as_deref(), length) {
+        (Some(NovaObject::List(list)), _) => list,
+        (Some(NovaObject::Int64(value)), Some(length)) => {
+            return Ok(Numbers::Ints(vec![*value; length]))
+        }
+        (Some(NovaObject::Float64(value)), Some(length)) => {
+            return Ok(Numbers::Floats(vec![*value; length]))
+        }
+        _ => return Err(format!("Function '{}()' requires a list of numbers", name)),
+    };
+
+    if let Some(ints) = list
+        .iter()
+        .map(|value| match value {
+            NovaObject::Int64(value) => Some(*value),
+            _ => None,
+        })
+        .collect::<Option<Vec<i64>>>()
+    {
+        return Ok(Numbers::Ints(ints));
+    }
+
+    list.iter()
+        .map(|value| match value {
+            NovaObject::Int64(value) => Ok(*value as f64),
+            NovaObject::Float64(value) => Ok(*value),
+            value => Err(format!(
+                "Function '{}()' requires a list of numbers, found {}",
+                name, value
+            )),
+        })
+        .collect::<Result<Vec<f64>, String>>()
+        .map(Numbers::Floats)
+}
+
+/// the two lists of a bulk operation, the second one can be a single number
+fn number_pair(arguments: &NativeArguments, name: &str) -> Result<(Numbers, Numbers), String> {
+    expect_arguments(arguments, name, 2)?;
+
+    let left = numbers_argument(arguments, 0, name, None)?;
+    let right = numbers_argument(arguments, 1, name, Some(left.len()))?;
+    if left.len() != right.len() {
+        return Err(format!(
+            "Function '{}()' needs lists of the same length, {} and {} values given",
+            name,
+            left.len(),
+            right.len()
+        ));
+    }
+
+    Ok((left, right))
+}
+
+/// the lists combined value by value in a plain loop over the numbers, integers wrap around
+/// like the arithmetic opcodes
+fn element_wise(
+    arguments: &NativeArguments,
+    name: &str,
+    int_op: fn(i64, i64) -> i64,
+    float_op: fn(f64, f64) -> f64,
+) -> NativeResult {
+    let combined: Vec<NovaObject> = match number_pair(arguments, name)? {
+        (Numbers::Ints(left), Numbers::Ints(right)) => left
+            .iter()
+            .zip(&right)
+            .map(|(left, right)| NovaObject::Int64(int_op(*left, *right)))
+            .collect(),
+        (left, right) => left
+            .into_floats()
+            .iter()
+            .zip(&right.into_floats())
+            .map(|(left, right)| NovaObject::Float64(float_op(*left, *right)))
+            .collect(),
+    };
+
+    Ok(NovaObject::List(Box::new(combined)))
+}
+
+/// `vec_add(list, other)` adds the values of two lists of numbers, or a number to every value
+pub fn vec_add_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        element_wise(&arguments, "vec_add", i64::wrapping_add, |left, right| {
+            left + right
+        })
+    };
+
+    NativeFunction {
+        name: "vec_add".to_string(),
+        function,
+    }
+}
+
+/// `vec_mul(list, other)` multiplies the values of two lists of numbers, or every value by a
+/// number
+pub fn vec_mul_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        element_wise(&arguments, "vec_mul", i64::wrapping_mul, |left, right| {
+            left * right
+        })
+    };
+
+    NativeFunction {
+        name: "vec_mul".to_string(),
+        function,
+    }
+}
+
+/// `sum(list)` adds up a list of numbers, 0 for an empty list
+pub fn sum_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "sum", 1)?;
+
+        match numbers_argument(&arguments, 0, "sum", None)? {
+            Numbers::Ints(values) => Ok(NovaObject::Int64(
+                values
+                    .iter()
+                    .fold(0i64, |sum, value| sum.wrapping_add(*value)),
+            )),
+            Numbers::Floats(values) => Ok(NovaObject::Float64(values.iter().sum())),
+        }
+    };
+
+    NativeFunction {
+        name: "sum".to_string(),
+        function,
+    }
+}
+
+/// `dot(list, other)` is the sum of the products of the values of two lists of numbers
+pub fn dot_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        match number_pair(&arguments, "dot")? {
+            (Numbers::Ints(left), Numbers::Ints(right)) => Ok(NovaObject::Int64(
+                left.iter().zip(&right).fold(0i64, |sum, (left, right)| {
+                    sum.wrapping_add(left.wrapping_mul(*right))
+                }),
+            )),
+            (left, right) => Ok(NovaObject::Float64(
+                left.into_floats()
+                    .iter()
+                    .zip(&right.into_floats())
+                    .map(|(left, right)| left * right)
+                    .sum(),
+            )),
+        }
+    };
+
+    NativeFunction {
+        name: "dot".to_string(),
+        function,
+    }
+}
+
+/// a sorted copy of the list, equal values keep their order
+pub fn sort_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 1 {
+            return Err(format!(
+                " Incorrect number of arguments for 'sort()', {} needed while {} provided",
+                1,
+                arguments.len()
+            ));
+        }
+
+        let NovaObject::List(list) = arguments.get(0).unwrap().into_owned() else {
+            return Err("Function 'sort()' requires a list".to_string());
+        };
+
+        // a pair that cannot be compared fails the whole sort
+        let mut list = *list;
+        let mut error = None;
+        list.sort_by(|first, second| match compare_objects(first, second) {
+            Ok(ordering) => ordering,
+            Err(message) => {
+                error.get_or_insert(message);
+                Ordering::Equal
+            }
+        });
+
+        match error {
+            Some(message) => Err(message),
+            None => Ok(NovaObject::List(Box::new(list))),
+        }
+    };
+
+    NativeFunction {
+        name: "sort".to_string(),
+        function,
+    }
+}
+
+/// equality as the `==` instruction sees it, integers and floats being equal by value
+pub fn objects_equal(left: &NovaObject, right: &NovaObject) -> bool {
+    match (left, right) {
+        (NovaObject::Int64(left), NovaObject::Float64(right)) => *left as f64 == *right,
+        (NovaObject::Float64(left), NovaObject::Int64(right)) => *left == *right as f64,
+        _ => left == right,
+    }
+}
+
+pub(crate) fn expect_arguments(
+    arguments: &NativeArguments,
+    name: &str,
+    needed: usize,
+) -> Result<(), String> {
+    if arguments.len() != needed {
+        return Err(format!(
+            " Incorrect number of arguments for '{}()', {} needed while {} provided",
+            name,
+            needed,
+            arguments.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// the argument as an index that is at most `length`
+fn index_argument(
+    arguments: &NativeArguments,
+    index: usize,
+    length: usize,
+) -> Result<usize, String> {
+    match arguments.get(index).as_deref() {
+        Some(NovaObject::Int64(position)) if *position >= 0 && *position as usize <= length => {
+            Ok(*position as usize)
+        }
+        Some(NovaObject::Int64(position)) => Err(format!(
+            "Index {} is out of range for a length of {}",
+            position, length
+        )),
+        _ => Err("List indices must be integers".to_string()),
+    }
+}
+
+fn list_argument<'a>(
+    arguments: &'a mut NativeArguments,
+    name: &str,
+) -> Result<&'a mut Vec<NovaObject>, String> {
+    arguments
+        .list_mut(0)
+        .ok_or_else(|| format!("Function '{}()' requires a list", name))
+}
+
+/// `list(values...)` is a new list of the values
+pub fn list_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        let values: Vec<NovaObject> = arguments.iter().map(Cow::into_owned).collect();
+        Ok(NovaObject::List(Box::new(values)))
+    };
+
+    NativeFunction {
+        name: "list".to_string(),
+        function,
+    }
+}
+
+/// `push(list, values...)` adds the values to the end of the list
+pub fn push_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        if arguments.is_empty() {
+            return Err(
+                " Incorrect number of arguments for 'push()', at least 1 needed".to_string(),
+            );
+        }
+
+        let values: Vec<NovaObject> = arguments.iter().skip(1).map(Cow::into_owned).collect();
+        list_argument(&mut arguments, "push")?.extend(values);
+
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "push".to_string(),
+        function,
+    }
+}
+
+/// `pop(list)` removes the last value of the list and returns it
+pub fn pop_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "pop", 1)?;
+
+        list_argument(&mut arguments, "pop")?
+            .pop()
+            .ok_or_else(|| "Cannot pop from an empty list".to_string())
+    };
+
+    NativeFunction {
+        name: "pop".to_string(),
+        function,
+    }
+}
+
+/// `insert(list, index, value)` puts the value at the index, moving the values after it
+pub fn insert_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "insert", 3)?;
+
+        let length = list_argument(&mut arguments, "insert")?.len();
+        let index = index_argument(&arguments, 1, length)?;
+        let value = arguments.get(2).unwrap().into_owned();
+        list_argument(&mut arguments, "insert")?.insert(index, value);
+
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "insert".to_string(),
+        function,
+    }
+}
+
+/// `remove(list, index)` takes the value at the index out of the list and returns it,
+/// `remove(map, key)` does the same for the value under the key
+pub fn remove_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "remove", 2)?;
+
+        if arguments.map_mut(0).is_some() {
+            let key = key_argument(&arguments, 1)?;
+            return arguments
+                .map_mut(0)
+                .unwrap()
+                .remove(&key)
+                .ok_or_else(|| format!("Key {:?} is not in the map", key));
+        }
+
+        let length = list_argument(&mut arguments, "remove")?.len();
+        let index = index_argument(&arguments, 1, length)?;
+        if index == length {
+            return Err(format!(
+                "Index {} is out of range for a length of {}",
+                index, length
+            ));
+        }
+
+        Ok(list_argument(&mut arguments, "remove")?.remove(index))
+    };
+
+    NativeFunction {
+        name: "remove".to_string(),
+        function,
+    }
+}
+
+/// `len(value)` is the number of values in a list or map, or characters in a string
+pub fn len_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "len", 1)?;
+
+        match arguments.get(0).unwrap().as_ref() {
+            NovaObject::List(list) => Ok(NovaObject::Int64(list.len() as i64)),
+            NovaObject::Map(map) => Ok(NovaObject::Int64(map.len() as i64)),
+            NovaObject::String(string) => Ok(NovaObject::Int64(string.chars().count() as i64)),
+            value => Err(format!("{} has no length", value.kind_name())),
+        }
+    };
+
+    NativeFunction {
+        name: "len".to_string(),
+        function,
+    }
+}
+
+/// `slice(list, start, end)` is a new list with the values from start up to end, `end`
+/// defaulting to the length. it is a copy, changing it leaves the original list alone
+pub fn slice_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 2 && arguments.len() != 3 {
+            return Err(format!(
+                " Incorrect number of arguments for 'slice()', 2 or 3 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        let value = arguments.get(0).unwrap();
+        let NovaObject::List(list) = value.as_ref() else {
+            return Err("Function 'slice()' requires a list".to_string());
+        };
+
+        let start = index_argument(&arguments, 1, list.len())?;
+        let end = match arguments.len() {
+            3 => index_argument(&arguments, 2, list.len())?,
+            _ => list.len(),
+        };
+        if start > end {
+            return Err(format!("Slice start {} is after its end {}", start, end));
+        }
+
+        Ok(NovaObject::List(Box::new(list[start..end].to_vec())))
+    };
+
+    NativeFunction {
+        name: "slice".to_string(),
+        function,
+    }
+}
+
+/// `contains(list, value)` checks the list for an equal value, `contains(string, part)`
+/// for a substring
+pub fn contains_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "contains", 2)?;
+
+        let container = arguments.get(0).unwrap();
+        let value = arguments.get(1).unwrap();
+
+        let contains = match (container.as_ref(), value.as_ref()) {
+            (NovaObject::List(list), value) => list.iter().any(|item| objects_equal(item, value)),
+            (NovaObject::String(string), NovaObject::String(part)) => {
+                string.contains(part.as_str())
+            }
+            _ => {
+                return Err(format!(
+                    "cannot look for {} in {}",
+                    value.kind_name(),
+                    container.kind_name()
+                ))
+            }
+        };
+
+        Ok(NovaObject::Bool(contains))
+    };
+
+    NativeFunction {
+        name: "contains".to_string(),
+        function,
+    }
+}
+
+/// `join(list, separator)` writes the values one after the other with the separator between
+pub fn join_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "join", 2)?;
+
+        let list = arguments.get(0).unwrap();
+        let separator = arguments.get(1).unwrap();
+        let (NovaObject::List(list), NovaObject::String(separator)) =
+            (list.as_ref(), separator.as_ref())
+        else {
+            return Err("Function 'join()' requires a list and a string".to_string());
+        };
+
+        let joined = list
+            .iter()
+            .map(|item| context.float_format.display(item).to_string())
+            .collect::<Vec<String>>()
+            .join(separator);
+
+        Ok(NovaObject::String(Box::new(joined)))
+    };
+
+    NativeFunction {
+        name: "join".to_string(),
+        function,
+    }
+}
+
+/// the values of the list argument, copied so the function they are passed to can run
+fn list_values(arguments: &NativeArguments, name: &str) -> Result<Vec<NovaObject>, String> {
+    match arguments.get(0).as_deref() {
+        Some(NovaObject::List(list)) => Ok(list.to_vec()),
+        _ => Err(format!("Function '{}()' requires a list", name)),
+    }
+}
+
+/// `map(list, function)` is a new list with the function's result for every value
+pub fn map_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "map", 2)?;
+
+        let mut mapped = Vec::new();
+        for value in list_values(&arguments, "map")? {
+            mapped.push(context.call(&mut arguments, 1, vec![value])?);
+        }
+
+        Ok(NovaObject::List(Box::new(mapped)))
+    };
+
+    NativeFunction {
+        name: "map".to_string(),
+        function,
+    }
+}
+
+/// `filter(list, function)` is a new list with the values the function returns something
+/// truthy for
+pub fn filter_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "filter", 2)?;
+
+        let mut kept = Vec::new();
+        for value in list_values(&arguments, "filter")? {
+            if context
+                .call(&mut arguments, 1, vec![value.clone()])?
+                .is_truthy()
+            {
+                kept.push(value);
+            }
+        }
+
+        Ok(NovaObject::List(Box::new(kept)))
+    };
+
+    NativeFunction {
+        name: "filter".to_string(),
+        function,
+    }
+}
+
+/// `reduce(list, function, initial)` passes the result so far and the next value to the
+/// function, starting from `initial` or the first value when it is left out
+pub fn reduce_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 2 && arguments.len() != 3 {
+            return Err(format!(
+                " Incorrect number of arguments for 'reduce()', 2 or 3 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        let mut values = list_values(&arguments, "reduce")?.into_iter();
+        let mut result = match arguments.get(2) {
+            Some(initial) => initial.into_owned(),
+            None => values.next().ok_or_else(|| {
+                "Cannot reduce an empty list without an initial value".to_string()
+            })?,
+        };
+
+        for value in values {
+            result = context.call(&mut arguments, 1, vec![result, value])?;
+        }
+
+        Ok(result)
+    };
+
+    NativeFunction {
+        name: "reduce".to_string(),
+        function,
+    }
+}
+
+/// `each(list, function)` calls the function with every value
+pub fn each_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "each", 2)?;
+
+        for value in list_values(&arguments, "each")? {
+            context.call(&mut arguments, 1, vec![value])?;
+        }
+
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "each".to_string(),
+        function,
+    }
+}
+
+fn key_argument(arguments: &NativeArguments, index: usize) -> Result<String, String> {
+    match arguments.get(index).as_deref() {
+        Some(NovaObject::String(key)) => Ok(key.to_string()),
+        Some(key) => Err(format!("Map keys must be strings, not {}", key.kind_name())),
+        None => Err("Missing map key".to_string()),
+    }
+}
+
+fn map_argument<'a>(
+    arguments: &'a NativeArguments,
+    index: usize,
+    name: &str,
+) -> Result<Cow<'a, NovaMap>, String> {
+    match arguments.get(index) {
+        Some(Cow::Borrowed(NovaObject::Map(map))) => Ok(Cow::Borrowed(map)),
+        Some(Cow::Owned(NovaObject::Map(map))) => Ok(Cow::Owned(*map)),
+        _ => Err(format!("Function '{}()' requires a map", name)),
+    }
+}
+
+/// `dict(key, value, ...)` is a new map with the keys in the order they are given
+pub fn dict_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if !arguments.len().is_multiple_of(2) {
+            return Err("Function 'dict()' requires a value for every key".to_string());
+        }
+
+        let mut map = NovaMap::new();
+        for index in (0..arguments.len()).step_by(2) {
+            let key = key_argument(&arguments, index)?;
+            map.insert(key, arguments.get(index + 1).unwrap().into_owned());
+        }
+
+        Ok(NovaObject::Map(Box::new(map)))
+    };
+
+    NativeFunction {
+        name: "dict".to_string(),
+        function,
+    }
+}
+
+/// `get(map, key, default)` is the value under the key, or `default` when the key is missing.
+/// `default` is None when it is left out
+pub fn get_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 2 && arguments.len() != 3 {
+            return Err(format!(
+                " Incorrect number of arguments for 'get()', 2 or 3 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        let map = map_argument(&arguments, 0, "get")?;
+        let key = key_argument(&arguments, 1)?;
+        match map.get(&key) {
+            Some(value) => Ok(value.clone()),
+            None => Ok(arguments
+                .get(2)
+                .map(Cow::into_owned)
+                .unwrap_or(NovaObject::None)),
+        }
+    };
+
+    NativeFunction {
+        name: "get".to_string(),
+        function,
+    }
+}
+
+/// `set(map, key, value)` puts the value under the key, a new key goes after the others
+pub fn set_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, mut arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "set", 3)?;
+
+        let key = key_argument(&arguments, 1)?;
+        let value = arguments.get(2).unwrap().into_owned();
+        arguments
+            .map_mut(0)
+            .ok_or_else(|| "Function 'set()' requires a map".to_string())?
+            .insert(key, value);
+
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "set".to_string(),
+        function,
+    }
+}
+
+/// `keys(map)` is a list of the keys in insertion order
+pub fn keys_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "keys", 1)?;
+
+        let map = map_argument(&arguments, 0, "keys")?;
+        let keys = map
+            .keys()
+            .map(|key| NovaObject::String(Box::new(key.clone())))
+            .collect();
+
+        Ok(NovaObject::List(Box::new(keys)))
+    };
+
+    NativeFunction {
+        name: "keys".to_string(),
+        function,
+    }
+}
+
+/// `values(map)` is a list of the values in the order of their keys
+pub fn values_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "values", 1)?;
+
+        let map = map_argument(&arguments, 0, "values")?;
+        Ok(NovaObject::List(Box::new(map.values().cloned().collect())))
+    };
+
+    NativeFunction {
+        name: "values".to_string(),
+        function,
+    }
+}
+
+/// `has(map, key)` checks whether the key is in the map
+pub fn has_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "has", 2)?;
+
+        let map = map_argument(&arguments, 0, "has")?;
+        Ok(NovaObject::Bool(
+            map.contains_key(&key_argument(&arguments, 1)?),
+        ))
+    };
+
+    NativeFunction {
+        name: "has".to_string(),
+        function,
+    }
+}
+
+/// `merge(first, second)` is a new map with the entries of both, the values of `second`
+/// winning. keys of `first` come first, followed by the keys only `second` has
+pub fn merge_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "merge", 2)?;
+
+        let first = map_argument(&arguments, 0, "merge")?;
+        let second = map_argument(&arguments, 1, "merge")?;
+
+        let mut merged = first.into_owned();
+        for (key, value) in second.iter() {
+            merged.insert(key.clone(), value.clone());
+        }
+
+        Ok(NovaObject::Map(Box::new(merged)))
+    };
+
+    NativeFunction {
+        name: "merge".to_string(),
+        function,
+    }
+}
+
+/// structural equality through any depth of lists and maps, integers and floats being equal by
+/// value wherever they are. values are trees, a list holds copies and never itself, so there are
+/// no cycles to guard against, but nesting is walked with a stack rather than recursion
+pub fn values_deep_equal(left: &NovaObject, right: &NovaObject) -> bool {
+    let mut pending = vec![(left, right)];
+
+    while let Some((left, right)) = pending.pop() {
+        match (left, right) {
+            (NovaObject::List(left), NovaObject::List(right)) => {
+                if left.len() != right.len() {
+                    return false;
+                }
+                pending.extend(left.iter().zip(right.iter()));
+            }
+            (NovaObject::Map(left), NovaObject::Map(right)) => {
+                if left.len() != right.len() {
+                    return false;
+                }
+                for (key, value) in left.iter() {
+                    match right.get(key) {
+                        Some(other) => pending.push((value, other)),
+                        None => return false,
+                    }
+                }
+            }
+            (left, right) => {
+                if !objects_equal(left, right) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    true
+}
+
+/// `deep_equals(a, b)` compares lists and maps by their contents at every depth, so unlike `==`
+/// `[1]` and `[1.0]` are equal
+pub fn deep_equals_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "deep_equals", 2)?;
+
+        let left = arguments.get(0).unwrap();
+        let right = arguments.get(1).unwrap();
+        Ok(NovaObject::Bool(values_deep_equal(&left, &right)))
+    };
+
+    NativeFunction {
+        name: "deep_equals".to_string(),
+        function,
+    }
+}
+
+/// `copy(value, deep)` is a copy that changing the original does not affect. `deep` defaults to
+/// true and is accepted for when values can be shared, nested lists and maps are held by value
+/// so a shallow copy is already independent of the original
+pub fn copy_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.is_empty() || arguments.len() > 2 {
+            return Err(format!(
+                " Incorrect number of arguments for 'copy()', 1 or 2 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        match arguments.get(1).as_deref() {
+            None | Some(NovaObject::Bool(_)) => {}
+            Some(deep) => {
+                return Err(format!(
+                    "Argument 'deep' of 'copy()' must be a bool, not {}",
+                    deep.kind_name()
+                ))
+            }
+        }
+
+        Ok(arguments.get(0).unwrap().into_owned())
+    };
+
+    NativeFunction {
+        name: "copy".to_string(),
+        function,
+    }
+}
+
+fn separator_argument(arguments: &NativeArguments, index: usize) -> Result<Option<String>, String> {
+    match arguments.get(index).as_deref() {
+        None => Ok(None),
+        Some(NovaObject::String(separator)) if separator.is_empty() => {
+            Err("Separator cannot be empty".to_string())
+        }
+        Some(NovaObject::String(separator)) => Ok(Some(separator.to_string())),
+        Some(separator) => Err(format!(
+            "Separator must be a string, not {}",
+            separator.kind_name()
+        )),
+    }
+}
+
+fn string_list(parts: impl Iterator<Item = impl Into<String>>) -> NovaObject {
+    let parts = parts
+        .map(|part| NovaObject::String(Box::new(part.into())))
+        .collect();
+    NovaObject::List(Box::new(parts))
+}
+
+/// `split(string, separator)` is the list of the parts between separators, so
+/// `join(split(text, separator), separator)` gives back the text. without a separator the
+/// string is split on whitespace and empty parts are dropped
+pub fn split_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 1 && arguments.len() != 2 {
+            return Err(format!(
+                " Incorrect number of arguments for 'split()', 1 or 2 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        let text = arguments.get(0).unwrap();
+        let NovaObject::String(text) = text.as_ref() else {
+            return Err("Function 'split()' requires a string".to_string());
+        };
+
+        match separator_argument(&arguments, 1)? {
+            Some(separator) => Ok(string_list(text.split(separator.as_str()))),
+            None => Ok(string_list(text.split_whitespace())),
+        }
+    };
+
+    NativeFunction {
+        name: "split".to_string(),
+        function,
+    }
+}
+
+/// `csv.parse_line(line, separator)` is the list of fields in one line of csv. fields can be
+/// quoted to hold the separator, a quote inside a quoted field is written twice. the separator
+/// is a comma when it is left out
+pub fn csv_parse_line_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 1 && arguments.len() != 2 {
+            return Err(format!(
+                " Incorrect number of arguments for 'csv.parse_line()', 1 or 2 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        let line = arguments.get(0).unwrap();
+        let NovaObject::String(line) = line.as_ref() else {
+            return Err("Function 'csv.parse_line()' requires a string".to_string());
+        };
+        let separator = separator_argument(&arguments, 1)?.unwrap_or_else(|| ",".to_string());
+
+        Ok(string_list(parse_csv_line(line, &separator)?.into_iter()))
+    };
+
+    NativeFunction {
+        name: "csv.parse_line".to_string(),
+        function,
+    }
+}
+
+fn parse_csv_line(line: &str, separator: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut rest = line;
+
+    loop {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            rest = quoted;
+            loop {
+                match rest.find('"') {
+                    Some(end) if rest[end + 1..].starts_with('"') => {
+                        field.push_str(&rest[..=end]);
+                        rest = &rest[end + 2..];
+                    }
+                    Some(end) => {
+                        field.push_str(&rest[..end]);
+                        rest = &rest[end + 1..];
+                        break;
+                    }
+                    None => return Err(format!("Unterminated quoted field in {:?}", line)),
+                }
+            }
+        }
+
+        match rest.find(separator) {
+            Some(end) => {
+                field.push_str(&rest[..end]);
+                fields.push(std::mem::take(&mut field));
+                rest = &rest[end + separator.len()..];
+            }
+            None => {
+                field.push_str(rest);
+                fields.push(field);
+                return Ok(fields);
+            }
+        }
+    }
+}
+
+/// `csv.format_line(values, separator)` writes the values as one line of csv, the opposite of
+/// `csv.parse_line`. fields holding the separator, a quote or a line break are quoted
+pub fn csv_format_line_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        if arguments.len() != 1 && arguments.len() != 2 {
+            return Err(format!(
+                " Incorrect number of arguments for 'csv.format_line()', 1 or 2 needed while {} provided",
+                arguments.len()
+            ));
+        }
+
+        let values = arguments.get(0).unwrap();
+        let NovaObject::List(values) = values.as_ref() else {
+            return Err("Function 'csv.format_line()' requires a list".to_string());
+        };
+        let separator = separator_argument(&arguments, 1)?.unwrap_or_else(|| ",".to_string());
+
+        let fields = values
+            .iter()
+            .map(|value| {
+                let field = context.float_format.display(value).to_string();
+                if field.contains(separator.as_str()) || field.contains(['"', '\n', '\r']) {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field
+                }
+            })
+            .collect::<Vec<String>>();
+
+        Ok(NovaObject::String(Box::new(fields.join(&separator))))
+    };
+
+    NativeFunction {
+        name: "csv.format_line".to_string(),
+        function,
+    }
+}
+
+/// `hash(value)` is the hash the virtual machine uses for its own tables. it is fast but not
+/// cryptographic and may change between versions, so keep it to cache keys and deduplication
+/// within a run. values that are equal with `==` hash the same
+pub fn hash_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "hash", 1)?;
+
+        let mut hasher = FxHasher::default();
+        match arguments.get(0).unwrap().as_ref() {
+            NovaObject::String(string) => string.hash(&mut hasher),
+            NovaObject::Int64(value) => value.hash(&mut hasher),
+            NovaObject::Float64(value) if value.fract() == 0.0 => (*value as i64).hash(&mut hasher),
+            NovaObject::Float64(value) => value.to_bits().hash(&mut hasher),
+            NovaObject::Bool(value) => value.hash(&mut hasher),
+            value => return Err(format!("cannot hash {}", value.kind_name())),
+        }
+
+        Ok(NovaObject::Int64(hasher.finish() as i64))
+    };
+
+    NativeFunction {
+        name: "hash".to_string(),
+        function,
+    }
+}
+
+/// the bytes of a string, or of a list of integers from 0 to 255
+#[cfg(feature = "digest")]
+fn bytes_argument(arguments: &NativeArguments, name: &str) -> Result<Vec<u8>, String> {
+    match arguments.get(0).as_deref() {
+        Some(NovaObject::String(string)) => Ok(string.as_bytes().to_vec()),
+        Some(NovaObject::List(list)) => list
+            .iter()
+            .map(|item| match item {
+                NovaObject::Int64(byte) if (0..=255).contains(byte) => Ok(*byte as u8),
+                _ => Err(format!("{} is not a byte", item)),
+            })
+            .collect(),
+        _ => Err(format!(
+            "Function '{}()' requires a string or a list of bytes",
+            name
+        )),
+    }
+}
+
+#[cfg(feature = "digest")]
+fn hex_digest(digest: &[u8]) -> NovaObject {
+    let hex = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    NovaObject::String(Box::new(hex))
+}
+
+/// `md5(value)` is the md5 digest of a string or byte list in hex, only with the `digest` feature
+#[cfg(feature = "digest")]
+pub fn md5_native() -> NativeFunction {
+    use md5::{Digest, Md5};
+
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "md5", 1)?;
+        Ok(hex_digest(&Md5::digest(bytes_argument(&arguments, "md5")?)))
+    };
+
+    NativeFunction {
+        name: "md5".to_string(),
+        function,
+    }
+}
+
+/// `sha256(value)` is the sha-256 digest of a string or byte list in hex, only with the
+/// `digest` feature
+#[cfg(feature = "digest")]
+pub fn sha256_native() -> NativeFunction {
+    use sha2::{Digest, Sha256};
+
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "sha256", 1)?;
+        Ok(hex_digest(&Sha256::digest(bytes_argument(
+            &arguments, "sha256",
+        )?)))
+    };
+
+    NativeFunction {
+        name: "sha256".to_string(),
+        function,
+    }
+}
+
+fn string_argument(
+    arguments: &NativeArguments,
+    index: usize,
+    name: &str,
+) -> Result<String, String> {
+    match arguments.get(index).as_deref() {
+        Some(NovaObject::String(string)) => Ok(string.to_string()),
+        _ => Err(format!("Function '{}()' requires a string", name)),
+    }
+}
+
+/// `os.env(name)` is the value of the environment variable, None when it is not set.
+/// needs `Capability::ReadEnvironment`
+pub fn os_env_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "os.env", 1)?;
+        context
+            .capabilities
+            .require(Capability::ReadEnvironment, "os.env")?;
+
+        let name = string_argument(&arguments, 0, "os.env")?;
+        match std::env::var(name) {
+            Ok(value) => Ok(NovaObject::String(Box::new(value))),
+            Err(_) => Ok(NovaObject::None),
+        }
+    };
+
+    NativeFunction {
+        name: "os.env".to_string(),
+        function,
+    }
+}
+
+/// `os.set_env(name, value)` sets the environment variable for the whole process.
+/// needs `Capability::WriteEnvironment`
+pub fn os_set_env_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "os.set_env", 2)?;
+        context
+            .capabilities
+            .require(Capability::WriteEnvironment, "os.set_env")?;
+
+        let name = string_argument(&arguments, 0, "os.set_env")?;
+        let value = string_argument(&arguments, 1, "os.set_env")?;
+        if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+            return Err(format!("Cannot set the environment variable {:?}", name));
+        }
+
+        std::env::set_var(name, value);
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "os.set_env".to_string(),
+        function,
+    }
+}
+
+/// `os.platform()` names the operating system, like "linux", "macos" or "windows"
+pub fn os_platform_native() -> NativeFunction {
+    let function = |_: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "os.platform", 0)?;
+        Ok(NovaObject::String(Box::new(
+            std::env::consts::OS.to_string(),
+        )))
+    };
+
+    NativeFunction {
+        name: "os.platform".to_string(),
+        function,
+    }
+}
+
+/// `os.cwd()` is the working directory. needs `Capability::ReadEnvironment`
+pub fn os_cwd_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "os.cwd", 0)?;
+        context
+            .capabilities
+            .require(Capability::ReadEnvironment, "os.cwd")?;
+
+        let directory = std::env::current_dir().map_err(|error| error.to_string())?;
+        Ok(NovaObject::String(Box::new(
+            directory.to_string_lossy().into_owned(),
+        )))
+    };
+
+    NativeFunction {
+        name: "os.cwd".to_string(),
+        function,
+    }
+}
+
+/// `os.chdir(path)` changes the working directory of the whole process.
+/// needs `Capability::WriteEnvironment`
+pub fn os_chdir_native() -> NativeFunction {
+    let function = |context: &mut NativeContext, arguments: NativeArguments| -> NativeResult {
+        expect_arguments(&arguments, "os.chdir", 1)?;
+        context
+            .capabilities
+            .require(Capability::WriteEnvironment, "os.chdir")?;
+
+        let path = string_argument(&arguments, 0, "os.chdir")?;
+        std::env::set_current_dir(&path)
+            .map_err(|error| format!("Cannot change directory to {:?}: {}", path, error))?;
+
+        Ok(NovaObject::None)
+    };
+
+    NativeFunction {
+        name: "os.chdir".to_string(),
+        function,
+    }
+}
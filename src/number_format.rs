@@ -0,0 +1,264 @@
+use alloc::{format, string::String};
+use core::fmt::{self, Display};
+
+#[cfg(not(feature = "std"))]
+use crate::float::Float;
+use crate::object::NovaObject;
+
+/// lists and maps nested deeper than this are written as `[...]` and `{...}`. values hold their
+/// contents by copy so they cannot contain themselves, the limit keeps printing a deeply nested
+/// value from exhausting the stack
+pub const MAX_DISPLAY_DEPTH: usize = 32;
+
+/// the significant digits `FloatFormat::Rounded` keeps, every decimal number with this many
+/// digits reads back from a float unchanged
+pub const SIGNIFICANT_DIGITS: usize = 15;
+
+/// how printing writes floats, set with `VirtualMachine::set_float_format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatFormat {
+    /// the shortest form of the float rounded to `SIGNIFICANT_DIGITS` digits, which drops the
+    /// noise of float arithmetic: `0.1 + 0.2` is `0.3` and `10 / 2` is `5`
+    #[default]
+    Rounded,
+    /// the fewest digits that read back as the exact same float, `0.1 + 0.2` is
+    /// `0.30000000000000004`
+    Shortest,
+    /// like `Rounded`, but whole floats keep a `.0` so they can be told apart from integers
+    Decimal,
+    /// always this many digits after the point
+    Fixed(usize),
+    /// one digit before the point and an exponent, `1234.5` is `1.2345e3`
+    Scientific,
+}
+
+impl FloatFormat {
+    pub fn write(self, f: &mut impl fmt::Write, value: f64) -> fmt::Result {
+        match self {
+            FloatFormat::Rounded => write!(f, "{}", round_significant(value)),
+            FloatFormat::Shortest => write!(f, "{}", value),
+            FloatFormat::Decimal if value.is_finite() && value.fract() == 0.0 => {
+                write!(f, "{:.1}", value)
+            }
+            FloatFormat::Decimal => write!(f, "{}", round_significant(value)),
+            FloatFormat::Fixed(precision) => write!(f, "{:.*}", precision, value),
+            FloatFormat::Scientific => write!(f, "{:e}", value),
+        }
+    }
+
+    pub fn format(self, value: f64) -> String {
+        let mut formatted = String::new();
+        let _ = self.write(&mut formatted, value);
+        formatted
+    }
+
+    /// the object written like `NovaObject` displays it, with this format for the floats
+    /// inside lists too
+    pub fn display(self, object: &NovaObject) -> FormattedObject<'_> {
+        FormattedObject {
+            object,
+            format: self,
+            depth: 0,
+        }
+    }
+}
+
+/// the value rounded to `SIGNIFICANT_DIGITS` significant digits
+fn round_significant(value: f64) -> f64 {
+    if !value.is_finite() {
+        return value;
+    }
+
+    format!("{:.*e}", SIGNIFICANT_DIGITS - 1, value)
+        .parse()
+        .unwrap_or(value)
+}
+
+/// an object displayed with a float format, from `FloatFormat::display`
+pub struct FormattedObject<'a> {
+    object: &'a NovaObject,
+    format: FloatFormat,
+    /// number of lists and maps around the object
+    depth: usize,
+}
+
+impl FormattedObject<'_> {
+    /// an item of the list or map being written, strings are quoted
+    fn write_item(&self, f: &mut fmt::Formatter<'_>, item: &NovaObject) -> fmt::Result {
+        match item {
+            NovaObject::String(string) => write!(f, "{:?}", string),
+            _ => {
+                let item = FormattedObject {
+                    object: item,
+                    format: self.format,
+                    depth: self.depth + 1,
+                };
+                write!(f, "{}", item)
+            }
+        }
+    }
+}
+
+impl Display for FormattedObject<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.object {
+            NovaObject::None => write!(f, "None"),
+            NovaObject::Bool(bool) => write!(f, "{}", bool),
+            NovaObject::Int64(int) => write!(f, "{}", int),
+            NovaObject::Float64(value) => self.format.write(f, *value),
+            NovaObject::String(string) => write!(f, "{}", string),
+            NovaObject::List(_) if self.depth >= MAX_DISPLAY_DEPTH => write!(f, "[...]"),
+            NovaObject::List(list) => {
+                write!(f, "[")?;
+                for (index, item) in list.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    self.write_item(f, item)?;
+                }
+                write!(f, "]")
+            }
+            NovaObject::Map(_) if self.depth >= MAX_DISPLAY_DEPTH => write!(f, "{{...}}"),
+            NovaObject::Map(map) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in map.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{:?}: ", key)?;
+                    self.write_item(f, value)?;
+                }
+                write!(f, "}}")
+            }
+            NovaObject::NovaFunction(nova_function) => {
+                write!(
+                    f,
+                    "function: {}, parameters: {}",
+                    nova_function.name, nova_function.arity
+                )
+            }
+            NovaObject::NativeFunction(native_function) => {
+                write!(f, "function: {}", native_function.name)
+            }
+        }
+    }
+}
+
+/// the second argument of `format` as a string: a style, or `.N` for N digits after the point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatSpec {
+    Style(IntegerStyle),
+    Precision(usize),
+}
+
+impl FormatSpec {
+    pub fn parse(spec: &str) -> Option<Self> {
+        match spec.strip_prefix('.') {
+            Some(digits) => digits.parse().ok().map(Self::Precision),
+            None => IntegerStyle::from_name(spec).map(Self::Style),
+        }
+    }
+}
+
+/// ways `format` can write an integer, named like the rust format traits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerStyle {
+    /// `x`
+    Hex,
+    /// `b`
+    Binary,
+    /// `o`
+    Octal,
+    /// `e`
+    Scientific,
+}
+
+impl IntegerStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "x" => Some(Self::Hex),
+            "b" => Some(Self::Binary),
+            "o" => Some(Self::Octal),
+            "e" => Some(Self::Scientific),
+            _ => None,
+        }
+    }
+
+    /// negative numbers are written as a minus sign and their magnitude, not in two's complement
+    pub fn format(self, value: i64) -> String {
+        let sign = if value < 0 { "-" } else { "" };
+        let magnitude = value.unsigned_abs();
+
+        match self {
+            IntegerStyle::Hex => format!("{}{:x}", sign, magnitude),
+            IntegerStyle::Binary => format!("{}{:b}", sign, magnitude),
+            IntegerStyle::Octal => format!("{}{:o}", sign, magnitude),
+            IntegerStyle::Scientific => format!("{:e}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod number_format_tests {
+    use crate::object::NovaObject;
+
+    use super::{FloatFormat, FormatSpec, IntegerStyle, MAX_DISPLAY_DEPTH};
+
+    #[test]
+    fn test_float_formats() {
+        assert_eq!(FloatFormat::default().format(0.1 + 0.2), "0.3");
+        assert_eq!(FloatFormat::Rounded.format(1.0 / 3.0), "0.333333333333333");
+        assert_eq!(FloatFormat::Rounded.format(1e20), "100000000000000000000");
+        assert_eq!(FloatFormat::Rounded.format(f64::INFINITY), "inf");
+        assert_eq!(
+            FloatFormat::Shortest.format(0.1 + 0.2),
+            "0.30000000000000004"
+        );
+        assert_eq!(FloatFormat::Shortest.format(5.0), "5");
+        assert_eq!(FloatFormat::Decimal.format(5.0), "5.0");
+        assert_eq!(FloatFormat::Decimal.format(2.5), "2.5");
+        assert_eq!(FloatFormat::Decimal.format(0.1 + 0.2), "0.3");
+        assert_eq!(FloatFormat::Fixed(2).format(1.0 / 3.0), "0.33");
+        assert_eq!(FloatFormat::Scientific.format(1234.5), "1.2345e3");
+
+        assert_eq!(IntegerStyle::Hex.format(255), "ff");
+        assert_eq!(IntegerStyle::Hex.format(-255), "-ff");
+        assert_eq!(IntegerStyle::Binary.format(5), "101");
+        assert_eq!(IntegerStyle::Octal.format(8), "10");
+        assert_eq!(IntegerStyle::Scientific.format(1500), "1.5e3");
+
+        assert_eq!(FormatSpec::parse(".2"), Some(FormatSpec::Precision(2)));
+        assert_eq!(
+            FormatSpec::parse("x"),
+            Some(FormatSpec::Style(IntegerStyle::Hex))
+        );
+        assert_eq!(FormatSpec::parse(".x"), None);
+        assert_eq!(FormatSpec::parse("y"), None);
+
+        let list = NovaObject::List(Box::new(vec![
+            NovaObject::Float64(1.0),
+            NovaObject::String(Box::new("a".to_string())),
+        ]));
+        assert_eq!(
+            FloatFormat::Decimal.display(&list).to_string(),
+            "[1.0, \"a\"]"
+        );
+    }
+
+    #[test]
+    fn test_deep_nesting_is_elided() {
+        let mut nested = NovaObject::Int64(1);
+        for _ in 0..MAX_DISPLAY_DEPTH + 2 {
+            nested = NovaObject::List(Box::new(vec![nested]));
+        }
+
+        let written = nested.to_string();
+        let depth = MAX_DISPLAY_DEPTH;
+        assert_eq!(
+            written,
+            format!("{}[...]{}", "[".repeat(depth), "]".repeat(depth))
+        );
+    }
+}
@@ -1,35 +1,173 @@
-use std::fmt::Display;
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt::Display;
 
-use crate::instruction::Instruction;
-use rustc_hash::FxHashMap;
+use crate::{
+    collections::{Map, Set},
+    instruction::Instruction,
+    natives::{NativeArguments, NativeContext},
+    number_format::FloatFormat,
+};
 
 pub type ValueID = String;
 pub type BaseNumber = f32;
-pub type MappedMemory = FxHashMap<ValueID, Instruction>;
+pub type MappedMemory = Map<ValueID, Instruction>;
+/// locations of the globals that cannot be written to, see `VirtualMachine::freeze_global`
+pub type FrozenGlobals = Set<Instruction>;
+pub type NativeResult = Result<NovaObject, String>;
+pub type NativeFunctionPointer = fn(&mut NativeContext, NativeArguments) -> NativeResult;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// name given to anonymous functions, they are not bound to a global
+pub const LAMBDA_NAME: &str = "<lambda>";
+
+#[derive(Debug, Clone)]
 pub struct NovaFunction {
     pub name: Box<String>,
     pub address: Instruction,
     pub arity: Instruction,
+    /// number of trailing parameters that have a default value
+    pub default_parameters: Instruction,
+    /// extra arguments are packed into a list passed after the named parameters
+    pub is_variadic: bool,
     pub is_method: bool,
     pub number_of_locals: Instruction,
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+impl NovaFunction {
+    pub fn is_lambda(&self) -> bool {
+        self.name.as_str() == LAMBDA_NAME
+    }
+}
+
+/// a function is only equal to itself, the address it starts at identifies it
+impl PartialEq for NovaFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.address == other.address
+    }
+}
+
+/// functions have no order, only a function and itself compare
+impl PartialOrd for NovaFunction {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        (self == other).then_some(core::cmp::Ordering::Equal)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct NativeFunction {
     pub name: String,
-    pub function: fn(Vec<NovaObject>) -> Result<NovaObject, String>,
+    pub function: NativeFunctionPointer,
+}
+
+/// a native is only equal to itself, natives are loaded once under their name
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
 }
 
+impl PartialOrd for NativeFunction {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        (self == other).then_some(core::cmp::Ordering::Equal)
+    }
+}
+
+/// values are equal when they are the same kind with the same contents, strings, lists and maps
+/// included. functions are compared by identity
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum NovaObject {
     None,
+    Bool(bool),
     Int64(i64),
     Float64(f64),
     NovaFunction(NovaFunction),
     NativeFunction(NativeFunction),
     String(Box<String>),
+    List(Box<Vec<NovaObject>>),
+    Map(Box<NovaMap>),
+}
+
+/// map with string keys. keys are kept in the order they were first inserted in and
+/// every iteration follows that order, so printing a map always gives the same output
+#[derive(Debug, Clone, Default)]
+pub struct NovaMap {
+    entries: Vec<(String, NovaObject)>,
+    /// position of every key in the entries
+    indices: Map<String, usize>,
+}
+
+impl NovaMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&NovaObject> {
+        let index = *self.indices.get(key)?;
+        Some(&self.entries[index].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    /// a key that is already in the map keeps its position, the old value is returned
+    pub fn insert(&mut self, key: String, value: NovaObject) -> Option<NovaObject> {
+        if let Some(&index) = self.indices.get(&key) {
+            return Some(core::mem::replace(&mut self.entries[index].1, value));
+        }
+
+        self.indices.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+        None
+    }
+
+    /// the keys after the removed one move up, keeping their order
+    pub fn remove(&mut self, key: &str) -> Option<NovaObject> {
+        let index = self.indices.remove(key)?;
+        let (_, value) = self.entries.remove(index);
+
+        for (key, _) in &self.entries[index..] {
+            *self.indices.get_mut(key).unwrap() -= 1;
+        }
+
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &NovaObject)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &NovaObject> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+/// maps are equal when they hold the same keys with equal values, in any order
+impl PartialEq for NovaMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+/// maps have no order, only equal maps compare
+impl PartialOrd for NovaMap {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        (self == other).then_some(core::cmp::Ordering::Equal)
+    }
 }
 
 pub enum NovaCallable<'a> {
@@ -67,6 +205,45 @@ impl NovaObject {
         matches!(self, NovaObject::String(_))
     }
 
+    /// the one truthiness model, used by conditions, `not`, `and`, `or` and the natives.
+    /// these are falsy and everything else, functions included, is truthy
+    ///
+    /// | value                | falsy when          |
+    /// |----------------------|---------------------|
+    /// | `None`               | always              |
+    /// | `Bool`               | `false`             |
+    /// | `Int64`              | `0`                 |
+    /// | `Float64`            | `0.0` or `-0.0`     |
+    /// | `String`             | empty               |
+    /// | `List`, `Map`        | empty               |
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            NovaObject::None => false,
+            NovaObject::Bool(bool) => *bool,
+            NovaObject::Int64(int) => *int != 0,
+            NovaObject::Float64(float) => *float != 0.0,
+            NovaObject::String(string) => !string.is_empty(),
+            NovaObject::List(list) => !list.is_empty(),
+            NovaObject::Map(map) => !map.is_empty(),
+            NovaObject::NovaFunction(_) | NovaObject::NativeFunction(_) => true,
+        }
+    }
+
+    /// name of the variant, used to group objects when inspecting the heap
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            NovaObject::None => "None",
+            NovaObject::Bool(_) => "Bool",
+            NovaObject::Int64(_) => "Int64",
+            NovaObject::Float64(_) => "Float64",
+            NovaObject::NovaFunction(_) => "NovaFunction",
+            NovaObject::NativeFunction(_) => "NativeFunction",
+            NovaObject::String(_) => "String",
+            NovaObject::List(_) => "List",
+            NovaObject::Map(_) => "Map",
+        }
+    }
+
     pub fn is_callable(&self) -> bool {
         matches!(
             self,
@@ -85,25 +262,11 @@ impl NovaObject {
     }
 }
 
+/// written the way `print` shows it, lists and maps nested deeper than `MAX_DISPLAY_DEPTH`
+/// are elided
 impl Display for NovaObject {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            NovaObject::None => write!(f, "None"),
-            NovaObject::Int64(int) => write!(f, "{}", int),
-            NovaObject::Float64(float) => write!(f, "{}", float),
-            NovaObject::String(string) => write!(f, "{}", string),
-            NovaObject::NovaFunction(nova_function) => {
-                write!(
-                    f,
-                    "function: {}, parameters: {}",
-                    nova_function.name, nova_function.arity
-                )
-            }
-
-            NovaObject::NativeFunction(native_function) => {
-                write!(f, "function: {}", native_function.name)
-            }
-        }
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", FloatFormat::default().display(self))
     }
 }
 
@@ -127,6 +290,9 @@ pub enum RegisterValueKind {
     StrImm,
 
     NovaFunctionID(NovaFunctionID),
+    /// Index of a native function in the natives table, the value being
+    /// the index of its object in memory
+    NativeFunctionID(u32),
 }
 
 impl RegisterValueKind {
@@ -145,15 +311,39 @@ impl RegisterValueKind {
         matches!(self, Self::Int64)
     }
 
+    #[inline(always)]
+    pub fn is_bool(&self) -> bool {
+        matches!(self, Self::Bool)
+    }
+
+    #[inline(always)]
+    pub fn is_nova_function(&self) -> bool {
+        matches!(self, Self::NovaFunctionID(_))
+    }
+
+    /// whether the value is the index of an object in memory, native functions included
     #[inline(always)]
     pub fn is_mem_address(&self) -> bool {
-        matches!(self, Self::MemAddress)
+        matches!(self, Self::MemAddress | Self::NativeFunctionID(_))
     }
 
     #[inline(always)]
     pub fn is_imm_address(&self) -> bool {
         matches!(self, Self::ImmAddress)
     }
+
+    /// the value is the address of an object, in memory or in the immutables
+    #[inline(always)]
+    pub fn is_object_address(&self) -> bool {
+        matches!(
+            self,
+            Self::MemAddress
+                | Self::ImmAddress
+                | Self::StrMem
+                | Self::StrImm
+                | Self::NativeFunctionID(_)
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -168,14 +358,14 @@ impl NovaFunctionID {
     ) -> Option<Self> {
         let mut value = 0u32;
 
-        if nova_function.number_of_locals > 32 {
+        if nova_function.number_of_locals > 31 {
             return None;
         }
 
         let shifted = (nova_function.number_of_locals as u32) << 27;
         value += shifted;
 
-        if nova_function.arity > 8 {
+        if nova_function.arity > 7 {
             return None;
         }
 
@@ -187,7 +377,21 @@ impl NovaFunctionID {
 
         value += shifted;
 
-        if name_address > 2u32.pow(20u32) {
+        if nova_function.default_parameters > 7 {
+            return None;
+        }
+
+        let shifted = nova_function.default_parameters << 20;
+        value += shifted;
+
+        let is_variadic = if nova_function.is_variadic {
+            1u32
+        } else {
+            0u32
+        };
+        value += is_variadic << 19;
+
+        if name_address >= 2u32.pow(19u32) {
             return None;
         }
 
@@ -198,7 +402,9 @@ impl NovaFunctionID {
 
     pub fn to_labelled(&self) -> NovaFunctionIDLabelled {
         let mut value = self.value;
-        let name_address = value & 0xfffff;
+        let name_address = value & 0x7ffff;
+        let is_variadic = (value >> 19) & 0x1 == 1;
+        let default_parameters = (value >> 20) & 0b111;
         value = value >> 23;
         let is_method = value & 0x1;
         let is_method = is_method == 1;
@@ -210,6 +416,8 @@ impl NovaFunctionID {
         NovaFunctionIDLabelled {
             name_address,
             arity,
+            default_parameters,
+            is_variadic,
             number_of_locals,
             is_method,
         }
@@ -219,6 +427,8 @@ impl NovaFunctionID {
 pub struct NovaFunctionIDLabelled {
     pub name_address: u32,
     pub arity: u32,
+    pub default_parameters: u32,
+    pub is_variadic: bool,
     pub number_of_locals: u32,
     pub is_method: bool,
 }
@@ -232,6 +442,8 @@ mod tests {
         let novafunction = NovaFunction {
             name: Box::new(String::from("Hello")),
             arity: 4,
+            default_parameters: 2,
+            is_variadic: true,
             address: 50,
             is_method: false,
             number_of_locals: 20,
@@ -243,6 +455,8 @@ mod tests {
         let labelled = nova_function_id.to_labelled();
 
         assert_eq!(novafunction.arity, labelled.arity);
+        assert_eq!(novafunction.default_parameters, labelled.default_parameters);
+        assert_eq!(novafunction.is_variadic, labelled.is_variadic);
         assert_eq!(novafunction.number_of_locals, labelled.number_of_locals);
         assert_eq!(novafunction.is_method, labelled.is_method);
         assert_eq!(name_address, labelled.name_address);
@@ -0,0 +1,174 @@
+//! what a virtual machine has loaded before any program: groups of native functions and
+//! modules of the standard library. embedders pick the ones their scripts may use instead of
+//! loading every native, see `VirtualMachine::with_prelude`
+
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::object::NativeFunction;
+#[cfg(all(feature = "nova_tw", feature = "natives"))]
+use crate::{
+    program::Program,
+    stdlib::{self, StdlibError},
+};
+
+/// a group of the native functions in the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeModule {
+    /// printing, assertions, formatting and the clock
+    Core,
+    /// numbers, vectors and matrices
+    Math,
+    /// lists, maps and splitting strings
+    Collections,
+    /// `csv.parse_line` and `csv.format_line`
+    Csv,
+    /// `hash` and the digests
+    Hash,
+    /// the environment of the process, the natives still need their capabilities
+    Os,
+}
+
+impl NativeModule {
+    pub const ALL: [NativeModule; 6] = [
+        NativeModule::Core,
+        NativeModule::Math,
+        NativeModule::Collections,
+        NativeModule::Csv,
+        NativeModule::Hash,
+        NativeModule::Os,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+
+    /// the natives of the module, there are none without the `natives` feature
+    pub fn functions(self) -> Vec<NativeFunction> {
+        #[cfg(feature = "natives")]
+        {
+            use crate::natives;
+
+            match self {
+                NativeModule::Core => natives::core_native_functions(),
+                NativeModule::Math => natives::math_native_functions(),
+                NativeModule::Collections => natives::collection_native_functions(),
+                NativeModule::Csv => natives::csv_native_functions(),
+                NativeModule::Hash => natives::hash_native_functions(),
+                NativeModule::Os => natives::os_native_functions(),
+            }
+        }
+
+        #[cfg(not(feature = "natives"))]
+        Vec::new()
+    }
+}
+
+impl Display for NativeModule {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            NativeModule::Core => "core",
+            NativeModule::Math => "math",
+            NativeModule::Collections => "collections",
+            NativeModule::Csv => "csv",
+            NativeModule::Hash => "hash",
+            NativeModule::Os => "os",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// the native modules and standard library modules a virtual machine loads before any
+/// program. the standard library calls the natives, so its modules need `Core` and
+/// `Collections` as well. nothing is loaded by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Prelude {
+    natives: u32,
+    /// the modules by their index in `stdlib::MODULES`
+    stdlib: u32,
+}
+
+impl Prelude {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// every native module, what the interpreter gives the scripts it runs
+    pub fn natives() -> Self {
+        NativeModule::ALL
+            .into_iter()
+            .fold(Self::none(), |prelude, module| prelude.with(module))
+    }
+
+    /// every native module and the whole standard library
+    pub fn all() -> Self {
+        #[allow(unused_mut)]
+        let mut prelude = Self::natives();
+        #[cfg(all(feature = "nova_tw", feature = "natives"))]
+        {
+            prelude.stdlib = (1 << stdlib::MODULES.len()) - 1;
+        }
+
+        prelude
+    }
+
+    pub fn with(mut self, module: NativeModule) -> Self {
+        self.natives |= module.bit();
+        self
+    }
+
+    pub fn without(mut self, module: NativeModule) -> Self {
+        self.natives &= !module.bit();
+        self
+    }
+
+    pub fn includes(&self, module: NativeModule) -> bool {
+        self.natives & module.bit() != 0
+    }
+
+    /// the natives of the included modules, in the order of `NativeModule::ALL`
+    pub fn native_functions(&self) -> Vec<NativeFunction> {
+        NativeModule::ALL
+            .into_iter()
+            .filter(|module| self.includes(*module))
+            .flat_map(NativeModule::functions)
+            .collect()
+    }
+
+    /// add the standard library module with the name, `collections` for `stdlib/collections.nova`.
+    /// panics if there is no such module
+    #[cfg(all(feature = "nova_tw", feature = "natives"))]
+    pub fn with_stdlib_module(mut self, name: &str) -> Self {
+        match stdlib::MODULES
+            .iter()
+            .position(|(module, _)| *module == name)
+        {
+            Some(index) => self.stdlib |= 1 << index,
+            None => panic!("there is no stdlib module named {}", name),
+        }
+
+        self
+    }
+
+    /// the names of the included standard library modules, in the order they are loaded
+    #[cfg(all(feature = "nova_tw", feature = "natives"))]
+    pub fn stdlib_modules(&self) -> impl Iterator<Item = &'static str> + '_ {
+        stdlib::MODULES
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.stdlib & 1 << index != 0)
+            .map(|(_, (name, _))| *name)
+    }
+
+    /// the compiled standard library modules to load
+    #[cfg(all(feature = "nova_tw", feature = "natives"))]
+    pub(crate) fn stdlib_programs(&self) -> Result<Vec<&'static Program>, StdlibError> {
+        Ok(stdlib::programs()?
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.stdlib & 1 << index != 0)
+            .map(|(_, program)| program)
+            .collect())
+    }
+}
@@ -0,0 +1,372 @@
+use alloc::{format, string::String, vec, vec::Vec};
+use core::{fmt::Display, time::Duration};
+
+use crate::{
+    bytecode::{OpCode, BYTECODE_COUNT, BYTECODE_LOOKUP_TABLE},
+    instruction::{instruction_decoder, Instruction},
+};
+
+// timing functions needs a clock
+#[cfg(feature = "std")]
+use rustc_hash::FxHashMap;
+#[cfg(feature = "std")]
+use std::{
+    io::{self, Write},
+    time::Instant,
+};
+
+#[cfg(feature = "std")]
+use crate::machine::VirtualMachine;
+
+/// observes the instructions run by `VirtualMachine::start_vm_with_profiler`. the calls are
+/// monomorphized, so `start_vm` running with `NoProfiler` pays nothing for them
+pub trait Profiler {
+    /// called after every instruction with the program counter and number of frames it left behind
+    fn executed(&mut self, instruction: Instruction, program_counter: usize, frame_depth: usize);
+
+    /// called once the program halted or raised an error
+    fn finish(&mut self) {}
+
+    /// checked after every instruction, a message stops the run with it as the error. a
+    /// profiler that never interrupts is optimized out of the check
+    #[inline(always)]
+    fn interrupt(&mut self) -> Option<String> {
+        None
+    }
+}
+
+/// the profiler used by `start_vm`, doing nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProfiler;
+
+impl Profiler for NoProfiler {
+    #[inline(always)]
+    fn executed(&mut self, _: Instruction, _: usize, _: usize) {}
+}
+
+/// run two profilers over the same execution
+impl<A: Profiler, B: Profiler> Profiler for (A, B) {
+    #[inline(always)]
+    fn executed(&mut self, instruction: Instruction, program_counter: usize, frame_depth: usize) {
+        self.0.executed(instruction, program_counter, frame_depth);
+        self.1.executed(instruction, program_counter, frame_depth);
+    }
+
+    #[inline(always)]
+    fn interrupt(&mut self) -> Option<String> {
+        self.0.interrupt().or_else(|| self.1.interrupt())
+    }
+
+    fn finish(&mut self) {
+        self.0.finish();
+        self.1.finish();
+    }
+}
+
+/// a profiler that can be switched off, costing a branch per instruction when it is
+impl<P: Profiler> Profiler for Option<P> {
+    #[inline(always)]
+    fn executed(&mut self, instruction: Instruction, program_counter: usize, frame_depth: usize) {
+        if let Some(profiler) = self {
+            profiler.executed(instruction, program_counter, frame_depth);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(profiler) = self {
+            profiler.finish();
+        }
+    }
+}
+
+/// time spent in one function, children included in `total_time` but not in `self_time`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionTiming {
+    pub calls: usize,
+    pub self_time: Duration,
+    pub total_time: Duration,
+}
+
+/// a function on the call stack, None being the top level code
+#[cfg(feature = "std")]
+type FunctionAddress = Option<usize>;
+
+#[cfg(feature = "std")]
+struct Call {
+    function: FunctionAddress,
+    start: Instant,
+    children: Duration,
+}
+
+/// attributes time to nova functions, entered through `Invoke` and left through `Return*`.
+/// calls to native functions are counted in the time of their caller
+#[cfg(feature = "std")]
+pub struct FunctionProfiler {
+    stack: Vec<Call>,
+    timings: FxHashMap<FunctionAddress, FunctionTiming>,
+    /// self time of every distinct call stack, for flamegraphs
+    stacks: FxHashMap<Vec<FunctionAddress>, Duration>,
+}
+
+#[cfg(feature = "std")]
+impl Default for FunctionProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl FunctionProfiler {
+    /// the top level code is timed from here until `finish`
+    pub fn new() -> Self {
+        let mut profiler = Self {
+            stack: Vec::new(),
+            timings: FxHashMap::default(),
+            stacks: FxHashMap::default(),
+        };
+        profiler.enter(None);
+
+        profiler
+    }
+
+    fn enter(&mut self, function: FunctionAddress) {
+        self.timings.entry(function).or_default().calls += 1;
+        self.stack.push(Call {
+            function,
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    fn leave(&mut self) {
+        let path: Vec<FunctionAddress> = self.stack.iter().map(|call| call.function).collect();
+        let Some(call) = self.stack.pop() else {
+            return;
+        };
+
+        let total_time = call.start.elapsed();
+        let self_time = total_time.saturating_sub(call.children);
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children += total_time;
+        }
+
+        let timing = self.timings.entry(call.function).or_default();
+        timing.self_time += self_time;
+        // recursive calls are already inside the total time of the outer call
+        if !self
+            .stack
+            .iter()
+            .any(|outer| outer.function == call.function)
+        {
+            timing.total_time += total_time;
+        }
+
+        *self.stacks.entry(path).or_default() += self_time;
+    }
+
+    pub fn timings(&self) -> &FxHashMap<Option<usize>, FunctionTiming> {
+        &self.timings
+    }
+
+    /// the timings with function names, slowest self time first
+    pub fn report(&self, vm: &VirtualMachine) -> FunctionReport {
+        let mut functions: Vec<(String, FunctionTiming)> = self
+            .timings
+            .iter()
+            .map(|(function, timing)| (function_name(vm, *function), *timing))
+            .collect();
+        functions.sort_by(|(name, timing), (other_name, other_timing)| {
+            other_timing
+                .self_time
+                .cmp(&timing.self_time)
+                .then_with(|| name.cmp(other_name))
+        });
+
+        FunctionReport { functions }
+    }
+
+    /// write one `caller;callee microseconds` line per call stack, the folded format read by
+    /// flamegraph tools
+    pub fn write_folded(&self, vm: &VirtualMachine, output: &mut impl Write) -> io::Result<()> {
+        let mut lines: Vec<(String, u128)> = self
+            .stacks
+            .iter()
+            .map(|(path, time)| {
+                let names: Vec<String> = path
+                    .iter()
+                    .map(|function| function_name(vm, *function))
+                    .collect();
+                (names.join(";"), time.as_micros())
+            })
+            .collect();
+        lines.sort();
+
+        for (path, micros) in lines {
+            writeln!(output, "{} {}", path, micros)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Profiler for FunctionProfiler {
+    #[inline(always)]
+    fn executed(&mut self, _: Instruction, program_counter: usize, frame_depth: usize) {
+        if frame_depth > self.stack.len() {
+            // the program counter is at the start of the invoked function
+            self.enter(Some(program_counter));
+        }
+
+        while frame_depth < self.stack.len() && self.stack.len() > 1 {
+            self.leave();
+        }
+    }
+
+    fn finish(&mut self) {
+        while !self.stack.is_empty() {
+            self.leave();
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn function_name(vm: &VirtualMachine, function: FunctionAddress) -> String {
+    match function {
+        Some(address) => vm.describe_function(address),
+        None => "<main>".to_string(),
+    }
+}
+
+/// per function timings from `FunctionProfiler::report`
+pub struct FunctionReport {
+    pub functions: Vec<(String, FunctionTiming)>,
+}
+
+impl Display for FunctionReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:<40}{:>10}{:>14}{:>14}",
+            "function", "calls", "self (ms)", "total (ms)"
+        )?;
+
+        for (name, timing) in &self.functions {
+            write!(
+                f,
+                "\n{:<40}{:>10}{:>14.3}{:>14.3}",
+                name,
+                timing.calls,
+                timing.self_time.as_secs_f64() * 1000.0,
+                timing.total_time.as_secs_f64() * 1000.0
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// number of opcode pairs shown by the `OpcodeProfiler` report
+pub const TOP_OPCODE_PAIRS: usize = 10;
+
+/// counts executed opcodes and which opcode follows which, the pairs being the candidates
+/// for superinstructions
+pub struct OpcodeProfiler {
+    counts: Vec<u64>,
+    /// indexed by `previous * BYTECODE_COUNT + current`
+    pairs: Vec<u64>,
+    previous: Option<usize>,
+}
+
+impl Default for OpcodeProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpcodeProfiler {
+    pub fn new() -> Self {
+        let opcodes = BYTECODE_COUNT as usize;
+
+        Self {
+            counts: vec![0; opcodes],
+            pairs: vec![0; opcodes * opcodes],
+            previous: None,
+        }
+    }
+
+    /// times each opcode was executed, most frequent first
+    pub fn counts(&self) -> Vec<(OpCode, u64)> {
+        let mut counts: Vec<(OpCode, u64)> = BYTECODE_LOOKUP_TABLE
+            .iter()
+            .zip(&self.counts)
+            .filter(|(_, count)| **count > 0)
+            .map(|(opcode, count)| (*opcode, *count))
+            .collect();
+        counts.sort_by(|(_, count), (_, other_count)| other_count.cmp(count));
+
+        counts
+    }
+
+    /// times each opcode ran right after another one, most frequent first
+    pub fn pairs(&self) -> Vec<((OpCode, OpCode), u64)> {
+        let opcodes = BYTECODE_COUNT as usize;
+        let mut pairs: Vec<((OpCode, OpCode), u64)> = self
+            .pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(index, count)| {
+                let first = BYTECODE_LOOKUP_TABLE[index / opcodes];
+                let second = BYTECODE_LOOKUP_TABLE[index % opcodes];
+                ((first, second), *count)
+            })
+            .collect();
+        pairs.sort_by(|(_, count), (_, other_count)| other_count.cmp(count));
+
+        pairs
+    }
+}
+
+impl Profiler for OpcodeProfiler {
+    #[inline(always)]
+    fn executed(&mut self, instruction: Instruction, _: usize, _: usize) {
+        let opcode = instruction_decoder::decode_opcode(instruction) as usize;
+        self.counts[opcode] += 1;
+
+        if let Some(previous) = self.previous {
+            self.pairs[previous * BYTECODE_COUNT as usize + opcode] += 1;
+        }
+        self.previous = Some(opcode);
+    }
+}
+
+impl Display for OpcodeProfiler {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let counts = self.counts();
+        let total: u64 = counts.iter().map(|(_, count)| count).sum();
+
+        write!(f, "{:<24}{:>14}{:>10}", "opcode", "executed", "%")?;
+        for (opcode, count) in &counts {
+            write!(
+                f,
+                "\n{:<24}{:>14}{:>10.2}",
+                format!("{:?}", opcode),
+                count,
+                *count as f64 * 100.0 / total as f64
+            )?;
+        }
+
+        write!(f, "\n\n{:<38}{:>14}", "opcode pair", "executed")?;
+        for ((first, second), count) in self.pairs().iter().take(TOP_OPCODE_PAIRS) {
+            write!(
+                f,
+                "\n{:<38}{:>14}",
+                format!("{:?} -> {:?}", first, second),
+                count
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,12 +1,54 @@
-use crate::{instruction::Instruction, object::NovaObject};
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{error::Error, fmt::Display};
+#[cfg(feature = "std")]
+use std::io::{self, Write};
 
-#[derive(Default)]
+#[cfg(feature = "std")]
+use crate::debug::debug_instruction;
+use crate::{
+    bytecode::{self, InvalidOpCode, OpCode},
+    frame::SAVED_REGISTERS,
+    immutables::Immutables,
+    instruction::{instruction_decoder, Instruction},
+    object::{NovaFunction, NovaObject},
+};
+
+#[cfg(feature = "file")]
+use crate::file;
+
+/// load a bytecode file written by `file::write_program_file` into the binary at compile time.
+/// the path is relative to the file using the macro, like `include_bytes!`, and the program
+/// is checked when the expression runs so it evaluates to a `Result<Program, _>`
+///
+/// ```ignore
+/// let program = nova::include_program!("../scripts/main.nvc").expect("invalid bytecode");
+/// ```
+#[cfg(feature = "file")]
+#[macro_export]
+macro_rules! include_program {
+    ($path:expr) => {
+        $crate::program::Program::from_bytes(include_bytes!($path))
+    };
+}
+
+/// the immutables of a program, shared by the clones of the program and the virtual machines
+/// loading it. changing them goes through `Arc::make_mut`, which copies a shared pool first
+pub type ImmutablePool = Arc<Immutables>;
+
+#[derive(Default, Clone)]
 pub struct Program {
     pub instructions: Vec<Instruction>,
-    pub immutables: Vec<NovaObject>,
+    pub immutables: ImmutablePool,
     /// mapping instruction to lines.
     /// vec of tuples (line_number, min_instruction_number, file_name)
     pub line_definitions: Vec<LineDefinition>,
+    /// names of the local slots, recorded by the compiler for debuggers. empty for programs
+    /// put together by hand or read without debug info
+    pub local_symbols: Vec<LocalSymbol>,
 }
 
 #[derive(Debug, Clone)]
@@ -14,4 +56,476 @@ pub struct LineDefinition {
     pub last_instruction: usize,
     pub source_line: usize,
     pub source_file: String,
+    /// the part of the line the instructions were compiled from, when the frontend knows it
+    pub columns: Option<ColumnSpan>,
+}
+
+/// columns of a source line, 1 based and counted in characters. the end is exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl ColumnSpan {
+    /// the single column
+    pub fn at(column: usize) -> Self {
+        Self {
+            start: column,
+            end: column + 1,
+        }
+    }
+
+    /// the columns the text of the line takes up without the surrounding whitespace,
+    /// none for a blank line
+    pub fn of_trimmed(line: &str) -> Option<Self> {
+        let text = line.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        let indentation = line.len() - line.trim_start().len();
+        let start = line[..indentation].chars().count() + 1;
+        Some(Self {
+            start,
+            end: start + text.chars().count(),
+        })
+    }
+
+    /// number of columns, at least one
+    pub fn width(&self) -> usize {
+        self.end.saturating_sub(self.start).max(1)
+    }
+}
+
+impl Display for ColumnSpan {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// the name of a local variable slot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalSymbol {
+    pub name: String,
+    /// immutable index of the function the slot belongs to, `None` for the top level code
+    pub function: Option<usize>,
+    pub slot: u32,
+    /// first instruction the name is in scope for, where the slot is allocated
+    pub start: usize,
+    /// the instruction after the block declaring the name, the slot may be reused from there
+    pub end: usize,
+}
+
+impl LocalSymbol {
+    pub fn is_in_scope(&self, instruction: usize) -> bool {
+        (self.start..self.end).contains(&instruction)
+    }
+}
+
+/// a global variable, looked up by the name stored in the immutables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalSymbol {
+    pub name: String,
+    /// immutable index of the name, the operand of the indirect global instructions
+    pub immutable: usize,
+}
+
+/// a function of the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSymbol {
+    pub name: String,
+    /// index of the first instruction of the body
+    pub address: usize,
+    /// immutable index of the function, what `LocalSymbol::function` refers to
+    pub immutable: usize,
+}
+
+/// names of the globals, functions and local slots of a program, from `Program::symbols`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    pub globals: Vec<GlobalSymbol>,
+    pub functions: Vec<FunctionSymbol>,
+    pub locals: Vec<LocalSymbol>,
+}
+
+impl SymbolTable {
+    /// the function whose body starts at the address
+    pub fn function_at(&self, address: usize) -> Option<&FunctionSymbol> {
+        self.functions
+            .iter()
+            .find(|function| function.address == address)
+    }
+
+    /// the names given to a slot of the function, a slot is reused once the block declaring
+    /// it ends so it can have more than one
+    pub fn local_names(&self, function: Option<usize>, slot: u32) -> Vec<&str> {
+        self.locals
+            .iter()
+            .filter(|local| local.function == function && local.slot == slot)
+            .map(|local| local.name.as_str())
+            .collect()
+    }
+
+    /// the name of the slot of the function at the instruction
+    pub fn local_name_at(
+        &self,
+        function: Option<usize>,
+        slot: u32,
+        instruction: usize,
+    ) -> Option<&str> {
+        self.locals
+            .iter()
+            .find(|local| {
+                local.function == function && local.slot == slot && local.is_in_scope(instruction)
+            })
+            .map(|local| local.name.as_str())
+    }
+}
+
+impl Display for SymbolTable {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "Globals")?;
+        for global in &self.globals {
+            writeln!(f, "[{}]: {}", global.immutable, global.name)?;
+        }
+
+        writeln!(f, "Functions")?;
+        for function in &self.functions {
+            writeln!(
+                f,
+                "[{}]: {} (address: {})",
+                function.immutable, function.name, function.address
+            )?;
+        }
+
+        writeln!(f, "Locals")?;
+        for local in &self.locals {
+            let function = local
+                .function
+                .and_then(|immutable| {
+                    self.functions
+                        .iter()
+                        .find(|function| function.immutable == immutable)
+                })
+                .map_or(crate::tools::MAIN_NAME, |function| function.name.as_str());
+            writeln!(
+                f,
+                "{} local[{}]: {} ({}..{})",
+                function, local.slot, local.name, local.start, local.end
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// an instruction decoded from a program, yielded by `Program::iter_instructions`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodedInstruction<'a> {
+    /// index of the instruction word, what jumps and line definitions count in
+    pub index: usize,
+    /// offset of the instruction word in bytes from the first instruction
+    pub offset: usize,
+    pub instruction: Instruction,
+    pub opcode: Result<OpCode, InvalidOpCode>,
+    /// the words after the instruction holding its operand, empty for most opcodes. shorter
+    /// than the opcode needs when the program ends first
+    pub operands: &'a [Instruction],
+}
+
+impl DecodedInstruction<'_> {
+    /// number of words the instruction takes, itself and its operands
+    pub fn width(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    /// whether the program ended before all of the operand words
+    pub fn is_truncated(&self) -> bool {
+        match self.opcode {
+            Ok(opcode) => self.operands.len() < opcode.operand_words(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// iterator over the instructions of a program, see `Program::iter_instructions`
+pub struct Instructions<'a> {
+    instructions: &'a [Instruction],
+    index: usize,
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = DecodedInstruction<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.index;
+        let instruction = *self.instructions.get(index)?;
+        let opcode = OpCode::try_from(instruction_decoder::decode_opcode(instruction));
+
+        // a word that is not an opcode is skipped on its own
+        let operand_words = opcode.map_or(0, |opcode| opcode.operand_words());
+        let end = (index + 1 + operand_words).min(self.instructions.len());
+        self.index = end;
+
+        Some(DecodedInstruction {
+            index,
+            offset: index * core::mem::size_of::<Instruction>(),
+            instruction,
+            opcode,
+            operands: &self.instructions[index + 1..end],
+        })
+    }
+}
+
+/// why `Program::verify` rejected a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// the instruction at `index` does not have a known opcode
+    InvalidOpCode { index: usize, opcode: InvalidOpCode },
+    /// the operand words of the instruction at `index` run past the end of the program
+    Truncated { index: usize },
+    /// the instruction at `index` has an opcode number kept for a category still to come
+    ReservedOpCode {
+        index: usize,
+        opcode: u32,
+        category: &'static str,
+    },
+    /// the function at immutable `immutable` has more parameters with defaults than parameters
+    TooManyDefaults { immutable: usize },
+    /// the function at immutable `immutable` takes more arguments than fit in the registers,
+    /// counting the list of extra arguments of a variadic one
+    TooManyParameters { immutable: usize },
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            VerifyError::InvalidOpCode { index, opcode } => {
+                write!(f, "{} at instruction {}", opcode, index)
+            }
+            VerifyError::Truncated { index } => {
+                write!(f, "instruction {} is missing its operands", index)
+            }
+            VerifyError::ReservedOpCode {
+                index,
+                opcode,
+                category,
+            } => write!(
+                f,
+                "opcode {} at instruction {} is reserved for {}",
+                opcode, index, category
+            ),
+            VerifyError::TooManyDefaults { immutable } => write!(
+                f,
+                "the function at immutable {} has more defaults than parameters",
+                immutable
+            ),
+            VerifyError::TooManyParameters { immutable } => write!(
+                f,
+                "the function at immutable {} has more parameters than registers",
+                immutable
+            ),
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+/// check the function at immutable `index` can be called, the virtual machine trusts its
+/// parameter counts when it sets up a frame
+pub(crate) fn verify_function(index: usize, function: &NovaFunction) -> Result<(), VerifyError> {
+    if function.default_parameters > function.arity {
+        return Err(VerifyError::TooManyDefaults { immutable: index });
+    }
+
+    // the arguments are passed in the registers a frame saves
+    if function.arity as usize + function.is_variadic as usize > SAVED_REGISTERS {
+        return Err(VerifyError::TooManyParameters { immutable: index });
+    }
+
+    Ok(())
+}
+
+/// check every instruction word has a known opcode or one `host` accepts, and all of its
+/// operand words. `Program::verify_with_host_opcodes` for instructions kept elsewhere
+pub fn verify_instructions(
+    instructions: &[Instruction],
+    host: impl Fn(u32) -> bool,
+) -> Result<(), VerifyError> {
+    let walk = Instructions {
+        instructions,
+        index: 0,
+    };
+    for decoded in walk {
+        let index = decoded.index;
+        if let Err(opcode) = decoded.opcode {
+            if host(opcode.0) {
+                continue;
+            }
+
+            return Err(match bytecode::reserved_category(opcode.0) {
+                Some(category) => VerifyError::ReservedOpCode {
+                    index,
+                    opcode: opcode.0,
+                    category,
+                },
+                None => VerifyError::InvalidOpCode { index, opcode },
+            });
+        }
+
+        if decoded.is_truncated() {
+            return Err(VerifyError::Truncated { index });
+        }
+    }
+
+    Ok(())
+}
+
+impl Program {
+    /// read a program serialized by `file::write_program_file`, for bytecode embedded with
+    /// `include_bytes!`. the version and size of the program are checked before it is returned
+    #[cfg(feature = "file")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        file::read_program_bytes(bytes)
+    }
+
+    /// like `from_bytes` for bytecode signed by `file::sign_program_bytes`, refused unless the
+    /// signature was made with the key
+    #[cfg(feature = "signing")]
+    pub fn from_signed_bytes(bytes: &[u8], key: &[u8]) -> Result<Self, Box<dyn Error>> {
+        file::read_signed_program_bytes(bytes, key)
+    }
+
+    /// serialize the program in the format read by `from_bytes`
+    #[cfg(feature = "file")]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        file::write_program_bytes(self)
+    }
+
+    /// check every instruction has a known opcode and all of its operand words, and every
+    /// function has its parameter counts in range. the virtual machine decodes opcodes without bounds checks, so it only runs verified programs
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        self.verify_with_host_opcodes(|_| false)
+    }
+
+    /// verify like `verify`, also accepting the host opcode numbers `host` accepts
+    pub fn verify_with_host_opcodes(&self, host: impl Fn(u32) -> bool) -> Result<(), VerifyError> {
+        verify_instructions(&self.instructions, host)?;
+
+        for (index, immutable) in self.immutables.iter_decoded() {
+            if let NovaObject::NovaFunction(function) = immutable {
+                verify_function(index, function)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// walk the instructions with their operand words, stepping over the operands of the wide
+    /// loads so every item starts at an instruction
+    pub fn iter_instructions(&self) -> Instructions<'_> {
+        Instructions {
+            instructions: &self.instructions,
+            index: 0,
+        }
+    }
+
+    /// the globals defined by the instructions, the functions in the immutables and the local
+    /// slot names from the debug info, so tools can show names instead of indices
+    pub fn symbols(&self) -> SymbolTable {
+        let mut globals: Vec<GlobalSymbol> = Vec::new();
+        for decoded in self.iter_instructions() {
+            if decoded.opcode != Ok(OpCode::DefineGlobalIndirect) {
+                continue;
+            }
+
+            let immutable =
+                instruction_decoder::decode_immutable_address_small(decoded.instruction) as usize;
+            let Some(NovaObject::String(name)) = self.immutables.get(immutable) else {
+                continue;
+            };
+
+            if !globals.iter().any(|global| global.immutable == immutable) {
+                globals.push(GlobalSymbol {
+                    name: name.to_string(),
+                    immutable,
+                });
+            }
+        }
+
+        let functions = self
+            .immutables
+            .iter()
+            .enumerate()
+            .filter_map(|(immutable, object)| match object {
+                NovaObject::NovaFunction(function) => Some(FunctionSymbol {
+                    name: function.name.to_string(),
+                    address: function.address as usize,
+                    immutable,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        SymbolTable {
+            globals,
+            functions,
+            locals: self.local_symbols.clone(),
+        }
+    }
+
+    /// write a stable, human readable listing of the program (instructions, immutables and line table).
+    /// the output only depends on the program contents so it can be used for snapshot tests
+    #[cfg(feature = "std")]
+    pub fn dump(&self, output: &mut impl Write) -> io::Result<()> {
+        writeln!(output, "Instructions")?;
+
+        for decoded in self.iter_instructions() {
+            let instruction_dbg = debug_instruction(&self.instructions, decoded.index as u64);
+            writeln!(
+                output,
+                "[{}]: {}",
+                decoded.index,
+                instruction_dbg.trim_end()
+            )?;
+        }
+
+        writeln!(output, "Immutables")?;
+
+        for (index, immutable) in self.immutables.iter().enumerate() {
+            match immutable {
+                NovaObject::String(string) => writeln!(output, "[{}]: String {:?}", index, string)?,
+                NovaObject::NovaFunction(function) => writeln!(
+                    output,
+                    "[{}]: Function {} (address: {}, arity: {}, locals: {})",
+                    index,
+                    function.name,
+                    function.address,
+                    function.arity,
+                    function.number_of_locals
+                )?,
+                _ => writeln!(output, "[{}]: {}", index, immutable)?,
+            }
+        }
+
+        writeln!(output, "Lines")?;
+
+        for line_definition in &self.line_definitions {
+            let columns = match line_definition.columns {
+                Some(columns) => format!(", columns {}", columns),
+                None => String::new(),
+            };
+            writeln!(
+                output,
+                "[{}]: line {}{} in '{}'",
+                line_definition.last_instruction,
+                line_definition.source_line,
+                columns,
+                line_definition.source_file
+            )?;
+        }
+
+        Ok(())
+    }
 }
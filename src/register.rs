@@ -1,4 +1,5 @@
-use std::fmt::Display;
+use alloc::{format, string::ToString};
+use core::fmt::Display;
 
 use crate::object::RegisterValueKind;
 
@@ -26,7 +27,7 @@ pub enum RegisterID {
     RCND,
     /// Return
     RRTN,
-    /// Max number of all registers / also stores number of local variables in called function
+    /// Max number of all registers
     RMax,
 }
 
@@ -62,7 +63,7 @@ impl Default for Register {
 }
 
 impl Display for Register {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let description = match self.kind {
             RegisterValueKind::Int64 => {
                 format!("{:<10} : {:>10}", "Int64", self.value as i64)
@@ -87,6 +88,10 @@ impl Display for Register {
             RegisterValueKind::NovaFunctionID(_) => {
                 "NovaFunctionID".to_string()
             }
+            RegisterValueKind::NativeFunctionID(index) => format!(
+                "{:<10} : {:>10} | {:>10}",
+                "NativeFunctionID", index, self.value
+            ),
             RegisterValueKind::StrMem => format!(
                 "{:<10} : {:>#10x} | {:>10}",
                 "StrMem", self.value, self.value
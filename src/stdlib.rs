@@ -0,0 +1,50 @@
+//! the standard library written in nova: helpers for collections, strings and passing
+//! functions around. the build script embeds the sources in `stdlib/` and they are compiled
+//! the first time a virtual machine loads them, after which every machine shares the programs
+
+use std::{error::Error, fmt::Display, sync::OnceLock};
+
+use crate::{compiler, program::Program};
+
+include!(concat!(env!("OUT_DIR"), "/stdlib_modules.rs"));
+
+static PROGRAMS: OnceLock<Result<Vec<Program>, StdlibError>> = OnceLock::new();
+
+/// a module of the standard library that does not compile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StdlibError {
+    pub module: &'static str,
+    pub message: String,
+}
+
+impl Display for StdlibError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the stdlib module {} does not compile: {}",
+            self.module, self.message
+        )
+    }
+}
+
+impl Error for StdlibError {}
+
+/// the compiled modules in the order of `MODULES`, loaded by `VirtualMachine::with_stdlib`.
+/// the first module that does not compile fails every call
+pub fn programs() -> Result<&'static [Program], StdlibError> {
+    PROGRAMS
+        .get_or_init(|| {
+            MODULES
+                .iter()
+                .map(|(name, source)| {
+                    let filename = format!("stdlib/{}.nova", name);
+                    compiler::compile(source, &filename).map_err(|error| StdlibError {
+                        module: name,
+                        message: error.to_string(),
+                    })
+                })
+                .collect()
+        })
+        .as_deref()
+        .map_err(StdlibError::clone)
+}
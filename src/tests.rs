@@ -1,6 +1,69 @@
 #[cfg(test)]
 mod tests {
-    use crate::{compiler, machine::VirtualMachine};
+    use std::{
+        cell::RefCell,
+        io::{self, Write},
+        panic::{self, AssertUnwindSafe},
+        rc::Rc,
+        sync::Arc,
+    };
+
+    use nova_tw::language::{AstParser, Scanner};
+
+    use crate::{
+        bytecode::{InvalidOpCode, OpCode},
+        capabilities::{Capabilities, Capability},
+        compiler::{
+            self,
+            error::CompileErrorKind,
+            optimizer::{OptLevel, OptStats},
+            warning::WarningKind,
+        },
+        diagnostics::{ColorChoice, Diagnostic, Palette},
+        error::{NovaError, ReplaceFunctionError, VmErrorKind},
+        extensions::Extensions,
+        frontend::{Frontend, NovaFrontend},
+        hooks::VmHooks,
+        instruction::{instruction_decoder, InstructionBuilder},
+        limits::ResourceLimits,
+        machine::{Coercion, ContextId, VirtualMachine, MAX_NATIVE_CALL_DEPTH},
+        natives::{self, NativeArguments, NativeContext},
+        number_format::FloatFormat,
+        object::{NativeFunction, NativeResult, NovaFunction, NovaObject},
+        prelude::{NativeModule, Prelude},
+        profiler::{FunctionProfiler, OpcodeProfiler},
+        program::{ColumnSpan, Program, VerifyError},
+        stdlib,
+        tools::{self, DiffLine},
+    };
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// run the source in a fresh virtual machine and return what it printed
+    fn run_and_capture(source: &str) -> String {
+        let program = compiler::compile(source, "").unwrap();
+        let output = SharedOutput::default();
+
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 0);
+
+        let printed = output.0.borrow().clone();
+        String::from_utf8(printed).unwrap()
+    }
 
     #[test]
     fn test_globals() {
@@ -10,6 +73,7 @@ mod tests {
         let program2 = compiler::compile(source2, "").unwrap();
 
         let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
         let mut offset = 0u32;
         vm.load_program(program);
         let code = vm.start_vm(offset);
@@ -27,9 +91,2411 @@ mod tests {
         let program = compiler::compile(source, "").unwrap();
 
         let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
         let offset = 0u32;
         vm.load_program(program);
         let code = vm.start_vm(offset);
         assert_eq!(code, 0);
     }
+
+    #[test]
+    fn test_program_dump() {
+        let source = "a := 4\nb := a * 2.5\n";
+        let program = compiler::compile(source, "dump.nova").unwrap();
+
+        let mut output = Vec::new();
+        program.dump(&mut output).unwrap();
+
+        let expected = "\
+Instructions
+[0]: LOADINT32 0 4
+[2]: DEFINEGLOBALINDIRECT 0
+[3]: STOREGLOBALINDIRECT 0 0
+[4]: LOADGLOBALINDIRECT 0 0
+[5]: LOADFLOAT32 1 2.5
+[7]: MUL 0 0 1
+[8]: DEFINEGLOBALINDIRECT 1
+[9]: STOREGLOBALINDIRECT 0 1
+[10]: HALT
+Immutables
+[0]: String \"a\"
+[1]: String \"b\"
+Lines
+[3]: line 1, columns 1..7 in 'dump.nova'
+[9]: line 2, columns 1..13 in 'dump.nova'
+";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_compile_warnings() {
+        let source = "block\na := 1\n_b := 2\nblock\na := 3\nprintln(a)\nend\nend\nfn f(x)\nreturn 1\nprintln(x)\nend\n";
+        let (_, warnings) = compiler::compile_with_warnings(source, "warn.nova").unwrap();
+
+        let summary: Vec<(WarningKind, usize)> = warnings
+            .iter()
+            .map(|warning| (warning.kind, warning.line))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (WarningKind::ShadowedVariable, 5),
+                (WarningKind::UnusedLocal, 2),
+                (WarningKind::UnreachableCode, 11),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_warnings() {
+        let source = "block\na := 1\nprintln(a)\nend\n";
+        let (_, warnings) = compiler::compile_with_warnings(source, "").unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_multiple_compile_errors() {
+        let source = "a := 1\nb := not a\nblock\nc := 3\nprintln(not c)\nend\n";
+        let errors = match compiler::compile_with_warnings(source, "errors.nova") {
+            Ok(_) => panic!("expected compile errors"),
+            Err(errors) => errors,
+        };
+
+        let lines: Vec<usize> = errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, vec![2, 5]);
+        assert!(errors.iter().all(|error| error.file == "errors.nova"));
+    }
+
+    #[test]
+    fn test_compile_error_render() {
+        let source = "a := 1\n  b := not a\n";
+        let errors = match compiler::compile_with_warnings(source, "render.nova") {
+            Ok(_) => panic!("expected compile errors"),
+            Err(errors) => errors,
+        };
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, CompileErrorKind::UnsupportedOperator);
+
+        let expected = "\
+error: unsupported operator: unhandled unary operator: Not
+ --> render.nova:2
+  |
+2 |   b := not a
+  |   ^^^^^^^^^^";
+        assert_eq!(errors[0].render(), expected);
+    }
+
+    #[test]
+    fn test_colored_diagnostics() {
+        let errors = match compiler::compile_with_warnings("a := 1\nb := not a\n", "color.nova") {
+            Ok(_) => panic!("expected compile errors"),
+            Err(errors) => errors,
+        };
+
+        let expected = "\
+\x1b[1;31merror\x1b[0m: unsupported operator: unhandled unary operator: Not
+ \x1b[36m-->\x1b[0m color.nova:2
+  \x1b[36m|\x1b[0m
+\x1b[36m2\x1b[0m \x1b[36m|\x1b[0m b := not a
+  \x1b[36m|\x1b[0m \x1b[1;31m^^^^^^^^^^\x1b[0m";
+        assert_eq!(errors[0].render_with(Palette::colored()), expected);
+        assert_eq!(errors[0].render_with(Palette::plain()), errors[0].render());
+
+        let program = compiler::compile("x := 1 - \"a\"\n", "color.nova").unwrap();
+        let mut vm = VirtualMachine::new();
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+        let error = vm.take_error().unwrap();
+        assert!(error
+            .render_with(Palette::colored())
+            .starts_with("\x1b[1;31mError:\x1b[0m '"));
+        assert!(error
+            .render_with(Palette::colored())
+            .ends_with("\x1b[36mOn line [1] in file 'color.nova'\x1b[0m"));
+
+        let arguments = ["--color=always".to_string(), "--no-color".to_string()];
+        assert_eq!(ColorChoice::from_args(&arguments), ColorChoice::Never);
+        assert_eq!(ColorChoice::from_args(&arguments[..1]), ColorChoice::Always);
+        assert_eq!(ColorChoice::from_args(&[]), ColorChoice::Auto);
+        assert!(ColorChoice::Always.palette().is_colored());
+        assert!(!ColorChoice::Never.palette().is_colored());
+    }
+
+    #[test]
+    fn test_runtime_error() {
+        let source = "fn fail()\nreturn \"a\" - 1\nend\nfail()\n";
+        let program = compiler::compile(source, "runtime.nova").unwrap();
+
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+
+        let error = NovaError::from(vm.take_error().unwrap());
+        let NovaError::Runtime(error) = error else {
+            panic!("expected a runtime error");
+        };
+
+        assert_eq!(error.location.map(|location| location.line), Some(2));
+        assert_eq!(error.call_stack.len(), 1);
+        assert_eq!(error.call_stack[0].line, 4);
+    }
+
+    #[test]
+    fn test_runtime_error_excerpt() {
+        let source = "fn fail()\n    return \"a\" - 1\nend\nfail()\n";
+        let path = std::env::temp_dir().join(format!("nova_excerpt_{}.nova", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let file = path.to_string_lossy().to_string();
+
+        let program = compiler::compile(source, &file).unwrap();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+        std::fs::remove_file(&path).unwrap();
+
+        let error = vm.take_error().unwrap();
+        assert_eq!(
+            error.location.as_ref().unwrap().columns,
+            Some(ColumnSpan { start: 5, end: 19 })
+        );
+        let rendered = error.to_string();
+        let expected = format!(
+            "On line [2] in file '{0}'\n  |\n2 |     return \"a\" - 1\n  |     ^^^^^^^^^^^^^^\n\
+             Called from line [4] in file '{0}'\n  |\n4 | fail()\n  | ^^^^^^",
+            file
+        );
+        assert!(rendered.ends_with(&expected), "{}", rendered);
+
+        // without a readable file only the location is shown
+        let program = compiler::compile(source, "missing.nova").unwrap();
+        let mut vm = VirtualMachine::new();
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+        assert!(vm
+            .take_error()
+            .unwrap()
+            .to_string()
+            .ends_with("Called from line [4] in file 'missing.nova'"));
+    }
+
+    #[test]
+    fn test_arity_error() {
+        let source = "fn add(a, b)\nreturn a + b\nend\nx := 1\nprintln(add(x))\n";
+        let program = compiler::compile(source, "arity.nova").unwrap();
+
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+
+        let error = vm.take_error().unwrap();
+        assert_eq!(
+            error.message,
+            "Not enough arguments for function 'add'.\n2 are required\n1 were provided"
+        );
+        assert_eq!(error.location.map(|location| location.line), Some(5));
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let source = "\
+fn side(x)
+println(\"side\")
+return x
+end
+println(false and side(true))
+println(true and side(5))
+println(1 == 1 or side(1))
+println(None or side(7))
+a := 0
+while a < 3 and side(true)
+a = a + 1
+end
+println(a)
+";
+        let expected = "false\nside\n5\ntrue\nside\n7\nside\nside\nside\n3\n";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_truthiness() {
+        let source = "\
+fn show(x)
+if x
+print(\"T\")
+else
+print(\"F\")
+end
+end
+fn identity(x)
+return x
+end
+values := list(None, false, true, 0, 3, 0.0, 1.5, \"\", \"a\", list(), list(0), dict(), show)
+each(values, show)
+println(\"\")
+println(len(filter(values, identity)))
+println(0 or \"empty\")
+println(\"\" and side)
+";
+        assert_eq!(run_and_capture(source), "FFTFTFTFTFTFT\n6\nempty\n\n");
+
+        // not always leaves a bool, whatever kind of value it negated
+        for (value, expected) in [(0.0f32, "true\n"), (2.5, "false\n")] {
+            let output = SharedOutput::default();
+            let mut vm = VirtualMachine::new();
+            vm.set_output(Box::new(output.clone()));
+            vm.load_program(Program {
+                instructions: vec![
+                    InstructionBuilder::new_load_float32_instruction(0),
+                    value.to_bits(),
+                    InstructionBuilder::new_not_instruction(0),
+                    InstructionBuilder::new_print_instruction(0, true),
+                    InstructionBuilder::new_halt_instruction(),
+                ],
+                ..Default::default()
+            });
+            assert_eq!(vm.start_vm(0), 0);
+            assert_eq!(
+                String::from_utf8(output.0.borrow().clone()).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_equality_semantics() {
+        let source = "\
+fn f()
+return 1
+end
+fn g()
+return 1
+end
+h := f
+println(f == f)
+println(f == g)
+println(h == f)
+println(list(f) == list(h))
+println(println == println)
+println(println == print)
+println(true == true)
+println(true == false)
+println(list(1, \"a\") == list(1, \"a\"))
+println(list(1) == list(2))
+println(f == 1)
+println(0.0 == -0.0)
+";
+        let expected =
+            "true\nfalse\ntrue\ntrue\ntrue\nfalse\ntrue\nfalse\ntrue\nfalse\nfalse\ntrue\n";
+        assert_eq!(run_and_capture(source), expected);
+
+        // functions are only equal to themselves, they have no order
+        let program = compiler::compile("fn f()\nend\nfn g()\nend\nprintln(f < g)\n", "").unwrap();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        let source = "\
+fn greet(name, greeting, punct)
+println(greeting + \" \" + name + punct)
+end
+greet(\"bob\", \"hi\", \"!\")
+greet(\"bob\", punct = \"?\", greeting = \"hello\")
+greet(punct = \".\", name = \"amy\", greeting = \"bye\")
+";
+        let expected = "hi bob!\nhello bob?\nbye amy.\n";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_named_argument_errors() {
+        let source = "\
+fn area(width, height)
+return width * height
+end
+area(height = 2)
+area(depth = 2, width = 1)
+area(1, width = 2)
+";
+        let errors = match compiler::compile_with_warnings(source, "") {
+            Ok(_) => panic!("expected compile errors"),
+            Err(errors) => errors,
+        };
+
+        let kinds: Vec<CompileErrorKind> = errors.iter().map(|error| error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                CompileErrorKind::InvalidCall,
+                CompileErrorKind::InvalidCall,
+                CompileErrorKind::InvalidCall,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_native() {
+        let source = "\
+println(list(1, \"two\", 3.5))
+println(list())
+";
+        let expected = "[1, \"two\", 3.5]\n[]\n";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_function_values() {
+        let source = "\
+fn apply(f, x)
+return f(x)
+end
+fn double(x)
+return x * 2
+end
+fn square(x)
+return x * x
+end
+f := square
+println(f(4))
+println(apply(square, 3))
+println(apply(double, 5))
+println(double)
+";
+        let expected = "16\n9\n10\nfunction: double, parameters: 1\n";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_forward_references() {
+        let source = "\
+println(later(2, 1))
+println(later(b = 1, a = 5))
+println(later)
+fn later(a, b)
+return a + b
+end
+";
+        let expected = "3\n6\nfunction: later, parameters: 2\n";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let source = "\
+fn fib(n)
+if n < 2
+return n
+end
+return fib(n - 1) + fib(n - 2)
+end
+fn fact(n)
+if n <= 1
+return 1
+end
+return n * fact(n - 1)
+end
+fn wide(n, a, b, c, d, e, f, g)
+if n == 0
+return a + b + c + d + e + f + g
+end
+return wide(n - 1, a, b, c, d, e, f, g) + 1
+end
+fn outer()
+fn countdown(n)
+if n == 0
+return 0
+end
+return countdown(n - 1) + 2
+end
+return countdown(3)
+end
+println(fib(15))
+println(fact(10))
+println(wide(3, 1, 1, 1, 1, 1, 1, 1))
+println(outer())
+";
+        let expected = "610\n3628800\n10\n6\n";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_nested_local_scopes() {
+        let source = "\
+fn sum_to(n)
+total := 0
+i := 0
+while i < n
+i = i + 1
+step := i
+block
+step := step * 10
+total = total + step
+end
+end
+return total
+end
+fn pick(flag)
+if flag
+a := 1
+return a
+else
+b := 2
+c := b + 1
+return c
+end
+end
+block
+x := 5
+fn inner(y)
+z := y + 1
+return z
+end
+println(inner(x))
+block
+x := x + 1
+println(x)
+end
+println(x)
+end
+println(sum_to(4))
+println(pick(true))
+println(pick(false))
+";
+        let expected = "6\n6\n5\n100\n1\n3\n";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_register_pressure() {
+        // nesting deeper than there are registers forces values to be spilled
+        let nested = (1..=20).rev().fold("0".to_string(), |expression, number| {
+            format!("{} + ({})", number, expression)
+        });
+
+        let source = format!(
+            "\
+fn deep(x)
+return x + {nested}
+end
+fn sub(a, b)
+return a - b
+end
+println(deep(1))
+println({nested})
+println(sub(sub(5, 1), sub(1, 2)) + sub(b = {nested}, a = 0))
+println(true and ({nested}) > 3)
+"
+        );
+
+        let expected = "211\n210\n-205\ntrue\n";
+        assert_eq!(run_and_capture(&source), expected);
+    }
+
+    /// a frontend that ignores the syntax and prints the whole source back
+    struct EchoFrontend;
+
+    impl Frontend for EchoFrontend {
+        fn compile(&self, source: &str, _filename: &str) -> Result<Program, NovaError> {
+            Ok(Program {
+                instructions: vec![
+                    InstructionBuilder::new_load_constant_instruction(0, 0),
+                    InstructionBuilder::new_print_instruction(0, true),
+                    InstructionBuilder::new_halt_instruction(),
+                ],
+                immutables: Arc::new(vec![NovaObject::String(Box::new(source.to_string()))].into()),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn test_frontends() {
+        let output = SharedOutput::default();
+        let program = EchoFrontend.compile("not nova code", "echo").unwrap();
+
+        let mut vm = VirtualMachine::new();
+        vm.set_output(Box::new(output.clone()));
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 0);
+        assert_eq!(output.0.borrow().as_slice(), b"not nova code\n");
+
+        let error = NovaFrontend.compile("x := 1 +\n", "broken.nova");
+        assert!(matches!(error, Err(NovaError::Compile(_))));
+    }
+
+    #[test]
+    fn test_compile_ast() {
+        let source = "x := 2\nprintln(x * 21)\n";
+        let tokens = Scanner::new()
+            .scan_tokens_with_filename(source, "ast.nova")
+            .unwrap();
+        let statements = AstParser::new(tokens).parse_ast().unwrap();
+
+        // the tree can be changed before generating code
+        let program = compiler::compile_ast(&statements[1..]).unwrap();
+        let output = SharedOutput::default();
+
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+        vm.load_program(program);
+        vm.start_vm(0);
+
+        assert!(vm.take_error().is_some());
+
+        let program = compiler::compile_ast(&statements).unwrap();
+        let from_source = compiler::compile(source, "ast.nova").unwrap();
+        assert_eq!(program.instructions, from_source.instructions);
+    }
+
+    #[test]
+    fn test_numeric_constant_pool() {
+        let source =
+            "x := 1.5\ny := x + 1.5\nz := 3000000000\nprintln(y * 1.5)\nprintln(z + 3000000000)\n";
+        let program = compiler::compile(source, "pool.nova").unwrap();
+
+        // the repeated float and the 64 bit integer are pooled once each
+        let numbers = program
+            .immutables
+            .iter()
+            .filter(|immutable| matches!(immutable, NovaObject::Float64(_) | NovaObject::Int64(_)))
+            .count();
+        assert_eq!(numbers, 2);
+
+        assert_eq!(run_and_capture(source), "4.5\n6000000000\n");
+    }
+
+    #[test]
+    fn test_branch_simplification() {
+        let source = "\
+if false
+println(\"debug\")
+else
+println(\"release\")
+end
+while false
+println(\"never\")
+end
+fn sign(n)
+if n < 0
+return 0 - 1
+else
+if n < 1
+return 0
+else
+return 1
+end
+end
+end
+println(sign(0 - 5))
+println(sign(0))
+println(sign(5))
+";
+        assert_eq!(run_and_capture(source), "release\n-1\n0\n1\n");
+
+        let program = compiler::compile(source, "").unwrap();
+        let mut listing = Vec::new();
+        program.dump(&mut listing).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+
+        // only the two conditions of sign are left, the constant ones are folded away
+        assert_eq!(listing.matches("JUMPFALSE").count(), 2);
+        assert!(!listing.contains("JUMP 1 forward"));
+    }
+
+    #[test]
+    fn test_opt_levels() {
+        let source = "\
+x := 2 * 3 + 1
+if false
+println(x)
+end
+println(x - 7)
+";
+        let listing = |opt_level| {
+            let (program, _, stats) =
+                compiler::compile_with_opt_level(source, "", opt_level).unwrap();
+            let mut listing = Vec::new();
+            program.dump(&mut listing).unwrap();
+            (String::from_utf8(listing).unwrap(), stats)
+        };
+
+        let (unoptimized, stats) = listing(OptLevel::O0);
+        assert!(unoptimized.contains("MUL"));
+        assert_eq!(stats.constants_folded, 0);
+        assert_eq!(stats.words_before, stats.words_after);
+
+        let (folded, stats) = listing(OptLevel::O1);
+        assert!(!folded.contains("MUL"));
+        assert!(folded.contains("LOADINT32 0 7"));
+        assert_eq!(stats.constants_folded, 1);
+        assert_eq!(stats.conditions_folded, 0);
+
+        let (optimized, stats) = listing(OptLevel::O2);
+        assert_eq!(stats.conditions_folded, 1);
+        assert_eq!(stats.unreachable_removed, 3);
+        assert!(stats.words_after < stats.words_before);
+        assert!(optimized.matches("INVOKE").count() < folded.matches("INVOKE").count());
+
+        assert_eq!(OptLevel::from_flag("-O1"), Some(OptLevel::O1));
+        assert_eq!(OptLevel::from_flag("-O3"), None);
+        assert_ne!(stats, OptStats::default());
+
+        assert_eq!(run_and_capture(source), "0\n");
+    }
+
+    #[test]
+    fn test_constant_loop_conditions() {
+        let source = "\
+fn first_square_over(limit)
+x := 0
+while true
+x = x + 1
+if x * x > limit
+return x
+end
+end
+end
+println(first_square_over(50))
+while false
+println(\"never\")
+end
+";
+        let (program, _, stats) =
+            compiler::compile_with_opt_level(source, "", OptLevel::O2).unwrap();
+        let mut listing = Vec::new();
+        program.dump(&mut listing).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+
+        // only the test of the if is left, neither loop loads a bool to test it
+        assert_eq!(stats.conditions_folded, 2);
+        assert_eq!(listing.matches("JUMPFALSE").count(), 1);
+        assert!(!listing.contains("LOADBOOL"));
+        assert_eq!(run_and_capture(source), "8\n");
+
+        let (program, _, stats) =
+            compiler::compile_with_opt_level(source, "", OptLevel::O0).unwrap();
+        let mut listing = Vec::new();
+        program.dump(&mut listing).unwrap();
+        assert_eq!(stats.conditions_folded, 0);
+        assert!(String::from_utf8(listing).unwrap().contains("LOADBOOL"));
+    }
+
+    #[test]
+    fn test_chained_comparisons() {
+        let source = "\
+fn middle(x)
+println(\"middle \", x)
+return x
+end
+fn loud(x)
+println(\"loud \", x)
+return x
+end
+println(1 < middle(2) < 3)
+println(3 < middle(2) < loud(5))
+println(1 <= 1 < 2 == 2 != 3 > 0 >= 0)
+x := 5
+println(0 < x <= 10, \" \", 0 < x < 5, \" \", (0 < x < 9) == true)
+";
+        let expected = "\
+middle 2
+true
+middle 2
+false
+true
+true false true
+";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let source = "\
+fn describe(name, count)
+return \"${name} has ${count + 1} items, ${count > 2}\"
+end
+println(describe(\"cart\", 2))
+println(\"${1.5} ${none}$${literal}\")
+x := 7
+println(\"x=${x}\")
+";
+        assert_eq!(
+            run_and_capture(source),
+            "cart has 3 items, false\n1.5 None${literal}\nx=7\n"
+        );
+
+        for source in [
+            "println(\"${x\")",
+            "println(\"${}\")",
+            "println(\"${1 +}\")",
+        ] {
+            let Err(errors) = compiler::compile_with_warnings(source, "") else {
+                panic!("expected compile errors for {}", source);
+            };
+            assert_eq!(errors[0].kind, CompileErrorKind::Syntax, "{}", source);
+        }
+
+        let source = "fn format(x)\nend\nprintln(\"${1}\")";
+        let Err(errors) = compiler::compile_with_warnings(source, "") else {
+            panic!("expected compile errors");
+        };
+        assert_eq!(errors[0].kind, CompileErrorKind::InvalidCall);
+    }
+
+    #[test]
+    fn test_elif_chain_has_one_exit() {
+        let source = "\
+x := 75
+if x > 90
+println(\"a\")
+elif x > 80
+println(\"b\")
+elif x > 70
+println(\"c\")
+else
+println(\"f\")
+end
+";
+        assert_eq!(run_and_capture(source), "c\n");
+
+        let (program, _, _) = compiler::compile_with_opt_level(source, "", OptLevel::O0).unwrap();
+        let instructions = &program.instructions;
+
+        // the jump ending each arm goes straight to the end of the chain
+        let exits: Vec<usize> = (1..instructions.len())
+            .filter(|&index| {
+                let opcode = instruction_decoder::decode_opcode(instructions[index]);
+                let previous = instruction_decoder::decode_opcode(instructions[index - 1]);
+                opcode == OpCode::Jump as u32 && previous == OpCode::NoInstruction as u32
+            })
+            .map(|index| {
+                index
+                    + instruction_decoder::decode_immutable_address_small(instructions[index])
+                        as usize
+            })
+            .collect();
+        assert_eq!(exits.len(), 3);
+        assert!(exits.iter().all(|&exit| exit == exits[0]));
+    }
+
+    #[test]
+    fn test_local_steps() {
+        let source = "\
+fn count(n)
+i := 0
+total := 0.5
+label := \"x\"
+while i < n
+i = i + 1
+total = total - 2
+end
+label = label + 3
+return label + \" \" + i + \" \" + total
+end
+println(count(4))
+";
+        assert_eq!(run_and_capture(source), "x3 4 -7.5\n");
+
+        let (program, _, stats) =
+            compiler::compile_with_opt_level(source, "", OptLevel::O1).unwrap();
+        assert_eq!(stats.local_steps_lowered, 3);
+        let mut listing = Vec::new();
+        program.dump(&mut listing).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        assert!(listing.contains("INC 1 1"), "{}", listing);
+        assert!(listing.contains("DEC 2 2"), "{}", listing);
+
+        let (_, _, stats) = compiler::compile_with_opt_level(source, "", OptLevel::O0).unwrap();
+        assert_eq!(stats.local_steps_lowered, 0);
+
+        // the number first, a float or a negative amount keep the add
+        let source = "fn f(i)\ni = 1 + i\ni = i + 1.5\ni = i + -1\ni = i - 300\nreturn i\nend\n";
+        let (_, _, stats) = compiler::compile_with_opt_level(source, "", OptLevel::O2).unwrap();
+        assert_eq!(stats.local_steps_lowered, 0);
+    }
+
+    #[test]
+    fn test_version_1_bytecode_runs() {
+        // written by the first release from test_code/bytecode/v1.nova
+        let program = Program::from_bytes(include_bytes!("../test_code/bytecode/v1.nvc")).unwrap();
+        let output = SharedOutput::default();
+
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 0);
+
+        let printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+        assert_eq!(printed, "100\ndone 88\n");
+    }
+
+    #[test]
+    fn test_counting_loops() {
+        let source = "\
+fn sums(n)
+up := 0
+i := 0
+while i < n
+up = up + i
+i = i + 1
+end
+odd := 0
+j := 1
+while j <= 9
+odd = odd + j
+j = j + 2
+end
+down := \"\"
+k := 3
+while k > 0
+down = down + k
+k = k - 1
+end
+half := 0
+x := 0.5
+while x < 3
+half = half + x
+x = x + 1
+end
+never := 0
+m := 10
+while m < n
+never = never + 1
+m = m + 1
+end
+return up + \" \" + odd + \" \" + down + \" \" + half + \" \" + i + \" \" + never
+end
+println(sums(5))
+";
+        assert_eq!(run_and_capture(source), "10 25 321 4.5 5 0\n");
+
+        let (program, _, stats) =
+            compiler::compile_with_opt_level(source, "", OptLevel::O1).unwrap();
+        assert_eq!(stats.counting_loops_fused, 5);
+        let mut listing = Vec::new();
+        program.dump(&mut listing).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        assert!(listing.contains("FORPREP"), "{}", listing);
+        assert!(listing.contains("FORLOOP"), "{}", listing);
+
+        let (_, _, stats) = compiler::compile_with_opt_level(source, "", OptLevel::O0).unwrap();
+        assert_eq!(stats.counting_loops_fused, 0);
+
+        // stepping before the end of the body, assigning the limit or a wrong direction
+        let source = "\
+fn f(n)
+i := 0
+while i < n
+i = i + 1
+n = n - 1
+end
+while i < n
+n = 2
+i = i + 1
+end
+while i > 0
+i = i + 1
+end
+return i
+end
+";
+        let (_, _, stats) = compiler::compile_with_opt_level(source, "", OptLevel::O2).unwrap();
+        assert_eq!(stats.counting_loops_fused, 0);
+    }
+
+    #[test]
+    fn test_unary_plus() {
+        let source = "\
+fn number(x)
+return +x
+end
+println(number(2), \" \", number(-1.5), \" \", +3)
+";
+        assert_eq!(run_and_capture(source), "2 -1.5 3\n");
+
+        for (source, message) in [
+            (
+                "fn number(x)\nreturn +x\nend\nnumber(\"2\")\n",
+                "Unary plus needs a number, found String",
+            ),
+            (
+                "x := true\nprintln(+x)\n",
+                "Unary plus needs a number, found Bool",
+            ),
+        ] {
+            let mut vm = VirtualMachine::new();
+            vm.load_natives(natives::common_native_functions());
+            vm.set_output(Box::new(io::sink()));
+            vm.load_program(compiler::compile(source, "").unwrap());
+            assert_eq!(vm.start_vm(0), 1);
+            assert_eq!(vm.take_error().unwrap().message, message);
+        }
+    }
+
+    #[test]
+    fn test_math_calls() {
+        let source = "\
+x := -2.5
+println(sqrt(16), abs(x), abs(-3), floor(x), ceil(x), floor(7))
+root := sqrt
+println(root(16), ceil(2.1))
+";
+        let listing = |opt_level| {
+            let (program, _, stats) =
+                compiler::compile_with_opt_level(source, "", opt_level).unwrap();
+            let mut listing = Vec::new();
+            program.dump(&mut listing).unwrap();
+            (String::from_utf8(listing).unwrap(), stats)
+        };
+
+        let (unoptimized, stats) = listing(OptLevel::O0);
+        assert!(!unoptimized.contains("SQRT"));
+        assert_eq!(stats.math_calls_lowered, 0);
+
+        let (lowered, stats) = listing(OptLevel::O1);
+        assert!(lowered.contains("SQRT"));
+        assert!(lowered.contains("CEIL"));
+        assert_eq!(stats.math_calls_lowered, 7);
+
+        // the opcodes and the natives they replace agree
+        assert_eq!(run_and_capture(source), "42.53-3-27\n43\n");
+
+        // a function of the program with the same name is called as usual
+        let source = "\
+fn abs(x)
+return 1
+end
+println(abs(-5))
+";
+        let (_, _, stats) = compiler::compile_with_opt_level(source, "", OptLevel::O2).unwrap();
+        assert_eq!(stats.math_calls_lowered, 0);
+        assert_eq!(run_and_capture(source), "1\n");
+
+        for source in [
+            "println(sqrt(\"four\"))\n",
+            "root := sqrt\nprintln(root(\"four\"))\n",
+        ] {
+            let mut vm = VirtualMachine::new();
+            vm.load_natives(natives::common_native_functions());
+            vm.set_output(Box::new(io::sink()));
+            vm.load_program(compiler::compile(source, "").unwrap());
+            assert_eq!(vm.start_vm(0), 1);
+            assert!(vm
+                .take_error()
+                .unwrap()
+                .message
+                .contains("Function 'sqrt()' needs a number"));
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+
+        vm.load_program(compiler::compile("x := 1\nprintln(x)\n", "").unwrap());
+        assert_eq!(vm.start_vm(0), 0);
+
+        // the program starts from the first instruction again, with natives and output kept
+        vm.reset();
+        vm.load_program(compiler::compile("println(2)\n", "").unwrap());
+        assert_eq!(vm.start_vm(0), 0);
+
+        vm.reset();
+        vm.load_program(compiler::compile("println(x)\n", "").unwrap());
+        assert_eq!(vm.start_vm(0), 1);
+
+        let printed = output.0.borrow().clone();
+        assert_eq!(String::from_utf8(printed).unwrap(), "1\n2\n");
+    }
+
+    #[test]
+    fn test_embedded_programs() {
+        let program = compiler::compile("println(6 * 7)\n", "").unwrap();
+        let bytes = program.to_bytes().unwrap();
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+        vm.load_program(Program::from_bytes(&bytes).unwrap());
+        assert_eq!(vm.start_vm(0), 0);
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "42\n"
+        );
+
+        // anything that is not bytecode is rejected when it is loaded
+        assert!(crate::include_program!("../Cargo.toml").is_err());
+    }
+
+    #[test]
+    fn test_iter_instructions() {
+        let program = Program {
+            instructions: vec![
+                InstructionBuilder::new_load_float32_instruction(0),
+                1.5f32.to_bits(),
+                InstructionBuilder::new_print_instruction(0, true),
+                0xfc00_0000,
+                InstructionBuilder::new_load_float32_instruction(1),
+            ],
+            ..Default::default()
+        };
+
+        let decoded: Vec<_> = program.iter_instructions().collect();
+        let summary: Vec<_> = decoded
+            .iter()
+            .map(|decoded| (decoded.index, decoded.offset, decoded.width()))
+            .collect();
+        assert_eq!(summary, [(0, 0, 2), (2, 8, 1), (3, 12, 1), (4, 16, 1)]);
+
+        assert_eq!(decoded[0].opcode, Ok(OpCode::LoadFloat32));
+        assert_eq!(decoded[0].operands, [1.5f32.to_bits()]);
+        assert_eq!(decoded[2].opcode, Err(InvalidOpCode(63)));
+        assert!(decoded[3].is_truncated());
+        assert!(!decoded[0].is_truncated());
+    }
+
+    #[test]
+    fn test_diff_programs() {
+        let old =
+            compiler::compile("x := 1\nfn f(a)\nreturn a + 2\nend\nprintln(f(2))\n", "").unwrap();
+        let new = compiler::compile(
+            "x := 1\nfn f(a)\nreturn a * 2\nend\nfn g()\nend\nprintln(f(2))\n",
+            "",
+        )
+        .unwrap();
+
+        let ranges = tools::function_ranges(&new);
+        let names: Vec<_> = ranges.iter().map(|range| range.name.as_str()).collect();
+        assert_eq!(names, [tools::MAIN_NAME, "f", "g"]);
+        assert_eq!(ranges[2].instructions.len(), 1);
+
+        let diff = tools::diff_programs(&old, &new);
+        let changed: Vec<_> = diff
+            .functions
+            .iter()
+            .map(|function| (function.name.as_str(), function.has_changes()))
+            .collect();
+        assert_eq!(
+            changed,
+            [(tools::MAIN_NAME, true), ("f", true), ("g", true)]
+        );
+
+        // the body of f only changed its operator
+        let f = &diff.functions[1].lines;
+        assert_eq!(f.iter().filter(|line| line.is_change()).count(), 2);
+        assert!(f.contains(&DiffLine::Removed("ADD 0 0 1".to_string())));
+        assert!(f.contains(&DiffLine::Added("MUL 0 0 1".to_string())));
+        assert!(diff.to_string().starts_with("@@ <main> @@\n"));
+
+        assert!(tools::diff_programs(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_program_report() {
+        let source = "fn f(a)\nb := a + 1\nreturn b * 2\nend\nprintln(f(\"x\"))\n";
+        let program = compiler::compile(source, "").unwrap();
+        let report = tools::report(&program);
+
+        let f = &report.functions[1];
+        assert_eq!(f.name, "f");
+        assert_eq!(f.locals, 2);
+        assert_eq!(f.registers, 2);
+        assert_eq!(report.constant_pool, program.immutables.len());
+
+        let words: usize = report.functions.iter().map(|function| function.words).sum();
+        assert_eq!(words, program.instructions.len());
+        assert!(report.to_string().contains("\nf  "));
+    }
+
+    #[test]
+    fn test_program_symbols() {
+        let source = "total := 0\nfn add(a, b)\nsum := a + b\nreturn sum\nend\ntotal = add(1, 2)\n";
+        let program = compiler::compile(source, "").unwrap();
+        let symbols = program.symbols();
+
+        assert!(symbols.globals.iter().any(|global| global.name == "total"));
+
+        let add = symbols
+            .functions
+            .iter()
+            .find(|function| function.name == "add")
+            .unwrap();
+        assert_eq!(symbols.function_at(add.address), Some(add));
+        assert_eq!(symbols.local_names(Some(add.immutable), 0), vec!["a"]);
+        assert_eq!(symbols.local_names(Some(add.immutable), 2), vec!["sum"]);
+        assert!(symbols.to_string().contains("add local[1]: b"));
+    }
+
+    #[test]
+    fn test_local_variable_names() {
+        let source = "fn f(a)\nx := a * 2\nif x > 0\ny := \"s\"\nprintln(y)\nend\nreturn x - \"b\"\nend\nf(5)\n";
+        let program = compiler::compile(source, "").unwrap();
+
+        // y is only named inside the if block
+        let symbols = program.symbols();
+        let y = symbols
+            .locals
+            .iter()
+            .find(|local| local.name == "y")
+            .unwrap();
+        assert_eq!(
+            symbols.local_name_at(y.function, y.slot, y.start),
+            Some("y")
+        );
+        assert_eq!(symbols.local_name_at(y.function, y.slot, y.end), None);
+
+        let mut vm = VirtualMachine::new();
+        vm.set_output(Box::new(io::sink()));
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+
+        assert_eq!(
+            vm.local_variables(),
+            vec![
+                ("a".to_string(), NovaObject::Int64(5)),
+                ("x".to_string(), NovaObject::Int64(10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_function() {
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+
+        let source = "count := 5\nfn speed()\nreturn 1\nend\nprintln(speed())\n";
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 0);
+
+        let fragment = compiler::compile("fn speed()\nreturn count * 2\nend\n", "").unwrap();
+        vm.replace_function("speed", fragment).unwrap();
+
+        // the globals survive the swap
+        let offset = vm.get_instruction_count();
+        vm.load_program(compiler::compile("println(speed())\n", "").unwrap());
+        assert_eq!(vm.start_vm(offset), 0);
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "1\n10\n"
+        );
+
+        let fragment = compiler::compile("fn other()\nend\n", "").unwrap();
+        assert_eq!(
+            vm.replace_function("speed", fragment),
+            Err(ReplaceFunctionError::MissingFromFragment(
+                "speed".to_string()
+            ))
+        );
+        let fragment = compiler::compile("fn count()\nend\n", "").unwrap();
+        assert_eq!(
+            vm.replace_function("count", fragment),
+            Err(ReplaceFunctionError::UnknownFunction("count".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_globals() {
+        let source = "\
+fn times(a, b)
+return a * b
+end
+count := 3
+ratio := times(0.5, 3)
+names := list(\"a\", list(true, None))
+config := dict(\"level\", 2)
+callbacks := list(println)
+";
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 0);
+
+        let mut saved = Vec::new();
+        vm.save_globals(&mut saved).unwrap();
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+        assert_eq!(vm.load_globals(&mut saved.as_slice()).unwrap(), 4);
+
+        // functions and the values holding them are not saved
+        let source =
+            "println(count)\nprintln(ratio)\nprintln(names)\nprintln(config)\nprintln(callbacks)\n";
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 1);
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "3\n1.5\n[\"a\", [true, None]]\n{\"level\": 2}\n"
+        );
+
+        assert!(vm.load_globals(&mut &saved[..saved.len() - 1]).is_err());
+        assert!(vm.load_globals(&mut &b"not globals"[..]).is_err());
+    }
+
+    #[test]
+    fn test_contexts() {
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+
+        let run = |vm: &mut VirtualMachine, source: &str| {
+            let offset = vm.get_instruction_count();
+            vm.load_program(compiler::compile(source, "").unwrap());
+            vm.start_vm(offset)
+        };
+
+        let plugin = vm.create_context();
+        assert_eq!(
+            run(
+                &mut vm,
+                "name := \"host\"\nfn greet()\nprintln(name)\nend\n"
+            ),
+            0
+        );
+
+        // the plugin has its own globals and functions, the natives are shared
+        assert!(vm.switch_context(plugin));
+        assert_eq!(
+            run(
+                &mut vm,
+                "name := \"plugin\"\nfn greet()\nprint(\"from \")\nprintln(name)\nend\ngreet()\n"
+            ),
+            0
+        );
+
+        assert!(vm.switch_context(ContextId::MAIN));
+        assert_eq!(run(&mut vm, "greet()\n"), 0);
+        assert_eq!(vm.current_context(), ContextId::MAIN);
+
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "from plugin\nhost\n"
+        );
+
+        let other = vm.create_context();
+        assert!(vm.switch_context(other));
+        assert_eq!(run(&mut vm, "greet()\n"), 1);
+    }
+
+    #[test]
+    fn test_frozen_globals() {
+        let run = |vm: &mut VirtualMachine, source: &str| {
+            let offset = vm.get_instruction_count();
+            vm.load_program(compiler::compile(source, "").unwrap());
+            vm.start_vm(offset)
+        };
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+
+        let source =
+            "limit := 10\nprintln(freeze(\"limit\"))\nprintln(undef(\"limit\"))\nlimit = 3\n";
+        assert_eq!(run(&mut vm, source), 1);
+        let error = vm.take_error().unwrap();
+        assert_eq!(error.message, "Cannot assign to frozen global: limit");
+        assert_eq!(run(&mut vm, "println(limit)\n"), 0);
+
+        // the host can freeze globals, redefining them is an error too
+        assert_eq!(run(&mut vm, "config := 1\n"), 0);
+        assert!(vm.freeze_global("config"));
+        assert_eq!(run(&mut vm, "config := 2\n"), 1);
+        assert!(vm.unfreeze_global("config"));
+        assert_eq!(run(&mut vm, "config = 3\nprintln(config)\n"), 0);
+        assert!(!vm.freeze_global("missing"));
+
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "true\nfalse\n10\n3\n"
+        );
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let run = |vm: &mut VirtualMachine, source: &str| {
+            let offset = vm.get_instruction_count();
+            vm.load_program(compiler::compile(source, "").unwrap());
+            vm.start_vm(offset)
+        };
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+        vm.set_resource_limits(
+            ResourceLimits::unlimited()
+                .with_max_string_length(8)
+                .with_max_collection_length(3),
+        );
+
+        assert_eq!(run(&mut vm, "s := \"abcd\"\ns = s + s\nprintln(s)\n"), 0);
+        assert_eq!(run(&mut vm, "s = s + s\n"), 1);
+        let error = vm.take_error().unwrap();
+        assert_eq!(
+            error.message,
+            "String of 16 bytes is longer than the limit of 8"
+        );
+
+        // lists grown in place by a native are checked after it returns
+        let source = "items := list(1, 2, 3)\npush(items, 4)\n";
+        assert_eq!(run(&mut vm, source), 1);
+        let error = vm.take_error().unwrap();
+        assert_eq!(
+            error.message,
+            "A list of 4 values is longer than the limit of 3"
+        );
+
+        // the limits are kept across a reset
+        vm.reset();
+        vm.load_natives(natives::common_native_functions());
+        assert_eq!(run(&mut vm, "println(\"a\" + \"123456789\")\n"), 1);
+
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "abcdabcd\n"
+        );
+    }
+
+    #[test]
+    fn test_coercion() {
+        let run = |vm: &mut VirtualMachine, source: &str| {
+            let offset = vm.get_instruction_count();
+            vm.load_program(compiler::compile(source, "").unwrap());
+            vm.start_vm(offset)
+        };
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+
+        // numbers are written into strings by default, the other operations never convert
+        assert_eq!(
+            run(&mut vm, "a := 1\nprintln(\"a\" + a, \" \", a + \"b\")\n"),
+            0
+        );
+        assert_eq!(run(&mut vm, "println(\"a\" - 1)\n"), 1);
+        assert_eq!(
+            vm.take_error().unwrap().message,
+            "cannot subtract StrImm to Int64"
+        );
+
+        vm.set_coercion(Coercion::Strict);
+        vm.reset();
+        assert_eq!(run(&mut vm, "a := 1\nprintln(\"a\" + a)\n"), 1);
+        assert_eq!(
+            vm.take_error().unwrap().message,
+            "cannot add a string and a number in strict mode, convert the number with format()"
+        );
+
+        vm.reset();
+        let source = "a := 1\nprintln(\"a\" + format(a), \" \", \"${a}b\", \" \", \"c\" + \"d\")\n";
+        assert_eq!(run(&mut vm, source), 0);
+        assert_eq!(run(&mut vm, "println(1 - \"a\")\n"), 1);
+
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "a1 1b\na1 1b cd\n"
+        );
+    }
+
+    #[test]
+    fn test_reentrancy() {
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(io::sink()));
+
+        // a native calling back into a function that calls the native again is cut off
+        let source = "fn deeper(x)\nreturn map(list(x), deeper)\nend\ndeeper(1)\n";
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 1);
+        let error = vm.take_error().unwrap();
+        assert_eq!(
+            error.message,
+            format!(
+                "Too many nested calls from native functions, the limit is {}",
+                MAX_NATIVE_CALL_DEPTH
+            )
+        );
+
+        // a run unwinding with a panic leaves the machine unable to start until it is reset
+        let panicking = NativeFunction {
+            name: "explode".to_string(),
+            function: |_, _| panic!("explode"),
+        };
+        vm.reset();
+        vm.load_natives(vec![panicking]);
+        vm.load_program(compiler::compile("explode()\n", "").unwrap());
+        let unwound = panic::catch_unwind(AssertUnwindSafe(|| vm.start_vm(0)));
+        assert!(unwound.is_err());
+
+        assert_eq!(vm.start_vm(0), 1);
+        assert!(matches!(
+            vm.take_error().unwrap().kind,
+            VmErrorKind::Reentered
+        ));
+
+        vm.reset();
+        vm.load_program(compiler::compile("x := 1\n", "").unwrap());
+        assert_eq!(vm.start_vm(0), 0);
+    }
+
+    #[test]
+    fn test_api_facade() {
+        use crate::api::{
+            common_native_functions, Frontend, NativeFunction, NovaFrontend, NovaValue,
+            VirtualMachine,
+        };
+
+        let double = NativeFunction {
+            name: "double".to_string(),
+            function: |_, arguments| match arguments.get(0).as_deref() {
+                Some(NovaValue::Int64(value)) => Ok(NovaValue::Int64(value * 2)),
+                _ => Err("double needs an integer".to_string()),
+            },
+        };
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(common_native_functions());
+        vm.load_natives(vec![double]);
+        vm.set_output(Box::new(output.clone()));
+
+        let program = NovaFrontend.compile("println(double(21))\n", "").unwrap();
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 0);
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "42\n"
+        );
+    }
+
+    #[test]
+    fn test_integer_division_by_zero() {
+        for (source, message) in [
+            ("x := 0\nprintln(1 / x)\n", "Division by zero"),
+            ("x := 0\nprintln(1 % x)\n", "Modulo by zero"),
+        ] {
+            let mut vm = VirtualMachine::new();
+            vm.load_natives(natives::common_native_functions());
+            vm.set_output(Box::new(io::sink()));
+            vm.load_program(compiler::compile(source, "").unwrap());
+            assert_eq!(vm.start_vm(0), 1);
+            assert_eq!(vm.take_error().unwrap().message, message);
+        }
+    }
+
+    #[test]
+    fn test_verify_program() {
+        let program = compiler::compile("x := 1.5\nprintln(x)\n", "").unwrap();
+        assert_eq!(program.verify(), Ok(()));
+
+        // an opcode number kept for later
+        let mut corrupted = Program {
+            instructions: vec![InstructionBuilder::new_halt_instruction(), 0xfc00_0000],
+            ..Default::default()
+        };
+        assert_eq!(
+            corrupted.verify(),
+            Err(VerifyError::ReservedOpCode {
+                index: 1,
+                opcode: 63,
+                category: "host extensions"
+            })
+        );
+        assert_eq!(
+            corrupted.verify().unwrap_err().to_string(),
+            "opcode 63 at instruction 1 is reserved for host extensions"
+        );
+
+        // a float without its operand word
+        corrupted.instructions = vec![InstructionBuilder::new_load_float32_instruction(0)];
+        assert_eq!(corrupted.verify(), Err(VerifyError::Truncated { index: 0 }));
+
+        let mut vm = VirtualMachine::new();
+        assert!(vm.try_load_program(corrupted).is_err());
+        assert_eq!(vm.get_instruction_count(), 0);
+
+        let mut bytes = program.to_bytes().unwrap();
+        // the opcode bits of the first instruction, after the 20 byte header
+        bytes[23] = 0xe8;
+        assert!(Program::from_bytes(&bytes).is_err());
+    }
+
+    /// a function of the program with its parameter counts changed by `tamper`
+    fn tampered_function(tamper: impl Fn(&mut NovaFunction)) -> Program {
+        let mut program = compiler::compile("fn half(a)\nreturn a / 2\nend\n", "").unwrap();
+        for immutable in Arc::make_mut(&mut program.immutables).iter_mut() {
+            if let NovaObject::NovaFunction(function) = immutable {
+                tamper(function);
+            }
+        }
+
+        program
+    }
+
+    #[test]
+    fn test_verify_function_parameters() {
+        let program = tampered_function(|function| function.default_parameters = 5);
+        let error = program.verify().unwrap_err();
+        assert!(matches!(error, VerifyError::TooManyDefaults { .. }));
+        assert!(error
+            .to_string()
+            .ends_with("has more defaults than parameters"));
+
+        let bytes = tampered_function(|_| ()).to_bytes().unwrap();
+        assert!(Program::from_bytes(&bytes).is_ok());
+        assert!(Program::from_bytes(&program.to_bytes().unwrap()).is_err());
+
+        let mut vm = VirtualMachine::new();
+        assert!(vm.try_load_program(program).is_err());
+    }
+
+    #[test]
+    fn test_jump_none() {
+        // the frontend has no `??`, bytecode from other frontends jumps on None
+        let program = crate::nova_asm! {
+            load_nil 0;
+            load_int 1, 5;
+            jump_none 0, skip;
+            println 0;
+            skip:
+            jump_none 1, done;
+            println 1;
+            done:
+            halt;
+        };
+        assert_eq!(program.verify(), Ok(()));
+        assert!(crate::lint::lint(&program).is_empty());
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.set_output(Box::new(output.clone()));
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 0);
+        assert_eq!(String::from_utf8(output.0.borrow().clone()).unwrap(), "5\n");
+    }
+
+    #[test]
+    fn test_defaults_and_variadic_calls() {
+        // the frontend declares neither, bytecode from other frontends can
+        let mut program = crate::nova_asm! {
+            jump start;
+            show:
+            println 0;
+            println 1;
+            println 2;
+            return_none;
+            start:
+            load_int 0, 1;
+            load_function 1, "show", 2, show;
+            invoke 0, 1;
+            load_int 1, 2;
+            load_int 2, 3;
+            load_int 3, 4;
+            load_function 4, "show", 2, show;
+            invoke 0, 4;
+            load_function 0, "show", 2, show;
+            invoke 0, 0;
+            halt;
+        };
+        for immutable in Arc::make_mut(&mut program.immutables).iter_mut() {
+            if let NovaObject::NovaFunction(function) = immutable {
+                function.default_parameters = 1;
+                function.is_variadic = true;
+            }
+        }
+        assert_eq!(program.verify(), Ok(()));
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.set_output(Box::new(output.clone()));
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "1\nNone\n[]\n1\n2\n[3, 4]\n"
+        );
+        assert_eq!(
+            vm.take_error().unwrap().message,
+            "Not enough arguments for function 'show'.\n1 to 2 are required\n0 were provided"
+        );
+    }
+
+    #[test]
+    fn test_host_opcodes() {
+        fn emit_particle(context: &mut NativeContext, arguments: NativeArguments) -> NativeResult {
+            let (Some(x), Some(y)) = (arguments.get(0), arguments.get(1)) else {
+                return Err("emit_particle needs a position".to_string());
+            };
+            let (NovaObject::Int64(x), NovaObject::Int64(y)) = (x.as_ref(), y.as_ref()) else {
+                return Err("emit_particle needs whole numbers".to_string());
+            };
+
+            context.print(&format!("particle at {} {}\n", x, y));
+            Ok(NovaObject::Int64(x + y))
+        }
+
+        let program = crate::nova_asm! {
+            load_int 0, 3;
+            load_int 1, 4;
+            host 61, 2, 0, 1;
+            println 2;
+            load_string 1, "up";
+            host 61, 2, 0, 1;
+            halt;
+        };
+
+        // nothing handles the opcode without the extension
+        let mut vm = VirtualMachine::new();
+        assert!(matches!(
+            vm.try_load_program(program.clone()),
+            Err(VerifyError::ReservedOpCode { opcode: 61, .. })
+        ));
+
+        let mut extensions = Extensions::new();
+        extensions
+            .register(61, "emit_particle", emit_particle)
+            .unwrap();
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::with_extensions(extensions);
+        vm.set_output(Box::new(output.clone()));
+        vm.try_load_program(program.clone()).unwrap();
+        assert_eq!(vm.start_vm(0), 1);
+
+        let printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+        assert_eq!(printed, "particle at 3 4\n7\n");
+        let error = vm.take_error().unwrap();
+        assert_eq!(error.kind, VmErrorKind::Runtime);
+        assert_eq!(error.message, "emit_particle needs whole numbers");
+
+        // the extensions outlive a reset, and files keep the opcode
+        vm.reset();
+        let program = Program::from_bytes(&program.to_bytes().unwrap()).unwrap();
+        assert!(vm.try_load_program(program).is_ok());
+    }
+
+    #[test]
+    fn test_illegal_instruction() {
+        let reserved = InstructionBuilder::new().add_opcode(OpCode::This).build();
+        let mut vm = VirtualMachine::new();
+        vm.load_program(Program {
+            instructions: vec![InstructionBuilder::new_load_bool(0, 1), reserved],
+            ..Default::default()
+        });
+        assert_eq!(vm.start_vm(0), 1);
+
+        let error = vm.take_error().unwrap();
+        assert_eq!(
+            error.message,
+            format!("Illegal instruction {:#010x}, This is reserved", reserved)
+        );
+        let VmErrorKind::IllegalInstruction {
+            pc,
+            opcode,
+            listing,
+            ..
+        } = error.kind
+        else {
+            panic!("expected an illegal instruction");
+        };
+        assert_eq!((pc, opcode), (1, OpCode::This.to_u32()));
+        assert_eq!(listing.len(), 2);
+        assert!(listing[1].starts_with("-> [1]: "));
+
+        // the operand word of a float is never run unless something jumps into it
+        let mut vm = VirtualMachine::new();
+        vm.load_program(Program {
+            instructions: vec![
+                InstructionBuilder::new_load_float32_instruction(0),
+                0xfc00_0000,
+                InstructionBuilder::new_halt_instruction(),
+            ],
+            ..Default::default()
+        });
+        assert_eq!(vm.start_vm(1), 1);
+
+        let error = vm.take_error().unwrap();
+        assert!(matches!(
+            error.kind,
+            VmErrorKind::IllegalInstruction {
+                pc: 1,
+                instruction: 0xfc00_0000,
+                opcode: 63,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_heap_stats() {
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(SharedOutput::default()));
+        vm.load_program(
+            compiler::compile("x := \"nova\"\ny := x + \"lang\"\nprintln(y + \"!\")\n", "")
+                .unwrap(),
+        );
+        assert_eq!(vm.start_vm(0), 0);
+
+        let natives = natives::common_native_functions().len();
+        let stats = vm.heap_stats();
+        assert_eq!(stats.kinds["NativeFunction"].count, natives);
+        assert_eq!(stats.kinds["String"].count, 2);
+        assert_eq!(stats.total().count, natives + 2);
+
+        // the string held by the global `y` survives a collection
+        let concatenated = vm
+            .heap_iter()
+            .find(|(_, object, _)| object.to_string() == "novalang")
+            .unwrap();
+        assert!(concatenated.2);
+    }
+
+    #[cfg(feature = "heap_sites")]
+    #[test]
+    fn test_heap_report() {
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(SharedOutput::default()));
+        vm.load_program(
+            compiler::compile(
+                "fn shout(word)\nreturn word + \"!\"\nend\na := shout(\"x\")\nb := shout(\"y\")\nc := \"z\" + \"w\"\n",
+                "sites.nova",
+            )
+            .unwrap(),
+        );
+        assert_eq!(vm.start_vm(0), 0);
+
+        let report = vm.heap_report();
+        let natives = report
+            .sites
+            .iter()
+            .find(|site| site.site.is_none())
+            .unwrap();
+        assert_eq!(natives.live, natives::common_native_functions().len());
+
+        // both calls allocate their string inside `shout`, so they share its site
+        let sites: Vec<_> = report
+            .sites
+            .iter()
+            .filter(|site| site.site.is_some())
+            .map(|site| {
+                (
+                    site.location.as_deref().unwrap(),
+                    site.live,
+                    site.kinds["String"],
+                )
+            })
+            .collect();
+        assert_eq!(sites, [("sites.nova:2", 2, 2), ("sites.nova:6", 1, 1)]);
+        assert!(report.sites[1].oldest > report.sites[2].oldest);
+        assert!(report.to_string().contains("sites.nova:2"));
+    }
+
+    #[test]
+    fn test_native_function_values() {
+        // natives are invoked through their index, but are still values like any other
+        let output =
+            run_and_capture("show := println\nshow(1)\nprintln(show)\nprintln(show == println)\n");
+        assert_eq!(output, "1\nfunction: println\ntrue\n");
+    }
+
+    #[test]
+    fn test_float_format() {
+        let source = "fn half(x)\nreturn x * 0.5\nend\nprintln(half(10))\nprint(half(3), \" \", format(half(2) / 3, 2), \" \", format(2, \".3\"))\nprintln(\"\")\nprintln(0.1 + 0.2)\n";
+        let output = SharedOutput::default();
+
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+        vm.set_float_format(FloatFormat::Decimal);
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 0);
+
+        let printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+        assert_eq!(printed, "5.0\n1.5 0.33 2.000\n0.3\n");
+    }
+
+    #[test]
+    fn test_number_styles() {
+        let source = "println(format(255, \"x\"), \" \", format(-5, \"b\"), \" \", format(1500, \"e\"))\nprintln(100000000000000000000)\n";
+        assert_eq!(
+            run_and_capture(source),
+            "ff -101 1.5e3\n100000000000000000000\n"
+        );
+    }
+
+    #[test]
+    fn test_ordering_natives() {
+        let source = "\
+println(min(3, 1.5, 2), \" \", max(list(3, 7.5, 2)), \" \", max(\"pear\", \"apple\"))
+println(clamp(12, 0, 10), \" \", clamp(-1, 0.5, 10), \" \", clamp(4, 0, 10))
+println(sort(list(3, 1.5, 2, -4)))
+println(sort(list(\"b\", \"c\", \"a\")))
+println(sort(list(list(2), list(1, 5), list(1))))
+";
+        let expected =
+            "1.5 7.5 pear\n10 0.5 4\n[-4, 1.5, 2, 3]\n[\"a\", \"b\", \"c\"]\n[[1], [1, 5], [2]]\n";
+        assert_eq!(run_and_capture(source), expected);
+
+        // values of different kinds have no order
+        for (source, message) in [
+            ("sort(list(1, \"a\"))\n", "cannot compare String to Int64"),
+            (
+                "sort(list(\"b\", list(1), \"a\"))\n",
+                "cannot compare List to String",
+            ),
+            ("min(\"a\", list(1))\n", "cannot compare List to String"),
+            ("max(list(1), \"a\")\n", "cannot compare String to List"),
+            (
+                "sort(list(list(1), list(\"a\")))\n",
+                "cannot compare String to Int64",
+            ),
+        ] {
+            let program = compiler::compile(source, "").unwrap();
+            let mut vm = VirtualMachine::new();
+            vm.load_natives(natives::common_native_functions());
+            vm.load_program(program);
+            assert_eq!(vm.start_vm(0), 1, "{}", source);
+            assert_eq!(vm.take_error().unwrap().message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_list_natives() {
+        let source = "\
+items := list(1, 2)
+push(items, 3, 4)
+println(items)
+println(pop(items), \" \", len(items))
+insert(items, 0, \"zero\")
+println(remove(items, 1), \" \", items)
+part := slice(items, 1)
+push(part, 9)
+println(part, \" \", items, \" \", slice(items, 0, 1))
+println(contains(items, 2.0), \" \", contains(items, 7), \" \", contains(\"nova\", \"ov\"))
+println(join(items, \", \"), \" \", len(\"héllo\"))
+";
+        let expected = "[1, 2, 3, 4]\n4 3\n1 [\"zero\", 2, 3]\n[2, 3, 9] [\"zero\", 2, 3] [\"zero\"]\ntrue false true\nzero, 2, 3 5\n";
+        assert_eq!(run_and_capture(source), expected);
+
+        let program = compiler::compile("remove(list(1), 1)\n", "").unwrap();
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(program);
+        assert_eq!(vm.start_vm(0), 1);
+        assert_eq!(
+            vm.take_error().unwrap().message,
+            "Index 1 is out of range for a length of 1"
+        );
+    }
+
+    #[test]
+    fn test_bulk_arithmetic_natives() {
+        let source = "\
+a := list(1, 2, 3)
+b := list(4, 5, 6)
+println(vec_add(a, b), \" \", vec_mul(a, b), \" \", vec_mul(a, 0.5))
+println(sum(a), \" \", sum(list(0.5, 1)), \" \", sum(list()), \" \", dot(a, b))
+";
+        let expected = "[5, 7, 9] [4, 10, 18] [0.5, 1, 1.5]\n6 1.5 0 32\n";
+        assert_eq!(run_and_capture(source), expected);
+
+        for (source, message) in [
+            (
+                "dot(list(1, 2), list(1))\n",
+                "Function 'dot()' needs lists of the same length, 2 and 1 values given",
+            ),
+            (
+                "sum(list(1, \"two\"))\n",
+                "Function 'sum()' requires a list of numbers, found two",
+            ),
+        ] {
+            let mut vm = VirtualMachine::new();
+            vm.load_natives(natives::common_native_functions());
+            vm.load_program(compiler::compile(source, "").unwrap());
+            assert_eq!(vm.start_vm(0), 1);
+            assert_eq!(vm.take_error().unwrap().message, message);
+        }
+    }
+
+    #[test]
+    fn test_higher_order_natives() {
+        let source = "\
+fn square(x)
+return x * x
+end
+fn even(x)
+return x % 2 == 0
+end
+fn add(a, b)
+return a + b
+end
+fn squares(x)
+return map(list(x, x + 1), square)
+end
+fn show(x)
+println(\"item \", x)
+end
+items := list(1, 2, 3)
+println(map(items, square), \" \", filter(items, even))
+println(reduce(items, add), \" \", reduce(items, add, 10))
+println(map(items, squares))
+each(items, show)
+";
+        let expected = "[1, 4, 9] [2]\n6 16\n[[1, 4], [4, 9], [9, 16]]\nitem 1\nitem 2\nitem 3\n";
+        assert_eq!(run_and_capture(source), expected);
+
+        let source = "fn bad(x)\nreturn x + missing\nend\nmap(list(1), bad)\n";
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 1);
+
+        let error = vm.take_error().unwrap();
+        assert_eq!(error.message, "Cannot find global named: missing");
+        assert_eq!(error.location.unwrap().line, 2);
+        assert_eq!(error.call_stack[0].line, 4);
+    }
+
+    #[test]
+    fn test_map_natives() {
+        let source = "\
+scores := dict(\"b\", 2, \"a\", 1.5)
+set(scores, \"c\", \"three\")
+set(scores, \"b\", 20)
+println(scores, \" \", len(scores))
+println(keys(scores), \" \", values(scores))
+println(has(scores, \"a\"), \" \", has(scores, \"z\"), \" \", get(scores, \"z\", 0), \" \", get(scores, \"a\"))
+println(remove(scores, \"b\"), \" \", scores)
+println(merge(scores, dict(\"a\", 1, \"d\", 4)))
+";
+        let expected = "\
+{\"b\": 20, \"a\": 1.5, \"c\": \"three\"} 3
+[\"b\", \"a\", \"c\"] [20, 1.5, \"three\"]
+true false 0 1.5
+20 {\"a\": 1.5, \"c\": \"three\"}
+{\"a\": 1, \"c\": \"three\", \"d\": 4}
+";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_deep_equals_and_copy_natives() {
+        // whole float constants are compiled as integers, so the floats are computed at runtime
+        let source = "\
+fn times(a, b)
+return a * b
+end
+a := list(1, list(2, dict(\"k\", times(1.5, 2), \"j\", list())))
+b := list(times(0.5, 2), list(2, dict(\"j\", list(), \"k\", 3)))
+println(deep_equals(a, b), \" \", a == b, \" \", deep_equals(a, list(1, list(2))))
+c := copy(a)
+push(c, 4)
+println(len(a), \" \", len(c), \" \", deep_equals(copy(a, false), a))
+";
+        assert_eq!(run_and_capture(source), "true false false\n2 3 true\n");
+
+        // deeply nested values are compared without recursing
+        let mut left = NovaObject::None;
+        let mut right = NovaObject::None;
+        for _ in 0..100_000 {
+            left = NovaObject::List(Box::new(vec![left]));
+            right = NovaObject::List(Box::new(vec![right]));
+        }
+        assert!(natives::values_deep_equal(&left, &right));
+        for value in [left, right] {
+            let mut value = value;
+            while let NovaObject::List(mut list) = value {
+                value = list.pop().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_and_csv_natives() {
+        let source = "\
+line := \"a,b,,c\"
+parts := split(line, \",\")
+println(parts, \" \", join(parts, \",\") == line, \" \", split(\"  one two \"))
+row := csv.format_line(list(\"name\", \"Doe, Jane\", 42, 2.5))
+println(row)
+println(csv.parse_line(row), \" \", csv.parse_line(\"x;y\", \";\"))
+";
+        let expected = "\
+[\"a\", \"b\", \"\", \"c\"] true [\"one\", \"two\"]
+name,\"Doe, Jane\",42,2.5
+[\"name\", \"Doe, Jane\", \"42\", \"2.5\"] [\"x\", \"y\"]
+";
+        assert_eq!(run_and_capture(source), expected);
+
+        let errors = match compiler::compile_with_warnings("println(a.b.c)\n", "get.nova") {
+            Ok(_) => panic!("expected compile errors"),
+            Err(errors) => errors,
+        };
+        assert_eq!(errors[0].kind, CompileErrorKind::UnsupportedExpression);
+    }
+
+    #[test]
+    fn test_string_comparison_across_storage() {
+        let source = "a := \"ab\"\nb := \"a\" + \"b\"\nprintln(a == b, \" \", b == \"ab\", \" \", \"a\" < b)\n";
+        assert_eq!(run_and_capture(source), "true true true\n");
+    }
+
+    #[test]
+    fn test_hash_native() {
+        let source = "\
+println(hash(\"nova\") == hash(\"no\" + \"va\"), \" \", hash(\"nova\") == hash(\"lang\"))
+println(hash(2) == hash(2.0), \" \", hash(true) == hash(true))
+";
+        assert_eq!(run_and_capture(source), "true false\ntrue true\n");
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_digest_natives() {
+        let source = "println(md5(\"\"))\nprintln(sha256(\"abc\"))\n";
+        let expected = "\
+d41d8cd98f00b204e9800998ecf8427e
+ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad
+";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[cfg(feature = "matrix")]
+    #[test]
+    fn test_matrix_natives() {
+        let source = "\
+a := list(list(1, 2), list(3, 4))
+println(matrix_mul(a, matrix_identity(2)), \" \", matrix_transpose(a))
+println(matrix_inverse(list(list(2, 0), list(0, 4))), \" \", matrix_shape(matrix_new(2, 3)))
+matrix_set(a, 1, 0, 9)
+println(matrix_get(a, 1, 0), \" \", a)
+";
+        let expected =
+            "[[1, 2], [3, 4]] [[1, 3], [2, 4]]\n[[0.5, 0], [0, 0.25]] [2, 3]\n9 [[1, 2], [9, 4]]\n";
+        assert_eq!(run_and_capture(source), expected);
+    }
+
+    #[test]
+    fn test_os_natives_need_capabilities() {
+        let source = "os.set_env(\"NOVA_CAPABILITY_TEST\", \"yes\")\nprintln(os.env(\"NOVA_CAPABILITY_TEST\"), \" \", os.env(\"NOVA_UNSET_VARIABLE\"))\nprintln(os.platform())\n";
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::new();
+        vm.set_capabilities(Capabilities::all());
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(output.clone()));
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 0);
+
+        let printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+        assert_eq!(printed, format!("yes None\n{}\n", std::env::consts::OS));
+
+        let mut vm = VirtualMachine::new();
+        vm.set_capabilities(Capabilities::all().deny(Capability::WriteEnvironment));
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 1);
+        assert_eq!(
+            vm.take_error().unwrap().message,
+            "'os.set_env()' needs the write_environment capability, which is not allowed"
+        );
+    }
+
+    #[test]
+    fn test_vm_hooks() {
+        let source = "fn square(x)\n    return x * x\nend\n\nfn show(x)\n    println(square(x))\nend\n\nshow(3)\nprint(missing)\n";
+        let events = Rc::new(RefCell::new(Vec::new()));
+
+        let (enter, exit, print, error) = (
+            events.clone(),
+            events.clone(),
+            events.clone(),
+            events.clone(),
+        );
+        let hooks = VmHooks::new()
+            .on_function_enter(move |event| {
+                enter
+                    .borrow_mut()
+                    .push(format!("enter {} {}", event.name, event.depth))
+            })
+            .on_function_exit(move |event| {
+                exit.borrow_mut()
+                    .push(format!("exit {} {}", event.name, event.depth))
+            })
+            .on_print(move |text| print.borrow_mut().push(format!("print {:?}", text)))
+            .on_error(move |vm_error| {
+                error
+                    .borrow_mut()
+                    .push(format!("error {}", vm_error.message))
+            });
+
+        let mut vm = VirtualMachine::new();
+        vm.set_hooks(hooks);
+        vm.set_output(Box::new(SharedOutput::default()));
+        vm.load_natives(natives::common_native_functions());
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 1);
+
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "enter show 2",
+                "enter square 3",
+                "exit square 3",
+                "print \"9\\n\"",
+                "exit show 2",
+                "error Cannot find global named: missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_returned_string_is_live() {
+        // the result is never loaded, so only the return register refers to it
+        let source = "fn greet(name)\n    return \"hi \" + name\nend\n\ngreet(\"nova\")\n";
+
+        let mut vm = VirtualMachine::new();
+        vm.set_output(Box::new(SharedOutput::default()));
+        vm.load_program(compiler::compile(source, "").unwrap());
+        assert_eq!(vm.start_vm(0), 0);
+
+        let greeting = vm
+            .heap_iter()
+            .find(|(_, object, _)| object.to_string() == "hi nova")
+            .unwrap();
+        assert!(greeting.2);
+    }
+
+    #[test]
+    fn test_function_profiler() {
+        let source = "fn square(x)\n    return x * x\nend\n\nfn sum(n)\n    return square(n) + square(n + 1)\nend\n\nprintln(sum(2))\nprintln(square(3))\n";
+
+        let mut vm = VirtualMachine::new();
+        vm.load_natives(natives::common_native_functions());
+        vm.set_output(Box::new(SharedOutput::default()));
+        vm.load_program(compiler::compile(source, "profile.nova").unwrap());
+
+        let mut profiler = FunctionProfiler::new();
+        assert_eq!(vm.start_vm_with_profiler(0, &mut profiler), 0);
+
+        let report = profiler.report(&vm);
+        let calls = |name: &str| {
+            report
+                .functions
+                .iter()
+                .find(|(function, _)| function.starts_with(name))
+                .map(|(_, timing)| timing.calls)
+        };
+        assert_eq!(calls("<main>"), Some(1));
+        assert_eq!(calls("square (profile.nova:2)"), Some(3));
+        assert_eq!(calls("sum (profile.nova:6)"), Some(1));
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&vm, &mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        let stacks: Vec<&str> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(
+            stacks,
+            vec![
+                "<main>",
+                "<main>;square (profile.nova:2)",
+                "<main>;sum (profile.nova:6)",
+                "<main>;sum (profile.nova:6);square (profile.nova:2)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_opcode_profiler() {
+        let program = crate::nova_asm! {
+            load_int 0, 3;
+            load_int 1, 0;
+            load_int 2, 1;
+            top:
+            less 3, 1, 0;
+            jump_false 3, done;
+            sub 0, 0, 2;
+            jump top;
+            done:
+            halt;
+        };
+
+        let mut vm = VirtualMachine::new();
+        vm.load_program(program);
+        let mut profiler = OpcodeProfiler::new();
+        assert_eq!(vm.start_vm_with_profiler(0, &mut profiler), 0);
+
+        let counts = profiler.counts();
+        assert_eq!(counts[0], (OpCode::Less, 4));
+        assert!(counts.contains(&(OpCode::Sub, 3)));
+        assert!(counts.contains(&(OpCode::Halt, 1)));
+
+        // the jump after a jump_false is taken by the jump_false itself
+        assert_eq!(profiler.pairs()[0], ((OpCode::Less, OpCode::JumpFalse), 4));
+        assert!(profiler
+            .pairs()
+            .contains(&((OpCode::JumpFalse, OpCode::Halt), 1)));
+    }
+
+    #[test]
+    fn test_stdlib() {
+        let source = "\
+numbers := range(0, 6)
+println(reverse(numbers), \" \", last(numbers), \" \", index_of(numbers, 4))
+println(flatten(list(list(1, 2), list(3))), \" \", unique(list(1, 2, 1, 3, 2)))
+println(pad_left(\"7\", 3, \"0\"), \" \", pad_right(\"ab\", 4, \".\"), \" \", repeat(\"-\", 3))
+println(starts_with(\"nova\", \"no\"), \" \", ends_with(\"nova\", \"no\"), \" \", replace(\"a-b-c\", \"-\", \"+\"))
+fn even(n)
+return n % 2 == 0
+end
+println(find(numbers, even), \" \", any(numbers, even), \" \", all(numbers, even), \" \", count_if(numbers, even))
+println(partition(numbers, even))
+";
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::with_stdlib();
+        vm.set_output(Box::new(output.clone()));
+
+        // the library leaves the machine ready for more programs after it
+        for _ in 0..2 {
+            let offset = vm.get_instruction_count();
+            vm.load_program(compiler::compile(source, "stdlib.nova").unwrap());
+            assert_eq!(vm.start_vm(offset), 0);
+            vm.reset();
+        }
+
+        let expected = "\
+[5, 4, 3, 2, 1, 0] 5 4
+[1, 2, 3] [1, 2, 3]
+007 ab.. ---
+true false a+b+c
+0 true false 3
+[[0, 2, 4], [1, 3, 5]]
+";
+        let printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+        assert_eq!(printed, expected.repeat(2));
+    }
+
+    #[test]
+    fn test_stdlib_compiles() {
+        let programs = stdlib::programs().unwrap();
+        assert_eq!(programs.len(), stdlib::MODULES.len());
+        for program in programs {
+            assert_eq!(program.verify(), Ok(()));
+        }
+
+        let mut vm = VirtualMachine::new();
+        assert_eq!(vm.try_load_prelude(Prelude::all()), Ok(()));
+    }
+
+    #[test]
+    fn test_prelude() {
+        let prelude = Prelude::none()
+            .with(NativeModule::Core)
+            .with(NativeModule::Collections)
+            .with_stdlib_module("collections");
+        assert!(prelude.includes(NativeModule::Core) && !prelude.includes(NativeModule::Math));
+        assert_eq!(
+            prelude.stdlib_modules().collect::<Vec<_>>(),
+            vec!["collections"]
+        );
+
+        let output = SharedOutput::default();
+        let mut vm = VirtualMachine::with_prelude(prelude);
+        vm.set_output(Box::new(output.clone()));
+        let offset = vm.get_instruction_count();
+        vm.load_program(
+            compiler::compile("println(reverse(list(1, 2)))\nprintln(hash(\"a\"))\n", "").unwrap(),
+        );
+        vm.start_vm(offset);
+        assert_eq!(output.0.borrow().as_slice(), b"[2, 1]\n");
+        assert!(vm.take_error().unwrap().message.contains("hash"));
+
+        let mut vm = VirtualMachine::with_prelude(Prelude::none());
+        assert_eq!(vm.get_instruction_count(), 0);
+        vm.load_program(compiler::compile("println(1)\n", "").unwrap());
+        vm.start_vm(0);
+        assert!(vm.take_error().unwrap().message.contains("println"));
+
+        assert_eq!(
+            Prelude::all().native_functions().len(),
+            natives::common_native_functions().len()
+        );
+    }
+
+    #[test]
+    fn test_operator_overloading() {
+        let source = "\
+fn vec(x, y)
+return dict(\"x\", x, \"y\", y, \"__add\", vec_add, \"__mul\", vec_scale, \"__eq\", vec_equal, \"__lt\", vec_shorter)
+end
+fn vec_add(a, b)
+return vec(get(a, \"x\") + get(b, \"x\"), get(a, \"y\") + get(b, \"y\"))
+end
+fn vec_scale(a, k)
+return vec(get(a, \"x\") * k, get(a, \"y\") * k)
+end
+fn vec_equal(a, b)
+return get(a, \"x\") == get(b, \"x\") and get(a, \"y\") == get(b, \"y\")
+end
+fn length(a)
+return get(a, \"x\") * get(a, \"x\") + get(a, \"y\") * get(a, \"y\")
+end
+fn vec_shorter(a, b)
+return length(a) < length(b)
+end
+fn show(a)
+println(get(a, \"x\"), \",\", get(a, \"y\"))
+end
+a := vec(1, 2)
+b := vec(3, 4)
+show(a + b)
+show(a * 3)
+println(a == vec(1, 2), \" \", a != b, \" \", a < b, \" \", a <= b, \" \", a > b, \" \", b >= a)
+println(dict(\"x\", 1) == dict(\"x\", 1))
+";
+        let expected = "4,6\n3,6\ntrue true true true false true\ntrue\n";
+        assert_eq!(run_and_capture(source), expected);
+
+        // an error in the function is the error of the operator, maps without one still fail
+        for (source, message) in [
+            (
+                "fn bad(a, b)\nreturn a - b\nend\nr := dict(\"__add\", bad)\nprintln(r + 1)\n",
+                "cannot subtract",
+            ),
+            ("println(dict() - 1)\n", "cannot subtract"),
+        ] {
+            let mut vm = VirtualMachine::new();
+            vm.load_natives(natives::common_native_functions());
+            vm.set_output(Box::new(io::sink()));
+            vm.load_program(compiler::compile(source, "").unwrap());
+            assert_eq!(vm.start_vm(0), 1);
+            assert!(vm.take_error().unwrap().message.starts_with(message));
+        }
+    }
 }
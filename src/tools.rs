@@ -0,0 +1,379 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Display;
+
+use crate::{
+    bytecode::OpCode,
+    debug::{debug_instruction, instruction_width},
+    instruction::instruction_decoder,
+    lint::{register_effects, RegisterSet},
+    object::NovaObject,
+    program::Program,
+};
+
+/// name the top level code of a program is listed under
+pub const MAIN_NAME: &str = "<main>";
+
+/// the instructions of one function, found with `function_ranges`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionRange {
+    pub name: String,
+    /// index of the first instruction, 0 for the top level code
+    pub start: usize,
+    /// indices of the instruction words that belong to the function itself, nested functions
+    /// and the code around them excluded
+    pub instructions: Vec<usize>,
+}
+
+/// split the instructions of the program by the function they belong to, the top level code
+/// first and then the functions in the order they start in. the generator jumps over every
+/// function body, so a body ends where the jump before it lands. the optimizer can thread the
+/// jumps over neighbouring functions into one, a function without a jump of its own ends with
+/// the one before it
+pub fn function_ranges(program: &Program) -> Vec<FunctionRange> {
+    let mut starts: Vec<(String, usize)> = program
+        .immutables
+        .iter()
+        .filter_map(|immutable| match immutable {
+            NovaObject::NovaFunction(function) => {
+                Some((function.name.to_string(), function.address as usize))
+            }
+            _ => None,
+        })
+        .collect();
+    starts.sort_by_key(|&(_, start)| start);
+
+    let mut functions: Vec<(String, usize, usize)> = Vec::with_capacity(starts.len());
+    for (name, start) in starts {
+        let end = match jump_over(program, start) {
+            Some(end) => end,
+            None => functions
+                .iter()
+                .rev()
+                .find(|&&(_, outer_start, outer_end)| (outer_start..outer_end).contains(&start))
+                .map_or(program.instructions.len(), |&(_, _, outer_end)| outer_end),
+        };
+        functions.push((name, start, end));
+    }
+
+    let mut ranges = vec![FunctionRange {
+        name: MAIN_NAME.to_string(),
+        start: 0,
+        instructions: Vec::new(),
+    }];
+    ranges.extend(functions.iter().map(|(name, start, _)| FunctionRange {
+        name: name.clone(),
+        start: *start,
+        instructions: Vec::new(),
+    }));
+
+    // the innermost function holding an instruction is the last one to start before it
+    for decoded in program.iter_instructions() {
+        let owner = functions
+            .iter()
+            .rposition(|&(_, start, end)| (start..end).contains(&decoded.index))
+            .map_or(0, |function| function + 1);
+        ranges[owner].instructions.push(decoded.index);
+    }
+
+    ranges
+}
+
+/// where the jump right before the body starting at `start` lands
+fn jump_over(program: &Program, start: usize) -> Option<usize> {
+    let jump = start
+        .checked_sub(1)
+        .and_then(|index| program.instructions.get(index).copied())
+        .filter(|&jump| instruction_decoder::decode_opcode(jump) == OpCode::Jump as u32)
+        .filter(|&jump| instruction_decoder::decode_destination_register(jump) != 0)?;
+
+    let offset = instruction_decoder::decode_immutable_address_small(jump) as usize;
+    Some((start - 1 + offset).min(program.instructions.len()))
+}
+
+/// a line of the listing of a function, see `diff_programs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+impl DiffLine {
+    pub fn is_change(&self) -> bool {
+        !matches!(self, DiffLine::Same(_))
+    }
+}
+
+/// the listings of a function in the old and new program, side by side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDiff {
+    pub name: String,
+    pub lines: Vec<DiffLine>,
+}
+
+impl FunctionDiff {
+    pub fn has_changes(&self) -> bool {
+        self.lines.iter().any(DiffLine::is_change)
+    }
+}
+
+/// how two programs differ, function by function. `Display` prints the functions that changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramDiff {
+    pub functions: Vec<FunctionDiff>,
+}
+
+impl ProgramDiff {
+    pub fn is_empty(&self) -> bool {
+        !self.functions.iter().any(FunctionDiff::has_changes)
+    }
+}
+
+impl Display for ProgramDiff {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for function in self
+            .functions
+            .iter()
+            .filter(|function| function.has_changes())
+        {
+            writeln!(f, "@@ {} @@", function.name)?;
+
+            for line in &function.lines {
+                match line {
+                    DiffLine::Same(text) => writeln!(f, "  {}", text)?,
+                    DiffLine::Removed(text) => writeln!(f, "- {}", text)?,
+                    DiffLine::Added(text) => writeln!(f, "+ {}", text)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// compare the instructions of two programs, lining up functions by name so code moving
+/// around the program does not show as a change. within a function the disassembly is
+/// compared line by line
+pub fn diff_programs(old: &Program, new: &Program) -> ProgramDiff {
+    let old_listings = listings(old);
+    let mut new_listings = listings(new);
+
+    let mut functions = Vec::new();
+    for (name, old_lines) in old_listings {
+        let new_lines = match new_listings
+            .iter()
+            .position(|(new_name, _)| *new_name == name)
+        {
+            Some(position) => new_listings.remove(position).1,
+            None => Vec::new(),
+        };
+
+        functions.push(FunctionDiff {
+            name,
+            lines: diff_lines(&old_lines, &new_lines),
+        });
+    }
+
+    for (name, new_lines) in new_listings {
+        functions.push(FunctionDiff {
+            name,
+            lines: diff_lines(&[], &new_lines),
+        });
+    }
+
+    ProgramDiff { functions }
+}
+
+/// the disassembly of every function, functions sharing a name are numbered after the first
+fn listings(program: &Program) -> Vec<(String, Vec<String>)> {
+    let mut listings: Vec<(String, Vec<String>)> = Vec::new();
+
+    for range in function_ranges(program) {
+        let repeats = listings
+            .iter()
+            .filter(|(name, _)| name.split('#').next() == Some(range.name.as_str()))
+            .count();
+        let name = match repeats {
+            0 => range.name,
+            _ => format!("{}#{}", range.name, repeats + 1),
+        };
+
+        let lines = range
+            .instructions
+            .iter()
+            .map(|&index| {
+                debug_instruction(&program.instructions, index as u64)
+                    .trim_end()
+                    .to_string()
+            })
+            .collect();
+        listings.push((name, lines));
+    }
+
+    listings
+}
+
+/// line diff from the longest common subsequence of the two listings
+fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].clone()));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i].clone()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].clone()));
+            j += 1;
+        }
+    }
+
+    lines.extend(old[i..].iter().cloned().map(DiffLine::Removed));
+    lines.extend(new[j..].iter().cloned().map(DiffLine::Added));
+    lines
+}
+
+/// size of one function, see `report`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionReport {
+    pub name: String,
+    pub instructions: usize,
+    /// instruction words including the operands of the wide loads
+    pub words: usize,
+    /// the highest general register the function uses plus one
+    pub registers: usize,
+    /// local variable slots the function allocates or uses
+    pub locals: usize,
+    /// distinct constants the function loads
+    pub constants: usize,
+}
+
+/// how big a program is and where, from `report`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramReport {
+    pub functions: Vec<FunctionReport>,
+    /// entries in the constant pool, strings and functions included
+    pub constant_pool: usize,
+}
+
+impl Display for ProgramReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name_width = self
+            .functions
+            .iter()
+            .map(|function| function.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("function".len());
+
+        writeln!(
+            f,
+            "{:<name_width$}  instructions  words  registers  locals  constants",
+            "function"
+        )?;
+        for function in &self.functions {
+            writeln!(
+                f,
+                "{:<name_width$}  {:>12}  {:>5}  {:>9}  {:>6}  {:>9}",
+                function.name,
+                function.instructions,
+                function.words,
+                function.registers,
+                function.locals,
+                function.constants
+            )?;
+        }
+
+        let words: usize = self.functions.iter().map(|function| function.words).sum();
+        writeln!(f, "total words: {}", words)?;
+        writeln!(f, "constant pool: {} entries", self.constant_pool)
+    }
+}
+
+/// the instruction count, register pressure, local slots and constants of every function, to
+/// find the ones worth slimming down before shipping bytecode
+pub fn report(program: &Program) -> ProgramReport {
+    let declared_locals = |start: usize| {
+        program
+            .immutables
+            .iter()
+            .find_map(|immutable| match immutable {
+                NovaObject::NovaFunction(function) if function.address as usize == start => {
+                    Some(function.number_of_locals as usize)
+                }
+                _ => None,
+            })
+    };
+
+    let functions = function_ranges(program)
+        .into_iter()
+        .enumerate()
+        .map(|(position, range)| {
+            let mut used_registers: RegisterSet = 0;
+            let mut locals = 0;
+            let mut constants = Vec::new();
+            let mut words = 0;
+
+            for &index in &range.instructions {
+                let instruction = program.instructions[index];
+                let (reads, writes) = register_effects(instruction);
+                used_registers |= reads | writes;
+                words += instruction_width(instruction);
+
+                let operand = instruction_decoder::decode_immutable_address_small(instruction);
+                match OpCode::try_from(instruction_decoder::decode_opcode(instruction)) {
+                    Ok(
+                        OpCode::LoadLocal
+                        | OpCode::StoreLocal
+                        | OpCode::Inc
+                        | OpCode::Dec
+                        | OpCode::ForPrep
+                        | OpCode::ForLoop,
+                    ) => locals = locals.max(operand as usize + 1),
+                    Ok(OpCode::AllocateLocal) => locals = locals.max(operand as usize),
+                    Ok(OpCode::LoadK) if !constants.contains(&operand) => constants.push(operand),
+                    _ => {}
+                }
+            }
+
+            // the top level code is not a function, it has nothing declared
+            if position > 0 {
+                locals = locals.max(declared_locals(range.start).unwrap_or(0));
+            }
+
+            FunctionReport {
+                name: range.name,
+                instructions: range.instructions.len(),
+                words,
+                registers: (RegisterSet::BITS - used_registers.leading_zeros()) as usize,
+                locals,
+                constants: constants.len(),
+            }
+        })
+        .collect();
+
+    ProgramReport {
+        functions,
+        constant_pool: program.immutables.len(),
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{
+    instruction::Instruction,
+    object::{MappedMemory, NovaObject},
+    register::Register,
+};
+
+/// a snapshot of the virtual machine state, emitted before every instruction when the
+/// `debug` feature is on. the `dbg_*` features choose which events are emitted
+#[derive(Debug, Clone, Copy)]
+pub enum TraceEvent<'a> {
+    /// the instruction about to run (`dbg_code`)
+    Instruction {
+        address: u64,
+        instruction: Instruction,
+        /// the instruction as written by `debug::debug_instruction`
+        listing: &'a str,
+    },
+    /// every register, indexed by `RegisterID` (`verbose`)
+    Registers(&'a [Register]),
+    /// the global variables (`dbg_global`)
+    Globals(&'a [Register]),
+    /// the local variables of every frame (`dbg_local`)
+    Locals(&'a [Register]),
+    /// names of the globals mapped to their addresses (`dbg_global`)
+    Identifiers(&'a MappedMemory),
+    /// the objects on the heap (`dbg_memory`)
+    Memory(&'a [NovaObject]),
+}
+
+/// receives the trace of a running virtual machine, set with `VirtualMachine::set_trace_sink`
+pub trait TraceSink {
+    fn trace(&mut self, event: TraceEvent);
+}
+
+/// the default sink, pretty printing every event to stdout
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConsoleTrace;
+
+#[cfg(feature = "std")]
+impl TraceSink for ConsoleTrace {
+    fn trace(&mut self, event: TraceEvent) {
+        match event {
+            TraceEvent::Instruction {
+                address, listing, ..
+            } => println!("[{}]: {}", address, listing),
+            TraceEvent::Registers(registers) => {
+                println!("{:=^30}", "Registers");
+                for (index, register) in registers.iter().enumerate() {
+                    println!("==> R{:<2}: {}", index, register);
+                }
+                println!("{:=^30}", "");
+            }
+            TraceEvent::Globals(globals) => print_registers("Globals", globals),
+            TraceEvent::Locals(locals) => print_registers("Locals", locals),
+            TraceEvent::Identifiers(identifiers) => {
+                println!("{:=^30}", "Identifiers");
+                println!("==> {:?}", identifiers);
+                println!("{:=^30}", "");
+            }
+            TraceEvent::Memory(memory) => {
+                println!("{:=^30}", "Heap");
+                println!("[");
+                for (index, object) in memory.iter().enumerate() {
+                    println!("\t[{}] {}", index, object);
+                }
+                println!("]");
+                println!("{:=^30}", "");
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn print_registers(title: &str, registers: &[Register]) {
+    println!("{:=^30}", title);
+    println!("[");
+    for (index, register) in registers.iter().enumerate() {
+        println!("\t[{}] {}, ", index, register)
+    }
+    println!("]");
+    println!("{:=^30}", "");
+}
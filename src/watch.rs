@@ -0,0 +1,82 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// how often the watched files are checked for changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// last seen state of a file, None if it could not be read
+type FileState = Option<(SystemTime, u64)>;
+
+/// polls a set of source files and reports when any of them changed.
+/// polling keeps it free of platform specific notification apis
+pub struct FileWatcher {
+    files: Vec<(PathBuf, FileState)>,
+}
+
+impl FileWatcher {
+    pub fn new<P: AsRef<Path>>(paths: &[P]) -> Self {
+        let files = paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref().to_path_buf();
+                let state = file_state(&path);
+                (path, state)
+            })
+            .collect();
+
+        Self { files }
+    }
+
+    /// whether any file was modified, created or removed since the last check
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, state) in self.files.iter_mut() {
+            let current = file_state(path);
+            if current != *state {
+                *state = current;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// block until one of the files changes
+    pub fn wait_for_change(&mut self) {
+        while !self.changed() {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+fn file_state(path: &Path) -> FileState {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use std::fs;
+
+    use super::FileWatcher;
+
+    #[test]
+    fn test_detects_changes() {
+        let path = std::env::temp_dir().join(format!("nova_watch_{}.nova", std::process::id()));
+        fs::write(&path, "println(1)\n").unwrap();
+
+        let mut watcher = FileWatcher::new(&[&path]);
+        assert!(!watcher.changed());
+
+        fs::write(&path, "println(1)\nprintln(2)\n").unwrap();
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.changed());
+    }
+}